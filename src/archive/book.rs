@@ -15,16 +15,87 @@
 // along with this program; if not, write to the Free Software
 // Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
 
-use reqwest::Client;
+use rocket::serde::json::Json;
 use rocket::State;
 use rocket_okapi::openapi;
+use schemars::Map;
 
-use crate::archive::model::Score;
-use crate::database::client::FindResponse;
+use crate::archive::model::{Book, Score, ScoreNeighbors};
+use crate::archive::score::check_anonymous_read;
+use crate::database::client::{DatabaseClient, FindResponse, OperationResponse};
 use crate::openapi::ApiResult;
 use crate::user::executives::{Archive, ExecutiveRole};
 use crate::Config;
 
+/// Get all books of the archive.
+/// If [crate::config::ArchiveConfig::anonymous_read] is set, this request is also available without the archive role.
+///
+/// # Arguments
+///
+/// * `archive_role`: the archive role guard, anonymous access is checked if absent
+/// * `conf`: the application configuration
+/// * `client`: the client to send the database requests with
+///
+/// returns: Result<Json<Vec<Book>>, Error>
+#[openapi(tag = "Archive")]
+#[get("/")]
+pub async fn get_books(
+    archive_role: Option<ExecutiveRole<Archive>>,
+    conf: &State<Config>,
+    client: &State<DatabaseClient>,
+) -> ApiResult<Vec<Book>> {
+    if archive_role.is_none() {
+        check_anonymous_read(conf)?;
+    }
+    crate::database::book::all_books(conf, client).await
+}
+
+/// Insert or update a book.
+/// When creating a new book, make sure to leave its `_id` and `_rev` to `None` and set both on update.
+/// Renaming a book does not update the pages of its scores.
+///
+/// # Arguments
+///
+/// * `book`: the book to insert or update
+/// * `_archive_role`: the archive role guard
+/// * `conf`: the application configuration
+/// * `client`: the client to perform the request with
+///
+/// returns: Result<Json<OperationResponse>, Error>
+#[openapi(tag = "Archive")]
+#[put("/", data = "<book>")]
+pub async fn put_book(
+    book: Json<Book>,
+    _archive_role: ExecutiveRole<Archive>,
+    conf: &State<Config>,
+    client: &State<DatabaseClient>,
+) -> ApiResult<OperationResponse> {
+    crate::database::book::put_book(conf, client, book.0).await
+}
+
+/// Delete a book by its id and revision.
+///
+/// # Arguments
+///
+/// * `id`: the id of the book to delete
+/// * `rev`: the revision of the book to delete
+/// * `_archive_role`: the archive role guard
+/// * `conf`: the application configuration
+/// * `client`: the client to perform the request
+///
+/// returns: Result<Json<OperationResponse>, Error>
+#[openapi(tag = "Archive")]
+#[delete("/<id>?<rev>")]
+pub async fn delete_book(
+    id: String,
+    rev: String,
+    _archive_role: ExecutiveRole<Archive>,
+    conf: &State<Config>,
+    client: &State<DatabaseClient>,
+) -> ApiResult<OperationResponse> {
+    crate::database::book::delete_book(conf, client, id, rev).await
+}
+
 /// Fetch all scores which are part of the given `book`.
 /// The scores are sorted as usual in books which means the following order:
 ///
@@ -46,7 +117,55 @@ pub async fn get_book_content(
     name: String,
     conf: &State<Config>,
     _archive_role: ExecutiveRole<Archive>,
-    client: &State<Client>,
+    client: &State<DatabaseClient>,
 ) -> ApiResult<FindResponse<Score>> {
     crate::database::score::get_book_content(conf, client, name).await
 }
+
+/// Fetch the content of multiple books at once.
+/// Each book content is sorted as described in [get_book_content].
+/// The books are fetched concurrently with a configurable limit, the response preserves the order of the requested `names`.
+///
+/// # Arguments
+///
+/// * `names`: the names of the books to fetch
+/// * `conf`: the application configuration
+/// * `_archive_role`: the archive role guard
+/// * `client`: the client to send the database requests with
+///
+/// returns: Result<Json<Map<String, FindResponse<Score>>>, Error>
+#[openapi(tag = "Archive")]
+#[get("/contents?<names>")]
+pub async fn get_books_content(
+    names: Vec<String>,
+    conf: &State<Config>,
+    _archive_role: ExecutiveRole<Archive>,
+    client: &State<DatabaseClient>,
+) -> ApiResult<Map<String, FindResponse<Score>>> {
+    crate::database::score::get_books_content(conf, client, names).await
+}
+
+/// Get the ids of the previous and next score of a score within a book.
+/// The scores are ordered as described in [get_book_content], the ids are `null` at the ends of the book.
+/// If the score is not part of the book, the server will return a 'Not Found'.
+///
+/// # Arguments
+///
+/// * `book`: the name of the book
+/// * `id`: the id of the score whose neighbors are requested
+/// * `conf`: the application configuration
+/// * `_archive_role`: the archive role guard
+/// * `client`: the client to send the database requests with
+///
+/// returns: Result<Json<ScoreNeighbors>, Error>
+#[openapi(tag = "Archive")]
+#[get("/<book>/scores/<id>/neighbors")]
+pub async fn get_score_neighbors(
+    book: String,
+    id: String,
+    conf: &State<Config>,
+    _archive_role: ExecutiveRole<Archive>,
+    client: &State<DatabaseClient>,
+) -> ApiResult<ScoreNeighbors> {
+    crate::database::score::book_score_neighbors(conf, client, book, id).await
+}
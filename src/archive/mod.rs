@@ -27,21 +27,48 @@ pub mod model;
 pub mod score;
 /// Controller module to handle endpoints regarding statistics.
 pub mod statistic;
+/// Module which notifies webhooks about changed scores.
+pub mod webhook;
 
 pub fn get_scores_routes_and_docs(settings: &OpenApiSettings) -> (Vec<rocket::Route>, OpenApi) {
     openapi_get_routes_spec![
         settings: score::get_scores,
+        score::get_sorted_scores,
         score::get_score,
+        score::get_scores_batch,
         score::search_scores,
+        score::get_missing_credits_scores,
+        score::get_unfiled_scores,
+        score::get_scores_by_subtitle,
+        score::get_scores_by_alias,
+        score::get_title_suggestions,
+        score::get_composer_suggestions,
+        score::get_arranger_suggestions,
         score::put_score,
+        score::bulk_put_scores,
+        score::check_duplicates,
+        score::normalize_genres,
         score::delete_score,
+        score::get_score_attachment,
+        score::put_score_attachment,
     ]
 }
 
 pub fn get_books_routes_and_docs(settings: &OpenApiSettings) -> (Vec<rocket::Route>, OpenApi) {
-    openapi_get_routes_spec![settings: book::get_book_content,]
+    openapi_get_routes_spec![
+        settings: book::get_books,
+        book::put_book,
+        book::delete_book,
+        book::get_book_content,
+        book::get_books_content,
+        book::get_score_neighbors,
+    ]
 }
 
 pub fn get_statistics_routes_and_docs(settings: &OpenApiSettings) -> (Vec<rocket::Route>, OpenApi) {
-    openapi_get_routes_spec![settings: statistic::get_count_statistic,]
+    openapi_get_routes_spec![
+        settings: statistic::get_count_statistic,
+        statistic::get_count_statistic_csv,
+        statistic::get_book_sizes,
+    ]
 }
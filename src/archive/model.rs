@@ -15,9 +15,11 @@
 // along with this program; if not, write to the Free Software
 // Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
 
+use crate::config::Capitalization;
 use crate::openapi::SchemaExample;
 use rocket::serde::{Deserialize, Serialize};
 use rocket_okapi::JsonSchema;
+use std::collections::HashMap;
 use std::fmt;
 
 /// Representation of a score considering the intellectual property, not a specific copy.
@@ -32,7 +34,11 @@ pub struct Score {
     #[serde(rename = "_rev", skip_serializing_if = "Option::is_none")]
     pub couch_revision: Option<String>,
     /// The legacy id of this score. Will be removed.
+    /// With the `omit-legacy-ids` feature, the ids are no longer part of the responses but are still read from and kept in the database.
+    /// To migrate, enable the feature and make sure no client relies on the ids anymore.
+    /// Afterwards, the ids can be removed from the documents and this field can be dropped.
     #[deprecated]
+    #[cfg_attr(feature = "omit-legacy-ids", serde(skip_serializing))]
     pub legacy_ids: Vec<i64>,
     /// The main title of this score.
     pub title: String,
@@ -68,11 +74,39 @@ pub struct Score {
     pub pages: Vec<Page>,
 }
 
+/// A book which contains the pages of scores such as a march book.
+#[derive(Clone, Default, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(crate = "rocket::serde", default)]
+#[schemars(example = "Self::example")]
+pub struct Book {
+    /// The id of the book which couch db is using
+    #[serde(rename = "_id")]
+    pub couch_id: Option<String>,
+    /// The revision of the document couch db is using
+    #[serde(rename = "_rev", skip_serializing_if = "Option::is_none")]
+    pub couch_revision: Option<String>,
+    /// The name of the book which is referred by the pages of the scores.
+    pub name: String,
+    /// The annotation of this book.
+    pub annotation: Option<String>,
+}
+
+impl SchemaExample for Book {
+    fn example() -> Self {
+        Self {
+            couch_id: Some("books:4bd52a22-5bd3-4c93-a4d2-8ae8fb4d7b14".to_string()),
+            couch_revision: Some("1-967a00dff5e02add41819138abb3284d".to_string()),
+            name: "Marschbuch".to_string(),
+            annotation: Some("The blue one".to_string()),
+        }
+    }
+}
+
 /// A page which represents where a particular score is located in a book.
 /// A page can only contain one score at maximum.
 /// When a page contains multiple scores, only the first one will be stored here.
 /// The other scores should be persisted via references.
-#[derive(Clone, Default, Debug, Serialize, Deserialize, JsonSchema)]
+#[derive(Clone, Default, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 #[serde(crate = "rocket::serde")]
 #[schemars(example = "Self::example")]
 pub struct Page {
@@ -86,7 +120,7 @@ pub struct Page {
 }
 
 /// A page-number.
-#[derive(Clone, Default, Debug, Serialize, Deserialize, JsonSchema)]
+#[derive(Clone, Default, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 #[serde(crate = "rocket::serde", rename_all = "camelCase")]
 #[schemars(example = "Self::example")]
 pub struct PageNumber {
@@ -118,18 +152,31 @@ impl fmt::Display for ScoreSearchTermField {
     }
 }
 
+/// The way a fuzzy search term has to match the searched attribute.
+/// Characters which are ignored by the fuzzy search are also treated as word separators.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, JsonSchema, FromFormField)]
+pub enum MatchMode {
+    /// The term may occur anywhere in the attribute.
+    #[default]
+    Substring,
+    /// The attribute has to start with the term.
+    Prefix,
+    /// The term has to occur as whole word within the attribute.
+    Word,
+}
+
 pub type CountStatistic = Statistic<String, u64>;
 
 /// A statistic from the database.
 /// Typically the result of reduced design documents.
-#[derive(JsonSchema, Serialize, Deserialize, Debug)]
+#[derive(JsonSchema, Serialize, Deserialize, Debug, Clone)]
 pub struct Statistic<K, V> {
     /// The rows of the statistic.
     pub rows: Vec<StatisticEntry<K, V>>,
 }
 
 /// A single statistic entry which may contain information such as a count as a value for an string id.
-#[derive(JsonSchema, Serialize, Deserialize, Debug)]
+#[derive(JsonSchema, Serialize, Deserialize, Debug, Clone)]
 pub struct StatisticEntry<K, V> {
     /// The key of this statistic entry.
     pub key: K,
@@ -161,6 +208,161 @@ impl SchemaExample for Score {
     }
 }
 
+impl Score {
+    /// Create a reduced copy of this score which is intended for the public.
+    /// This omits all fields which are only relevant for the archive internally such as the location or the annotation.
+    ///
+    /// returns: Score
+    #[allow(deprecated)]
+    pub fn public(self) -> Self {
+        Self {
+            couch_revision: None,
+            legacy_ids: vec![],
+            annotation: None,
+            location: None,
+            ..self
+        }
+    }
+
+    /// Apply the capitalization to all genres of this score.
+    /// Genres which are equal after the capitalization will only be kept once.
+    ///
+    /// # Arguments
+    ///
+    /// * `capitalization`: the capitalization to apply
+    pub fn normalize_genres(&mut self, capitalization: Capitalization) {
+        if capitalization == Capitalization::Unchanged {
+            return;
+        }
+        let mut genres: Vec<String> = vec![];
+        self.genres
+            .iter()
+            .map(|genre| capitalize(genre, capitalization))
+            .for_each(|genre| {
+                if !genres.contains(&genre) {
+                    genres.push(genre);
+                }
+            });
+        self.genres = genres;
+    }
+}
+
+/// Apply a capitalization to a free-text value.
+/// For [Capitalization::TitleCase], every letter which follows a whitespace or a hyphen is considered as the beginning of a word.
+///
+/// # Arguments
+///
+/// * `value`: the value to capitalize
+/// * `capitalization`: the capitalization to apply
+///
+/// returns: String
+fn capitalize(value: &str, capitalization: Capitalization) -> String {
+    match capitalization {
+        Capitalization::Unchanged => value.to_string(),
+        Capitalization::Lowercase => value.to_lowercase(),
+        Capitalization::TitleCase => {
+            let mut word_begin = true;
+            value
+                .chars()
+                .flat_map(|c| {
+                    let capitalized: Vec<char> = if word_begin {
+                        c.to_uppercase().collect()
+                    } else {
+                        c.to_lowercase().collect()
+                    };
+                    word_begin = c.is_whitespace() || c == '-';
+                    capitalized
+                })
+                .collect()
+        }
+    }
+}
+
+/// The result of normalizing all the genres in the archive.
+#[derive(Clone, Default, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(crate = "rocket::serde", rename_all = "camelCase")]
+#[schemars(example = "Self::example")]
+pub struct GenreNormalization {
+    /// The number of scores which were examined.
+    pub examined: u64,
+    /// The number of scores whose genres were changed and stored.
+    pub modified: u64,
+}
+
+impl SchemaExample for GenreNormalization {
+    fn example() -> Self {
+        Self {
+            examined: 1200,
+            modified: 17,
+        }
+    }
+}
+
+/// The neighbors of a score within a book in the usual book order.
+/// Intended for the navigation to the previous or next score in a folder.
+#[derive(Clone, Default, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(crate = "rocket::serde", rename_all = "camelCase")]
+#[schemars(example = "Self::example")]
+pub struct ScoreNeighbors {
+    /// The id of the previous score, `None` if the score is the first one.
+    pub previous: Option<String>,
+    /// The id of the next score, `None` if the score is the last one.
+    pub next: Option<String>,
+}
+
+impl SchemaExample for ScoreNeighbors {
+    fn example() -> Self {
+        Self {
+            previous: Some("scores:6a1f3c0e-5b1e-4a4f-8d7e-2f1c0b9a8e71".to_string()),
+            next: None,
+        }
+    }
+}
+
+/// The ids of the scores to receive at once.
+#[derive(Clone, Default, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(crate = "rocket::serde", rename_all = "camelCase")]
+#[schemars(example = "Self::example")]
+pub struct ScoreBatchRequest {
+    /// The ids of the scores, each must start with the score partition.
+    pub ids: Vec<String>,
+}
+
+impl SchemaExample for ScoreBatchRequest {
+    fn example() -> Self {
+        Self {
+            ids: vec![
+                "scores:6a1f3c0e-5b1e-4a4f-8d7e-2f1c0b9a8e71".to_string(),
+                "scores:0b5c9d2e-3f4a-4b6c-9d8e-7f6a5b4c3d2e".to_string(),
+            ],
+        }
+    }
+}
+
+/// Multiple scores received at once by their ids.
+#[derive(Clone, Default, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(crate = "rocket::serde", rename_all = "camelCase")]
+#[schemars(example = "Self::example")]
+pub struct ScoreBatch {
+    /// The found scores by their id.
+    pub scores: HashMap<String, Score>,
+    /// The requested ids which do not exist or have been deleted.
+    pub missing: Vec<String>,
+}
+
+impl SchemaExample for ScoreBatch {
+    fn example() -> Self {
+        let score = Score::example();
+        Self {
+            scores: HashMap::from([(
+                "scores:6a1f3c0e-5b1e-4a4f-8d7e-2f1c0b9a8e71".to_string(),
+                score,
+            )]),
+            missing: vec!["scores:0b5c9d2e-3f4a-4b6c-9d8e-7f6a5b4c3d2e".to_string()],
+        }
+    }
+}
+
 impl SchemaExample for PageNumber {
     fn example() -> Self {
         Self {
@@ -188,6 +390,17 @@ impl SchemaExample for ScoreSearchTermField {
 }
 
 impl ScoreSearchTermField {
+    /// All fields which can be searched, used to search every text field of a score at once.
+    pub const ALL: [ScoreSearchTermField; 7] = [
+        ScoreSearchTermField::Title,
+        ScoreSearchTermField::Genres,
+        ScoreSearchTermField::Subtitles,
+        ScoreSearchTermField::Arrangers,
+        ScoreSearchTermField::Composers,
+        ScoreSearchTermField::Alias,
+        ScoreSearchTermField::Publisher,
+    ];
+
     pub fn is_array(&self) -> bool {
         match self {
             ScoreSearchTermField::Title => false,
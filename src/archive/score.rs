@@ -15,24 +15,39 @@
 // along with this program; if not, write to the Free Software
 // Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
 
-use reqwest::Client;
+use std::io::Cursor;
+
+use futures::future::join;
+use okapi::map;
+use okapi::openapi3::{RefOr, Responses};
+use rocket::http::{ContentType, MediaType, Status};
+use rocket::response::Responder;
 use rocket::serde::json::Json;
-use rocket::State;
+use rocket::{Request, Response, State};
+use rocket_okapi::gen::OpenApiGenerator;
 use rocket_okapi::openapi;
+use rocket_okapi::response::OpenApiResponderInner;
 
-use crate::archive::model::Score;
-use crate::database::client::{FindResponse, OperationResponse, Pagination};
+use crate::archive::model::{
+    CountStatistic, GenreNormalization, Score, ScoreBatch, ScoreBatchRequest, ScoreSearchTermField,
+};
+use crate::archive::statistic::CountStatisticType;
+use crate::archive::webhook::{ScoreChange, WebhookQueue};
+use crate::database::client::{DatabaseClient, FindResponse, OperationResponse, Pagination};
 use crate::database::score::{all_scores, ScoreSearchParameters};
-use crate::openapi::ApiResult;
+use crate::database::statistic::prefix_statistic;
+use crate::openapi::{ApiError, ApiResult};
+use crate::upload::Upload;
+use crate::user::auth::authorization_error;
 use crate::user::executives::{Archive, ExecutiveRole};
 use crate::Config;
 
 /// Get all scores from the database with pagination.
-/// The parameters specify the value itself, the fields to search for and the ordering.
+/// The scores are ordered by their id, see [get_sorted_scores] in order to sort them by a field.
 ///
 /// # Arguments
 ///
-/// * `limit`: the maximum amount of returned rows
+/// * `limit`: the maximum amount of returned rows, at most [crate::config::DatabaseConfig::max_page_limit]
 /// * `skip`: how many scores should be skipped
 /// * `_archive_role`: the archive role guard
 /// * `conf`: the application configuration
@@ -46,11 +61,49 @@ pub async fn get_scores(
     skip: u64,
     _archive_role: ExecutiveRole<Archive>,
     conf: &State<Config>,
-    client: &State<Client>,
+    client: &State<DatabaseClient>,
 ) -> ApiResult<Pagination<Score>> {
     all_scores(conf, client, limit, skip).await
 }
 
+/// Browse all scores sorted by a field.
+/// Sorting by a field requires a matching index in the database, see [crate::config::DatabaseConfig::sort_index].
+/// Scores without the field are omitted.
+/// The results support the same pagination as [search_scores].
+///
+/// # Arguments
+///
+/// * `sort`: the field to sort the scores by
+/// * `ascending`: the direction of the sort, defaults to ascending
+/// * `limit`: the limit of documents for a result page, at most [crate::config::DatabaseConfig::max_page_limit]
+/// * `bookmark`: the bookmark used for pagination
+/// * `_archive_role`: the archive role guard
+/// * `conf`: the application configuration
+/// * `client`: the client to perform the database requests with
+///
+/// returns: ApiResult<FindResponse<Score>>
+#[openapi(tag = "Archive")]
+#[get("/sorted?<sort>&<ascending>&<limit>&<bookmark>")]
+pub async fn get_sorted_scores(
+    sort: ScoreSearchTermField,
+    ascending: Option<bool>,
+    limit: u64,
+    bookmark: Option<String>,
+    _archive_role: ExecutiveRole<Archive>,
+    conf: &State<Config>,
+    client: &State<DatabaseClient>,
+) -> ApiResult<FindResponse<Score>> {
+    crate::database::score::sorted_scores(
+        conf,
+        client,
+        sort,
+        ascending.unwrap_or(true),
+        limit,
+        bookmark,
+    )
+    .await
+}
+
 /// A request for searching scores in the database.
 ///
 /// # Paginating
@@ -61,11 +114,17 @@ pub async fn get_scores(
 /// E.g.: if `limit = 10` and `bookmark` is unset, the first 10 results will be shown.
 /// Within this response, the server will return a `bookmark` string.
 /// This string can be used in the next request in order to retrieve the next 10 results and so on.
+/// If [crate::config::DatabaseConfig::bookmark_secret] is set, the bookmark is signed and a modified one is rejected with 'Bad Request'.
+///
+/// # Anonymous Access
+///
+/// If [crate::config::ArchiveConfig::anonymous_read] is set, this request is also available without the archive role.
+/// In this case, the location criteria is ignored and only reduced scores are returned.
 ///
 /// # Arguments
 ///
 /// * `parameters`: the parameters to perform the search
-/// * `_archive_role`: the archive role guard
+/// * `archive_role`: the archive role guard, anonymous access is checked if absent
 /// * `conf`: the application configuration
 /// * `client`: the http client to perform the database query
 ///
@@ -74,19 +133,234 @@ pub async fn get_scores(
 #[get("/searches?<parameters..>")]
 pub async fn search_scores(
     parameters: ScoreSearchParameters,
+    archive_role: Option<ExecutiveRole<Archive>>,
+    conf: &State<Config>,
+    client: &State<DatabaseClient>,
+) -> ApiResult<FindResponse<Score>> {
+    if archive_role.is_some() {
+        return crate::database::score::search_scores(conf, client, parameters).await;
+    }
+    check_anonymous_read(conf)?;
+    let mut response =
+        crate::database::score::search_scores(conf, client, parameters.public()).await?;
+    response.docs = response.docs.drain(..).map(Score::public).collect();
+    Ok(response)
+}
+
+/// Search all scores which have neither composers nor arrangers.
+/// This is intended as a report for librarians to find scores which require attribution research.
+/// The results are sorted by their title and support the same pagination as [search_scores].
+///
+/// # Arguments
+///
+/// * `limit`: the limit of documents for a result page, at most [crate::config::DatabaseConfig::max_page_limit]
+/// * `bookmark`: the bookmark used for pagination
+/// * `_archive_role`: the archive role guard
+/// * `conf`: the application configuration
+/// * `client`: the http client to perform the database query
+///
+/// returns: Result<Json<FindResponse<Score>>, Error>
+#[openapi(tag = "Archive")]
+#[get("/missing-credits?<limit>&<bookmark>")]
+pub async fn get_missing_credits_scores(
+    limit: u64,
+    bookmark: Option<String>,
+    _archive_role: ExecutiveRole<Archive>,
+    conf: &State<Config>,
+    client: &State<DatabaseClient>,
+) -> ApiResult<FindResponse<Score>> {
+    crate::database::score::missing_credits_scores(conf, client, limit, bookmark).await
+}
+
+/// Search all scores which have never been assigned to any page, such as legacy imports.
+/// Scores whose pages have been removed are not considered as unfiled.
+/// The results are sorted by their title and support the same pagination as [search_scores].
+///
+/// # Arguments
+///
+/// * `limit`: the limit of documents for a result page, at most [crate::config::DatabaseConfig::max_page_limit]
+/// * `bookmark`: the bookmark used for pagination
+/// * `_archive_role`: the archive role guard
+/// * `conf`: the application configuration
+/// * `client`: the http client to perform the database query
+///
+/// returns: Result<Json<FindResponse<Score>>, Error>
+#[openapi(tag = "Archive")]
+#[get("/unfiled?<limit>&<bookmark>")]
+pub async fn get_unfiled_scores(
+    limit: u64,
+    bookmark: Option<String>,
     _archive_role: ExecutiveRole<Archive>,
     conf: &State<Config>,
-    client: &State<Client>,
+    client: &State<DatabaseClient>,
 ) -> ApiResult<FindResponse<Score>> {
-    crate::database::score::search_scores(conf, client, parameters).await
+    crate::database::score::unfiled_scores(conf, client, limit, bookmark).await
+}
+
+/// Search all scores which contain a subtitle matching the term.
+/// This is intended for the common lookup which potpourri or medley contains a certain tune.
+/// The results are sorted by their title and support the same pagination as [search_scores].
+/// If [crate::config::ArchiveConfig::anonymous_read] is set, this request is also available without the archive role but only returns reduced scores.
+///
+/// # Arguments
+///
+/// * `term`: the term to search for in the subtitles
+/// * `regex`: if `true` the `term` will be interpreted as a regular expression instead of a fuzzy search term
+/// * `limit`: the limit of documents for a result page, at most [crate::config::DatabaseConfig::max_page_limit]
+/// * `bookmark`: the bookmark used for pagination
+/// * `archive_role`: the archive role guard, anonymous access is checked if absent
+/// * `conf`: the application configuration
+/// * `client`: the http client to perform the database query
+///
+/// returns: Result<Json<FindResponse<Score>>, Error>
+#[openapi(tag = "Archive")]
+#[get("/by-subtitle?<term>&<regex>&<limit>&<bookmark>")]
+pub async fn get_scores_by_subtitle(
+    term: String,
+    regex: Option<bool>,
+    limit: u64,
+    bookmark: Option<String>,
+    archive_role: Option<ExecutiveRole<Archive>>,
+    conf: &State<Config>,
+    client: &State<DatabaseClient>,
+) -> ApiResult<FindResponse<Score>> {
+    if archive_role.is_none() {
+        check_anonymous_read(conf)?;
+    }
+    let mut response =
+        crate::database::score::subtitle_scores(conf, client, term, regex, limit, bookmark).await?;
+    if archive_role.is_none() {
+        response.docs = response.docs.drain(..).map(Score::public).collect();
+    }
+    Ok(response)
+}
+
+/// Search all scores which contain an alias matching the term.
+/// This allows to find a score by an alternative title such as its bohemian name.
+/// The results are sorted by their title and support the same pagination as [search_scores].
+/// If [crate::config::ArchiveConfig::anonymous_read] is set, this request is also available without the archive role but only returns reduced scores.
+///
+/// # Arguments
+///
+/// * `term`: the term to search for in the aliases
+/// * `regex`: if `true` the `term` will be interpreted as a regular expression instead of a fuzzy search term
+/// * `limit`: the limit of documents for a result page, at most [crate::config::DatabaseConfig::max_page_limit]
+/// * `bookmark`: the bookmark used for pagination
+/// * `archive_role`: the archive role guard, anonymous access is checked if absent
+/// * `conf`: the application configuration
+/// * `client`: the http client to perform the database query
+///
+/// returns: Result<Json<FindResponse<Score>>, Error>
+#[openapi(tag = "Archive")]
+#[get("/by-alias?<term>&<regex>&<limit>&<bookmark>")]
+pub async fn get_scores_by_alias(
+    term: String,
+    regex: Option<bool>,
+    limit: u64,
+    bookmark: Option<String>,
+    archive_role: Option<ExecutiveRole<Archive>>,
+    conf: &State<Config>,
+    client: &State<DatabaseClient>,
+) -> ApiResult<FindResponse<Score>> {
+    if archive_role.is_none() {
+        check_anonymous_read(conf)?;
+    }
+    let mut response =
+        crate::database::score::alias_scores(conf, client, term, regex, limit, bookmark).await?;
+    if archive_role.is_none() {
+        response.docs = response.docs.drain(..).map(Score::public).collect();
+    }
+    Ok(response)
+}
+
+/// Get the titles which start with the prefix, ignoring the case.
+/// This is intended for autocompletion while typing and is much cheaper than a fuzzy search.
+/// The number of returned titles is limited by [crate::config::ArchiveConfig::title_suggestions_limit].
+/// If [crate::config::ArchiveConfig::anonymous_read] is set, this request is also available without the archive role.
+///
+/// # Arguments
+///
+/// * `prefix`: the prefix of the titles
+/// * `archive_role`: the archive role guard, anonymous access is checked if absent
+/// * `conf`: the application configuration
+/// * `client`: the http client to perform the database query
+///
+/// returns: Result<Json<Vec<String>>, Error>
+#[openapi(tag = "Archive")]
+#[get("/title-suggestions?<prefix>")]
+pub async fn get_title_suggestions(
+    prefix: String,
+    archive_role: Option<ExecutiveRole<Archive>>,
+    conf: &State<Config>,
+    client: &State<DatabaseClient>,
+) -> ApiResult<Vec<String>> {
+    if archive_role.is_none() {
+        check_anonymous_read(conf)?;
+    }
+    crate::database::score::title_suggestions(conf, client, prefix).await
+}
+
+/// Get the composers which start with the prefix together with their number of scores.
+/// This is intended for autocompletion while typing, the prefix is case-sensitive.
+/// The number of returned composers is limited by [crate::config::ArchiveConfig::title_suggestions_limit].
+/// If [crate::config::ArchiveConfig::anonymous_read] is set, this request is also available without the archive role.
+///
+/// # Arguments
+///
+/// * `prefix`: the prefix of the composers
+/// * `archive_role`: the archive role guard, anonymous access is checked if absent
+/// * `conf`: the application configuration
+/// * `client`: the http client to perform the database query
+///
+/// returns: Result<Json<Statistic<String, u64>>, Error>
+#[openapi(tag = "Archive")]
+#[get("/composers?<prefix>")]
+pub async fn get_composer_suggestions(
+    prefix: String,
+    archive_role: Option<ExecutiveRole<Archive>>,
+    conf: &State<Config>,
+    client: &State<DatabaseClient>,
+) -> ApiResult<CountStatistic> {
+    if archive_role.is_none() {
+        check_anonymous_read(conf)?;
+    }
+    prefix_statistic(conf, client, CountStatisticType::Composers, prefix).await
+}
+
+/// Get the arrangers which start with the prefix together with their number of scores.
+/// This is intended for autocompletion while typing, the prefix is case-sensitive.
+/// The number of returned arrangers is limited by [crate::config::ArchiveConfig::title_suggestions_limit].
+/// If [crate::config::ArchiveConfig::anonymous_read] is set, this request is also available without the archive role.
+///
+/// # Arguments
+///
+/// * `prefix`: the prefix of the arrangers
+/// * `archive_role`: the archive role guard, anonymous access is checked if absent
+/// * `conf`: the application configuration
+/// * `client`: the http client to perform the database query
+///
+/// returns: Result<Json<Statistic<String, u64>>, Error>
+#[openapi(tag = "Archive")]
+#[get("/arrangers?<prefix>")]
+pub async fn get_arranger_suggestions(
+    prefix: String,
+    archive_role: Option<ExecutiveRole<Archive>>,
+    conf: &State<Config>,
+    client: &State<DatabaseClient>,
+) -> ApiResult<CountStatistic> {
+    if archive_role.is_none() {
+        check_anonymous_read(conf)?;
+    }
+    prefix_statistic(conf, client, CountStatisticType::Arrangers, prefix).await
 }
 
 /// Find a single score by its id.
+/// If [crate::config::ArchiveConfig::anonymous_read] is set, this request is also available without the archive role but only returns a reduced score.
 ///
 /// # Arguments
 ///
 /// * `id`: the id of the document which contains the score
-/// * `_archive_role`: the archive role guard
+/// * `archive_role`: the archive role guard, anonymous access is checked if absent
 /// * `conf`: the application configuration
 /// * `client` the client to send the request with
 ///
@@ -95,53 +369,314 @@ pub async fn search_scores(
 #[get("/<id>")]
 pub async fn get_score(
     id: String,
-    _archive_role: ExecutiveRole<Archive>,
+    archive_role: Option<ExecutiveRole<Archive>>,
     conf: &State<Config>,
-    client: &State<Client>,
+    client: &State<DatabaseClient>,
 ) -> ApiResult<Score> {
-    crate::database::score::get_score(conf, client, id).await
+    if archive_role.is_some() {
+        return crate::database::score::get_score(conf, client, id).await;
+    }
+    check_anonymous_read(conf)?;
+    let score = crate::database::score::get_score(conf, client, id).await?;
+    Ok(Json(score.0.public()))
+}
+
+/// Get multiple scores by their ids within a single request.
+/// The ids which do not belong to a score are listed as missing.
+/// If any id does not start with the score partition or there are more than [crate::config::DatabaseConfig::max_page_limit] ids, the whole request is rejected with `422 Unprocessable Entity`.
+///
+/// # Arguments
+///
+/// * `batch`: the ids of the scores
+/// * `_archive_role`: the archive role guard
+/// * `conf`: the application configuration
+/// * `client`: the client to send the request with
+///
+/// returns: Result<Json<ScoreBatch>, Error>
+#[openapi(tag = "Archive")]
+#[post("/batch", data = "<batch>")]
+pub async fn get_scores_batch(
+    batch: Json<ScoreBatchRequest>,
+    _archive_role: ExecutiveRole<Archive>,
+    conf: &State<Config>,
+    client: &State<DatabaseClient>,
+) -> ApiResult<ScoreBatch> {
+    crate::database::score::get_scores_batch(conf, client, batch.0.ids).await
 }
 
 /// Insert a score into the database.
 /// When creating a new score, make sure to leave its `_id` and `rev` to `None` and set both on update.
 /// In the case of an `409 Conflict` just get the current revision of the score and try again.
+/// If `force` is set, this is done once by the server which overwrites concurrent changes of the score.
 ///
 /// # Arguments
 ///
 /// * `score`: the score to insert
+/// * `force`: whether to retry once with the current revision on a conflict
 /// * `_archive_role`: the archive role guard
 /// * `conf`: the application configuration
 /// * `client`: the client to perform the request with
+/// * `webhooks`: the queue to notify the webhooks about the changed score
 #[openapi(tag = "Archive")]
-#[put("/", data = "<score>")]
+#[put("/?<force>", data = "<score>")]
 pub async fn put_score(
     score: Json<Score>,
+    force: Option<bool>,
+    _archive_role: ExecutiveRole<Archive>,
+    conf: &State<Config>,
+    client: &State<DatabaseClient>,
+    webhooks: &State<WebhookQueue>,
+) -> ApiResult<OperationResponse> {
+    let response = if force.unwrap_or(false) {
+        crate::database::score::upsert_score(conf, client, score.0).await?
+    } else {
+        crate::database::score::put_score(conf, client, score.0).await?
+    };
+    webhooks.notify(ScoreChange::from_response(&response, false));
+    Ok(response)
+}
+
+/// Find existing scores which are likely duplicates of a score before inserting it.
+/// The title and aliases of the score are searched fuzzily as whole words within the titles and aliases of the stored scores.
+/// This is only advisory, the score is neither checked nor rejected by [put_score].
+///
+/// # Arguments
+///
+/// * `score`: the score to check
+/// * `_archive_role`: the archive role guard
+/// * `conf`: the application configuration
+/// * `client`: the client to perform the requests with
+///
+/// returns: Result<Json<Vec<Score>>, Error> the likely duplicates sorted by their title
+#[openapi(tag = "Archive")]
+#[post("/check-duplicates", data = "<score>")]
+pub async fn check_duplicates(
+    score: Json<Score>,
+    _archive_role: ExecutiveRole<Archive>,
+    conf: &State<Config>,
+    client: &State<DatabaseClient>,
+) -> ApiResult<Vec<Score>> {
+    crate::database::score::duplicate_scores(conf, client, &score).await
+}
+
+/// Insert or update multiple scores at once.
+/// This is intended for imports of many scores and much faster than inserting them one by one.
+/// Every score must follow the same rules as in [put_score], otherwise none of them is stored.
+/// The response contains the result of each score in the same order, failed scores such as conflicts contain an `error` and a `reason`.
+///
+/// # Arguments
+///
+/// * `scores`: the scores to insert or update
+/// * `_archive_role`: the archive role guard
+/// * `conf`: the application configuration
+/// * `client`: the client to perform the request with
+/// * `webhooks`: the queue to notify the webhooks about the changed scores
+///
+/// returns: Result<Json<Vec<OperationResponse>>, Error>
+#[openapi(tag = "Archive")]
+#[post("/bulk", data = "<scores>")]
+pub async fn bulk_put_scores(
+    scores: Json<Vec<Score>>,
+    _archive_role: ExecutiveRole<Archive>,
+    conf: &State<Config>,
+    client: &State<DatabaseClient>,
+    webhooks: &State<WebhookQueue>,
+) -> ApiResult<Vec<OperationResponse>> {
+    let responses = crate::database::score::bulk_put_scores(conf, client, scores.0).await?;
+    responses
+        .iter()
+        .filter(|r| r.ok)
+        .for_each(|r| webhooks.notify(ScoreChange::from_response(r, false)));
+    Ok(responses)
+}
+
+/// Apply the configured genre capitalization to all scores in the database.
+/// New and updated scores are normalized on insertion anyway, this is intended to clean up the existing ones.
+/// Fails if no genre capitalization is configured.
+/// The webhooks are notified about every score whose genres changed.
+///
+/// # Arguments
+///
+/// * `_archive_role`: the archive role guard
+/// * `conf`: the application configuration
+/// * `client`: the client to perform the requests with
+/// * `webhooks`: the queue to notify the webhooks about the changed scores
+///
+/// returns: Result<Json<GenreNormalization>, Error>
+#[openapi(tag = "Archive")]
+#[post("/genre-normalizations")]
+pub async fn normalize_genres(
+    _archive_role: ExecutiveRole<Archive>,
+    conf: &State<Config>,
+    client: &State<DatabaseClient>,
+    webhooks: &State<WebhookQueue>,
+) -> ApiResult<GenreNormalization> {
+    let (normalization, responses) =
+        crate::database::score::normalize_all_genres(conf, client).await?;
+    responses
+        .iter()
+        .for_each(|r| webhooks.notify(ScoreChange::from_response(r, false)));
+    Ok(Json(normalization))
+}
+
+/// The sheet music of a score as it is stored in the database.
+pub struct ScoreAttachment(pub ContentType, pub Vec<u8>);
+
+impl<'r> Responder<'r, 'static> for ScoreAttachment {
+    fn respond_to(self, _request: &'r Request<'_>) -> rocket::response::Result<'static> {
+        Response::build()
+            .header(self.0)
+            .streamed_body(Cursor::new(self.1))
+            .ok()
+    }
+}
+
+impl OpenApiResponderInner for ScoreAttachment {
+    fn responses(_gen: &mut OpenApiGenerator) -> rocket_okapi::Result<Responses> {
+        let pdf = okapi::openapi3::MediaType::default();
+        let pdf_response = okapi::openapi3::Response {
+            description: "The sheet music of the score".to_string(),
+            content: map! {MediaType::PDF.to_string() => pdf},
+            ..okapi::openapi3::Response::default()
+        };
+        let responses = map! {"200".to_string() => RefOr::Object(pdf_response)};
+        Ok(Responses {
+            default: None,
+            responses,
+            extensions: map! {},
+        })
+    }
+}
+
+/// Get the sheet music of a score.
+/// If the score has no sheet music attached, 'Not Found' will be returned.
+///
+/// # Arguments
+///
+/// * `id`: the id of the score
+/// * `_archive_role`: the archive role guard
+/// * `conf`: the application configuration
+/// * `client`: the client to perform the request with
+///
+/// returns: Result<ScoreAttachment, ApiError>
+#[openapi(tag = "Archive")]
+#[get("/<id>/attachment")]
+pub async fn get_score_attachment(
+    id: String,
+    _archive_role: ExecutiveRole<Archive>,
+    conf: &State<Config>,
+    client: &State<DatabaseClient>,
+) -> Result<ScoreAttachment, ApiError> {
+    let (content_type, content) =
+        crate::database::score::get_score_attachment(conf, client, id).await?;
+    let content_type = content_type
+        .and_then(|c| ContentType::parse_flexible(&c))
+        .unwrap_or(ContentType::PDF);
+    Ok(ScoreAttachment(content_type, content))
+}
+
+/// Attach the sheet music as PDF document to a score.
+/// An existing attachment will be replaced.
+/// The revision must match the current one of the score, the response contains the new revision.
+/// The document is passed on to the database while it is received.
+/// The maximum size of the document is determined by the `bytes` limit of the server.
+///
+/// # Arguments
+///
+/// * `id`: the id of the score
+/// * `rev`: the current revision of the score
+/// * `pdf`: the PDF document
+/// * `_archive_role`: the archive role guard
+/// * `conf`: the application configuration
+/// * `client`: the client to perform the request with
+/// * `webhooks`: the queue to notify the webhooks about the changed score
+///
+/// returns: Result<Json<OperationResponse>, Error>
+#[openapi(tag = "Archive")]
+#[put("/<id>/attachment?<rev>", format = "application/pdf", data = "<pdf>")]
+pub async fn put_score_attachment(
+    id: String,
+    rev: String,
+    pdf: Upload<'_>,
     _archive_role: ExecutiveRole<Archive>,
     conf: &State<Config>,
-    client: &State<Client>,
+    client: &State<DatabaseClient>,
+    webhooks: &State<WebhookQueue>,
 ) -> ApiResult<OperationResponse> {
-    crate::database::score::put_score(conf, client, score.0).await
+    let (body, upload) = pdf.forward(None);
+    let (response, uploaded) = join(
+        crate::database::score::put_score_attachment(conf, client, id, rev, body),
+        upload,
+    )
+    .await;
+    uploaded?;
+    let response = response?;
+    webhooks.notify(ScoreChange::from_response(&response, false));
+    Ok(response)
 }
 
 /// Delete a score by its id and revision.
+/// If `force` is set, the `rev` must be omitted and the current revision is used, a conflict is retried once.
+/// This discards concurrent changes of the score, so it should only be used if the score is deleted anyway.
+/// Setting both is rejected with `400 Bad Request` as the revision would be ignored.
 ///
 /// # Arguments
 ///
 /// * `id`: the id of the score to delete
-/// * `rev`: the revision of the score to delete
+/// * `rev`: the revision of the score to delete, required unless `force` is set and forbidden otherwise
+/// * `force`: whether to delete the current revision of the score
 /// * `_archive_role`: the archive role guard
 /// * `conf`: the application configuration
 /// * `client`: the client to perform the request
+/// * `webhooks`: the queue to notify the webhooks about the changed score
 ///
 /// returns: Result<Json<OperationResponse>, Error>
 #[openapi(tag = "Archive")]
-#[delete("/<id>?<rev>")]
+#[delete("/<id>?<rev>&<force>")]
 pub async fn delete_score(
     id: String,
-    rev: String,
+    rev: Option<String>,
+    force: Option<bool>,
     _archive_role: ExecutiveRole<Archive>,
     conf: &State<Config>,
-    client: &State<Client>,
+    client: &State<DatabaseClient>,
+    webhooks: &State<WebhookQueue>,
 ) -> ApiResult<OperationResponse> {
-    crate::database::score::delete_score(conf, client, id, rev).await
+    let response = match (rev, force.unwrap_or(false)) {
+        (Some(rev), false) => crate::database::score::delete_score(conf, client, id, rev).await?,
+        (None, true) => crate::database::score::force_delete_score(conf, client, id).await?,
+        (None, false) => {
+            return Err(ApiError {
+                err: "Bad Request".to_string(),
+                msg: Some("The revision is required unless 'force' is set".to_string()),
+                http_status_code: Status::BadRequest.code,
+            })
+        }
+        (Some(_), true) => {
+            return Err(ApiError {
+                err: "Bad Request".to_string(),
+                msg: Some("The revision cannot be combined with 'force'".to_string()),
+                http_status_code: Status::BadRequest.code,
+            })
+        }
+    };
+    webhooks.notify(ScoreChange::from_response(&response, true));
+    Ok(response)
+}
+
+/// Check whether reading the archive without the archive role is allowed.
+/// This is intended for routes which use an optional archive role guard.
+///
+/// # Arguments
+///
+/// * `conf`: the application configuration
+///
+/// returns: Result<(), ApiError>
+pub(crate) fn check_anonymous_read(conf: &Config) -> Result<(), ApiError> {
+    if conf.archive.anonymous_read {
+        Ok(())
+    } else {
+        debug!("Anonymous read of the archive is disabled");
+        Err(authorization_error())
+    }
 }
@@ -15,19 +15,26 @@
 // along with this program; if not, write to the Free Software
 // Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
 
-use reqwest::Client;
+use okapi::openapi3::{Parameter, ParameterValue};
+use rocket::request::FromParam;
 use rocket::serde::{Deserialize, Serialize};
 use rocket::State;
+use rocket_okapi::gen::OpenApiGenerator;
+use rocket_okapi::request::OpenApiFromParam;
 use rocket_okapi::{openapi, JsonSchema};
+use schemars::schema::SchemaObject;
+use serde_json::Value::String as Vs;
 
 use crate::archive::model::CountStatistic;
-use crate::database::statistic::count_statistic;
-use crate::openapi::ApiResult;
+use crate::csv::{csv_field, CsvAttachment};
+use crate::database::client::DatabaseClient;
+use crate::database::statistic::{cached_count_statistic, StatisticCache};
+use crate::openapi::{ApiError, ApiResult};
 use crate::user::executives::{Archive, ExecutiveRole};
 use crate::Config;
 
 /// Representation of a score field which can be used in a search.
-#[derive(Serialize, Deserialize, JsonSchema, FromFormField)]
+#[derive(Serialize, Deserialize, JsonSchema, FromFormField, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum CountStatisticType {
     Genres,
     Arrangers,
@@ -37,23 +44,168 @@ pub enum CountStatisticType {
     Books,
 }
 
+const COUNT_STATISTIC_IDS: [&str; 6] = [
+    "genres",
+    "arrangers",
+    "composers",
+    "publishers",
+    "locations",
+    "books",
+];
+
+/// A [CountStatisticType] given as path parameter with a `.csv` extension such as `genres.csv`.
+/// The second field holds the lowercase file name of the statistic.
+pub struct CsvCountStatisticType(CountStatisticType, String);
+
+impl FromParam<'_> for CsvCountStatisticType {
+    type Error = ();
+
+    fn from_param(param: &'_ str) -> Result<Self, Self::Error> {
+        let subject = param.strip_suffix(".csv").ok_or(())?.to_lowercase();
+        let statistic_type = match subject.as_str() {
+            "genres" => CountStatisticType::Genres,
+            "arrangers" => CountStatisticType::Arrangers,
+            "composers" => CountStatisticType::Composers,
+            "publishers" => CountStatisticType::Publishers,
+            "locations" => CountStatisticType::Locations,
+            "books" => CountStatisticType::Books,
+            _ => return Err(()),
+        };
+        Ok(CsvCountStatisticType(
+            statistic_type,
+            format!("{}.csv", subject),
+        ))
+    }
+}
+
+impl OpenApiFromParam<'_> for CsvCountStatisticType {
+    fn path_parameter(
+        _gen: &mut OpenApiGenerator,
+        name: String,
+    ) -> rocket_okapi::Result<Parameter> {
+        Ok(Parameter {
+            name,
+            location: "path".to_string(),
+            description: Some("The type of the statistic with the `.csv` extension".to_string()),
+            required: true,
+            deprecated: false,
+            allow_empty_value: false,
+            value: ParameterValue::Schema {
+                style: None,
+                explode: None,
+                allow_reserved: false,
+                schema: SchemaObject {
+                    format: Some("string".to_string()),
+                    enum_values: Some(
+                        COUNT_STATISTIC_IDS
+                            .iter()
+                            .map(|id| Vs(format!("{}.csv", id)))
+                            .collect(),
+                    ),
+                    ..Default::default()
+                },
+                example: None,
+                examples: None,
+            },
+            extensions: Default::default(),
+        })
+    }
+}
+
 /// Fetch the statistic for various items such as genres with their count.
+/// The statistics are cached for [crate::config::DatabaseConfig::statistic_cache_ttl] seconds unless `refresh` is set.
 ///
 /// # Arguments
 ///
 /// * `subject`: the type of the statistic to fetch
+/// * `refresh`: if `true`, the statistic is fetched from the database regardless of the cache
 /// * `_archive_role`: the archive role guard
+/// * `cache`: the cache of the statistics
 /// * `conf`: the application configuration
 /// * `client`: the client to perform database requests with
 ///
 /// returns: ApiResult<CountStatistic>
 #[openapi(tag = "Archive")]
-#[get("/counts?<subject>")]
+#[get("/counts?<subject>&<refresh>")]
 pub async fn get_count_statistic(
     subject: CountStatisticType,
+    refresh: Option<bool>,
     _archive_role: ExecutiveRole<Archive>,
+    cache: &State<StatisticCache>,
     conf: &State<Config>,
-    client: &State<Client>,
+    client: &State<DatabaseClient>,
 ) -> ApiResult<CountStatistic> {
-    count_statistic(conf, client, subject).await
+    cached_count_statistic(cache, conf, client, subject, refresh.unwrap_or(false)).await
+}
+
+/// Fetch all books with the number of scores filed in each of them.
+/// The books are sorted descending by their count and cached like [get_count_statistic].
+///
+/// # Arguments
+///
+/// * `refresh`: if `true`, the statistic is fetched from the database regardless of the cache
+/// * `_archive_role`: the archive role guard
+/// * `cache`: the cache of the statistics
+/// * `conf`: the application configuration
+/// * `client`: the client to perform database requests with
+///
+/// returns: ApiResult<CountStatistic>
+#[openapi(tag = "Archive")]
+#[get("/book-sizes?<refresh>")]
+pub async fn get_book_sizes(
+    refresh: Option<bool>,
+    _archive_role: ExecutiveRole<Archive>,
+    cache: &State<StatisticCache>,
+    conf: &State<Config>,
+    client: &State<DatabaseClient>,
+) -> ApiResult<CountStatistic> {
+    let mut statistic = cached_count_statistic(
+        cache,
+        conf,
+        client,
+        CountStatisticType::Books,
+        refresh.unwrap_or(false),
+    )
+    .await?;
+    statistic
+        .rows
+        .sort_by_key(|row| std::cmp::Reverse(row.value));
+    Ok(statistic)
+}
+
+/// Fetch the statistic for various items such as genres with their count as CSV.
+/// The CSV contains a header line and the columns `key` and `count`.
+/// The statistics are cached like [get_count_statistic].
+///
+/// # Arguments
+///
+/// * `subject`: the type of the statistic to fetch, suffixed with `.csv`
+/// * `refresh`: if `true`, the statistic is fetched from the database regardless of the cache
+/// * `_archive_role`: the archive role guard
+/// * `cache`: the cache of the statistics
+/// * `conf`: the application configuration
+/// * `client`: the client to perform database requests with
+///
+/// returns: Result<CsvAttachment, ApiError>
+#[openapi(tag = "Archive")]
+#[get("/<subject>?<refresh>")]
+pub async fn get_count_statistic_csv(
+    subject: CsvCountStatisticType,
+    refresh: Option<bool>,
+    _archive_role: ExecutiveRole<Archive>,
+    cache: &State<StatisticCache>,
+    conf: &State<Config>,
+    client: &State<DatabaseClient>,
+) -> Result<CsvAttachment, ApiError> {
+    let statistic =
+        cached_count_statistic(cache, conf, client, subject.0, refresh.unwrap_or(false)).await?;
+    let csv = statistic
+        .rows
+        .iter()
+        .map(|row| format!("{},{}\r\n", csv_field(&row.key), row.value))
+        .fold("key,count\r\n".to_string(), |csv, row| csv + &row);
+    Ok(CsvAttachment {
+        filename: subject.1,
+        content: csv,
+    })
 }
@@ -0,0 +1,191 @@
+// OpenKeg, the lightweight backend of the Musikverein Leopoldsdorf.
+// Copyright (C) 2022  Richard Stöckl
+//
+// This program is free software; you can redistribute it and/or
+// modify it under the terms of the GNU General Public License
+// as published by the Free Software Foundation; either version 2
+// of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+
+use std::time::Duration;
+
+use reqwest::Client;
+use rocket::serde::Serialize;
+use rocket::tokio::sync::mpsc::error::TrySendError;
+use rocket::tokio::sync::mpsc::{channel, Receiver, Sender};
+use rocket::tokio::{task, time};
+
+use crate::config::WebhookConfig;
+use crate::database::client::OperationResponse;
+use crate::keg_user_agent;
+
+#[cfg(test)]
+#[path = "webhook_tests.rs"]
+mod webhook_tests;
+
+/// The notification about a changed score which is sent to the webhooks.
+#[derive(Debug, Clone, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ScoreChange {
+    /// The id of the changed score.
+    pub id: String,
+    /// The revision of the score after the change.
+    pub rev: String,
+    /// Whether the score has been deleted.
+    pub deleted: bool,
+}
+
+impl ScoreChange {
+    /// Create the notification from the response of a successful database operation.
+    ///
+    /// # Arguments
+    ///
+    /// * `response`: the response of the operation which changed the score
+    /// * `deleted`: whether the operation deleted the score
+    ///
+    /// returns: ScoreChange
+    pub fn from_response(response: &OperationResponse, deleted: bool) -> Self {
+        Self {
+            id: response.id.clone(),
+            rev: response.rev.clone(),
+            deleted,
+        }
+    }
+}
+
+/// The bounded queues of score changes which are delivered to the webhooks in the background.
+/// Every webhook has its own queue and task, such that a slow or failing webhook does not delay the others.
+/// If no webhooks are configured, notifications are discarded immediately.
+pub struct WebhookQueue(Vec<(String, Sender<ScoreChange>)>);
+
+impl WebhookQueue {
+    /// Create a queue for every webhook and spawn the tasks which deliver the notifications in their order.
+    ///
+    /// # Arguments
+    ///
+    /// * `conf`: the configuration of the webhooks
+    ///
+    /// returns: WebhookQueue
+    pub fn new(conf: &WebhookConfig) -> Self {
+        if conf.urls.is_empty() {
+            return Self(vec![]);
+        }
+        let client = match Client::builder()
+            .user_agent(keg_user_agent())
+            .timeout(Duration::from_secs(conf.timeout))
+            .build()
+        {
+            Ok(client) => client,
+            Err(err) => {
+                error!("Cannot create the client for the webhooks: {}", err);
+                return Self(vec![]);
+            }
+        };
+        let queues = conf
+            .urls
+            .iter()
+            .map(|url| {
+                let (sender, receiver) = channel(conf.queue_size.max(1));
+                task::spawn(deliver_changes(
+                    client.clone(),
+                    conf.clone(),
+                    url.clone(),
+                    receiver,
+                ));
+                (url.clone(), sender)
+            })
+            .collect();
+        Self(queues)
+    }
+
+    /// Enqueue the notification about a changed score for every webhook without waiting for its delivery.
+    /// If the queue of a webhook is full, the notification is dropped for this webhook and a warning is logged.
+    ///
+    /// # Arguments
+    ///
+    /// * `change`: the notification to deliver
+    ///
+    /// returns: ()
+    pub fn notify(&self, change: ScoreChange) {
+        for (url, sender) in &self.0 {
+            match sender.try_send(change.clone()) {
+                Ok(_) => {}
+                Err(TrySendError::Full(change)) => warn!(
+                    "The queue of webhook '{}' is full, drop the notification for score '{}'",
+                    url, change.id
+                ),
+                Err(TrySendError::Closed(change)) => warn!(
+                    "The delivery to webhook '{}' has stopped, drop the notification for score '{}'",
+                    url, change.id
+                ),
+            }
+        }
+    }
+}
+
+/// Receive the score changes and deliver them to a single webhook until the queue is closed.
+///
+/// # Arguments
+///
+/// * `client`: the client to perform the requests with
+/// * `conf`: the configuration of the webhooks
+/// * `url`: the url of the webhook
+/// * `receiver`: the receiving end of the queue of the webhook
+///
+/// returns: ()
+async fn deliver_changes(
+    client: Client,
+    conf: WebhookConfig,
+    url: String,
+    mut receiver: Receiver<ScoreChange>,
+) {
+    while let Some(change) = receiver.recv().await {
+        deliver_change(&client, &conf, &url, &change).await;
+    }
+}
+
+/// Deliver a single notification to a webhook.
+/// Failed deliveries are retried with an exponential backoff until the configured attempts are exhausted.
+///
+/// # Arguments
+///
+/// * `client`: the client to perform the requests with
+/// * `conf`: the configuration of the webhooks
+/// * `url`: the url of the webhook
+/// * `change`: the notification to deliver
+///
+/// returns: ()
+async fn deliver_change(client: &Client, conf: &WebhookConfig, url: &str, change: &ScoreChange) {
+    let attempts = conf.attempts.max(1);
+    for attempt in 1..=attempts {
+        let result = client
+            .post(url)
+            .json(change)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status());
+        match result {
+            Ok(_) => return,
+            Err(err) if attempt < attempts => {
+                let delay = conf.retry_delay.saturating_mul(1 << (attempt - 1).min(16));
+                warn!(
+                    "Attempt {} to notify webhook '{}' about score '{}' failed, retry in {} seconds: {}",
+                    attempt, url, change.id, delay, err
+                );
+                time::sleep(Duration::from_secs(delay)).await;
+            }
+            Err(err) => error!(
+                "Failed to notify webhook '{}' about score '{}' after {} attempts: {}",
+                url, change.id, attempts, err
+            ),
+        }
+    }
+}
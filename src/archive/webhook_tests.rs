@@ -0,0 +1,104 @@
+// OpenKeg, the lightweight backend of the Musikverein Leopoldsdorf.
+// Copyright (C) 2022  Richard Stöckl
+//
+// This program is free software; you can redistribute it and/or
+// modify it under the terms of the GNU General Public License
+// as published by the Free Software Foundation; either version 2
+// of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::sync::mpsc;
+use std::thread;
+
+use super::*;
+
+/// Start a webhook which never responds.
+fn silent_webhook() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("listener");
+    let url = format!("http://{}", listener.local_addr().expect("address"));
+    thread::spawn(move || {
+        let mut streams = vec![];
+        for stream in listener.incoming() {
+            streams.push(stream);
+        }
+    });
+    url
+}
+
+/// Start a webhook which responds immediately and passes the received bodies on.
+fn responding_webhook() -> (String, mpsc::Receiver<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("listener");
+    let url = format!("http://{}", listener.local_addr().expect("address"));
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        for mut stream in listener.incoming().flatten() {
+            let mut request = vec![];
+            let mut buffer = [0; 1024];
+            let body = loop {
+                let read = stream.read(&mut buffer).expect("request");
+                request.extend_from_slice(&buffer[..read]);
+                let text = String::from_utf8_lossy(&request).to_string();
+                if let Some((head, body)) = text.split_once("\r\n\r\n") {
+                    let length = head
+                        .lines()
+                        .find_map(|l| {
+                            l.to_lowercase()
+                                .strip_prefix("content-length: ")
+                                .map(String::from)
+                        })
+                        .and_then(|l| l.trim().parse::<usize>().ok())
+                        .unwrap_or_default();
+                    if body.len() >= length {
+                        break body.to_string();
+                    }
+                }
+            };
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")
+                .expect("response");
+            sender.send(body).expect("body");
+        }
+    });
+    (url, receiver)
+}
+
+#[rocket::async_test]
+async fn silent_webhook_does_not_delay_others() {
+    let (url, received) = responding_webhook();
+    let conf = WebhookConfig {
+        urls: vec![silent_webhook(), url],
+        timeout: 30,
+        ..Default::default()
+    };
+    let queue = WebhookQueue::new(&conf);
+    for id in ["scores:1", "scores:2"] {
+        queue.notify(ScoreChange {
+            id: id.to_string(),
+            rev: "1-a".to_string(),
+            deleted: false,
+        });
+    }
+    let bodies = task::spawn_blocking(move || {
+        (0..2)
+            .map(|_| {
+                received
+                    .recv_timeout(Duration::from_secs(5))
+                    .expect("notification")
+            })
+            .collect::<Vec<_>>()
+    })
+    .await
+    .expect("bodies");
+    assert!(bodies[0].contains("scores:1"));
+    assert!(bodies[1].contains("scores:2"));
+}
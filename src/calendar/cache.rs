@@ -0,0 +1,238 @@
+// OpenKeg, the lightweight backend of the Musikverein Leopoldsdorf.
+// Copyright (C) 2023  Richard Stöckl
+//
+// This program is free software; you can redistribute it and/or
+// modify it under the terms of the GNU General Public License
+// as published by the Free Software Foundation; either version 2
+// of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use reqwest::Client;
+use rocket::http::Status;
+use rocket::tokio::sync::RwLock;
+
+use crate::calendar::model::{CalendarType, Event};
+use crate::config::{CalendarConfig, Config};
+use crate::keg_user_agent;
+use crate::openapi::ApiError;
+
+/// The parsed events of each calendar and the instant they were fetched.
+type CalendarEntries = HashMap<CalendarType, (Instant, Vec<Event>)>;
+
+/// The cache of the parsed events of each calendar together with the client to fetch them from upstream.
+/// Each entry holds the instant the events were fetched in order to detect expired entries.
+/// The cache is cheap to clone, all clones share the same entries.
+#[derive(Clone)]
+pub struct CalendarCache {
+    /// The client to fetch the calendars with.
+    client: Client,
+    /// The cached events of each calendar.
+    entries: Arc<RwLock<CalendarEntries>>,
+}
+
+impl CalendarCache {
+    /// Create an empty cache whose client gives up on upstream requests after [CalendarConfig::timeout].
+    ///
+    /// # Arguments
+    ///
+    /// * `conf`: the configuration of the calendars
+    ///
+    /// returns: CalendarCache
+    pub fn new(conf: &CalendarConfig) -> Self {
+        Self {
+            client: Client::builder()
+                .user_agent(keg_user_agent())
+                .timeout(Duration::from_secs(conf.timeout))
+                .build()
+                .expect("calendar client"),
+            entries: Default::default(),
+        }
+    }
+}
+
+/// Get the events of a calendar from the cache.
+/// The events are fetched from upstream if the cache holds no entry, the cache is only locked to store them afterwards.
+/// If the entry is older than [`crate::config::CalendarConfig::cache_ttl`], the expired events are returned while they are fetched again in the background.
+///
+/// # Arguments
+///
+/// * `cache`: the cache to read from and to store freshly fetched events into
+/// * `cal_type`: the type of the calendar to get the events from
+/// * `conf`: the application configuration
+///
+/// returns: Result<Vec<Event>, ApiError>
+pub async fn cached_events(
+    cache: &CalendarCache,
+    cal_type: CalendarType,
+    conf: &Config,
+) -> Result<Vec<Event>, ApiError> {
+    let ttl = Duration::from_secs(conf.calendar.cache_ttl);
+    if let Some((_, events)) = cache
+        .entries
+        .read()
+        .await
+        .get(&cal_type)
+        .filter(|(fetched, _)| fetched.elapsed() < ttl)
+    {
+        return Ok(events.clone());
+    }
+    if let Some((fetched, events)) = cache.entries.write().await.get_mut(&cal_type) {
+        if fetched.elapsed() >= ttl {
+            // renew the entry to prevent concurrent requests from fetching the calendar as well
+            *fetched = Instant::now();
+            rocket::tokio::spawn(refetch_events(cache.clone(), cal_type, conf.clone()));
+        }
+        return Ok(events.clone());
+    }
+    let events = fetch_events(&cache.client, cal_type, conf).await?;
+    cache
+        .entries
+        .write()
+        .await
+        .insert(cal_type, (Instant::now(), events.clone()));
+    Ok(events)
+}
+
+/// Fetch the events of an expired calendar in the background.
+/// If the calendar cannot be fetched, the expired events are kept until the next attempt.
+///
+/// # Arguments
+///
+/// * `cache`: the cache to store the fetched events into
+/// * `cal_type`: the type of the calendar to fetch
+/// * `conf`: the application configuration
+async fn refetch_events(cache: CalendarCache, cal_type: CalendarType, conf: Config) {
+    match fetch_events(&cache.client, cal_type, &conf).await {
+        Ok(events) => {
+            cache
+                .entries
+                .write()
+                .await
+                .insert(cal_type, (Instant::now(), events));
+        }
+        Err(_) => log::warn!(
+            "Unable to refresh the calendar {:?}, serve the expired events",
+            cal_type
+        ),
+    }
+}
+
+/// Fetch the events of a calendar and replace the cached events.
+/// The cached events are kept if the calendar cannot be fetched.
+///
+/// # Arguments
+///
+/// * `cache`: the cache to refresh
+/// * `cal_type`: the type of the calendar to refresh
+/// * `conf`: the application configuration
+///
+/// returns: Result<usize, ApiError> the number of events after the refresh
+pub async fn refresh_events(
+    cache: &CalendarCache,
+    cal_type: CalendarType,
+    conf: &Config,
+) -> Result<usize, ApiError> {
+    let events = fetch_events(&cache.client, cal_type, conf).await?;
+    let count = events.len();
+    cache
+        .entries
+        .write()
+        .await
+        .insert(cal_type, (Instant::now(), events));
+    Ok(count)
+}
+
+/// Fetch and parse all events of a calendar from upstream.
+/// If the url of the calendar is not configured, an [ApiError] with 'Service Unavailable' will be returned.
+///
+/// # Arguments
+///
+/// * `client`: the client to fetch the calendar with
+/// * `cal_type`: the type of the calendar to fetch the events from
+/// * `conf`: the application configuration, including the URLs for the calendars
+///
+/// returns: Result<Vec<Event>, ApiError>
+async fn fetch_events(
+    client: &Client,
+    cal_type: CalendarType,
+    conf: &Config,
+) -> Result<Vec<Event>, ApiError> {
+    let calendar_config = &conf.calendar;
+    let url = match cal_type {
+        CalendarType::Public => &calendar_config.ical_url,
+        CalendarType::Internal => &calendar_config.ical_internal_url,
+    };
+    if url.is_empty() {
+        log::warn!(
+            "Unable to fetch the calendar {:?}, its url is not configured",
+            cal_type
+        );
+        return Err(not_configured_error());
+    }
+    log::debug!("Fetch calendar from {}", url);
+    let ical_body_future = client.get(url).send().await.map_err(|e| {
+        log::error!("Unable to retrieve the calendar from the ical url {}", e);
+        upstream_error()
+    })?;
+    let ical_body = ical_body_future.text().await.map_err(|e| {
+        log::error!("Unable to read the body from the calendar response {}", e);
+        upstream_error()
+    })?;
+    let parser = ical::IcalParser::new(Cursor::new(ical_body));
+    let mut parse_result = Ok(());
+    let events: Vec<Event> = parser
+        .flat_map(|c| {
+            c.map_err(|e| {
+                log::error!("Unable to parse calendar {}", e);
+                parse_result = Err(upstream_error())
+            })
+            .map(|i| i.events)
+            .unwrap_or_default()
+        })
+        .map(|e| Event::from(&e))
+        .collect();
+    Ok(events)
+}
+
+/// Returns an [ApiError] indicating an upstream error during calendar retrieval.
+///
+/// The returned error has the error message "Internal Error", the message "Unable to retrieve the calendar from upstream", and the HTTP status code set to `Status::BadGateway.code`.
+///
+/// # Examples
+///
+/// ```
+/// let error = upstream_error();
+/// assert_eq!(error.err, "Internal Error");
+/// assert_eq!(error.msg, Some("Unable to retrieve the calendar from upstream"));
+/// assert_eq!(error.http_status_code, Status::BadGateway.code);
+/// ```
+fn upstream_error() -> ApiError {
+    ApiError {
+        err: "Internal Error".to_string(),
+        msg: Some("Unable to retrieve the calendar from upstream".to_string()),
+        http_status_code: Status::BadGateway.code,
+    }
+}
+
+/// Returns an [ApiError] indicating that the url of the requested calendar is not configured.
+/// This distinguishes a calendar which is not set up from an upstream which is not available.
+fn not_configured_error() -> ApiError {
+    ApiError {
+        err: "Service Unavailable".to_string(),
+        msg: Some("The calendar is not configured".to_string()),
+        http_status_code: Status::ServiceUnavailable.code,
+    }
+}
@@ -15,88 +15,115 @@
 // along with this program; if not, write to the Free Software
 // Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
 
-use std::io::Cursor;
-
-use ical;
+use chrono::{DateTime, Days, Local, NaiveDate, TimeZone, Utc};
 use rocket::http::Status;
 use rocket::serde::json::Json;
 use rocket::State;
 use rocket_okapi::openapi;
 
-use crate::calendar::model::{CalendarType, Event};
+use crate::calendar::cache::{cached_events, refresh_events, CalendarCache};
+use crate::calendar::model::{
+    select_events, CalendarRefresh, CalendarType, EventFormat, Events, StructuredEvent,
+};
 use crate::config::Config;
 use crate::openapi::{ApiError, ApiResult};
+use crate::user::executives::{Calendar, ExecutiveRole};
 
 /// Retrieves all events from a calendar based on the specified `cal_type`.
 ///
 /// # Arguments
 ///
 /// * `cal_type` - A [CalendarType] enum value indicating the type of calendar to retrieve events from.
+/// * `from` - The first day in ISO 8601 format, events which begin before are omitted.
+/// * `to` - The last day in ISO 8601 format, events which begin after are omitted.
+/// * `limit` - The maximum number of events, useful in combination with `from` to get the upcoming events.
+/// * `format` - The [EventFormat] of the events, the raw ical properties are returned by default.
+/// * `cache` - The cache of the events which is used while the events are not expired.
 /// * `conf` - The configuration information, including the URLs for the calendars.
 ///
 /// # Returns
 ///
-/// If the events are retrieved successfully, the function returns a [Vec<Event>] wrapped in an [ApiResult].
+/// If the events are retrieved successfully, the function returns the [Events] wrapped in an [ApiResult].
+/// Structured events omit events without a valid begin.
+/// If a range or a limit is given, the events are sorted by their begin and events without a valid begin are omitted.
+/// If `from` or `to` is not a valid date, the [ApiError] has the HTTP status code `Status::BadRequest.code`.
 ///
 /// If an error occurs during the retrieval process, the function returns an [ApiError] with an appropriate error message.
+/// If the url of the calendar is not configured, the [ApiError] has the HTTP status code `Status::ServiceUnavailable.code`.
 ///
 /// # Examples
 ///
 /// ```
 /// let cal_type = CalendarType::Public;
 /// let conf = State::new(Config::new());
-/// let result = get_all_events(cal_type, &conf);
+/// let cache = State::new(CalendarCache::new(&conf.calendar));
+/// let result = get_all_events(cal_type, None, None, None, None, &cache, &conf);
 /// assert!(result.is_ok());
 /// ```
 #[openapi(tag = "Calendar")]
-#[get("/?<cal_type>")]
-pub async fn get_all_events(cal_type: CalendarType, conf: &State<Config>) -> ApiResult<Vec<Event>> {
-    let calendar_config = &conf.calendar;
-    let url = match cal_type {
-        CalendarType::Public => &calendar_config.ical_url,
-        CalendarType::Internal => &calendar_config.ical_internal_url,
-    };
-    log::debug!("Fetch calendar from {}", url);
-    let ical_body_future = reqwest::get(url).await.map_err(|e| {
-        log::error!("Unable to retrieve the calendar from the ical url {}", e);
-        upstream_error()
-    })?;
-    let ical_body = ical_body_future.text().await.map_err(|e| {
-        log::error!("Unable to read the body from the calendar response {}", e);
-        upstream_error()
-    })?;
-    let parser = ical::IcalParser::new(Cursor::new(ical_body));
-    let mut parse_result = Ok(());
-    let events: Vec<Event> = parser
-        .flat_map(|c| {
-            c.map_err(|e| {
-                log::error!("Unable to parse calendar {}", e);
-                parse_result = Err(upstream_error())
-            })
-            .map(|i| i.events)
-            .unwrap_or_default()
+#[get("/?<cal_type>&<from>&<to>&<limit>&<format>")]
+pub async fn get_all_events(
+    cal_type: CalendarType,
+    from: Option<&str>,
+    to: Option<&str>,
+    limit: Option<usize>,
+    format: Option<EventFormat>,
+    cache: &State<CalendarCache>,
+    conf: &State<Config>,
+) -> ApiResult<Events> {
+    let from = from.map(|from| day_start(from, 0)).transpose()?;
+    let to = to.map(|to| day_start(to, 1)).transpose()?;
+    let events = select_events(cached_events(cache, cal_type, conf).await?, from, to, limit);
+    Ok(Json(match format.unwrap_or_default() {
+        EventFormat::Raw => Events::Raw(events),
+        EventFormat::Structured => {
+            Events::Structured(events.iter().filter_map(StructuredEvent::from).collect())
+        }
+    }))
+}
+
+/// Parse an ISO 8601 date and get the begin of the day in the local zone of the server.
+///
+/// # Arguments
+///
+/// * `date`: the date to parse
+/// * `offset`: the number of days to add to the date
+///
+/// returns: Result<DateTime<Utc>, ApiError> the begin of the day or an error if the date is invalid
+fn day_start(date: &str, offset: u64) -> Result<DateTime<Utc>, ApiError> {
+    NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .ok()
+        .and_then(|date| date.checked_add_days(Days::new(offset)))
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .and_then(|time| Local.from_local_datetime(&time).earliest())
+        .map(|time| time.with_timezone(&Utc))
+        .ok_or_else(|| ApiError {
+            err: "Bad Request".to_string(),
+            msg: Some(format!("'{}' is not a valid date", date)),
+            http_status_code: Status::BadRequest.code,
         })
-        .map(|e| Event::from(&e))
-        .collect();
-    Ok(Json(events))
 }
 
-/// Returns an [ApiError] indicating an upstream error during calendar retrieval.
+/// Fetch the events of a calendar again from upstream and replace the cached events.
+/// The request waits until the calendar is fetched, this allows to show changes of the calendar before the cache expires.
+/// If the calendar cannot be fetched, the cached events are kept.
 ///
-/// The returned error has the error message "Internal Error", the message "Unable to retrieve the calendar from upstream", and the HTTP status code set to `Status::BadGateway.code`.
+/// # Arguments
 ///
-/// # Examples
+/// * `cal_type` - The type of the calendar to refresh.
+/// * `_calendar_role` - The executive role which is required to refresh the calendar.
+/// * `cache` - The cache of the events to refresh.
+/// * `conf` - The configuration information, including the URLs for the calendars.
 ///
-/// ```
-/// let error = upstream_error();
-/// assert_eq!(error.err, "Internal Error");
-/// assert_eq!(error.msg, Some("Unable to retrieve the calendar from upstream"));
-/// assert_eq!(error.http_status_code, Status::BadGateway.code);
-/// ```
-fn upstream_error() -> ApiError {
-    ApiError {
-        err: "Internal Error".to_string(),
-        msg: Some("Unable to retrieve the calendar from upstream".to_string()),
-        http_status_code: Status::BadGateway.code,
-    }
+/// returns: ApiResult<CalendarRefresh> the number of events after the refresh
+#[openapi(tag = "Calendar")]
+#[post("/refresh?<cal_type>")]
+pub async fn refresh_calendar(
+    cal_type: CalendarType,
+    _calendar_role: ExecutiveRole<Calendar>,
+    cache: &State<CalendarCache>,
+    conf: &State<Config>,
+) -> ApiResult<CalendarRefresh> {
+    let events = refresh_events(cache, cal_type, conf).await?;
+    Ok(Json(CalendarRefresh { events }))
 }
@@ -19,11 +19,16 @@ use okapi::openapi3::OpenApi;
 use rocket_okapi::openapi_get_routes_spec;
 use rocket_okapi::settings::OpenApiSettings;
 
+/// The cache which holds the parsed events of the calendars.
+pub mod cache;
 /// The controller which provides the REST API and its implementation
 pub mod controller;
 /// The model declared within this module
 pub mod model;
 
 pub fn get_routes_and_docs(settings: &OpenApiSettings) -> (Vec<rocket::Route>, OpenApi) {
-    openapi_get_routes_spec![settings: controller::get_all_events]
+    openapi_get_routes_spec![
+        settings: controller::get_all_events,
+        controller::refresh_calendar
+    ]
 }
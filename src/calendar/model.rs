@@ -16,17 +16,26 @@
 // Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
 
 use std::collections::HashMap;
+use std::ops::{Bound, RangeBounds};
 
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
 use ical::parser::ical::component::IcalEvent;
 use rocket::serde::{Deserialize, Serialize};
 use rocket_okapi::JsonSchema;
 
 use crate::openapi::SchemaExample;
 
+#[cfg(test)]
+#[path = "model_tests.rs"]
+mod model_tests;
+
 /// The type of the calendar.
 /// The public calendar which contains events everybody can attend.
 /// An internal calendar which contains preparations, exercises and similar events.
-#[derive(Serialize, Deserialize, JsonSchema, FromFormField)]
+#[derive(
+    Serialize, Deserialize, JsonSchema, FromFormField, Clone, Copy, PartialEq, Eq, Hash, Debug,
+)]
 pub enum CalendarType {
     Public,
     Internal,
@@ -36,7 +45,7 @@ pub enum CalendarType {
 /// It features the properties the ical server propagates.
 /// A reference which of them are utilized can be found at https://www.rfc-editor.org/rfc/rfc5545.
 /// However, this structure also supports properties which are not covered by this rfc.
-#[derive(Serialize, Deserialize, JsonSchema)]
+#[derive(Serialize, Deserialize, JsonSchema, Clone)]
 #[schemars(example = "Self::example")]
 pub struct Event {
     /// The map which contains all properties.
@@ -70,6 +79,54 @@ impl Event {
             .collect();
         Event { properties }
     }
+
+    /// The begin of the event.
+    ///
+    /// returns: Option<DateTime<Utc>> the begin or [None] if the event has no valid `DTSTART`
+    pub fn start(&self) -> Option<DateTime<Utc>> {
+        self.properties
+            .get("dtstart")
+            .and_then(parse_date)
+            .map(|(start, _)| start)
+    }
+}
+
+/// Select the events which begin within a range.
+/// If neither a range nor a limit is given, all events are returned as they are.
+/// Otherwise, events without a valid begin are omitted and the remaining events are sorted by their begin.
+///
+/// # Arguments
+///
+/// * `events`: the events to select from
+/// * `from`: the inclusive lower bound of the begin
+/// * `to`: the exclusive upper bound of the begin
+/// * `limit`: the maximum number of events to select
+///
+/// returns: Vec<Event> the selected events
+pub fn select_events(
+    events: Vec<Event>,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+    limit: Option<usize>,
+) -> Vec<Event> {
+    if from.is_none() && to.is_none() && limit.is_none() {
+        return events;
+    }
+    let range = (
+        from.map_or(Bound::Unbounded, Bound::Included),
+        to.map_or(Bound::Unbounded, Bound::Excluded),
+    );
+    let mut selected: Vec<(DateTime<Utc>, Event)> = events
+        .into_iter()
+        .filter_map(|event| event.start().map(|start| (start, event)))
+        .filter(|(start, _)| range.contains(start))
+        .collect();
+    selected.sort_by_key(|(start, _)| *start);
+    selected
+        .into_iter()
+        .map(|(_, event)| event)
+        .take(limit.unwrap_or(usize::MAX))
+        .collect()
 }
 
 impl SchemaExample for Event {
@@ -80,10 +137,144 @@ impl SchemaExample for Event {
     }
 }
 
+/// The representation of the events returned by the API.
+/// The raw format contains all ical properties as they are, the structured format contains parsed events.
+#[derive(FromFormField, Clone, Copy, PartialEq, Eq, Debug, Default, JsonSchema)]
+pub enum EventFormat {
+    #[default]
+    Raw,
+    Structured,
+}
+
+/// The events of a calendar in the requested [EventFormat].
+#[derive(Serialize, JsonSchema)]
+#[serde(untagged)]
+pub enum Events {
+    Raw(Vec<Event>),
+    Structured(Vec<StructuredEvent>),
+}
+
+/// An event with the commonly used properties parsed.
+/// In contrast to [Event], the dates are already converted to UTC.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+#[schemars(example = "Self::example")]
+pub struct StructuredEvent {
+    /// The begin of the event.
+    #[schemars(with = "String")]
+    pub start: DateTime<Utc>,
+    /// The end of the event, this is the begin if the event does not specify an end.
+    #[schemars(with = "Option<String>")]
+    pub end: Option<DateTime<Utc>>,
+    /// The title of the event.
+    pub summary: Option<String>,
+    /// The place where the event takes place.
+    pub location: Option<String>,
+    /// Further details of the event.
+    pub description: Option<String>,
+    /// Whether the event lasts the whole day, in this case only the dates are meaningful.
+    pub all_day: bool,
+}
+
+impl StructuredEvent {
+    /// Parse the commonly used properties of an event.
+    ///
+    /// # Arguments
+    ///
+    /// * `event`: the raw event to parse
+    ///
+    /// returns: Option<StructuredEvent> the parsed event or [None] if the event has no valid begin
+    pub fn from(event: &Event) -> Option<Self> {
+        let (start, all_day) = event.properties.get("dtstart").and_then(parse_date)?;
+        let end = event
+            .properties
+            .get("dtend")
+            .and_then(parse_date)
+            .map(|(end, _)| end)
+            .unwrap_or(start);
+        let text = |name: &str| {
+            event
+                .properties
+                .get(name)
+                .and_then(|property| property.value.clone())
+        };
+        Some(StructuredEvent {
+            start,
+            end: Some(end),
+            summary: text("summary"),
+            location: text("location"),
+            description: text("description"),
+            all_day,
+        })
+    }
+}
+
+impl SchemaExample for StructuredEvent {
+    fn example() -> Self {
+        let start = Utc.with_ymd_and_hms(2023, 12, 24, 16, 0, 0).unwrap();
+        Self {
+            start,
+            end: Some(start + chrono::Duration::hours(2)),
+            summary: Some("Turmblasen".to_string()),
+            location: Some("Kirchenplatz".to_string()),
+            description: None,
+            all_day: false,
+        }
+    }
+}
+
+/// Parse an ical DATE or DATE-TIME value.
+/// Times with a `Z` suffix are in UTC, times with a `TZID` parameter in the given zone.
+/// Floating times and dates are interpreted in the local zone of the server.
+///
+/// # Arguments
+///
+/// * `property`: the property which contains the date
+///
+/// returns: Option<(DateTime<Utc>, bool)> the date and whether the value is a date without time
+fn parse_date(property: &EventProperty) -> Option<(DateTime<Utc>, bool)> {
+    let value = property.value.as_deref()?.trim();
+    let param = |name: &str| {
+        property
+            .params
+            .get(name)
+            .and_then(|values| values.first())
+            .map(String::as_str)
+    };
+    let (naive, all_day) = if param("value") == Some("DATE") || !value.contains('T') {
+        let date = NaiveDate::parse_from_str(value, "%Y%m%d").ok()?;
+        (date.and_hms_opt(0, 0, 0)?, true)
+    } else if let Some(utc) = value.strip_suffix('Z') {
+        let time = NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S").ok()?;
+        return Some((Utc.from_utc_datetime(&time), false));
+    } else {
+        (
+            NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").ok()?,
+            false,
+        )
+    };
+    let time = match param("tzid").and_then(|tzid| {
+        tzid.trim_matches('"')
+            .trim_start_matches('/')
+            .parse::<Tz>()
+            .ok()
+    }) {
+        Some(zone) => zone
+            .from_local_datetime(&naive)
+            .earliest()?
+            .with_timezone(&Utc),
+        None => Local
+            .from_local_datetime(&naive)
+            .earliest()?
+            .with_timezone(&Utc),
+    };
+    Some((time, all_day))
+}
+
 /// A single event property.
 /// This structure contains the value to an ical event property.
 /// In addition, it contains the parameters of the value.
-#[derive(Serialize, Deserialize, JsonSchema)]
+#[derive(Serialize, Deserialize, JsonSchema, Clone)]
 #[schemars(example = "Self::example")]
 pub struct EventProperty {
     /// The value of the property.
@@ -100,3 +291,17 @@ impl SchemaExample for EventProperty {
         }
     }
 }
+
+/// The result of a calendar refresh.
+#[derive(Serialize, Deserialize, JsonSchema)]
+#[schemars(example = "Self::example")]
+pub struct CalendarRefresh {
+    /// The number of events after the refresh.
+    pub events: usize,
+}
+
+impl SchemaExample for CalendarRefresh {
+    fn example() -> Self {
+        Self { events: 42 }
+    }
+}
@@ -0,0 +1,143 @@
+// OpenKeg, the lightweight backend of the Musikverein Leopoldsdorf.
+// Copyright (C) 2023  Richard Stöckl
+//
+// This program is free software; you can redistribute it and/or
+// modify it under the terms of the GNU General Public License
+// as published by the Free Software Foundation; either version 2
+// of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+
+use super::*;
+
+fn property(value: &str, params: &[(&str, &str)]) -> EventProperty {
+    EventProperty {
+        value: Some(value.to_string()),
+        params: params
+            .iter()
+            .map(|(k, v)| (k.to_string(), vec![v.to_string()]))
+            .collect(),
+    }
+}
+
+fn event(properties: Vec<(&str, EventProperty)>) -> Event {
+    Event {
+        properties: properties
+            .into_iter()
+            .map(|(name, property)| (name.to_string(), property))
+            .collect(),
+    }
+}
+
+#[test]
+fn utc_date_time() {
+    let (time, all_day) = parse_date(&property("20231224T160000Z", &[])).unwrap();
+    assert_eq!(time, Utc.with_ymd_and_hms(2023, 12, 24, 16, 0, 0).unwrap());
+    assert!(!all_day);
+}
+
+#[test]
+fn date_time_with_zone() {
+    let (winter, _) =
+        parse_date(&property("20231224T160000", &[("tzid", "Europe/Vienna")])).unwrap();
+    assert_eq!(
+        winter,
+        Utc.with_ymd_and_hms(2023, 12, 24, 15, 0, 0).unwrap()
+    );
+    let (summer, _) =
+        parse_date(&property("20230801T190000", &[("tzid", "Europe/Vienna")])).unwrap();
+    assert_eq!(summer, Utc.with_ymd_and_hms(2023, 8, 1, 17, 0, 0).unwrap());
+}
+
+#[test]
+fn floating_date_time_is_local() {
+    let (time, _) = parse_date(&property("20230801T190000", &[])).unwrap();
+    let local = Local
+        .with_ymd_and_hms(2023, 8, 1, 19, 0, 0)
+        .earliest()
+        .unwrap();
+    assert_eq!(time, local.with_timezone(&Utc));
+}
+
+#[test]
+fn date_is_all_day() {
+    let (time, all_day) =
+        parse_date(&property("20230801", &[("value", "DATE"), ("tzid", "UTC")])).unwrap();
+    assert_eq!(time, Utc.with_ymd_and_hms(2023, 8, 1, 0, 0, 0).unwrap());
+    assert!(all_day);
+}
+
+#[test]
+fn invalid_date() {
+    assert!(parse_date(&property("tomorrow", &[])).is_none());
+    assert!(parse_date(&property("20231324T160000Z", &[])).is_none());
+}
+
+#[test]
+fn structured_event() {
+    let structured = StructuredEvent::from(&event(vec![
+        ("dtstart", property("20231224T160000Z", &[])),
+        ("dtend", property("20231224T180000Z", &[])),
+        ("summary", property("Turmblasen", &[])),
+        ("location", property("Kirchenplatz", &[])),
+    ]))
+    .unwrap();
+    assert_eq!(
+        structured.end,
+        Some(Utc.with_ymd_and_hms(2023, 12, 24, 18, 0, 0).unwrap())
+    );
+    assert_eq!(structured.summary.as_deref(), Some("Turmblasen"));
+    assert_eq!(structured.location.as_deref(), Some("Kirchenplatz"));
+    assert_eq!(structured.description, None);
+}
+
+#[test]
+fn missing_end_falls_back_to_start() {
+    let structured =
+        StructuredEvent::from(&event(vec![("dtstart", property("20231224T160000Z", &[]))]))
+            .unwrap();
+    assert_eq!(structured.end, Some(structured.start));
+}
+
+#[test]
+fn missing_start() {
+    let structured = StructuredEvent::from(&event(vec![("summary", property("Probe", &[]))]));
+    assert!(structured.is_none());
+}
+
+#[test]
+fn select_events_within_range() {
+    let events = vec![
+        event(vec![("dtstart", property("20231224T160000Z", &[]))]),
+        event(vec![("summary", property("Probe", &[]))]),
+        event(vec![("dtstart", property("20230801T160000Z", &[]))]),
+        event(vec![("dtstart", property("20240101T100000Z", &[]))]),
+    ];
+    let from = Utc.with_ymd_and_hms(2023, 8, 1, 0, 0, 0).unwrap();
+    let to = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+    let starts: Vec<_> = select_events(events.clone(), Some(from), Some(to), None)
+        .iter()
+        .map(|event| event.start().unwrap())
+        .collect();
+    assert_eq!(
+        starts,
+        vec![
+            Utc.with_ymd_and_hms(2023, 8, 1, 16, 0, 0).unwrap(),
+            Utc.with_ymd_and_hms(2023, 12, 24, 16, 0, 0).unwrap()
+        ]
+    );
+    assert_eq!(select_events(events.clone(), None, None, None).len(), 4);
+    let upcoming = select_events(events, Some(from), None, Some(1));
+    assert_eq!(
+        upcoming[0].start(),
+        Some(Utc.with_ymd_and_hms(2023, 8, 1, 16, 0, 0).unwrap())
+    );
+    assert_eq!(upcoming.len(), 1);
+}
@@ -15,12 +15,21 @@
 // along with this program; if not, write to the Free Software
 // Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
 
+#[cfg(test)]
+#[path = "config_tests.rs"]
+mod config_tests;
+
+use std::fs;
+
 use figment::{
     providers::{Env, Format, Serialized, Toml},
     Figment, Profile,
 };
+use reqwest::Url;
 use rocket::serde::{Deserialize, Serialize};
 
+use crate::user::tokens::DEFAULT_RENEWAL_NAME;
+
 /// The application configuration.
 #[derive(Default, Debug, Serialize, Deserialize, Clone)]
 pub struct Config {
@@ -32,9 +41,15 @@ pub struct Config {
     pub cert: CertConfig,
     /// The configuration of the database.
     pub database: DatabaseConfig,
+    /// The configuration of the archive.
+    pub archive: ArchiveConfig,
     /// The url to use for a server entry in the OpenApi schema.
     /// It is highly recommended to use a URL to this server instance.
     pub openapi_url: String,
+    /// Whether the [Config::openapi_url] must use HTTPS.
+    /// If set, an URL with another scheme is omitted from the OpenApi schema.
+    /// This should be enabled for production profiles.
+    pub enforce_https_openapi_url: bool,
     /// Whether expose a directory to the public or not.
     /// May be used to serve the swagger ui or the RapiDoc.
     pub serve_static_directory: bool,
@@ -46,6 +61,92 @@ pub struct Config {
     pub document_server: DocumentServer,
     /// The configuration for the calendar.
     pub calendar: CalendarConfig,
+    /// The configuration for the member rest interface.
+    pub members: MembersConfig,
+    /// The configuration of security related measures.
+    pub security: SecurityConfig,
+    /// The configuration of the cross-origin resource sharing.
+    pub cors: CorsConfig,
+    /// The configuration of the health endpoint.
+    pub health: HealthConfig,
+    /// The configuration of the metrics endpoint.
+    pub metrics: MetricsConfig,
+}
+
+impl Config {
+    /// Check the invariants of the configuration which cannot be expressed by the defaults.
+    /// Without these, the server would start but fail at the first request which relies on them.
+    ///
+    /// returns: Result<(), String> with a message naming the first invalid key
+    pub fn validate(&self) -> Result<(), String> {
+        let database_url = parse_url("database.url", &self.database.url)?;
+        if !["http", "https"].contains(&database_url.scheme()) {
+            return Err(format!(
+                "'database.url' must use the http or https scheme, but uses '{}'",
+                database_url.scheme()
+            ));
+        }
+        for (key, value) in self.database.database_mapping.endpoints() {
+            if value.trim().is_empty() {
+                return Err(format!(
+                    "'database.database_mapping.{}' must not be empty",
+                    key
+                ));
+            }
+        }
+        parse_url("ldap.server", &self.ldap.server)?;
+        if self.jwt.expiration <= 0 {
+            return Err(format!(
+                "'jwt.expiration' must be positive, but is {}",
+                self.jwt.expiration
+            ));
+        }
+        if self.jwt.renewal_expiration <= 0 {
+            return Err(format!(
+                "'jwt.renewal_expiration' must be positive, but is {}",
+                self.jwt.renewal_expiration
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Read the passwords from their files if configured.
+/// The trailing line break of a file is removed.
+///
+/// # Arguments
+///
+/// * `config`: the configuration which references the password files
+///
+/// returns: Result<Vec<(&str, String)>, String> with the keys of the passwords and their content
+pub fn read_password_files(config: &Config) -> Result<Vec<(&'static str, String)>, String> {
+    [
+        ("ldap.password", &config.ldap.password_file),
+        ("database.password", &config.database.password_file),
+    ]
+    .into_iter()
+    .filter_map(|(key, path)| path.as_ref().map(|path| (key, path)))
+    .map(|(key, path)| {
+        fs::read_to_string(path)
+            .map(|password| (key, password.trim_end_matches(['\r', '\n']).to_string()))
+            .map_err(|e| format!("'{}_file' cannot be read from '{}': {}", key, path, e))
+    })
+    .collect()
+}
+
+/// Parse the value of a configuration key as URL.
+///
+/// # Arguments
+///
+/// * `key`: the key of the value used in the error message
+/// * `value`: the value to parse
+///
+/// returns: Result<Url, String>
+fn parse_url(key: &str, value: &str) -> Result<Url, String> {
+    if value.trim().is_empty() {
+        return Err(format!("'{}' must not be empty", key));
+    }
+    Url::parse(value).map_err(|e| format!("'{}' is not a valid URL ({}): {}", key, value, e))
 }
 
 /// The configuration of the directory server.
@@ -58,8 +159,29 @@ pub struct LdapConfig {
     pub dn: Option<String>,
     /// The password for the dn.
     pub password: Option<String>,
+    /// The path of a file which contains the password for the dn, such as a mounted secret.
+    /// If set, the content of the file takes precedence over [LdapConfig::password].
+    pub password_file: Option<String>,
     /// The synchronization interval for the member and groups in *seconds*.
     pub synchronization_interval: u64,
+    /// The maximum number of attempts to fetch the member and groups within a single synchronization.
+    /// Only connection-level errors are retried, the next attempt is made at the next interval otherwise.
+    pub synchronization_attempts: u32,
+    /// The delay before the first retry of a failed synchronization in *milliseconds*.
+    /// The delay is doubled for every further retry.
+    pub synchronization_retry_delay: u64,
+    /// The time in *seconds* to wait for a running synchronization to finish when the server shuts down.
+    /// Rocket only waits for its own grace and mercy periods, so this should not exceed their sum.
+    pub synchronization_shutdown_timeout: u64,
+    /// The maximum number of pooled authenticated connections which are open for searches at the same time, at least one.
+    /// Idle connections are kept for reuse up to this number.
+    /// If all of them are in use, one-off connections are opened which are closed after their search.
+    pub pool_size: usize,
+    /// The time in *seconds* after which an idle pooled connection is closed instead of being reused.
+    pub pool_idle_timeout: u64,
+    /// The number of entries requested per page with the paged results control.
+    /// Should not exceed the size limit of the directory server.
+    pub page_size: i32,
     /// The base dn where to start to search for member.
     pub member_base: String,
     /// The filter to use to search member.
@@ -80,6 +202,10 @@ pub struct LdapConfig {
     pub executives_base: String,
     /// The filter to use to search executives.
     pub executives_filter: String,
+    /// An optional filter template which the entry of a member must match after a successful bind.
+    /// The placeholder `{username}` is replaced with the escaped username, e.g. `(&(uid={username})(!(pwdAccountLockedTime=*)))`.
+    /// If `None`, a successful bind is sufficient for the authentication.
+    pub auth_filter: Option<String>,
     /// The mapping for the member attributes.
     pub member_mapping: MemberMapping,
     /// The mapping for the address attributes.
@@ -90,6 +216,26 @@ pub struct LdapConfig {
     pub executive_mapping: ExecutiveMapping,
     /// The ordering of the titles.
     pub title_ordering: Vec<String>,
+    /// The configuration of the secure connection to the directory server.
+    pub tls: LdapTlsConfig,
+    /// Whether string attributes which are not valid UTF-8 are decoded lossily instead of being treated as absent.
+    /// Invalid characters are replaced and a warning is logged for every such value.
+    pub lossy_decoding: bool,
+}
+
+/// The configuration of the secure connection to the directory server.
+/// A secure connection is either established with an `ldaps://` server url or with StartTLS.
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+pub struct LdapTlsConfig {
+    /// Whether to upgrade a plain `ldap://` connection with StartTLS.
+    /// If the upgrade fails, the connection will be aborted.
+    pub start_tls: bool,
+    /// The path to the CA certificates in the PEM format which are used to verify the server certificate.
+    /// If `None`, the certificates of the system are used.
+    pub ca_cert_path: Option<String>,
+    /// Whether to skip the verification of the server certificate.
+    /// This is insecure and should only be used for testing purposes.
+    pub accept_invalid_certs: bool,
 }
 
 impl Default for LdapConfig {
@@ -98,7 +244,14 @@ impl Default for LdapConfig {
             server: "auth://localhost:389".to_string(),
             dn: None,
             password: None,
+            password_file: None,
             synchronization_interval: 300,
+            synchronization_attempts: 3,
+            synchronization_retry_delay: 1000,
+            synchronization_shutdown_timeout: 4,
+            pool_size: 2,
+            pool_idle_timeout: 300,
+            page_size: 500,
             member_base: "".to_string(),
             member_filter: "(objectClass=*)".to_string(),
             sutler_base: "".to_string(),
@@ -109,11 +262,14 @@ impl Default for LdapConfig {
             register_filter: "(objectClass=*)".to_string(),
             executives_base: "".to_string(),
             executives_filter: "(objectClass=*)".to_string(),
+            auth_filter: None,
             member_mapping: Default::default(),
             address_mapping: Default::default(),
             group_mapping: Default::default(),
             executive_mapping: Default::default(),
             title_ordering: Default::default(),
+            tls: Default::default(),
+            lossy_decoding: false,
         }
     }
 }
@@ -121,77 +277,136 @@ impl Default for LdapConfig {
 /// The mapping to member.
 /// This refers to an LDAP structure which likely has the 'mvlMember' object class.
 /// The attribute descriptions refer to the content of the object attribute and provide an example often seen for the mapping.
+/// Each field accepts either a single attribute or a list of [AttributeCandidates].
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct MemberMapping {
     /// The short username mapping, something like 'cn' or 'uid'.
-    pub username: String,
+    pub username: AttributeCandidates,
     /// The full username mapping, normally 'dn'.
-    pub full_username: String,
+    pub full_username: AttributeCandidates,
     /// The first name mapping such as 'givenName'.
-    pub first_name: String,
+    pub first_name: AttributeCandidates,
     /// The last name mapping such as 'sn'.
-    pub last_name: String,
+    pub last_name: AttributeCandidates,
     /// The common name, this is normally how someone wants to be called.
     /// Normally 'cn'.
-    pub common_name: String,
+    pub common_name: AttributeCandidates,
     /// Whether this member uses the WhatsApp services or not.
     /// Something like 'wa'.
-    pub whatsapp: String,
+    pub whatsapp: AttributeCandidates,
     /// The year when this member joined the society.
     /// Something like 'joining'.
-    pub joining: String,
+    pub joining: AttributeCandidates,
     /// Whether this member is listed publicly or not.
     /// Something like 'listed'.
-    pub listed: String,
+    pub listed: AttributeCandidates,
     /// Whether this member is registered at the ÖBV or not.
     /// Something like 'official'.
-    pub official: String,
+    pub official: AttributeCandidates,
     /// The gender of this member.
     /// Something like 'gender'.
-    pub gender: String,
+    pub gender: AttributeCandidates,
     /// Whether this member is active or not.
     /// Something like 'active'.
-    pub active: String,
+    pub active: AttributeCandidates,
     /// The mobile number of this member.
     /// Normally 'mobile'
-    pub mobile: String,
+    pub mobile: AttributeCandidates,
     /// The date of birth of this member.
     /// Something like 'birthday'.
-    pub birthday: String,
+    pub birthday: AttributeCandidates,
     /// The email address of this member.
     /// Normally 'mail'.
-    pub mail: String,
+    pub mail: AttributeCandidates,
     /// The titles of this member such as 'Kapellmeister'.
     /// Normally 'title'.
-    pub titles: String,
+    pub titles: AttributeCandidates,
     /// The photo of this member.
     /// Normally 'jpegPhoto'
-    pub photo: String,
+    pub photo: AttributeCandidates,
+}
+
+impl MemberMapping {
+    /// Get all candidate attributes of the mapping except the ones of the photo.
+    ///
+    /// returns: Vec<String>
+    pub fn attributes_without_photo(&self) -> Vec<String> {
+        [
+            &self.username,
+            &self.full_username,
+            &self.first_name,
+            &self.last_name,
+            &self.common_name,
+            &self.whatsapp,
+            &self.joining,
+            &self.listed,
+            &self.official,
+            &self.gender,
+            &self.active,
+            &self.mobile,
+            &self.birthday,
+            &self.mail,
+            &self.titles,
+        ]
+        .iter()
+        .flat_map(|c| c.candidates().iter().cloned())
+        .collect()
+    }
 }
 
 impl Default for MemberMapping {
     fn default() -> Self {
         MemberMapping {
-            username: "uid".to_string(),
-            full_username: "dn".to_string(),
-            first_name: "givenName".to_string(),
-            last_name: "sn".to_string(),
-            common_name: "cn".to_string(),
-            whatsapp: "wa".to_string(),
-            joining: "joining".to_string(),
-            listed: "listed".to_string(),
-            official: "official".to_string(),
-            gender: "gender".to_string(),
-            active: "active".to_string(),
-            mobile: "mobile".to_string(),
-            birthday: "birthday".to_string(),
-            mail: "mail".to_string(),
-            titles: "title".to_string(),
-            photo: "jpegPhoto".to_string(),
+            username: "uid".into(),
+            full_username: "dn".into(),
+            first_name: "givenName".into(),
+            last_name: "sn".into(),
+            common_name: "cn".into(),
+            whatsapp: "wa".into(),
+            joining: "joining".into(),
+            listed: "listed".into(),
+            official: "official".into(),
+            gender: "gender".into(),
+            active: "active".into(),
+            mobile: "mobile".into(),
+            birthday: "birthday".into(),
+            mail: "mail".into(),
+            titles: "title".into(),
+            photo: "jpegPhoto".into(),
         }
     }
 }
 
+/// The candidate attributes of a mapped field.
+/// Either a single attribute or a list of attributes which are tried in order, the first one with a non-empty value is used.
+/// This supports mixed schemas where entries use different attributes for the same field such as 'givenName' and 'gn'.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum AttributeCandidates {
+    /// A single attribute.
+    Single(String),
+    /// Multiple attributes in the order of their preference.
+    Multiple(Vec<String>),
+}
+
+impl AttributeCandidates {
+    /// Get all the candidate attributes in the order of their preference.
+    ///
+    /// returns: &[String]
+    pub fn candidates(&self) -> &[String] {
+        match self {
+            AttributeCandidates::Single(attribute) => std::slice::from_ref(attribute),
+            AttributeCandidates::Multiple(attributes) => attributes,
+        }
+    }
+}
+
+impl From<&str> for AttributeCandidates {
+    fn from(attribute: &str) -> Self {
+        AttributeCandidates::Single(attribute.to_string())
+    }
+}
+
 /// The mapping to addresses.
 /// This refers to an LDAP structure which supports addresses.
 /// The attribute descriptions refer to the content of the object attribute and provide an example often seen for the mapping.
@@ -217,6 +432,22 @@ pub struct AddressMapping {
     pub country_code: String,
 }
 
+impl AddressMapping {
+    /// Get all attributes of the mapping.
+    ///
+    /// returns: Vec<String>
+    pub fn attributes(&self) -> Vec<String> {
+        vec![
+            self.street.clone(),
+            self.house_number.clone(),
+            self.postal_code.clone(),
+            self.city.clone(),
+            self.state.clone(),
+            self.country_code.clone(),
+        ]
+    }
+}
+
 impl Default for AddressMapping {
     fn default() -> Self {
         Self {
@@ -267,12 +498,18 @@ impl Default for GroupMapping {
 pub struct ExecutiveMapping {
     /// Role to manage the archive, both reading and writing.
     pub archive: String,
+    /// Role to manage the calendar such as refreshing the cached events.
+    pub calendar: String,
+    /// Role to keep the governance records such as auditing the executive groups.
+    pub secretary: String,
 }
 
 impl Default for ExecutiveMapping {
     fn default() -> Self {
         Self {
             archive: "".to_string(),
+            calendar: "".to_string(),
+            secretary: "".to_string(),
         }
     }
 }
@@ -286,6 +523,11 @@ pub struct JwtConfig {
     pub renewal_expiration: i64,
     /// The issuer used for token generation
     pub issuer: String,
+    /// The name of the header and the cookie which carry the renewal token.
+    pub renewal_name: String,
+    /// The audience of the tokens, e.g. the URL of this instance.
+    /// If set, the tokens contain it as `aud` claim and tokens without this audience are rejected.
+    pub audience: Option<String>,
 }
 
 impl Default for JwtConfig {
@@ -294,6 +536,8 @@ impl Default for JwtConfig {
             expiration: 2 * 60,
             renewal_expiration: 365 * 24,
             issuer: "keg".to_string(),
+            renewal_name: DEFAULT_RENEWAL_NAME.to_string(),
+            audience: None,
         }
     }
 }
@@ -326,10 +570,55 @@ pub struct DatabaseConfig {
     pub username: String,
     /// The password of the CouchDB user
     pub password: String,
+    /// The path of a file which contains the password of the CouchDB user, such as a mounted secret.
+    /// If set, the content of the file takes precedence over [DatabaseConfig::password].
+    pub password_file: Option<String>,
     /// The score partition prefix
     pub score_partition: String,
+    /// The book partition prefix
+    pub book_partition: String,
     /// The database url mappings
     pub database_mapping: DatabaseMapping,
+    /// The maximum number of books whose content is fetched concurrently when requesting multiple books at once.
+    pub book_content_concurrency: usize,
+    /// The maximum number of scores fetched within a single request when fetching the content of a book.
+    /// Books with more scores are fetched page by page.
+    /// The limit is clamped to [DatabaseConfig::max_page_limit].
+    pub book_content_limit: u64,
+    /// The maximum number of documents requested from the database within a single page.
+    /// Larger limits requested by clients are clamped to this value instead of being rejected.
+    /// Only batches of scores with more ids are rejected as they cannot be clamped.
+    pub max_page_limit: u64,
+    /// The time in *seconds* after a reauthentication in which no further reauthentication is performed.
+    /// Requests which fail due to an expired session within this time are retried with the refreshed session instead.
+    pub reauthentication_threshold: u64,
+    /// The path of the file to persist the session cookie to.
+    /// If set, the session is reused across restarts as long as it is still valid.
+    /// If `None`, a new session is created on every start.
+    pub cookie_path: Option<String>,
+    /// Whether the execution statistics of the database are requested and included in search responses.
+    pub include_execution_stats: bool,
+    /// The time in *seconds* the count statistics are cached before they are fetched from the database again.
+    pub statistic_cache_ttl: u64,
+    /// The design document of the indexes used to browse all scores sorted by a field, see [crate::database::score::sorted_scores].
+    /// CouchDB can only sort by fields which are part of an index, without a matching index sorted browsing fails.
+    /// If `None`, CouchDB selects a matching index by itself.
+    pub sort_index: Option<String>,
+    /// The secret to sign the bookmarks of score searches with.
+    /// If set, bookmarks which have not been issued by this server are rejected.
+    pub bookmark_secret: Option<String>,
+    /// The time in *seconds* to wait for a connection to the database.
+    pub connect_timeout: u64,
+    /// The time in *seconds* to wait for a request to the database to complete, including the response body.
+    pub request_timeout: u64,
+    /// The maximum number of attempts of a request which fails due to a transient error such as a timeout.
+    pub retry_attempts: u32,
+    /// The delay before the first retry of a failed request in *milliseconds*.
+    /// The delay is doubled for every further retry.
+    pub retry_delay: u64,
+    /// Whether requests which are not idempotent, such as `PUT`, `POST` and `DELETE`, are retried as well.
+    /// Read-only `POST` requests such as the ones to `_find` are always retried.
+    pub retry_non_idempotent: bool,
 }
 
 impl Default for DatabaseConfig {
@@ -338,8 +627,24 @@ impl Default for DatabaseConfig {
             url: "".to_string(),
             username: "".to_string(),
             password: "".to_string(),
+            password_file: None,
             score_partition: "scores".to_string(),
+            book_partition: "books".to_string(),
             database_mapping: Default::default(),
+            book_content_concurrency: 4,
+            book_content_limit: 500,
+            max_page_limit: 500,
+            reauthentication_threshold: 5,
+            cookie_path: None,
+            include_execution_stats: true,
+            statistic_cache_ttl: 60,
+            sort_index: None,
+            bookmark_secret: None,
+            connect_timeout: 5,
+            request_timeout: 30,
+            retry_attempts: 3,
+            retry_delay: 100,
+            retry_non_idempotent: false,
         }
     }
 }
@@ -356,10 +661,22 @@ pub struct DatabaseMapping {
     pub find_scores: String,
     /// The endpoint to receive a single score by its id
     pub get_score: String,
+    /// The endpoint to receive multiple scores by their ids at once, requires `include_docs` and `keys`
+    pub batch_scores: String,
     /// The endpoint to put a single score
     pub put_score: String,
+    /// The endpoint to insert and update multiple scores at once
+    pub bulk_scores: String,
     /// The endpoint to delete a single score by its id and revision
     pub delete_score: String,
+    /// The endpoint of the documents whose attachments contain the sheet music of the scores
+    pub score_attachment: String,
+    /// The endpoint which returns all books
+    pub all_books: String,
+    /// The endpoint to put a single book
+    pub put_book: String,
+    /// The endpoint to delete a single book by its id and revision
+    pub delete_book: String,
     /// The endpoint for the genres count statistic.
     pub genres_statistic: String,
     /// The endpoint for the composers count statistic.
@@ -372,6 +689,37 @@ pub struct DatabaseMapping {
     pub books_statistic: String,
     /// The endpoint for the locations count statistic.
     pub locations_statistic: String,
+    /// The endpoint of the view which emits the lowercased title as key and the title as value.
+    pub title_suggestions: String,
+}
+
+impl DatabaseMapping {
+    /// All endpoints with the name of their key.
+    ///
+    /// returns: Vec<(&str, &str)>
+    pub fn endpoints(&self) -> Vec<(&str, &str)> {
+        vec![
+            ("authentication", &self.authentication),
+            ("all_scores", &self.all_scores),
+            ("find_scores", &self.find_scores),
+            ("get_score", &self.get_score),
+            ("batch_scores", &self.batch_scores),
+            ("put_score", &self.put_score),
+            ("bulk_scores", &self.bulk_scores),
+            ("delete_score", &self.delete_score),
+            ("score_attachment", &self.score_attachment),
+            ("all_books", &self.all_books),
+            ("put_book", &self.put_book),
+            ("delete_book", &self.delete_book),
+            ("genres_statistic", &self.genres_statistic),
+            ("composers_statistic", &self.composers_statistic),
+            ("arrangers_statistic", &self.arrangers_statistic),
+            ("publishers_statistic", &self.publishers_statistic),
+            ("books_statistic", &self.books_statistic),
+            ("locations_statistic", &self.locations_statistic),
+            ("title_suggestions", &self.title_suggestions),
+        ]
+    }
 }
 
 impl Default for DatabaseMapping {
@@ -381,36 +729,161 @@ impl Default for DatabaseMapping {
             all_scores: "".to_string(),
             find_scores: "".to_string(),
             get_score: "".to_string(),
+            batch_scores: "/archive/_partition/scores/_all_docs".to_string(),
             put_score: "".to_string(),
+            bulk_scores: "/archive/_bulk_docs".to_string(),
             delete_score: "".to_string(),
+            score_attachment: "/archive".to_string(),
+            all_books: "/archive/_partition/books/_all_docs".to_string(),
+            put_book: "/archive".to_string(),
+            delete_book: "/archive".to_string(),
             genres_statistic: "".to_string(),
             composers_statistic: "".to_string(),
             arrangers_statistic: "".to_string(),
             publishers_statistic: "".to_string(),
             books_statistic: "".to_string(),
             locations_statistic: "".to_string(),
+            title_suggestions: "/archive/_design/score/_view/titles".to_string(),
+        }
+    }
+}
+
+/// The configuration of the archive and its rest interface.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ArchiveConfig {
+    /// Whether searching and reading scores is allowed without the archive role.
+    /// Unauthorized users will only receive a reduced score without internal fields such as the location.
+    pub anonymous_read: bool,
+    /// The capitalization which is applied to the genres of a score when it is stored.
+    pub genre_capitalization: Capitalization,
+    /// The maximum number of titles returned as suggestions for a prefix.
+    pub title_suggestions_limit: u64,
+    /// The maximum length of a search term which is interpreted as regular expression.
+    /// Longer patterns are rejected in order to protect the database from expensive searches.
+    pub max_regex_length: usize,
+    /// The webhooks which are notified about changed scores.
+    pub webhook: WebhookConfig,
+}
+
+impl Default for ArchiveConfig {
+    fn default() -> Self {
+        Self {
+            anonymous_read: false,
+            genre_capitalization: Default::default(),
+            title_suggestions_limit: 10,
+            max_regex_length: 64,
+            webhook: Default::default(),
+        }
+    }
+}
+
+/// The configuration of the webhooks which are notified about changed scores, e.g. to invalidate a downstream cache.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WebhookConfig {
+    /// The urls which receive a `POST` request for every changed score.
+    /// No webhooks are notified if empty.
+    pub urls: Vec<String>,
+    /// The number of attempts to deliver a notification before it is dropped.
+    pub attempts: u32,
+    /// The delay in *seconds* before the first retry, it is doubled for every further retry.
+    pub retry_delay: u64,
+    /// The timeout in *seconds* of a single delivery.
+    pub timeout: u64,
+    /// The maximum number of notifications which wait for their delivery to a single webhook.
+    /// Further notifications are dropped for this webhook until its queue has capacity again.
+    pub queue_size: usize,
+}
+
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        Self {
+            urls: vec![],
+            attempts: 5,
+            retry_delay: 2,
+            timeout: 10,
+            queue_size: 256,
         }
     }
 }
 
+/// The capitalization to apply to free-text values such as genres.
+#[derive(Default, Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Capitalization {
+    /// Leave the value as it is.
+    #[default]
+    Unchanged,
+    /// Uppercase the first letter of each word and lowercase the others such as 'Polka Mazurka'.
+    TitleCase,
+    /// Lowercase all letters.
+    Lowercase,
+}
+
 /// Configuration of the document server which provides all the documents for access.
 /// The server must implement the WebDav specification.
 /// In the context of a music society, this is typically a nextcloud instance.
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct DocumentServer {
+    /// How the documents are accessed, either on the local filesystem or on the WebDav server.
+    pub access: DocumentAccess,
     /// The root of all documents.
     /// Must be a full qualified HTTP URL to the WebDav instance.
     /// May already contain directories.
     pub base_url: String,
+    /// The user to authenticate at the WebDav server, no authentication is used if not set.
+    pub username: Option<String>,
+    /// The password of the user to authenticate at the WebDav server.
+    pub password: Option<String>,
     /// The mappings of the document types to server directories.
+    /// The directories are relative to the [DocumentServer::base_url] or paths on the local filesystem, depending on the [DocumentServer::access].
     pub mapping: DocumentMapping,
+    /// The maximum depth of directories to descend into when listing documents recursively.
+    /// A depth of `0` only lists the files directly located in the directory of the document type.
+    pub max_listing_depth: usize,
+    /// The maximum number of files returned when listing documents recursively.
+    pub max_listing_entries: usize,
+    /// The maximum sizes of the documents per document type.
+    pub max_document_sizes: DocumentSizeLimits,
 }
 
 impl Default for DocumentServer {
     fn default() -> Self {
         Self {
+            access: Default::default(),
             base_url: "".to_string(),
+            username: None,
+            password: None,
             mapping: Default::default(),
+            max_listing_depth: 2,
+            max_listing_entries: 500,
+            max_document_sizes: Default::default(),
+        }
+    }
+}
+
+/// The way the documents are accessed.
+#[derive(Default, Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DocumentAccess {
+    /// Read the documents from the local filesystem, for example a mounted share of the document server.
+    #[default]
+    Local,
+    /// Request the documents from the WebDav server at [DocumentServer::base_url].
+    WebDav,
+}
+
+/// The maximum sizes of the documents per document type in *bytes*.
+/// Documents which exceed the size are rejected instead of being served.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DocumentSizeLimits {
+    /// The maximum size of a blackboard document.
+    pub blackboard: u64,
+}
+
+impl Default for DocumentSizeLimits {
+    fn default() -> Self {
+        Self {
+            blackboard: 1024 * 1024,
         }
     }
 }
@@ -440,6 +913,11 @@ pub struct CalendarConfig {
     pub ical_url: String,
     /// The URL to the ical which contains all events which are for internal usage only such as preparations.
     pub ical_internal_url: String,
+    /// The time in *seconds* the events of a calendar are cached before they are fetched again.
+    /// Expired events are still served while they are fetched in the background or if the calendar is not available.
+    pub cache_ttl: u64,
+    /// The timeout in *seconds* of fetching a calendar from upstream.
+    pub timeout: u64,
 }
 
 impl Default for CalendarConfig {
@@ -447,19 +925,125 @@ impl Default for CalendarConfig {
         Self {
             ical_url: "".to_string(),
             ical_internal_url: "".to_string(),
+            cache_ttl: 300,
+            timeout: 10,
+        }
+    }
+}
+
+/// The configuration related to the member rest interface.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MembersConfig {
+    /// Whether members with neither a mobile number nor a mail address are omitted from the contact sheet.
+    pub contact_sheet_omit_without_contact: bool,
+    /// The maximum width and height of photo thumbnails in *pixels*.
+    /// Thumbnails are only generated if the application is built with the `thumbnails` feature.
+    pub thumbnail_size: u32,
+    /// Whether the entity tags of the member photos are computed during the synchronization.
+    /// Otherwise, they are computed on every photo request.
+    pub hash_photos: bool,
+    /// Whether the member photos are fetched in a separate pass after the synchronization of the other attributes.
+    /// This makes the member data available quickly while the photos are filled in afterwards.
+    pub deferred_photos: bool,
+    /// The maximum number of photos which are fetched concurrently if [MembersConfig::deferred_photos] is set.
+    pub photo_concurrency: usize,
+    /// The format of the birthday attribute as accepted by [chrono::NaiveDate::parse_from_str].
+    /// Birthdays which do not match the format are ignored for the upcoming birthdays.
+    pub birthday_format: String,
+}
+
+impl Default for MembersConfig {
+    fn default() -> Self {
+        Self {
+            contact_sheet_omit_without_contact: false,
+            thumbnail_size: 128,
+            hash_photos: true,
+            deferred_photos: false,
+            photo_concurrency: 4,
+            birthday_format: "%Y-%m-%d".to_string(),
+        }
+    }
+}
+
+/// The configuration of security related measures.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SecurityConfig {
+    /// The number of failed logins per username and client address after which further logins are rejected.
+    pub login_attempts: u32,
+    /// The window in *seconds* in which failed logins are counted.
+    /// Logins are accepted again once the window of the first failed login has passed.
+    pub login_window: u64,
+}
+
+impl Default for SecurityConfig {
+    fn default() -> Self {
+        Self {
+            login_attempts: 5,
+            login_window: 300,
+        }
+    }
+}
+
+/// The configuration of the health endpoint.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct HealthConfig {
+    /// The time in *seconds* each check may take before the subsystem is considered as failed.
+    pub timeout: u64,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        Self { timeout: 2 }
+    }
+}
+
+/// The configuration of the metrics endpoint.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct MetricsConfig {
+    /// Whether the metrics are exposed in the Prometheus text format at `/metrics`.
+    /// The endpoint requires no authentication and should be disabled for public deployments.
+    pub enabled: bool,
+}
+
+/// The configuration of the cross-origin resource sharing (CORS).
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CorsConfig {
+    /// The origins which are allowed to access the server such as `https://mvl.at`.
+    /// If it contains `*`, any origin is allowed but credentials are not.
+    /// Otherwise, the origin of a request is only echoed back if it is in this list and credentials are allowed.
+    pub allowed_origins: Vec<String>,
+    /// The HTTP methods which are allowed in preflight requests.
+    pub allowed_methods: Vec<String>,
+    /// The HTTP headers which are allowed in preflight requests.
+    /// If it contains `*`, the requested headers are allowed.
+    pub allowed_headers: Vec<String>,
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        Self {
+            allowed_origins: vec!["*".to_string()],
+            allowed_methods: ["HEAD", "GET", "POST", "PUT", "PATCH", "DELETE"]
+                .map(ToString::to_string)
+                .to_vec(),
+            allowed_headers: vec!["*".to_string()],
         }
     }
 }
 
 /// Read the configuration from `keg.toml` and set the `KEG_` prefix for all rocket related environment variables.
+/// Another path to the configuration file may be specified with the `KEG_CONFIG` environment variable.
 /// Furthermore, the profile will be selected.
-/// Note, that the functionality to specify another `keg.toml` path via the `KEG_CONFIG` environment variable is currently broken.
 ///
-/// returns: Figment for the configuration  
+/// returns: Figment for the configuration
 pub fn read_config() -> Figment {
     Figment::from(rocket::Config::default())
         .merge(Serialized::defaults(Config::default()))
-        .merge(Toml::file("keg.toml").nested())
-        .merge(Env::prefixed("KEG_").global())
+        .merge(Toml::file(Env::var_or("KEG_CONFIG", "keg.toml")).nested())
+        .merge(
+            Env::prefixed("KEG_")
+                .ignore(&["CONFIG", "PROFILE", "LOG_FORMAT"])
+                .global(),
+        )
         .select(Profile::from_env_or("KEG_PROFILE", "default"))
 }
@@ -0,0 +1,180 @@
+// OpenKeg, the lightweight backend of the Musikverein Leopoldsdorf.
+// Copyright (C) 2022  Richard Stöckl
+//
+// This program is free software; you can redistribute it and/or
+// modify it under the terms of the GNU General Public License
+// as published by the Free Software Foundation; either version 2
+// of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+
+use figment::Jail;
+
+use super::*;
+
+fn valid_config() -> Config {
+    let mut conf = Config::default();
+    conf.database.url = "http://localhost:5984".to_string();
+    conf.ldap.server = "ldap://localhost:389".to_string();
+    let endpoints = conf
+        .database
+        .database_mapping
+        .endpoints()
+        .into_iter()
+        .map(|(key, _)| {
+            (
+                key.to_string(),
+                serde_json::Value::from("/archive/_design/archive"),
+            )
+        })
+        .collect();
+    conf.database.database_mapping =
+        serde_json::from_value(serde_json::Value::Object(endpoints)).unwrap();
+    conf
+}
+
+#[test]
+fn valid() {
+    assert_eq!(valid_config().validate(), Ok(()));
+}
+
+#[test]
+fn defaults_of_added_mappings() {
+    let mut conf = Config::default();
+    conf.database.url = "http://localhost:5984".to_string();
+    conf.ldap.server = "ldap://localhost:389".to_string();
+    let mapping = &mut conf.database.database_mapping;
+    for endpoint in [
+        &mut mapping.all_scores,
+        &mut mapping.find_scores,
+        &mut mapping.get_score,
+        &mut mapping.put_score,
+        &mut mapping.delete_score,
+        &mut mapping.genres_statistic,
+        &mut mapping.composers_statistic,
+        &mut mapping.arrangers_statistic,
+        &mut mapping.publishers_statistic,
+        &mut mapping.books_statistic,
+        &mut mapping.locations_statistic,
+    ] {
+        *endpoint = "/archive".to_string();
+    }
+    assert_eq!(conf.validate(), Ok(()));
+}
+
+#[test]
+fn missing_database_url() {
+    let mut conf = valid_config();
+    conf.database.url = "".to_string();
+    assert_eq!(
+        conf.validate(),
+        Err("'database.url' must not be empty".to_string())
+    );
+    conf.database.url = "/couchdb".to_string();
+    assert_eq!(
+        conf.validate(),
+        Err(
+            "'database.url' is not a valid URL (/couchdb): relative URL without a base".to_string()
+        )
+    );
+    conf.database.url = "localhost:5984".to_string();
+    assert_eq!(
+        conf.validate(),
+        Err("'database.url' must use the http or https scheme, but uses 'localhost'".to_string())
+    );
+}
+
+#[test]
+fn missing_mapping() {
+    let mut conf = valid_config();
+    conf.database.database_mapping.find_scores = " ".to_string();
+    assert_eq!(
+        conf.validate(),
+        Err("'database.database_mapping.find_scores' must not be empty".to_string())
+    );
+}
+
+#[test]
+fn invalid_ldap_server() {
+    let mut conf = valid_config();
+    conf.ldap.server = "ldap.example.com".to_string();
+    assert!(conf
+        .validate()
+        .is_err_and(|msg| msg.starts_with("'ldap.server' is not a valid URL")));
+}
+
+#[test]
+fn non_positive_expiration() {
+    let mut conf = valid_config();
+    conf.jwt.expiration = 0;
+    assert_eq!(
+        conf.validate(),
+        Err("'jwt.expiration' must be positive, but is 0".to_string())
+    );
+    conf.jwt.expiration = 10;
+    conf.jwt.renewal_expiration = -1;
+    assert_eq!(
+        conf.validate(),
+        Err("'jwt.renewal_expiration' must be positive, but is -1".to_string())
+    );
+}
+
+#[test]
+fn password_files() {
+    let path = std::env::temp_dir().join(format!("keg-password-{}", std::process::id()));
+    fs::write(&path, "Ibimsdastaschasserkoal\n").expect("password file");
+    let mut conf = valid_config();
+    conf.ldap.password = Some("inline".to_string());
+    conf.ldap.password_file = Some(path.to_string_lossy().to_string());
+    let passwords = read_password_files(&conf);
+    fs::remove_file(&path).expect("removed password file");
+    assert_eq!(
+        passwords,
+        Ok(vec![(
+            "ldap.password",
+            "Ibimsdastaschasserkoal".to_string()
+        )])
+    );
+    let merged: Config = Figment::from(Serialized::defaults(conf.clone()))
+        .merge(Serialized::global(
+            "ldap.password",
+            "Ibimsdastaschasserkoal",
+        ))
+        .extract()
+        .expect("config");
+    assert_eq!(
+        merged.ldap.password.as_deref(),
+        Some("Ibimsdastaschasserkoal")
+    );
+    conf.database.password_file = Some(path.to_string_lossy().to_string());
+    assert!(read_password_files(&conf)
+        .is_err_and(|msg| msg.starts_with("'ldap.password_file' cannot be read from")));
+}
+
+#[test]
+// the jail only accepts closures which return the error of figment
+#[allow(clippy::result_large_err)]
+fn custom_config_path() {
+    Jail::expect_with(|jail| {
+        jail.create_file(
+            "custom.toml",
+            "[default.database]\nurl = \"http://couchdb:5984\"\nusername = \"keg\"\n\n[staging.database]\nusername = \"staging\"\n",
+        )?;
+        jail.set_env("KEG_CONFIG", "custom.toml");
+        let default: Config = read_config().extract()?;
+        jail.set_env("KEG_PROFILE", "staging");
+        let staging: Config = read_config().extract()?;
+        assert_eq!(default.database.url, "http://couchdb:5984");
+        assert_eq!(default.database.username, "keg");
+        assert_eq!(staging.database.url, "http://couchdb:5984");
+        assert_eq!(staging.database.username, "staging");
+        Ok(())
+    });
+}
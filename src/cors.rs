@@ -15,12 +15,46 @@
 // along with this program; if not, write to the Free Software
 // Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
 
-use crate::user::tokens::{AUTHORIZATION_HEADER, AUTHORIZATION_RENEWAL_HEADER};
+use crate::config::CorsConfig;
+use crate::user::tokens::{renewal_name, AUTHORIZATION_HEADER, LEGACY_RENEWAL_HEADER};
 use rocket::fairing::{Fairing, Info, Kind};
-use rocket::http::Header;
+use rocket::http::{Header, Method};
 use rocket::{Request, Response};
 
-pub struct Cors;
+/// The wildcard which allows any origin or header.
+const WILDCARD: &str = "*";
+
+/// The fairing which adds the CORS headers according to the [CorsConfig].
+pub struct Cors(pub CorsConfig);
+
+impl Cors {
+    /// Determine the allowed origin for a request.
+    ///
+    /// # Arguments
+    ///
+    /// * `origin`: the value of the `Origin` header of the request
+    ///
+    /// returns: Option<&str> the value of the `Access-Control-Allow-Origin` header, no header should be set if [None]
+    fn allowed_origin<'a>(&self, origin: Option<&'a str>) -> Option<&'a str> {
+        if self.wildcard_origin() {
+            return Some(WILDCARD);
+        }
+        origin.filter(|origin| {
+            self.0
+                .allowed_origins
+                .iter()
+                .any(|allowed| allowed == origin)
+        })
+    }
+
+    /// Whether any origin is allowed.
+    fn wildcard_origin(&self) -> bool {
+        self.0
+            .allowed_origins
+            .iter()
+            .any(|origin| origin == WILDCARD)
+    }
+}
 
 #[rocket::async_trait]
 impl Fairing for Cors {
@@ -31,19 +65,40 @@ impl Fairing for Cors {
         }
     }
 
-    async fn on_response<'r>(&self, _request: &'r Request<'_>, response: &mut Response<'r>) {
-        response.set_header(Header::new("Access-Control-Allow-Origin", "*"));
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let origin = match self.allowed_origin(request.headers().get_one("Origin")) {
+            Some(origin) => origin,
+            None => return,
+        };
         response.set_header(Header::new(
-            "Access-Control-Allow-Methods",
-            "HEAD, GET, POST, PUT, PATCH, DELETE",
+            "Access-Control-Allow-Origin",
+            origin.to_string(),
         ));
-        response.set_header(Header::new("Access-Control-Allow-Headers", "*"));
-        response.set_header(Header::new("Access-Control-Allow-Credentials", "true"));
+        if !self.wildcard_origin() {
+            response.set_header(Header::new("Access-Control-Allow-Credentials", "true"));
+            response.adjoin_header(Header::new("Vary", "Origin"));
+        }
+        if request.method() == Method::Options {
+            response.set_header(Header::new(
+                "Access-Control-Allow-Methods",
+                self.0.allowed_methods.join(", "),
+            ));
+            let requested_headers = request.headers().get_one("Access-Control-Request-Headers");
+            let allowed_headers = match requested_headers {
+                Some(requested) if self.0.allowed_headers.iter().any(|h| h == WILDCARD) => {
+                    requested.to_string()
+                }
+                _ => self.0.allowed_headers.join(", "),
+            };
+            response.set_header(Header::new("Access-Control-Allow-Headers", allowed_headers));
+        }
         response.set_header(Header::new(
             "Access-Control-Expose-Headers",
             format!(
-                "{}, {}, *",
-                AUTHORIZATION_HEADER, AUTHORIZATION_RENEWAL_HEADER
+                "{}, {}, {}, *",
+                AUTHORIZATION_HEADER,
+                renewal_name(request),
+                LEGACY_RENEWAL_HEADER
             ),
         ));
     }
@@ -0,0 +1,80 @@
+// OpenKeg, the lightweight backend of the Musikverein Leopoldsdorf.
+// Copyright (C) 2022  Richard Stöckl
+//
+// This program is free software; you can redistribute it and/or
+// modify it under the terms of the GNU General Public License
+// as published by the Free Software Foundation; either version 2
+// of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+
+use std::io::Cursor;
+
+use okapi::map;
+use okapi::openapi3::{RefOr, Responses};
+use rocket::http::{ContentType, Header};
+use rocket::response::Responder;
+use rocket::{Request, Response};
+use rocket_okapi::gen::OpenApiGenerator;
+use rocket_okapi::response::OpenApiResponderInner;
+
+/// A CSV document which is served as attachment in order to be saved or imported into a spreadsheet.
+pub struct CsvAttachment {
+    /// The name of the file the document is saved as.
+    pub filename: String,
+    /// The whole document including the header line.
+    pub content: String,
+}
+
+impl<'r> Responder<'r, 'static> for CsvAttachment {
+    fn respond_to(self, _request: &'r Request<'_>) -> rocket::response::Result<'static> {
+        Response::build()
+            .header(ContentType::CSV)
+            .header(Header::new(
+                "Content-Disposition",
+                format!("attachment; filename=\"{}\"", self.filename),
+            ))
+            .sized_body(self.content.len(), Cursor::new(self.content))
+            .ok()
+    }
+}
+
+impl OpenApiResponderInner for CsvAttachment {
+    fn responses(_gen: &mut OpenApiGenerator) -> rocket_okapi::Result<Responses> {
+        let csv_response = okapi::openapi3::Response {
+            description: "The CSV document as attachment".to_string(),
+            content: map! {
+                ContentType::CSV.to_string() => okapi::openapi3::MediaType::default()
+            },
+            ..okapi::openapi3::Response::default()
+        };
+        Ok(Responses {
+            default: None,
+            responses: map! {"200".to_string() => RefOr::Object(csv_response)},
+            extensions: map! {},
+        })
+    }
+}
+
+/// Quote a CSV field if it contains a separator, a quote or a line break.
+/// Quotes within the field are doubled.
+///
+/// # Arguments
+///
+/// * `field`: the field to quote
+///
+/// returns: String
+pub fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
@@ -0,0 +1,131 @@
+// OpenKeg, the lightweight backend of the Musikverein Leopoldsdorf.
+// Copyright (C) 2022  Richard Stöckl
+//
+// This program is free software; you can redistribute it and/or
+// modify it under the terms of the GNU General Public License
+// as published by the Free Software Foundation; either version 2
+// of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+
+use std::collections::HashMap;
+
+use reqwest::Method;
+use rocket::http::Status;
+use rocket::serde::json::Json;
+
+use crate::archive::model::Book;
+use crate::database::client::{
+    check_document_partition, generate_document_id, request, DatabaseClient, OperationResponse,
+    Pagination,
+};
+use crate::openapi::{ApiError, ApiResult};
+use crate::Config;
+
+/// Fetch all books from the database ordered by their id.
+///
+/// # Arguments
+///
+/// * `conf`: the application configuration
+/// * `client`: the client to send the requests with
+///
+/// returns: Result<Json<Vec<Book>>, Error>
+pub async fn all_books(conf: &Config, client: &DatabaseClient) -> ApiResult<Vec<Book>> {
+    let mut parameters = HashMap::new();
+    parameters.insert("include_docs".to_string(), "true".to_string());
+    let books: Pagination<Book> = request(
+        conf,
+        client,
+        Box::new(|r| r),
+        Method::GET,
+        &conf.database.database_mapping.all_books,
+        &parameters,
+    )
+    .await?;
+    Ok(Json(books.rows.into_iter().map(|r| r.doc).collect()))
+}
+
+/// Insert or update a book in the database.
+/// When creating a new book, leave its `_id` and `_rev` unset, both must be set on update.
+///
+/// # Arguments
+///
+/// * `conf`: the application configuration
+/// * `client`: the client to perform the request with
+/// * `book`: the book to insert or update
+///
+/// returns: Result<Json<OperationResponse>, Error>
+pub async fn put_book(
+    conf: &Config,
+    client: &DatabaseClient,
+    mut book: Book,
+) -> ApiResult<OperationResponse> {
+    if book.couch_id.is_some() != book.couch_revision.is_some() {
+        return Err(ApiError {
+            err: "invalid id".to_string(),
+            msg: Some("you must either provide both id and rev, in order to update a document, or provide none of them, in order to insert one".to_string()),
+            http_status_code: Status::BadRequest.code,
+        });
+    }
+    let couch_id = match &book.couch_id {
+        Some(couch_id) => {
+            check_document_partition(couch_id, &conf.database.book_partition)?;
+            couch_id.clone()
+        }
+        None => {
+            let couch_id = generate_document_id(&conf.database.book_partition);
+            book.couch_id = Some(couch_id.clone());
+            couch_id
+        }
+    };
+    let parameters: HashMap<String, String> = HashMap::new();
+    request(
+        conf,
+        client,
+        Box::new(|r| r.json(&book)),
+        Method::PUT,
+        &format!("{}/{}", conf.database.database_mapping.put_book, couch_id),
+        &parameters,
+    )
+    .await
+    .map(Json)
+}
+
+/// Delete a book by its id and revision.
+/// The pages of the scores which refer to the book are left untouched.
+///
+/// # Arguments
+///
+/// * `conf`: the application configuration
+/// * `client`: the client to perform the request
+/// * `id`: the id of the book to delete
+/// * `rev`: the revision of the book to delete
+///
+/// returns: Result<Json<OperationResponse>, Error>
+pub async fn delete_book(
+    conf: &Config,
+    client: &DatabaseClient,
+    id: String,
+    rev: String,
+) -> ApiResult<OperationResponse> {
+    check_document_partition(&id, &conf.database.book_partition)?;
+    let mut parameters: HashMap<String, String> = HashMap::new();
+    parameters.insert("rev".to_string(), rev);
+    request(
+        conf,
+        client,
+        Box::new(|r| r),
+        Method::DELETE,
+        &format!("{}/{}", conf.database.database_mapping.delete_book, id),
+        &parameters,
+    )
+    .await
+    .map(Json)
+}
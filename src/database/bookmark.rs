@@ -0,0 +1,78 @@
+// OpenKeg, the lightweight backend of the Musikverein Leopoldsdorf.
+// Copyright (C) 2022  Richard Stöckl
+//
+// This program is free software; you can redistribute it and/or
+// modify it under the terms of the GNU General Public License
+// as published by the Free Software Foundation; either version 2
+// of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+
+use base64::{engine, Engine};
+use ring::hmac;
+use rocket::http::Status;
+
+use crate::openapi::ApiError;
+
+#[cfg(test)]
+#[path = "bookmark_tests.rs"]
+mod bookmark_tests;
+
+/// Wrap the bookmark of the database into a token which is signed with the secret.
+/// The token contains the base64url encoded bookmark and its HMAC-SHA256 separated by a dot.
+///
+/// # Arguments
+///
+/// * `secret`: the secret to sign the bookmark with
+/// * `bookmark`: the bookmark returned by the database
+///
+/// returns: String the signed bookmark
+pub fn sign_bookmark(secret: &str, bookmark: &str) -> String {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+    let tag = hmac::sign(&key, bookmark.as_bytes());
+    let encoder = &engine::general_purpose::URL_SAFE_NO_PAD;
+    format!(
+        "{}.{}",
+        encoder.encode(bookmark),
+        encoder.encode(tag.as_ref())
+    )
+}
+
+/// Verify a token created by [sign_bookmark] and extract the bookmark of the database.
+///
+/// # Arguments
+///
+/// * `secret`: the secret the bookmark has been signed with
+/// * `token`: the signed bookmark
+///
+/// returns: Result<String, ApiError> the bookmark of the database or 'Bad Request' if the token has been tampered with
+pub fn verify_bookmark(secret: &str, token: &str) -> Result<String, ApiError> {
+    let decoder = &engine::general_purpose::URL_SAFE_NO_PAD;
+    let (bookmark, tag) = token.split_once('.').ok_or_else(invalid_bookmark)?;
+    let bookmark = decoder.decode(bookmark).map_err(|_| invalid_bookmark())?;
+    let tag = decoder.decode(tag).map_err(|_| invalid_bookmark())?;
+    let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+    hmac::verify(&key, &bookmark, &tag).map_err(|_| invalid_bookmark())?;
+    String::from_utf8(bookmark).map_err(|_| invalid_bookmark())
+}
+
+/// Create the error for a bookmark which has not been signed by this server.
+///
+/// returns: ApiError
+fn invalid_bookmark() -> ApiError {
+    debug!("Reject a bookmark with an invalid signature");
+    ApiError {
+        err: "Invalid Bookmark".to_string(),
+        msg: Some(
+            "The bookmark is invalid, please use the one of the previous response".to_string(),
+        ),
+        http_status_code: Status::BadRequest.code,
+    }
+}
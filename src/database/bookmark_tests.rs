@@ -0,0 +1,49 @@
+// OpenKeg, the lightweight backend of the Musikverein Leopoldsdorf.
+// Copyright (C) 2022  Richard Stöckl
+//
+// This program is free software; you can redistribute it and/or
+// modify it under the terms of the GNU General Public License
+// as published by the Free Software Foundation; either version 2
+// of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+
+use super::*;
+
+const SECRET: &str = "Ibimsdastaschasserkoal";
+
+#[test]
+fn signed_bookmark_round_trip() {
+    let token = sign_bookmark(SECRET, "g1AAAABueJzLYWBgYMpgSmHg");
+    assert_eq!(
+        verify_bookmark(SECRET, &token).expect("valid bookmark"),
+        "g1AAAABueJzLYWBgYMpgSmHg"
+    );
+}
+
+#[test]
+fn tampered_bookmark_rejected() {
+    let token = sign_bookmark(SECRET, "g1AAAABueJzLYWBgYMpgSmHg");
+    let (_, tag) = token.split_once('.').expect("separator");
+    let forged = format!(
+        "{}.{}",
+        engine::general_purpose::URL_SAFE_NO_PAD.encode("g1AAAABueJzLYWBgYMpgSmHh"),
+        tag
+    );
+    let error = verify_bookmark(SECRET, &forged).expect_err("forged bookmark");
+    assert_eq!(error.http_status_code, Status::BadRequest.code);
+}
+
+#[test]
+fn foreign_secret_rejected() {
+    let token = sign_bookmark("another secret", "g1AAAABueJzLYWBgYMpgSmHg");
+    assert!(verify_bookmark(SECRET, &token).is_err());
+    assert!(verify_bookmark(SECRET, "g1AAAABueJzLYWBgYMpgSmHg").is_err());
+}
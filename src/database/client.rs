@@ -15,24 +15,73 @@
 // along with this program; if not, write to the Free Software
 // Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
 
+use crate::archive::model::Statistic;
 use crate::openapi::{ApiError, SchemaExample};
+use crate::request_id::{current_request_id, REQUEST_ID_HEADER};
 use crate::{keg_user_agent, Config};
-use reqwest::{Client, ClientBuilder, Method, RequestBuilder, StatusCode, Url};
+use reqwest::cookie::Jar;
+use reqwest::header::{CONTENT_TYPE, SET_COOKIE};
+use reqwest::{Client, ClientBuilder, Method, Request, RequestBuilder, Response, StatusCode, Url};
 use rocket::http::Status;
+use rocket::tokio::sync::Mutex;
 use schemars::JsonSchema;
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
 use std::error::Error;
+use std::fs;
+use std::io;
+use std::io::Write;
+use std::ops::Deref;
+use std::os::unix::fs::OpenOptionsExt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use uuid::Uuid;
 
-/// An alias for the database HTTP client.
-/// Used to be able to let rocket manage multiple HTTP clients, each for its specialized purpose.
-/// May be replaced with a tuple struct in the future.
-pub type DatabaseClient = Client;
+#[cfg(test)]
+#[path = "client_tests.rs"]
+mod client_tests;
+
+/// The HTTP client of the database together with the state of its session.
+/// A distinct type in order to let rocket manage multiple HTTP clients, each for its specialized purpose.
+pub struct DatabaseClient {
+    /// The HTTP client which holds the session cookie.
+    client: Client,
+    /// The moment of the last successful reauthentication.
+    /// The mutex ensures that only one reauthentication is performed at a time.
+    last_reauthentication: Mutex<Option<Instant>>,
+}
+
+impl DatabaseClient {
+    /// Wrap an HTTP client which has not been reauthenticated yet.
+    ///
+    /// # Arguments
+    ///
+    /// * `client`: the HTTP client with cookie support
+    ///
+    /// returns: DatabaseClient
+    pub fn new(client: Client) -> Self {
+        Self {
+            client,
+            last_reauthentication: Mutex::new(None),
+        }
+    }
+}
+
+impl Deref for DatabaseClient {
+    type Target = Client;
+
+    fn deref(&self) -> &Self::Target {
+        &self.client
+    }
+}
 
 /// Initialize the database client and configures it.
+/// The connect and request timeouts are taken from the [crate::config::DatabaseConfig].
 /// If the initialization fails this function will panic.
 /// After the initialization this functions tries to authenticate against the database interface using cookies.
+/// If a persisted session cookie is configured and still valid, it is reused instead.
 /// When this fails, an error will be printed.
 ///
 /// # Arguments
@@ -41,15 +90,23 @@ pub type DatabaseClient = Client;
 ///
 /// returns: the configured [`DatabaseClient`]
 pub async fn initialize_client(conf: &Config) -> DatabaseClient {
+    let jar = Arc::new(Jar::default());
+    let restored = restore_session_cookie(conf, &jar);
     let client = ClientBuilder::new()
         .user_agent(keg_user_agent().as_str())
-        .cookie_store(true)
+        .cookie_provider(jar)
+        .connect_timeout(Duration::from_secs(conf.database.connect_timeout))
+        .timeout(Duration::from_secs(conf.database.request_timeout))
         .build()
         .map_err(|e| {
             error!("Unable to initialize http client: {}", e);
             e
         })
         .expect("First database client");
+    if restored && session_valid(conf, &client).await {
+        info!("Reuse the persisted session of the database interface");
+        return DatabaseClient::new(client);
+    }
     authenticate(conf, &client)
         .await
         .map_err(|e| {
@@ -57,7 +114,7 @@ pub async fn initialize_client(conf: &Config) -> DatabaseClient {
             e
         })
         .expect("First authenticated client");
-    client
+    DatabaseClient::new(client)
 }
 
 /// Internal holder for username, password credentials.
@@ -94,9 +151,139 @@ pub(crate) async fn authenticate(conf: &Config, client: &Client) -> Result<(), B
         conf.database.url, conf.database.database_mapping.authentication
     ))?;
     let request = client.post(url).form(&<Credentials>::from(conf)).build()?;
-    let response = client.execute(request).await?;
-    response.error_for_status()?;
+    let response = client.execute(request).await?.error_for_status()?;
     info!("Authentication to the database interface was successful");
+    persist_session_cookie(conf, &response);
+    Ok(())
+}
+
+/// The session information returned by the database.
+#[derive(Deserialize)]
+struct Session {
+    /// The context of the user the session belongs to.
+    #[serde(rename = "userCtx")]
+    user_context: SessionUserContext,
+}
+
+/// The user context of a session.
+#[derive(Deserialize)]
+struct SessionUserContext {
+    /// The name of the user, `None` if the session is not authenticated.
+    name: Option<String>,
+}
+
+/// Check whether the client holds an authenticated session.
+///
+/// # Arguments
+///
+/// * `conf`: the application configuration
+/// * `client`: the HTTP client to use, cookie support is required
+///
+/// returns: bool
+async fn session_valid(conf: &Config, client: &Client) -> bool {
+    let url = format!(
+        "{}{}",
+        conf.database.url, conf.database.database_mapping.authentication
+    );
+    let session = match client.get(url).send().await {
+        Ok(response) => response.json::<Session>().await,
+        Err(err) => Err(err),
+    };
+    session.map_or_else(
+        |err| {
+            info!("Unable to validate the persisted session: {}", err);
+            false
+        },
+        |s| s.user_context.name.is_some(),
+    )
+}
+
+/// Load the persisted session cookie into the cookie jar if configured.
+///
+/// # Arguments
+///
+/// * `conf`: the application configuration
+/// * `jar`: the cookie jar of the HTTP client
+///
+/// returns: bool whether a cookie was restored
+fn restore_session_cookie(conf: &Config, jar: &Jar) -> bool {
+    let (Some(path), Ok(url)) = (&conf.database.cookie_path, Url::parse(&conf.database.url)) else {
+        return false;
+    };
+    match fs::read_to_string(path) {
+        Ok(cookies) => {
+            cookies
+                .lines()
+                .filter(|c| !c.is_empty())
+                .for_each(|c| jar.add_cookie_str(c, &url));
+            true
+        }
+        Err(err) => {
+            info!("No persisted session cookie at '{}': {}", path, err);
+            false
+        }
+    }
+}
+
+/// Persist the session cookies of an authentication response if configured.
+/// The file is only readable by the owner since it grants access to the database.
+/// Failures are logged but do not affect the authentication.
+///
+/// # Arguments
+///
+/// * `conf`: the application configuration
+/// * `response`: the response of the authentication request
+fn persist_session_cookie(conf: &Config, response: &Response) {
+    let Some(path) = &conf.database.cookie_path else {
+        return;
+    };
+    let cookies: Vec<&str> = response
+        .headers()
+        .get_all(SET_COOKIE)
+        .iter()
+        .filter_map(|c| c.to_str().ok())
+        .collect();
+    let result = fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)
+        .and_then(|mut file| file.write_all(cookies.join("\n").as_bytes()));
+    if let Err(err) = result {
+        warn!(
+            "Unable to persist the session cookie to '{}': {}",
+            path, err
+        );
+    }
+}
+
+/// Reauthenticate against the database after a request failed due to an expired session.
+/// Only one reauthentication is performed at a time, concurrent callers wait for it and reuse the refreshed session cookie.
+/// The reauthentication is skipped if another one succeeded after the failed request was sent or within the [crate::config::DatabaseConfig::reauthentication_threshold].
+///
+/// # Arguments
+///
+/// * `conf`: the application configuration
+/// * `client`: the client of the database which holds the moment of its last reauthentication
+/// * `request_start`: the moment when the failed request was sent
+///
+/// returns: Result<(), Error>
+async fn reauthenticate(
+    conf: &Config,
+    client: &DatabaseClient,
+    request_start: Instant,
+) -> Result<(), Box<dyn Error>> {
+    let mut last_reauthentication = client.last_reauthentication.lock().await;
+    let threshold = Duration::from_secs(conf.database.reauthentication_threshold);
+    if let Some(last) = *last_reauthentication {
+        if last >= request_start || last.elapsed() < threshold {
+            debug!("The session was already renewed recently, skip reauthentication");
+            return Ok(());
+        }
+    }
+    authenticate(conf, client).await?;
+    *last_reauthentication = Some(Instant::now());
     Ok(())
 }
 
@@ -173,7 +360,34 @@ where
     /// The bookmark used for pagination.
     pub bookmark: String,
     /// The execution statistics generated by the database.
-    pub execution_stats: ExecutionStats,
+    /// Omitted if disabled by [crate::config::DatabaseConfig::include_execution_stats].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub execution_stats: Option<ExecutionStats>,
+    /// The maximum number of documents of the page as requested.
+    #[serde(default)]
+    pub limit: u64,
+    /// Whether there may be more documents after this page.
+    /// This is the case if the page is full, the next page may still be empty though.
+    #[serde(default)]
+    pub has_more: bool,
+}
+
+impl<D> FindResponse<D>
+where
+    D: Serialize + JsonSchema + SchemaExample,
+{
+    /// Add the pagination metadata to a response of the database.
+    ///
+    /// # Arguments
+    ///
+    /// * `limit`: the maximum number of documents which were requested for the page
+    ///
+    /// returns: FindResponse<D>
+    pub fn paginated(mut self, limit: u64) -> Self {
+        self.limit = limit;
+        self.has_more = limit > 0 && self.docs.len() as u64 >= limit;
+        self
+    }
 }
 
 impl<D> SchemaExample for FindResponse<D>
@@ -184,7 +398,9 @@ where
         Self {
             docs: vec![],
             bookmark: "g1AAAABueJzLYWBgYMpgSmHgKy5JLCrJTq2MT8lPzkzJBYprFyfnF6UWW6WZWFgamhiZ6yYZG1jqmpglJ-smGhgZ6JokJ6WlWqYmp6ZZpoKM4IAZkQPUzAgygTcksyg_J7VIwTEFSGZlAQCcwx9S".to_string(),
-            execution_stats: SchemaExample::example(),
+            execution_stats: Some(SchemaExample::example()),
+            limit: 20,
+            has_more: false,
         }
     }
 }
@@ -238,6 +454,35 @@ impl From<(DatabaseError, StatusCode)> for ApiError {
     }
 }
 
+/// Map an unsuccessful response of the database to an [ApiError] which keeps the status of the response.
+/// If the body is not a [DatabaseError], a generic message is used instead.
+///
+/// # Arguments
+///
+/// * `status`: the status of the response
+/// * `body`: the body of the response
+///
+/// returns: ApiError
+fn database_error(status: StatusCode, body: &[u8]) -> ApiError {
+    match serde_json::from_slice::<DatabaseError>(body) {
+        Ok(error) => ApiError::from((error, status)),
+        Err(e) => {
+            warn!(
+                "Unable to read the error of the database with status {}: {}",
+                status, e
+            );
+            ApiError {
+                err: status
+                    .canonical_reason()
+                    .unwrap_or("Database Error")
+                    .to_string(),
+                msg: Some("The database is unable to perform the request".to_string()),
+                http_status_code: status.as_u16(),
+            }
+        }
+    }
+}
+
 #[derive(Clone, Default, Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(crate = "rocket::serde")]
 #[schemars(example = "Self::example")]
@@ -245,9 +490,18 @@ pub struct OperationResponse {
     /// The id of the deleted document.
     pub id: String,
     /// The status of the operation.
+    #[serde(default)]
     pub ok: bool,
     /// The revision of the document of the operation context.
+    /// Empty if the operation failed within a bulk operation.
+    #[serde(default)]
     pub rev: String,
+    /// The error if the operation failed within a bulk operation such as `conflict`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// The reason of the error if the operation failed within a bulk operation.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
 }
 
 impl SchemaExample for OperationResponse {
@@ -256,6 +510,8 @@ impl SchemaExample for OperationResponse {
             id: "scores:s8eu".to_string(),
             ok: true,
             rev: "1-h98rgu".to_string(),
+            error: None,
+            reason: None,
         }
     }
 }
@@ -270,6 +526,88 @@ fn request_error() -> ApiError {
     }
 }
 
+/// Execute a request and retry it if it fails due to a transient error such as a timeout or a reset connection.
+/// At most [crate::config::DatabaseConfig::retry_attempts] attempts are made with an exponential backoff in between.
+/// Requests which are not idempotent are only retried if they are read-only or [crate::config::DatabaseConfig::retry_non_idempotent] is set.
+///
+/// # Arguments
+///
+/// * `conf`: the application configuration
+/// * `client`: the client to execute the request with
+/// * `request`: the request to execute
+/// * `read_only`: whether the request does not modify the database regardless of its method, such as a `POST` to `_find`
+///
+/// returns: Result<Response, Error> the response of the last attempt
+async fn execute_with_retries(
+    conf: &Config,
+    client: &DatabaseClient,
+    request: Request,
+    read_only: bool,
+) -> Result<Response, reqwest::Error> {
+    let retryable = read_only
+        || conf.database.retry_non_idempotent
+        || matches!(
+            *request.method(),
+            Method::GET | Method::HEAD | Method::OPTIONS
+        );
+    let attempts = if retryable {
+        conf.database.retry_attempts.max(1)
+    } else {
+        1
+    };
+    let mut delay = Duration::from_millis(conf.database.retry_delay);
+    let mut pending = request;
+    let mut attempt = 1;
+    loop {
+        let url = pending.url().clone();
+        let next = if attempt < attempts {
+            pending.try_clone()
+        } else {
+            None
+        };
+        match (client.execute(pending).await, next) {
+            (Err(e), Some(next)) if is_transient(&e) => {
+                warn!(
+                    "Attempt {} of {} to request {} failed, retry in {:?}: {}",
+                    attempt, attempts, url, delay, e
+                );
+                rocket::tokio::time::sleep(delay).await;
+                delay *= 2;
+                attempt += 1;
+                pending = next;
+            }
+            (result, _) => return result,
+        }
+    }
+}
+
+/// Whether a request failed due to an error which may not occur again, such as a timeout or a reset connection.
+///
+/// # Arguments
+///
+/// * `error`: the error of the request
+///
+/// returns: bool
+fn is_transient(error: &reqwest::Error) -> bool {
+    if error.is_timeout() || error.is_connect() {
+        return true;
+    }
+    let mut source = error.source();
+    while let Some(cause) = source {
+        if let Some(io_error) = cause.downcast_ref::<io::Error>() {
+            return matches!(
+                io_error.kind(),
+                io::ErrorKind::ConnectionReset
+                    | io::ErrorKind::ConnectionAborted
+                    | io::ErrorKind::BrokenPipe
+                    | io::ErrorKind::TimedOut
+            );
+        }
+        source = cause.source();
+    }
+    false
+}
+
 /// Request a resource from the couch database.
 /// If anything goes wrong during `URL`, request build or body deserialization, an appropriate [`Error`] will be returned which can be passed to the clients.
 ///
@@ -285,7 +623,7 @@ fn request_error() -> ApiError {
 /// returns: Result<R, Error>
 pub(crate) async fn request<'a, R, P>(
     conf: &Config,
-    client: &Client,
+    client: &DatabaseClient,
     request_hook: Box<dyn FnOnce(RequestBuilder) -> RequestBuilder + Send + 'a>,
     method: Method,
     api_url: &str,
@@ -294,6 +632,229 @@ pub(crate) async fn request<'a, R, P>(
 where
     P: Serialize + ?Sized,
     R: DeserializeOwned,
+{
+    let response = execute(
+        conf,
+        client,
+        request_hook,
+        method,
+        api_url,
+        parameters,
+        false,
+    )
+    .await?;
+    deserialize_response(response).await
+}
+
+/// Request a resource from the couch database with a request which does not modify the database, such as a `POST` to `_find`.
+/// Behaves like [request] but retries the request on transient errors regardless of its method.
+///
+/// # Arguments
+///
+/// * `conf`: the application configuration
+/// * `client`: the client to use for the database request, likely is required to be authenticated with a cookie
+/// * `request_hook`: a function used to modify the request, can be used to insert information such as the body
+/// * `method`: the `HTTP` method used for the request - replaces the current one
+/// * `api_url`: the `URL` relative to the base `URL` of the database
+/// * `parameters`: the query parameters being used for the request
+///
+/// returns: Result<R, Error>
+pub(crate) async fn request_read_only<'a, R, P>(
+    conf: &Config,
+    client: &DatabaseClient,
+    request_hook: Box<dyn FnOnce(RequestBuilder) -> RequestBuilder + Send + 'a>,
+    method: Method,
+    api_url: &str,
+    parameters: &P,
+) -> Result<R, ApiError>
+where
+    P: Serialize + ?Sized,
+    R: DeserializeOwned,
+{
+    let response = execute(
+        conf,
+        client,
+        request_hook,
+        method,
+        api_url,
+        parameters,
+        true,
+    )
+    .await?;
+    deserialize_response(response).await
+}
+
+/// Deserialize the body of a successful response of the database.
+///
+/// # Arguments
+///
+/// * `response`: the response of the database
+///
+/// returns: Result<R, Error>
+async fn deserialize_response<R>(response: Response) -> Result<R, ApiError>
+where
+    R: DeserializeOwned,
+{
+    response.json::<R>().await.map_err(|e| {
+        warn!("Unable to deserialize a response from the database: {}", e);
+        request_error()
+    })
+}
+
+/// Request a binary resource such as an attachment from the couch database.
+/// Behaves like [request] but returns the raw body with its content type instead of deserializing it.
+///
+/// # Arguments
+///
+/// * `conf`: the application configuration
+/// * `client`: the client to use for the database request, likely is required to be authenticated with a cookie
+/// * `request_hook`: a function used to modify the request, can be used to insert information such as the body
+/// * `method`: the `HTTP` method used for the request - replaces the current one
+/// * `api_url`: the `URL` relative to the base `URL` of the database
+/// * `parameters`: the query parameters being used for the request
+///
+/// returns: Result<(Option<String>, Vec<u8>), Error> the content type, if any, and the body
+pub(crate) async fn request_raw<'a, P>(
+    conf: &Config,
+    client: &DatabaseClient,
+    request_hook: Box<dyn FnOnce(RequestBuilder) -> RequestBuilder + Send + 'a>,
+    method: Method,
+    api_url: &str,
+    parameters: &P,
+) -> Result<(Option<String>, Vec<u8>), ApiError>
+where
+    P: Serialize + ?Sized,
+{
+    let response = execute(
+        conf,
+        client,
+        request_hook,
+        method,
+        api_url,
+        parameters,
+        false,
+    )
+    .await?;
+    let content_type = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(ToString::to_string);
+    let body = response.bytes().await.map_err(|e| {
+        warn!("Unable to read a response from the database: {}", e);
+        request_error()
+    })?;
+    Ok((content_type, body.to_vec()))
+}
+
+/// The optional parameters of a query on a view of a design document.
+#[derive(Default, Debug, Clone)]
+pub struct ViewQuery {
+    /// The key to start the results with, inclusive.
+    pub startkey: Option<Value>,
+    /// The key to end the results with, inclusive.
+    pub endkey: Option<Value>,
+    /// Whether the results of a reduced view are grouped by their key.
+    pub group: Option<bool>,
+    /// The maximum number of returned rows.
+    pub limit: Option<u64>,
+    /// The partition to query the view for.
+    pub partition: Option<String>,
+}
+
+impl ViewQuery {
+    /// Create a query for all keys which start with the prefix.
+    ///
+    /// # Arguments
+    ///
+    /// * `prefix`: the prefix of the keys
+    ///
+    /// returns: ViewQuery
+    pub fn prefix(prefix: &str) -> Self {
+        Self {
+            startkey: Some(json!(prefix)),
+            endkey: Some(json!(format!("{}\u{fff0}", prefix))),
+            ..Default::default()
+        }
+    }
+}
+
+/// Query a view of a design document and deserialize its rows.
+/// The keys and values of the rows may be of any type, such as a count for reduced views.
+///
+/// # Arguments
+///
+/// * `conf`: the application configuration
+/// * `client`: the client to use for the database request
+/// * `view_url`: the `URL` of the view relative to the base `URL` of the database
+/// * `query`: the parameters of the query
+///
+/// returns: Result<Statistic<K, V>, Error>
+pub(crate) async fn query_view<K, V>(
+    conf: &Config,
+    client: &DatabaseClient,
+    view_url: &str,
+    query: ViewQuery,
+) -> Result<Statistic<K, V>, ApiError>
+where
+    K: DeserializeOwned,
+    V: DeserializeOwned,
+{
+    let mut parameters = HashMap::new();
+    if let Some(startkey) = query.startkey {
+        parameters.insert("startkey", startkey.to_string());
+    }
+    if let Some(endkey) = query.endkey {
+        parameters.insert("endkey", endkey.to_string());
+    }
+    if let Some(group) = query.group {
+        parameters.insert("group", group.to_string());
+    }
+    if let Some(limit) = query.limit {
+        parameters.insert("limit", limit.to_string());
+    }
+    if let Some(partition) = query.partition {
+        parameters.insert("partition", partition);
+    }
+    request(
+        conf,
+        client,
+        Box::new(|r| r),
+        Method::GET,
+        view_url,
+        &parameters,
+    )
+    .await
+}
+
+/// Execute a request against the couch database and ensure its success.
+/// Requests which fail due to a transient error are retried as described in [execute_with_retries].
+/// If the session is expired, the client will be reauthenticated and the request is repeated once.
+/// A response which is not successful is converted into an [`ApiError`].
+/// The id of the originating request is forwarded in the [REQUEST_ID_HEADER] and contained in the logged errors.
+///
+/// # Arguments
+///
+/// * `conf`: the application configuration
+/// * `client`: the client to use for the database request, likely is required to be authenticated with a cookie
+/// * `request_hook`: a function used to modify the request, can be used to insert information such as the body
+/// * `method`: the `HTTP` method used for the request - replaces the current one
+/// * `api_url`: the `URL` relative to the base `URL` of the database
+/// * `parameters`: the query parameters being used for the request
+/// * `read_only`: whether the request does not modify the database regardless of its method
+///
+/// returns: Result<Response, Error>
+async fn execute<'a, P>(
+    conf: &Config,
+    client: &DatabaseClient,
+    request_hook: Box<dyn FnOnce(RequestBuilder) -> RequestBuilder + Send + 'a>,
+    method: Method,
+    api_url: &str,
+    parameters: &P,
+    read_only: bool,
+) -> Result<Response, ApiError>
+where
+    P: Serialize + ?Sized,
 {
     let url_string = format!("{}{}", conf.database.url, api_url);
     let url = Url::parse(&*url_string).map_err(|e| {
@@ -304,7 +865,10 @@ where
         request_error()
     })?;
     debug!("The request URL is: {}", url);
-    let request_builder = client.request(method, url).query(parameters);
+    let mut request_builder = client.request(method, url).query(parameters);
+    if let Some(request_id) = current_request_id() {
+        request_builder = request_builder.header(REQUEST_ID_HEADER, request_id);
+    }
     let request = request_hook(request_builder).build().map_err(|e| {
         warn!(
             "Unable to build the request provided by the application: {}",
@@ -313,52 +877,53 @@ where
         request_error()
     })?;
     let request_clone_optional = request.try_clone();
-    let mut response = client.execute(request).await.map_err(|e| {
-        warn!(
-            "Unable to execute the request provided by the application: {}",
-            e
-        );
-        request_error()
-    })?;
+    let request_start = Instant::now();
+    let mut response = execute_with_retries(conf, client, request, read_only)
+        .await
+        .map_err(|e| {
+            warn!(
+                "Unable to execute the request provided by the application: {}",
+                e
+            );
+            request_error()
+        })?;
     let mut status = response.status();
     if status == StatusCode::UNAUTHORIZED {
         info!("The session cookie seems to be expired, try to reauthenticate");
-        authenticate(conf, client).await.map_err(|e| {
-            warn!("Unable to re-authenticate to the database: {}", e);
-            ApiError {
-                err: "Database Error".to_string(),
-                msg: Some(
-                    "Cannot connect to the database, please contact the administrator".to_string(),
-                ),
-                http_status_code: Status::InternalServerError.code,
-            }
-        })?;
+        reauthenticate(conf, client, request_start)
+            .await
+            .map_err(|e| {
+                warn!("Unable to re-authenticate to the database: {}", e);
+                ApiError {
+                    err: "Database Error".to_string(),
+                    msg: Some(
+                        "Cannot connect to the database, please contact the administrator"
+                            .to_string(),
+                    ),
+                    http_status_code: Status::InternalServerError.code,
+                }
+            })?;
         let request_clone = request_clone_optional.ok_or(ApiError {
             err: "Database Error".to_string(),
             msg: Some("Unable to reproduce the request, you may try again immediately".to_string()),
             http_status_code: Status::ServiceUnavailable.code,
         })?;
-        response = client.execute(request_clone).await.map_err(|e| {
-            warn!(
-                "Unable to execute the second request provided by the application: {}",
-                e
-            );
-            request_error()
-        })?;
+        response = execute_with_retries(conf, client, request_clone, read_only)
+            .await
+            .map_err(|e| {
+                warn!(
+                    "Unable to execute the second request provided by the application: {}",
+                    e
+                );
+                request_error()
+            })?;
         status = response.status();
     }
     if !status.is_success() {
-        let couch_error = response.json::<DatabaseError>().await.map_err(|e| {
-            warn!("Unable to return error to client: {}", e);
-            request_error()
-        })?;
-        return Err(ApiError::from((couch_error, status)));
+        let body = response.bytes().await.unwrap_or_default();
+        return Err(database_error(status, &body));
     }
-    let deserialized_body = response.json::<R>().await.map_err(|e| {
-        warn!("Unable to deserialize a response from the database: {}", e);
-        request_error()
-    })?;
-    Ok(deserialized_body)
+    Ok(response)
 }
 
 /// Checks if the document `id` starts with the `partition` string.
@@ -403,6 +968,19 @@ pub(crate) fn check_document_partition(id: &str, partition: &str) -> Result<(),
     }
 }
 
+/// Clamp the requested number of documents of a page to [crate::config::DatabaseConfig::max_page_limit].
+/// Larger pages are not rejected but reduced, clients notice this by the `limit` of the response.
+///
+/// # Arguments
+///
+/// * `conf`: the application configuration
+/// * `limit`: the requested number of documents
+///
+/// returns: u64 the effective number of documents
+pub(crate) fn page_limit(conf: &Config, limit: u64) -> u64 {
+    limit.min(conf.database.max_page_limit)
+}
+
 /// Generate an id for a document with a given partition.
 /// A UUID will be used, the format will be `partition:UUID`.
 ///
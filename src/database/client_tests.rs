@@ -0,0 +1,177 @@
+// OpenKeg, the lightweight backend of the Musikverein Leopoldsdorf.
+// Copyright (C) 2022  Richard Stöckl
+//
+// This program is free software; you can redistribute it and/or
+// modify it under the terms of the GNU General Public License
+// as published by the Free Software Foundation; either version 2
+// of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+
+use std::net::TcpListener;
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+use std::thread;
+
+use super::*;
+use crate::archive::model::Score;
+
+#[test]
+fn not_found() {
+    let error = database_error(
+        StatusCode::NOT_FOUND,
+        br#"{"error":"not_found","reason":"missing"}"#,
+    );
+    assert_eq!(error.http_status_code, Status::NotFound.code);
+    assert_eq!(error.err, "not_found");
+    assert_eq!(error.msg.as_deref(), Some("missing"));
+}
+
+#[test]
+fn conflict() {
+    let error = database_error(
+        StatusCode::CONFLICT,
+        br#"{"error":"conflict","reason":"Document update conflict."}"#,
+    );
+    assert_eq!(error.http_status_code, Status::Conflict.code);
+    assert_eq!(error.err, "conflict");
+}
+
+#[test]
+fn precondition_failed() {
+    let error = database_error(
+        StatusCode::PRECONDITION_FAILED,
+        br#"{"error":"file_exists","reason":"The database could not be created, the file already exists."}"#,
+    );
+    assert_eq!(error.http_status_code, Status::PreconditionFailed.code);
+}
+
+#[test]
+fn invalid_body_keeps_status() {
+    let error = database_error(StatusCode::NOT_FOUND, b"<html>Not Found</html>");
+    assert_eq!(error.http_status_code, Status::NotFound.code);
+    assert_eq!(error.err, "Not Found");
+    let error = database_error(StatusCode::CONFLICT, b"");
+    assert_eq!(error.http_status_code, Status::Conflict.code);
+}
+
+fn find_response(docs: usize) -> FindResponse<Score> {
+    FindResponse {
+        docs: vec![Score::example(); docs],
+        ..Default::default()
+    }
+}
+
+#[test]
+fn has_more_if_page_is_full() {
+    let response = find_response(20).paginated(20);
+    assert!(response.has_more);
+    assert_eq!(response.limit, 20);
+}
+
+#[test]
+fn no_more_if_page_is_not_full() {
+    let response = find_response(7).paginated(20);
+    assert!(!response.has_more);
+    assert_eq!(response.limit, 20);
+    assert!(!find_response(0).paginated(0).has_more);
+}
+
+/// Accept connections without ever answering them and count them.
+fn silent_server() -> (String, Arc<AtomicUsize>) {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("listener");
+    let url = format!("http://{}", listener.local_addr().expect("address"));
+    let connections = Arc::new(AtomicUsize::new(0));
+    let counter = connections.clone();
+    thread::spawn(move || {
+        let mut streams = vec![];
+        for stream in listener.incoming() {
+            counter.fetch_add(1, AtomicOrdering::SeqCst);
+            streams.push(stream);
+        }
+    });
+    (url, connections)
+}
+
+fn retry_config() -> Config {
+    let mut conf = Config::default();
+    conf.database.retry_attempts = 3;
+    conf.database.retry_delay = 20;
+    conf.database.retry_non_idempotent = false;
+    conf
+}
+
+fn timeout_client() -> DatabaseClient {
+    DatabaseClient::new(
+        Client::builder()
+            .timeout(Duration::from_millis(100))
+            .build()
+            .expect("client"),
+    )
+}
+
+async fn attempts(method: Method, read_only: bool) -> (usize, Duration) {
+    let (url, connections) = silent_server();
+    let client = timeout_client();
+    let request = client.request(method, url).build().expect("request");
+    let start = Instant::now();
+    let result = execute_with_retries(&retry_config(), &client, request, read_only).await;
+    assert!(result.expect_err("timeout").is_timeout());
+    (connections.load(AtomicOrdering::SeqCst), start.elapsed())
+}
+
+#[rocket::async_test]
+async fn timeout_is_transient() {
+    let (url, _) = silent_server();
+    let error = timeout_client().get(url).send().await.expect_err("timeout");
+    assert!(is_transient(&error));
+}
+
+#[rocket::async_test]
+async fn refused_connection_is_transient() {
+    let address = TcpListener::bind("127.0.0.1:0")
+        .expect("listener")
+        .local_addr()
+        .expect("address");
+    let error = Client::new()
+        .get(format!("http://{}", address))
+        .send()
+        .await
+        .expect_err("refused");
+    assert!(is_transient(&error));
+}
+
+#[rocket::async_test]
+async fn invalid_request_is_not_transient() {
+    let error = Client::new()
+        .get("http://")
+        .send()
+        .await
+        .expect_err("invalid url");
+    assert!(!is_transient(&error));
+}
+
+#[rocket::async_test]
+async fn retries_idempotent_requests_with_backoff() {
+    let (connections, elapsed) = attempts(Method::GET, false).await;
+    assert_eq!(connections, 3);
+    assert!(elapsed >= Duration::from_millis(3 * 100 + 20 + 40));
+}
+
+#[rocket::async_test]
+async fn no_retry_of_modifying_requests() {
+    let (connections, _) = attempts(Method::POST, false).await;
+    assert_eq!(connections, 1);
+}
+
+#[rocket::async_test]
+async fn retries_read_only_posts() {
+    let (connections, _) = attempts(Method::POST, true).await;
+    assert_eq!(connections, 3);
+}
@@ -19,6 +19,8 @@
 #[path = "fuzzy_tests.rs"]
 mod fuzzy_tests;
 
+use crate::archive::model::MatchMode;
+
 /// Convert the search term into a fuzzy one.
 /// The resulting regex may be very large and inefficient.
 /// It is not recommended using this function when an intelligent string library which ignore diacritics is available.
@@ -26,55 +28,67 @@ mod fuzzy_tests;
 /// # Arguments
 ///
 /// * `term`: the term to convert
+/// * `mode`: how the term is anchored within the searched attribute
 ///
 /// returns: String
-pub fn fuzzy_regex(term: String) -> String {
-    term.chars()
+pub fn fuzzy_regex(term: String, mode: MatchMode) -> String {
+    let regex = term
+        .chars()
         .map(|c| {
             let next = ALPHABET_CLASSES.iter().find(|cl| cl.contains(c));
 
-            let chars = next.map(|cl| format!("[{}]{}", cl, SPECIALS)).unwrap_or({
-                if NUMBERS.contains(c) {
-                    format!("{}{}", c, SPECIALS)
-                } else {
-                    "".to_string()
-                }
-            });
+            let chars = next
+                .map(|cl| format!("[{}][{}]*", cl, SEPARATORS))
+                .unwrap_or({
+                    if NUMBERS.contains(c) {
+                        format!("{}[{}]*", c, SEPARATORS)
+                    } else {
+                        "".to_string()
+                    }
+                });
             chars
         })
         .collect::<Vec<String>>()
-        .join("")
+        .join("");
+    match mode {
+        MatchMode::Substring => regex,
+        MatchMode::Prefix => format!("^[{}]*{}", SEPARATORS, regex),
+        MatchMode::Word => format!("(^|[{0}]){1}([{0}]|$)", SEPARATORS, regex),
+    }
 }
 
+/// The classes of letters which are considered as equal, each consisting of a base letter and its accented variants.
+/// The classes are used in both directions, a term with or without diacritics matches the other variants.
 const ALPHABET_CLASSES: &[&str] = &[
-    "aàáâãäåæAÀÁÂÃÄÅÆ",
+    "aàáâãäåæāăąAÀÁÂÃÄÅÆĀĂĄ",
     "bB",
-    "cçćĉčCÇĆĈČ",
-    "dďDÐĎ",
-    "eèéêëēěEÈÉÊËĒĚ",
+    "cçćĉċčCÇĆĈĊČ",
+    "dďđDÐĎĐ",
+    "eèéêëēĕėęěEÈÉÊËĒĔĖĘĚ",
     "fF",
-    "gĝGĜ",
-    "hĥHĤ",
-    "iìíîïIÌÍÎÏ",
-    "jJ",
-    "kK",
-    "lL",
+    "gĝğġģGĜĞĠĢ",
+    "hĥħHĤĦ",
+    "iìíîïĩīĭįıIÌÍÎÏĨĪĬĮİ",
+    "jĵJĴ",
+    "kķKĶ",
+    "lĺļľŀłLĹĻĽĿŁ",
     "mM",
-    "nñńňNÑŃŇ",
-    "oòóôõöøOÒÓÔÕÖØ",
+    "nñńņňNÑŃŅŇ",
+    "oòóôõöøōŏőœOÒÓÔÕÖØŌŎŐŒ",
     "pP",
     "qQ",
-    "rŕřRŔŘ",
-    "sśŝşšSŚŜŞŠß",
-    "tťTŤ",
-    "uùúûüUÙÚÛÜ",
+    "rŕŗřRŔŖŘ",
+    "sśŝşšșSŚŜŞŠȘß",
+    "tţťŧțTŢŤŦȚ",
+    "uùúûüũūŭůűųUÙÚÛÜŨŪŬŮŰŲ",
     "vV",
     "wŵWŴ",
     "xX",
-    "yýŷYÝŶ",
-    "zžZŹ",
+    "yýÿŷYÝŸŶ",
+    "zźżžZŹŻŽ",
 ];
 
 // const SPECIAL_CHARACTERS: &str = "`°+\"*#%&$|§=?€<>,.-;:_()!~[]{}/\\ ";
 const NUMBERS: &str = "0123456789";
-const SPECIALS: &str = r#"[`°\+"'\^\*\#%&\$\|§=\?€<>,\.\-;:_\(\)!~\[\]\{\}/\\ ]*"#;
+/// The characters which are ignored within a term and separate the words of a term.
+const SEPARATORS: &str = r#"`°\+"'\^\*\#%&\$\|§=\?€<>,\.\-;:_\(\)!~\[\]\{\}/\\ "#;
@@ -21,7 +21,11 @@ mod fuzzy_tests {
     use regex::Regex;
 
     fn matches_fuzzy(search_term: &str, stored: &str) -> bool {
-        Regex::new(fuzzy_regex(search_term.to_string()).as_str())
+        matches_fuzzy_mode(search_term, stored, MatchMode::Substring)
+    }
+
+    fn matches_fuzzy_mode(search_term: &str, stored: &str, mode: MatchMode) -> bool {
+        Regex::new(fuzzy_regex(search_term.to_string(), mode).as_str())
             .map(|r| r.is_match(stored))
             .expect("regex")
     }
@@ -88,6 +92,83 @@ mod fuzzy_tests {
         assert_eq!(matches_fuzzy("Osterreich", "Ost\\erreich"), true);
     }
 
+    #[test]
+    fn regex_accents() {
+        assert_eq!(matches_fuzzy("Dvorak", "Dvořák"), true);
+        assert_eq!(matches_fuzzy("Dvořák", "Dvorak"), true);
+        assert_eq!(matches_fuzzy("Espana", "España"), true);
+        assert_eq!(matches_fuzzy("Francaise", "Française"), true);
+        assert_eq!(matches_fuzzy("Cafe", "Café"), true);
+        assert_eq!(matches_fuzzy("Lodz", "Łódź"), true);
+        assert_eq!(matches_fuzzy("Zizkov", "Žižkov"), true);
+    }
+
+    #[test]
+    fn regex_accents_unrelated() {
+        assert_eq!(matches_fuzzy("Dvorak", "Dvořec"), false);
+        assert_eq!(matches_fuzzy("Espana", "Espena"), false);
+        assert_eq!(matches_fuzzy("Łódź", "Lida"), false);
+        assert!(ALPHABET_CLASSES
+            .iter()
+            .all(|class| Regex::new(&format!("[{}]", class)).is_ok()));
+    }
+
+    #[test]
+    fn regex_substring_mode() {
+        assert_eq!(matches_fuzzy("Mar", "Marsch"), true);
+        assert_eq!(matches_fuzzy("Mar", "Primaria"), true);
+    }
+
+    #[test]
+    fn regex_prefix_mode() {
+        assert_eq!(matches_fuzzy_mode("Mar", "Marsch", MatchMode::Prefix), true);
+        assert_eq!(
+            matches_fuzzy_mode("Mar", "Primaria", MatchMode::Prefix),
+            false
+        );
+        assert_eq!(
+            matches_fuzzy_mode("Mar", "\"Märchen\"", MatchMode::Prefix),
+            true
+        );
+        assert_eq!(
+            matches_fuzzy_mode("Rad Marsch", "Radetzky Marsch", MatchMode::Prefix),
+            false
+        );
+        assert_eq!(
+            matches_fuzzy_mode("Radetzky", "Radetzky-Marsch", MatchMode::Prefix),
+            true
+        );
+    }
+
+    #[test]
+    fn regex_word_mode() {
+        assert_eq!(matches_fuzzy_mode("Mar", "Marsch", MatchMode::Word), false);
+        assert_eq!(
+            matches_fuzzy_mode("Mar", "Primaria", MatchMode::Word),
+            false
+        );
+        assert_eq!(
+            matches_fuzzy_mode("Marsch", "Radetzky-Marsch", MatchMode::Word),
+            true
+        );
+        assert_eq!(
+            matches_fuzzy_mode("Marsch", "Marsch (Radetzky)", MatchMode::Word),
+            true
+        );
+        assert_eq!(
+            matches_fuzzy_mode("Bohmischer Traum", "Böhmischer Traum", MatchMode::Word),
+            true
+        );
+        assert_eq!(
+            matches_fuzzy_mode("Bohmischer Traum", "Böhmischer Träume", MatchMode::Word),
+            false
+        );
+        assert_eq!(
+            matches_fuzzy_mode("Rock n Roll", "Rock'n'Roll", MatchMode::Word),
+            true
+        );
+    }
+
     #[test]
     fn regex_numbers() {
         assert_eq!(matches_fuzzy("4 religiös", "4 Religiöse Aufzüge"), true);
@@ -15,6 +15,10 @@
 // along with this program; if not, write to the Free Software
 // Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
 
+/// Module which contains the database requests for book related services.
+pub mod book;
+/// Module which signs the bookmarks used for pagination.
+pub mod bookmark;
 /// A module which contains generic functionality for the database.
 /// The most important are client initialization, authentication, request and response types.
 pub mod client;
@@ -16,25 +16,42 @@
 // Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
 
 use std::cmp::Ordering;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
-use reqwest::{Client, Method};
+use futures::stream::{FuturesUnordered, StreamExt};
+use reqwest::header::CONTENT_TYPE;
+use reqwest::{Body, Method};
 use rocket::http::Status;
 use rocket::serde::json::Json;
-use schemars::JsonSchema;
+use rocket::serde::Deserialize;
+use rocket::tokio::sync::Semaphore;
+use schemars::{JsonSchema, Map};
 use serde_json::{json, Value};
 
-use crate::archive::model::{Score, ScoreSearchTermField};
+use crate::archive::model::{
+    GenreNormalization, MatchMode, Page, Score, ScoreBatch, ScoreNeighbors, ScoreSearchTermField,
+    Statistic,
+};
+use crate::config::Capitalization;
+use crate::database::bookmark::{sign_bookmark, verify_bookmark};
 use crate::database::client::{
-    check_document_partition, generate_document_id, request, FindResponse, OperationResponse,
-    Pagination,
+    check_document_partition, generate_document_id, page_limit, query_view, request, request_raw,
+    request_read_only, DatabaseClient, FindResponse, OperationResponse, Pagination, ViewQuery,
 };
 use crate::database::fuzzy;
 use crate::openapi::{ApiError, ApiResult};
 use crate::Config;
 
-/// Try to fetch all scores from the database.
+#[cfg(test)]
+#[path = "score_tests.rs"]
+mod score_tests;
+
+/// The name of the CouchDB attachment which contains the sheet music of a score.
+const SCORE_ATTACHMENT_NAME: &str = "score.pdf";
+
+/// Try to fetch all scores from the database ordered by their id.
 /// Not recommended to use with too high `limit`.
+/// See [sorted_scores] in order to browse the scores sorted by a field.
 ///
 /// # Arguments
 ///
@@ -46,13 +63,13 @@ use crate::Config;
 /// returns: Result<Json<Pagination<Score>>, ApiError>
 pub async fn all_scores(
     conf: &Config,
-    client: &Client,
+    client: &DatabaseClient,
     limit: u64,
     skip: u64,
 ) -> ApiResult<Pagination<Score>> {
     let mut parameters = HashMap::new();
     parameters.insert("include_docs".to_string(), "true".to_string());
-    parameters.insert("limit".to_string(), limit.to_string());
+    parameters.insert("limit".to_string(), page_limit(conf, limit).to_string());
     parameters.insert("skip".to_string(), skip.to_string());
     request(
         conf,
@@ -66,6 +83,81 @@ pub async fn all_scores(
     .map(Json)
 }
 
+/// Browse all scores sorted by a field with a Mango query.
+/// Sorting requires an index on the field, see [crate::config::DatabaseConfig::sort_index].
+/// Scores without the field are omitted.
+/// The results are paginated in the same way as [search_scores], the total number of scores is not counted.
+///
+/// # Arguments
+///
+/// * `conf`: the application configuration
+/// * `client`: the client to perform the database request with
+/// * `sort`: the field to sort the scores by
+/// * `ascending`: the direction of the sort
+/// * `limit`: the limit of documents for a result page
+/// * `bookmark`: the bookmark used for pagination
+///
+/// returns: Result<Json<FindResponse<Score>>, ApiError>
+pub async fn sorted_scores(
+    conf: &Config,
+    client: &DatabaseClient,
+    sort: ScoreSearchTermField,
+    ascending: bool,
+    limit: u64,
+    bookmark: Option<String>,
+) -> ApiResult<FindResponse<Score>> {
+    let limit = page_limit(conf, limit);
+    let filter = sorted_filter(conf, sort, ascending, limit, bookmark);
+    debug!("Using filter to browse sorted scores: {}", filter);
+    let parameters: HashMap<String, String> = HashMap::new();
+    request_read_only::<FindResponse<Score>, _>(
+        conf,
+        client,
+        Box::new(|r| r.json(&filter)),
+        Method::POST,
+        &conf.database.database_mapping.find_scores,
+        &parameters,
+    )
+    .await
+    .map(|response| Json(response.paginated(limit)))
+}
+
+/// Create the Mango query for a page of all scores sorted by a field.
+///
+/// # Arguments
+///
+/// * `conf`: the application configuration
+/// * `sort`: the field to sort the scores by
+/// * `ascending`: the direction of the sort
+/// * `limit`: the limit of documents for a result page
+/// * `bookmark`: the bookmark used for pagination
+///
+/// returns: Value
+fn sorted_filter(
+    conf: &Config,
+    sort: ScoreSearchTermField,
+    ascending: bool,
+    limit: u64,
+    bookmark: Option<String>,
+) -> Value {
+    let field = sort.to_string().to_lowercase();
+    let mut filter = json!({
+        "selector": {field: {"$exists": true}},
+        "sort": sort_criteria(&[sort], &[ascending]),
+        "skip": 0,
+        "bookmark": bookmark,
+        "limit": page_limit(conf, limit),
+        "execution_stats": conf.database.include_execution_stats,
+    });
+    if let Some(index) = &conf.database.sort_index {
+        filter["use_index"] = json!(index);
+    }
+    filter
+}
+
+/// The maximum number of duplicate candidates fetched per title or alias.
+const DUPLICATE_CANDIDATES_LIMIT: u64 = 10;
+
 /// The parameters used to search scores.
 #[derive(FromForm, JsonSchema)]
 pub struct ScoreSearchParameters {
@@ -73,24 +165,48 @@ pub struct ScoreSearchParameters {
     search_term: Option<String>,
     /// If `true` the `search_term` will be interpreted as a regular expression instead of a fuzzy search term.
     regex: Option<bool>,
+    /// How a fuzzy `search_term` has to match the attributes, defaults to [MatchMode::Substring].
+    /// This is ignored if the `search_term` is interpreted as a regular expression.
+    match_mode: Option<MatchMode>,
     /// The attributes to search for.
     attributes: Vec<ScoreSearchTermField>,
-    /// If set, the score must contain a page with exactly this book.
-    book: Option<String>,
+    /// If `true` the `search_term` is searched in every attribute, `attributes` must be empty then.
+    all_fields: Option<bool>,
+    /// If set, the score must contain a page with exactly one of these books.
+    book: Vec<String>,
     /// If set, the score must be have set a location with exact this string.
     location: Option<String>,
-    /// The field which should be used to sort the results (database relative, not page).
-    sort: Option<ScoreSearchTermField>,
-    /// If unset or `true` the results will be sorted ascending, descending otherwise.
-    ascending: Option<bool>,
-    /// The limit of documents for a result page.
+    /// If set, the score must have a grade of at least this one, see [grade_criteria] for the comparison.
+    grade_min: Option<String>,
+    /// If set, the score must have a grade of at most this one, see [grade_criteria] for the comparison.
+    grade_max: Option<String>,
+    /// The fields which should be used to sort the results (database relative, not page).
+    /// The results are sorted by the first field, then by the second one and so on.
+    sort: Vec<ScoreSearchTermField>,
+    /// The direction for each field in `sort`, `true` for ascending and `false` for descending.
+    /// A single value applies to all fields, missing values are ascending.
+    ascending: Vec<bool>,
+    /// The limit of documents for a result page, at most [crate::config::DatabaseConfig::max_page_limit].
     limit: u64,
     /// The bookmark used for pagination.
     bookmark: Option<String>,
 }
 
+impl ScoreSearchParameters {
+    /// Remove all criteria which are not intended for the public such as the location.
+    ///
+    /// returns: ScoreSearchParameters
+    pub fn public(self) -> Self {
+        Self {
+            location: None,
+            ..self
+        }
+    }
+}
+
 /// The service function to search for scores according to the given criteria.
 /// All criteria are chained with the `$and` operator.
+/// If [crate::config::DatabaseConfig::bookmark_secret] is set, the bookmarks are signed and verified.
 ///
 /// # Arguments
 ///
@@ -101,13 +217,73 @@ pub struct ScoreSearchParameters {
 /// returns: Result<Json<FindResponse<Score>>, Error>
 pub async fn search_scores(
     conf: &Config,
-    client: &Client,
-    parameters: ScoreSearchParameters,
+    client: &DatabaseClient,
+    mut parameters: ScoreSearchParameters,
 ) -> ApiResult<FindResponse<Score>> {
-    let filter = construct_filter(parameters);
+    let secret = conf.database.bookmark_secret.as_deref();
+    if let (Some(secret), Some(bookmark)) = (secret, &parameters.bookmark) {
+        parameters.bookmark = Some(verify_bookmark(secret, bookmark)?);
+    }
+    let limit = page_limit(conf, parameters.limit);
+    parameters.limit = limit;
+    let filter = construct_filter(conf, parameters)?;
     debug!("Using filter to search scores: {}", filter);
     let parameters: HashMap<String, String> = HashMap::new();
-    request(
+    let mut response = request_read_only::<FindResponse<Score>, _>(
+        conf,
+        client,
+        Box::new(|r| r.json(&filter)),
+        Method::POST,
+        &conf.database.database_mapping.find_scores,
+        &parameters,
+    )
+    .await?
+    .paginated(limit);
+    if let Some(secret) = secret {
+        response.bookmark = sign_bookmark(secret, &response.bookmark);
+    }
+    Ok(Json(response))
+}
+
+/// Search all scores which have neither composers nor arrangers.
+/// The results are sorted by their title and paginated in the same way as [search_scores].
+///
+/// # Arguments
+///
+/// * `conf`: the application configuration
+/// * `client`: the client to send the requests with
+/// * `limit`: the limit of documents for a result page
+/// * `bookmark`: the bookmark used for pagination
+///
+/// returns: Result<Json<FindResponse<Score>>, Error>
+pub async fn missing_credits_scores(
+    conf: &Config,
+    client: &DatabaseClient,
+    limit: u64,
+    bookmark: Option<String>,
+) -> ApiResult<FindResponse<Score>> {
+    let limit = page_limit(conf, limit);
+    let empty_criteria = |field: &str| {
+        json!({"$or": [
+            {field: {"$size": 0}},
+            {field: {"$exists": false}},
+        ]})
+    };
+    let filter = json!({
+        "selector": {"$and": [empty_criteria("composers"), empty_criteria("arrangers")]},
+        "sort": [{"title": "asc"}],
+        "stable": true,
+        "skip": 0,
+        "execution_stats": conf.database.include_execution_stats,
+        "bookmark": bookmark,
+        "limit": limit,
+    });
+    debug!(
+        "Using filter to search scores with missing credits: {}",
+        filter
+    );
+    let parameters: HashMap<String, String> = HashMap::new();
+    request_read_only::<FindResponse<Score>, _>(
         conf,
         client,
         Box::new(|r| r.json(&filter)),
@@ -116,7 +292,248 @@ pub async fn search_scores(
         &parameters,
     )
     .await
-    .map(Json)
+    .map(|response| Json(response.paginated(limit)))
+}
+
+/// Search all scores whose pages are missing entirely, which is the case for legacy imports.
+/// Scores with an empty list of pages are not included.
+/// The results are sorted by their title and paginated in the same way as [search_scores].
+///
+/// # Arguments
+///
+/// * `conf`: the application configuration
+/// * `client`: the client to send the requests with
+/// * `limit`: the limit of documents for a result page
+/// * `bookmark`: the bookmark used for pagination
+///
+/// returns: Result<Json<FindResponse<Score>>, Error>
+pub async fn unfiled_scores(
+    conf: &Config,
+    client: &DatabaseClient,
+    limit: u64,
+    bookmark: Option<String>,
+) -> ApiResult<FindResponse<Score>> {
+    let limit = page_limit(conf, limit);
+    let filter = json!({
+        "selector": {"pages": {"$exists": false}},
+        "sort": [{"title": "asc"}],
+        "stable": true,
+        "skip": 0,
+        "execution_stats": conf.database.include_execution_stats,
+        "bookmark": bookmark,
+        "limit": limit,
+    });
+    debug!("Using filter to search unfiled scores: {}", filter);
+    let parameters: HashMap<String, String> = HashMap::new();
+    request_read_only::<FindResponse<Score>, _>(
+        conf,
+        client,
+        Box::new(|r| r.json(&filter)),
+        Method::POST,
+        &conf.database.database_mapping.find_scores,
+        &parameters,
+    )
+    .await
+    .map(|response| Json(response.paginated(limit)))
+}
+
+/// Search all scores which contain a subtitle matching the term.
+/// This is intended to find potpourris or medleys by a contained tune.
+/// The results are sorted by their title and paginated in the same way as [search_scores].
+///
+/// # Arguments
+///
+/// * `conf`: the application configuration
+/// * `client`: the client to send the requests with
+/// * `term`: the term to search for in the subtitles
+/// * `regex`: `Some(true)` if `term` should be interpreted as regex, otherwise it will be interpreted as a fuzzy search term
+/// * `limit`: the limit of documents for a result page
+/// * `bookmark`: the bookmark used for pagination
+///
+/// returns: Result<Json<FindResponse<Score>>, Error>
+pub async fn subtitle_scores(
+    conf: &Config,
+    client: &DatabaseClient,
+    term: String,
+    regex: Option<bool>,
+    limit: u64,
+    bookmark: Option<String>,
+) -> ApiResult<FindResponse<Score>> {
+    let limit = page_limit(conf, limit);
+    let filter = json!({
+        "selector": {"subtitles": {
+            "$elemMatch": {
+                "$regex": term_from_regex(term, &regex, MatchMode::Substring, conf)?
+            }
+        }},
+        "sort": [{"title": "asc"}],
+        "stable": true,
+        "skip": 0,
+        "execution_stats": conf.database.include_execution_stats,
+        "bookmark": bookmark,
+        "limit": limit,
+    });
+    debug!("Using filter to search scores by subtitle: {}", filter);
+    let parameters: HashMap<String, String> = HashMap::new();
+    request_read_only::<FindResponse<Score>, _>(
+        conf,
+        client,
+        Box::new(|r| r.json(&filter)),
+        Method::POST,
+        &conf.database.database_mapping.find_scores,
+        &parameters,
+    )
+    .await
+    .map(|response| Json(response.paginated(limit)))
+}
+
+/// Search all scores which contain an alias matching the term.
+/// This is intended to find a score by an alternative title such as its bohemian name.
+/// The filter is constructed in the same way as for [search_scores] and the results are sorted by their title.
+///
+/// # Arguments
+///
+/// * `conf`: the application configuration
+/// * `client`: the client to send the requests with
+/// * `term`: the term to search for in the aliases
+/// * `regex`: `Some(true)` if `term` should be interpreted as regex, otherwise it will be interpreted as a fuzzy search term
+/// * `limit`: the limit of documents for a result page
+/// * `bookmark`: the bookmark used for pagination
+///
+/// returns: Result<Json<FindResponse<Score>>, Error>
+pub async fn alias_scores(
+    conf: &Config,
+    client: &DatabaseClient,
+    term: String,
+    regex: Option<bool>,
+    limit: u64,
+    bookmark: Option<String>,
+) -> ApiResult<FindResponse<Score>> {
+    search_scores(
+        conf,
+        client,
+        ScoreSearchParameters {
+            search_term: Some(term),
+            regex,
+            match_mode: None,
+            attributes: vec![ScoreSearchTermField::Alias],
+            all_fields: None,
+            book: vec![],
+            location: None,
+            grade_min: None,
+            grade_max: None,
+            sort: vec![ScoreSearchTermField::Title],
+            ascending: vec![true],
+            limit,
+            bookmark,
+        },
+    )
+    .await
+}
+
+/// Find existing scores which are likely duplicates of the given score.
+/// The title and every alias of the score are searched as whole words within the titles and aliases of the stored scores, using the fuzzy search of [search_scores].
+/// The score itself is never reported as its own duplicate.
+/// This is only advisory and intended to warn before a score is inserted.
+///
+/// # Arguments
+///
+/// * `conf`: the application configuration
+/// * `client`: the client to send the requests with
+/// * `score`: the score to find the duplicates of
+///
+/// returns: Result<Json<Vec<Score>>, Error> the candidates sorted by their title
+pub async fn duplicate_scores(
+    conf: &Config,
+    client: &DatabaseClient,
+    score: &Score,
+) -> ApiResult<Vec<Score>> {
+    let mut terms: Vec<&str> = std::iter::once(&score.title)
+        .chain(score.alias.iter())
+        .map(|term| term.trim())
+        .filter(|term| term.chars().any(char::is_alphanumeric))
+        .collect();
+    terms.sort_unstable();
+    terms.dedup();
+    let mut candidates: Vec<Score> = vec![];
+    for term in terms {
+        let found = search_scores(
+            conf,
+            client,
+            ScoreSearchParameters {
+                search_term: Some(term.to_string()),
+                regex: Some(false),
+                match_mode: Some(MatchMode::Word),
+                attributes: vec![ScoreSearchTermField::Title, ScoreSearchTermField::Alias],
+                all_fields: None,
+                book: vec![],
+                location: None,
+                grade_min: None,
+                grade_max: None,
+                sort: vec![ScoreSearchTermField::Title],
+                ascending: vec![true],
+                limit: DUPLICATE_CANDIDATES_LIMIT,
+                bookmark: None,
+            },
+        )
+        .await?
+        .into_inner();
+        for candidate in found.docs {
+            let known = candidates.iter().any(|c| c.couch_id == candidate.couch_id);
+            if !known && (score.couch_id.is_none() || candidate.couch_id != score.couch_id) {
+                candidates.push(candidate);
+            }
+        }
+    }
+    candidates.sort_by(|a, b| a.title.cmp(&b.title));
+    Ok(Json(candidates))
+}
+
+/// Find the titles which start with the prefix, ignoring the case.
+/// The titles are looked up in a view keyed by the lowercased title which allows fast range queries.
+/// Titles which are used by multiple scores are only returned once.
+/// The number of titles is limited after removing the duplicates, hence all rows of the prefix are queried.
+///
+/// # Arguments
+///
+/// * `conf`: the application configuration
+/// * `client`: the client to send the requests with
+/// * `prefix`: the prefix of the titles
+///
+/// returns: Result<Json<Vec<String>>, Error>
+pub async fn title_suggestions(
+    conf: &Config,
+    client: &DatabaseClient,
+    prefix: String,
+) -> ApiResult<Vec<String>> {
+    let query = ViewQuery::prefix(&prefix.to_lowercase());
+    let suggestions: Statistic<String, String> = query_view(
+        conf,
+        client,
+        &conf.database.database_mapping.title_suggestions,
+        query,
+    )
+    .await?;
+    Ok(Json(unique_titles(
+        suggestions.rows.into_iter().map(|r| r.value),
+        conf.archive.title_suggestions_limit,
+    )))
+}
+
+/// Remove the duplicates of the titles while keeping their order and limit their number afterwards.
+///
+/// # Arguments
+///
+/// * `titles`: the titles which may contain duplicates
+/// * `limit`: the maximum number of the returned titles
+///
+/// returns: Vec<String>
+fn unique_titles(titles: impl Iterator<Item = String>, limit: u64) -> Vec<String> {
+    let mut seen = HashSet::new();
+    titles
+        .filter(|title| seen.insert(title.clone()))
+        .take(limit as usize)
+        .collect()
 }
 
 /// Find a single score by its id.
@@ -128,7 +545,7 @@ pub async fn search_scores(
 /// * `id`: the id of the document which contains the score
 ///
 /// returns: Result<Json<Score>, Error>
-pub async fn get_score(conf: &Config, client: &Client, id: String) -> ApiResult<Score> {
+pub async fn get_score(conf: &Config, client: &DatabaseClient, id: String) -> ApiResult<Score> {
     check_document_partition(&id, &conf.database.score_partition)?;
     let parameters: HashMap<String, String> = HashMap::new();
     request(
@@ -143,9 +560,98 @@ pub async fn get_score(conf: &Config, client: &Client, id: String) -> ApiResult<
     .map(Json)
 }
 
+/// A row of the `_all_docs` endpoint queried with `include_docs`.
+/// Rows of unknown ids only contain an error, rows of deleted documents contain no document.
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+struct BatchRow {
+    /// The requested id.
+    key: String,
+    /// The document if it exists.
+    #[serde(default)]
+    doc: Option<Score>,
+}
+
+/// The response of the `_all_docs` endpoint queried with `keys`.
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+struct BatchResponse {
+    rows: Vec<BatchRow>,
+}
+
+/// Receive multiple scores by their ids within a single request.
+/// If any of the ids does not start with the score partition, the whole request is rejected.
+/// The request is also rejected if it contains more than [crate::config::DatabaseConfig::max_page_limit] ids.
+/// Duplicate ids are only requested and reported once.
+///
+/// # Arguments
+///
+/// * `conf`: the application configuration
+/// * `client`: the client to send the request with
+/// * `ids`: the ids of the scores
+///
+/// returns: Result<Json<ScoreBatch>, Error> the found scores by their id and the missing ids
+pub async fn get_scores_batch(
+    conf: &Config,
+    client: &DatabaseClient,
+    ids: Vec<String>,
+) -> ApiResult<ScoreBatch> {
+    if ids.len() as u64 > conf.database.max_page_limit {
+        return Err(ApiError {
+            err: "Unprocessable Entity".to_string(),
+            msg: Some(format!(
+                "At most {} ids can be requested at once, but got {}",
+                conf.database.max_page_limit,
+                ids.len()
+            )),
+            http_status_code: Status::UnprocessableEntity.code,
+        });
+    }
+    for id in &ids {
+        check_document_partition(id, &conf.database.score_partition)?;
+    }
+    let mut seen = HashSet::new();
+    let ids: Vec<String> = ids
+        .into_iter()
+        .filter(|id| seen.insert(id.clone()))
+        .collect();
+    let body = json!({ "keys": ids });
+    let parameters = HashMap::from([("include_docs", "true")]);
+    let response: BatchResponse = request_read_only(
+        conf,
+        client,
+        Box::new(|r| r.json(&body)),
+        Method::POST,
+        &conf.database.database_mapping.batch_scores,
+        &parameters,
+    )
+    .await?;
+    Ok(Json(collect_batch(response)))
+}
+
+/// Split the rows of a batch response into the found scores and the missing ids.
+///
+/// # Arguments
+///
+/// * `response`: the response of the `_all_docs` endpoint
+///
+/// returns: ScoreBatch
+fn collect_batch(response: BatchResponse) -> ScoreBatch {
+    let mut batch = ScoreBatch::default();
+    for row in response.rows {
+        match row.doc {
+            Some(score) => {
+                batch.scores.insert(row.key, score);
+            }
+            None => batch.missing.push(row.key),
+        }
+    }
+    batch
+}
+
 /// Insert a score into the database.
 /// When creating a new score, make sure to leave its `_id` and `rev` to `None` and set both on update.
-/// In the case of an `409 Conflict` just get the current revision of the score and try again.
+/// In the case of an `409 Conflict` just get the current revision of the score and try again or use [upsert_score].
 ///
 /// # Arguments
 ///
@@ -154,9 +660,165 @@ pub async fn get_score(conf: &Config, client: &Client, id: String) -> ApiResult<
 /// * `score`: the score to insert
 pub async fn put_score<'de>(
     conf: &Config,
-    client: &Client,
+    client: &DatabaseClient,
     mut score: Score,
 ) -> ApiResult<OperationResponse> {
+    prepare_score(conf, &mut score)?;
+    keep_legacy_ids(conf, client, &mut score).await;
+    let document = score_document(&score)?;
+    let api_url = format!(
+        "{}/{}",
+        conf.database.database_mapping.put_score,
+        score
+            .couch_id
+            .as_ref()
+            .expect("Checked or generated score id")
+    );
+    let parameters: HashMap<String, String> = HashMap::new();
+    request(
+        conf,
+        client,
+        Box::new(|r| r.json(&document)),
+        Method::PUT,
+        &api_url,
+        &parameters,
+    )
+    .await
+    .map(Json)
+}
+
+/// Insert a score into the database like [put_score] but resolve a `409 Conflict` once.
+/// On a conflict, the current revision of the score is fetched and the score is stored again with this revision.
+/// This means concurrent changes of the score are overwritten.
+/// If the second attempt conflicts as well, the conflict is returned.
+///
+/// # Arguments
+///
+/// * `conf`: the application configuration
+/// * `client`: the client to perform the request with
+/// * `score`: the score to insert or overwrite
+///
+/// returns: Result<Json<OperationResponse>, Error>
+pub async fn upsert_score(
+    conf: &Config,
+    client: &DatabaseClient,
+    score: Score,
+) -> ApiResult<OperationResponse> {
+    let couch_id = score.couch_id.clone();
+    match (put_score(conf, client, score.clone()).await, couch_id) {
+        (Err(err), Some(couch_id)) if err.http_status_code == Status::Conflict.code => {
+            info!(
+                "The score '{}' has been changed concurrently, retry with the current revision",
+                couch_id
+            );
+            let current = get_score(conf, client, couch_id).await?;
+            let mut score = score;
+            score.couch_revision = current.0.couch_revision;
+            put_score(conf, client, score).await
+        }
+        (result, _) => result,
+    }
+}
+
+/// Insert or update multiple scores within a single database request.
+/// Every score must satisfy the same rules as in [put_score], otherwise none of the scores will be stored.
+/// The database stores each score on its own, this means some scores may be stored while others fail e.g. due to a conflict.
+///
+/// # Arguments
+///
+/// * `conf`: the application configuration
+/// * `client`: the client to perform the request with
+/// * `scores`: the scores to insert or update
+///
+/// returns: Result<Json<Vec<OperationResponse>>, Error> the result of each score in the same order
+pub async fn bulk_put_scores(
+    conf: &Config,
+    client: &DatabaseClient,
+    mut scores: Vec<Score>,
+) -> ApiResult<Vec<OperationResponse>> {
+    let mut documents = vec![];
+    for score in scores.iter_mut() {
+        prepare_score(conf, score)?;
+        keep_legacy_ids(conf, client, score).await;
+        documents.push(score_document(score)?);
+    }
+    let body = json!({ "docs": documents });
+    let parameters: HashMap<String, String> = HashMap::new();
+    request(
+        conf,
+        client,
+        Box::new(|r| r.json(&body)),
+        Method::POST,
+        &conf.database.database_mapping.bulk_scores,
+        &parameters,
+    )
+    .await
+    .map(Json)
+}
+
+/// Serialize the score into the document which is stored in the database.
+/// The legacy ids are always part of the document, even if they are omitted from the responses.
+///
+/// # Arguments
+///
+/// * `score`: the score to serialize
+///
+/// returns: Result<Value, ApiError>
+#[allow(deprecated)]
+fn score_document(score: &Score) -> Result<Value, ApiError> {
+    let mut document = serde_json::to_value(score).map_err(|e| ApiError {
+        err: "Internal Server Error".to_string(),
+        msg: Some(format!("Unable to serialize the score: {}", e)),
+        http_status_code: Status::InternalServerError.code,
+    })?;
+    if let Some(fields) = document.as_object_mut() {
+        fields.insert("legacy_ids".to_string(), json!(score.legacy_ids));
+    }
+    Ok(document)
+}
+
+/// Take over the legacy ids of the stored score when updating a score without any.
+/// Clients do not receive the legacy ids with the `omit-legacy-ids` feature, so they would be lost otherwise.
+/// Without the feature, clients send the legacy ids they received and nothing is done.
+///
+/// # Arguments
+///
+/// * `conf`: the application configuration
+/// * `client`: the client to fetch the stored score with
+/// * `score`: the score to update
+///
+/// returns: ()
+#[allow(deprecated)]
+async fn keep_legacy_ids(conf: &Config, client: &DatabaseClient, score: &mut Score) {
+    if !cfg!(feature = "omit-legacy-ids")
+        || !score.legacy_ids.is_empty()
+        || score.couch_revision.is_none()
+    {
+        return;
+    }
+    let Some(id) = score.couch_id.clone() else {
+        return;
+    };
+    match get_score(conf, client, id).await {
+        Ok(stored) => score.legacy_ids = stored.into_inner().legacy_ids,
+        Err(e) => debug!(
+            "Unable to fetch the legacy ids of the stored score: {:?}",
+            e
+        ),
+    }
+}
+
+/// Check the id and revision of a score before storing it and normalize it.
+/// Either both the id and the revision must be set or none of them.
+/// An id will be generated for scores without one.
+///
+/// # Arguments
+///
+/// * `conf`: the application configuration
+/// * `score`: the score to prepare
+///
+/// returns: Result<(), ApiError>
+fn prepare_score(conf: &Config, score: &mut Score) -> Result<(), ApiError> {
     if (score.couch_id.is_none() && score.couch_revision.is_some())
         || (score.couch_id.is_some() && score.couch_revision.is_none())
     {
@@ -171,27 +833,170 @@ pub async fn put_score<'de>(
     } else {
         score.couch_id = Some(generate_document_id(&conf.database.score_partition));
     }
-    let api_url = format!(
-        "{}/{}",
-        conf.database.database_mapping.put_score,
-        score
-            .couch_id
-            .as_ref()
-            .expect("Checked or generated score id")
-    );
+    score.normalize_genres(conf.archive.genre_capitalization);
+    validate_score(score)
+}
+
+/// Validate and clean up the pages of a score.
+/// The book names are trimmed and identical pages are removed.
+/// Pages which end before they begin within the same prefix and suffix are rejected with 'Unprocessable Entity'.
+///
+/// # Arguments
+///
+/// * `score`: the score to validate
+///
+/// returns: Result<(), ApiError>
+fn validate_score(score: &mut Score) -> Result<(), ApiError> {
+    let mut pages: Vec<Page> = Vec::with_capacity(score.pages.len());
+    for mut page in score.pages.drain(..) {
+        page.book = page.book.trim().to_string();
+        if !pages.contains(&page) {
+            pages.push(page);
+        }
+    }
+    score.pages = pages;
+    let invalid_page = score.pages.iter().find(|page| {
+        page.end.as_ref().is_some_and(|end| {
+            end.prefix == page.begin.prefix
+                && end.suffix == page.begin.suffix
+                && end
+                    .number
+                    .zip(page.begin.number)
+                    .is_some_and(|(e, b)| e < b)
+        })
+    });
+    match invalid_page {
+        Some(page) => Err(ApiError {
+            err: "Unprocessable Entity".to_string(),
+            msg: Some(format!(
+                "The page in the book '{}' ends before it begins",
+                page.book
+            )),
+            http_status_code: Status::UnprocessableEntity.code,
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Get the sheet music attachment of a score.
+/// If the score or the attachment does not exist, an [ApiError] with 'Not Found' will be returned.
+///
+/// # Arguments
+///
+/// * `conf`: the application configuration
+/// * `client`: the client to perform the request with
+/// * `id`: the id of the score
+///
+/// returns: Result<(Option<String>, Vec<u8>), ApiError> the content type and the attachment
+pub async fn get_score_attachment(
+    conf: &Config,
+    client: &DatabaseClient,
+    id: String,
+) -> Result<(Option<String>, Vec<u8>), ApiError> {
+    check_document_partition(&id, &conf.database.score_partition)?;
     let parameters: HashMap<String, String> = HashMap::new();
+    request_raw(
+        conf,
+        client,
+        no_op(),
+        Method::GET,
+        &format!(
+            "{}/{}/{}",
+            &conf.database.database_mapping.score_attachment, id, SCORE_ATTACHMENT_NAME
+        ),
+        &parameters,
+    )
+    .await
+}
+
+/// Store the sheet music of a score as PDF attachment of the score.
+/// An existing attachment will be replaced.
+/// The revision must be the current revision of the score, the response contains the new one.
+/// Since the document is streamed, the request is neither retried nor repeated after a reauthentication.
+///
+/// # Arguments
+///
+/// * `conf`: the application configuration
+/// * `client`: the client to perform the request with
+/// * `id`: the id of the score
+/// * `rev`: the current revision of the score
+/// * `pdf`: the body which streams the PDF document
+///
+/// returns: Result<Json<OperationResponse>, Error>
+pub async fn put_score_attachment(
+    conf: &Config,
+    client: &DatabaseClient,
+    id: String,
+    rev: String,
+    pdf: Body,
+) -> ApiResult<OperationResponse> {
+    check_document_partition(&id, &conf.database.score_partition)?;
+    let mut parameters = HashMap::new();
+    parameters.insert("rev".to_string(), rev);
     request(
         conf,
         client,
-        Box::new(|r| r.json(&score)),
+        Box::new(|r| r.header(CONTENT_TYPE, "application/pdf").body(pdf)),
         Method::PUT,
-        &api_url,
+        &format!(
+            "{}/{}/{}",
+            &conf.database.database_mapping.score_attachment, id, SCORE_ATTACHMENT_NAME
+        ),
         &parameters,
     )
     .await
     .map(Json)
 }
 
+/// Apply the configured genre capitalization to all scores which are already stored in the database.
+/// The scores are fetched in batches and only the ones whose genres change will be stored again.
+/// If the genre capitalization is [Capitalization::Unchanged], nothing will be done and an error will be returned.
+///
+/// # Arguments
+///
+/// * `conf`: the application configuration
+/// * `client`: the client to perform the requests with
+///
+/// returns: Result<(GenreNormalization, Vec<OperationResponse>), ApiError> the summary and the responses of the stored scores
+pub async fn normalize_all_genres(
+    conf: &Config,
+    client: &DatabaseClient,
+) -> Result<(GenreNormalization, Vec<OperationResponse>), ApiError> {
+    let capitalization = conf.archive.genre_capitalization;
+    if capitalization == Capitalization::Unchanged {
+        return Err(ApiError {
+            err: "Normalization disabled".to_string(),
+            msg: Some("there is no genre capitalization configured".to_string()),
+            http_status_code: Status::BadRequest.code,
+        });
+    }
+    const BATCH_SIZE: u64 = 100;
+    let mut normalization = GenreNormalization::default();
+    let mut responses = vec![];
+    loop {
+        let page = all_scores(conf, client, BATCH_SIZE, normalization.examined).await?;
+        if page.rows.is_empty() {
+            break;
+        }
+        normalization.examined += page.rows.len() as u64;
+        for row in page.0.rows {
+            let mut score = row.doc;
+            let genres = score.genres.clone();
+            score.normalize_genres(capitalization);
+            if score.genres != genres {
+                debug!("Normalize the genres of score {}", row.id);
+                responses.push(put_score(conf, client, score).await?.0);
+                normalization.modified += 1;
+            }
+        }
+    }
+    info!(
+        "Normalized the genres of {} out of {} scores",
+        normalization.modified, normalization.examined
+    );
+    Ok((normalization, responses))
+}
+
 /// Delete a score by its id and revision.
 ///
 /// # Arguments
@@ -204,7 +1009,7 @@ pub async fn put_score<'de>(
 /// returns: Result<Json<OperationResponse>, Error>
 pub async fn delete_score(
     conf: &Config,
-    client: &Client,
+    client: &DatabaseClient,
     id: String,
     rev: String,
 ) -> ApiResult<OperationResponse> {
@@ -223,6 +1028,68 @@ pub async fn delete_score(
     .map(Json)
 }
 
+/// Delete a score by its id with its current revision.
+/// The current revision is fetched first, on a `409 Conflict` it is fetched again and the deletion is retried once.
+/// This means concurrent changes of the score are discarded.
+///
+/// # Arguments
+///
+/// * `conf`: the application configuration
+/// * `client`: the client to perform the request
+/// * `id`: the id of the score to delete
+///
+/// returns: Result<Json<OperationResponse>, Error>
+pub async fn force_delete_score(
+    conf: &Config,
+    client: &DatabaseClient,
+    id: String,
+) -> ApiResult<OperationResponse> {
+    check_document_partition(&id, &conf.database.score_partition)?;
+    let rev = current_revision(conf, client, &id).await?;
+    match delete_score(conf, client, id.clone(), rev).await {
+        Err(err) if err.http_status_code == Status::Conflict.code => {
+            info!(
+                "The score '{}' has been changed concurrently, retry the deletion with the current revision",
+                id
+            );
+            let rev = current_revision(conf, client, &id).await?;
+            delete_score(conf, client, id, rev).await
+        }
+        result => result,
+    }
+}
+
+/// Fetch the current revision of a score.
+///
+/// # Arguments
+///
+/// * `conf`: the application configuration
+/// * `client`: the client to perform the request
+/// * `id`: the id of the score
+///
+/// returns: Result<String, ApiError>
+async fn current_revision(
+    conf: &Config,
+    client: &DatabaseClient,
+    id: &str,
+) -> Result<String, ApiError> {
+    get_score(conf, client, id.to_string())
+        .await?
+        .0
+        .couch_revision
+        .ok_or_else(|| {
+            warn!(
+                "The database returned the score '{}' without a revision",
+                id
+            );
+            ApiError {
+                err: "Internal Server Error".to_string(),
+                msg: Some("The revision of the score is unknown".to_string()),
+                http_status_code: Status::InternalServerError.code,
+            }
+        })
+}
+
 /// Fetch all scores which are part of the given `book`.
 /// The scores are sorted as usual in books which means the following order:
 ///
@@ -230,6 +1097,8 @@ pub async fn delete_score(
 /// . `number`
 /// . `suffix` (`None` last)
 ///
+/// The scores are fetched in pages of [crate::config::DatabaseConfig::book_content_limit] scores which are concatenated.
+///
 /// # Arguments
 ///
 /// * `conf`: the application configuration
@@ -239,92 +1108,387 @@ pub async fn delete_score(
 /// returns: Result<Json<FindResponse<Score>>, Error>
 pub async fn get_book_content(
     conf: &Config,
-    client: &Client,
+    client: &DatabaseClient,
     book: String,
 ) -> ApiResult<FindResponse<Score>> {
-    let mut response = search_scores(
-        conf,
-        client,
-        ScoreSearchParameters {
-            search_term: None,
-            regex: None,
-            attributes: vec![],
-            book: Some(book.clone()),
-            location: None,
-            sort: None,
-            ascending: None,
-            limit: 0xffff,
-            bookmark: None,
-        },
-    )
-    .await?;
+    let limit = page_limit(conf, conf.database.book_content_limit).max(1);
+    let mut response: Option<FindResponse<Score>> = None;
+    let mut bookmark = None;
+    loop {
+        let page = search_scores(
+            conf,
+            client,
+            ScoreSearchParameters {
+                search_term: None,
+                regex: None,
+                match_mode: None,
+                attributes: vec![],
+                all_fields: None,
+                book: vec![book.clone()],
+                location: None,
+                grade_min: None,
+                grade_max: None,
+                sort: vec![],
+                ascending: vec![],
+                limit,
+                bookmark,
+            },
+        )
+        .await?
+        .into_inner();
+        let last_page = !page.has_more;
+        bookmark = Some(page.bookmark.clone());
+        match response.as_mut() {
+            None => response = Some(page),
+            Some(response) => {
+                response.docs.extend(page.docs);
+                response.bookmark = page.bookmark;
+                response.execution_stats = page.execution_stats;
+                response.has_more = page.has_more;
+            }
+        }
+        if last_page {
+            break;
+        }
+    }
+    let mut response = response.unwrap_or_default();
     let scores = &mut response.docs;
     sort_by_book_page(&book, scores);
-    Ok(response)
+    Ok(Json(response))
+}
+
+/// Find the previous and next score of a score within a book.
+/// The scores are ordered as described in [get_book_content].
+/// If the score is not part of the book, an [ApiError] with 'Not Found' will be returned.
+///
+/// # Arguments
+///
+/// * `conf`: the application configuration
+/// * `client`: the client to send the database requests with
+/// * `book`: the name of the book
+/// * `id`: the id of the score whose neighbors are requested
+///
+/// returns: Result<Json<ScoreNeighbors>, Error>
+pub async fn book_score_neighbors(
+    conf: &Config,
+    client: &DatabaseClient,
+    book: String,
+    id: String,
+) -> ApiResult<ScoreNeighbors> {
+    let content = get_book_content(conf, client, book).await?;
+    let ids: Vec<Option<&String>> = content.docs.iter().map(|s| s.couch_id.as_ref()).collect();
+    let position = ids
+        .iter()
+        .position(|score_id| score_id == &Some(&id))
+        .ok_or_else(|| ApiError {
+            err: "Not Found".to_string(),
+            msg: Some("The score is not part of the book".to_string()),
+            http_status_code: Status::NotFound.code,
+        })?;
+    let neighbor = |index: Option<usize>| {
+        index
+            .and_then(|i| ids.get(i))
+            .and_then(|score_id| score_id.cloned())
+    };
+    Ok(Json(ScoreNeighbors {
+        previous: neighbor(position.checked_sub(1)),
+        next: neighbor(Some(position + 1)),
+    }))
+}
+
+/// Fetch the content of multiple books at once.
+/// The books are fetched concurrently, but at most [crate::config::DatabaseConfig::book_content_concurrency] at the same time in order to not overwhelm the database.
+/// Each book content is sorted as described in [get_book_content].
+/// The resulting map preserves the order of the requested `books`, regardless of the order the fetches complete in.
+///
+/// # Arguments
+///
+/// * `conf`: the application configuration
+/// * `client`: the client to send the database requests with
+/// * `books`: the names of the books to fetch
+///
+/// returns: Result<Json<Map<String, FindResponse<Score>>>, Error>
+pub async fn get_books_content(
+    conf: &Config,
+    client: &DatabaseClient,
+    books: Vec<String>,
+) -> ApiResult<Map<String, FindResponse<Score>>> {
+    let semaphore = &Semaphore::new(conf.database.book_content_concurrency.max(1));
+    let mut fetches: FuturesUnordered<_> = books
+        .iter()
+        .cloned()
+        .map(|book| async move {
+            let _permit = semaphore
+                .acquire()
+                .await
+                .expect("Semaphore is never closed");
+            debug!("Fetch content of book {}", book);
+            let content = get_book_content(conf, client, book.clone()).await;
+            (book, content)
+        })
+        .collect();
+    let mut contents = HashMap::new();
+    while let Some((book, content)) = fetches.next().await {
+        contents.insert(book, content?);
+    }
+    Ok(Json(
+        books
+            .into_iter()
+            .flat_map(|book| contents.remove(&book).map(|content| (book, content.0)))
+            .collect(),
+    ))
 }
 
 /// Construct a filter for the couchdb to search scores.
 ///
 /// # Arguments
 ///
+/// * `conf`: the application configuration
 /// * `parameters`: the parameters to construct the json value filter for
 ///
-/// returns: Value
-fn construct_filter(parameters: ScoreSearchParameters) -> Value {
-    let sort_value = parameters.sort.map(|s| json!([{s.to_string().to_lowercase().as_str(): if parameters.ascending.unwrap_or(true) {"asc"} else {"desc}"}}])).unwrap_or(json!([]));
+/// returns: Result<Value, ApiError>
+fn construct_filter(conf: &Config, parameters: ScoreSearchParameters) -> Result<Value, ApiError> {
+    let sort_value = sort_criteria(&parameters.sort, &parameters.ascending);
     let mut and_criteria = HashMap::new();
     let mut search_term_criteria = vec![];
-    if let Some(book) = parameters.book {
-        let book_criteria = json!({"$elemMatch": {"book": book}});
-        and_criteria.insert("pages".to_string(), book_criteria);
+    match parameters.book.as_slice() {
+        [] => {}
+        [book] => {
+            let book_criteria = json!({"$elemMatch": {"book": book}});
+            and_criteria.insert("pages".to_string(), book_criteria);
+        }
+        books => {
+            let book_criteria: Vec<Value> = books
+                .iter()
+                .map(|book| json!({"pages": {"$elemMatch": {"book": book}}}))
+                .collect();
+            and_criteria.insert("$and".to_string(), json!([{ "$or": book_criteria }]));
+        }
     }
     if let Some(l) = parameters.location {
         and_criteria.insert("location".to_string(), Value::String(l));
     }
+    if let Some(grade) = grade_criteria(parameters.grade_min, parameters.grade_max)? {
+        and_criteria.insert("grade".to_string(), grade);
+    }
+    let all_fields = parameters.all_fields.unwrap_or(false);
+    if all_fields && !parameters.attributes.is_empty() {
+        return Err(ApiError {
+            err: "Unprocessable Entity".to_string(),
+            msg: Some("'all_fields' cannot be combined with explicit 'attributes'".to_string()),
+            http_status_code: Status::UnprocessableEntity.code,
+        });
+    }
+    let attributes = if all_fields {
+        &ScoreSearchTermField::ALL[..]
+    } else {
+        &parameters.attributes[..]
+    };
     if let Some(term) = parameters.search_term {
-        parameters.attributes.iter().for_each(|a| {
+        let regex_term = term_from_regex(
+            term,
+            &parameters.regex,
+            parameters.match_mode.unwrap_or_default(),
+            conf,
+        )?;
+        attributes.iter().for_each(|a| {
             let key = a.to_string().to_lowercase();
             let value = if a.is_array() {
                 json!({key: {
                         "$elemMatch": {
-                            "$regex": term_from_regex(term.clone(), &parameters.regex)
+                            "$regex": regex_term
                         }
                     }
                 })
             } else {
                 json!({key: {
-                        "$regex": term_from_regex(term.clone(), &parameters.regex)
+                        "$regex": regex_term
                 }})
             };
             search_term_criteria.push(value);
         });
         and_criteria.insert("$or".to_string(), json!(search_term_criteria));
     }
-    json!({
+    Ok(json!({
         "selector": json!(and_criteria),
         "sort": sort_value,
         "stable": true,
         "skip": 0,
-        "execution_stats": true,
+        "execution_stats": conf.database.include_execution_stats,
         "bookmark": parameters.bookmark,
-        "limit": parameters.limit,
-    })
+        "limit": page_limit(conf, parameters.limit),
+    }))
+}
+
+/// The maximum difference between the bounds of an enumerated numeric grade range.
+const MAX_NUMERIC_GRADE_SPAN: u32 = 100;
+
+/// Create the Mango selector for the `grade` field within the given bounds.
+/// Since the grade is stored as a string, the comparison is chosen by the bounds:
+///
+/// * if every given bound is a non-negative integer, the range is compared numerically and matches the grades which are exactly one of the integers within the bounds, such as `"2"`, `"3"` and `"4"`
+/// * a single numeric bound enumerates [MAX_NUMERIC_GRADE_SPAN] grades above the lower or below the upper bound
+/// * otherwise, i.e. if a bound is not numeric or the bounds are too far apart, both bounds are compared lexicographically as CouchDB collates strings, such that `"10"` is less than `"2"`
+///
+/// Scores without a grade are never matched by a range.
+///
+/// # Arguments
+///
+/// * `min`: the inclusive lower bound of the grade
+/// * `max`: the inclusive upper bound of the grade
+///
+/// returns: Result<Option<Value>, ApiError> the selector for the `grade` field or `None` if no bound is set, 'Unprocessable Entity' if the lower bound is greater than the upper one
+fn grade_criteria(min: Option<String>, max: Option<String>) -> Result<Option<Value>, ApiError> {
+    let numeric = |bound: &Option<String>| bound.as_deref().map(|b| b.trim().parse::<u32>());
+    let numeric_range = match (numeric(&min), numeric(&max)) {
+        (Some(Ok(min)), Some(Ok(max))) if min > max => return Err(invalid_grade_range()),
+        (Some(Ok(min)), Some(Ok(max))) => {
+            (max - min <= MAX_NUMERIC_GRADE_SPAN).then_some(min..=max)
+        }
+        (Some(Ok(min)), None) => Some(min..=min.saturating_add(MAX_NUMERIC_GRADE_SPAN)),
+        (None, Some(Ok(max))) => Some(max.saturating_sub(MAX_NUMERIC_GRADE_SPAN)..=max),
+        _ => None,
+    };
+    if let Some(range) = numeric_range {
+        let grades: Vec<String> = range.map(|grade| grade.to_string()).collect();
+        return Ok(Some(json!({ "$in": grades })));
+    }
+    if matches!((&min, &max), (Some(min), Some(max)) if min > max) {
+        return Err(invalid_grade_range());
+    }
+    let mut range = serde_json::Map::new();
+    if let Some(min) = min {
+        range.insert("$gte".to_string(), Value::String(min));
+    }
+    if let Some(max) = max {
+        range.insert("$lte".to_string(), Value::String(max));
+    }
+    Ok((!range.is_empty()).then_some(Value::Object(range)))
+}
+
+/// The error if the lower bound of the grade is greater than the upper one.
+///
+/// returns: ApiError
+fn invalid_grade_range() -> ApiError {
+    ApiError {
+        err: "Unprocessable Entity".to_string(),
+        msg: Some("'grade_min' must not be greater than 'grade_max'".to_string()),
+        http_status_code: Status::UnprocessableEntity.code,
+    }
+}
+
+/// Create the Mango sort array for the fields in their order.
+/// If only one direction is given, it applies to all fields, missing directions are ascending.
+///
+/// # Arguments
+///
+/// * `fields`: the fields to sort by
+/// * `ascending`: the directions of the fields
+///
+/// returns: Value the sort array such as `[{"composers": "asc"}, {"title": "desc"}]`
+fn sort_criteria(fields: &[ScoreSearchTermField], ascending: &[bool]) -> Value {
+    let sort = fields
+        .iter()
+        .enumerate()
+        .map(|(i, field)| {
+            let ascending = match ascending {
+                [single] => *single,
+                _ => ascending.get(i).copied().unwrap_or(true),
+            };
+            let direction = if ascending { "asc" } else { "desc" };
+            json!({field.to_string().to_lowercase(): direction})
+        })
+        .collect();
+    Value::Array(sort)
 }
 
 /// Convenient function to convert the search term into a fuzzy one.
+/// Terms which are interpreted as regular expression are checked to not be too expensive for the database.
+/// This means they must not exceed [crate::config::ArchiveConfig::max_regex_length] and must not contain nested quantifiers.
 ///
 /// # Arguments
 ///
 /// * `term`: the term to convert
 /// * `regex`: `Some(true)` if `search_term` should be interpreted as regex, otherwise it will be interpreted as a fuzzy search term
+/// * `mode`: how a fuzzy search term has to match
+/// * `conf`: the application configuration
 ///
-/// returns: String
-fn term_from_regex(term: String, regex: &Option<bool>) -> String {
-    if regex.unwrap_or(false) {
-        term
+/// returns: Result<String, ApiError>
+fn term_from_regex(
+    term: String,
+    regex: &Option<bool>,
+    mode: MatchMode,
+    conf: &Config,
+) -> Result<String, ApiError> {
+    if !regex.unwrap_or(false) {
+        return Ok(fuzzy::fuzzy_regex(term, mode));
+    }
+    let rejection = if term.chars().count() > conf.archive.max_regex_length {
+        Some(format!(
+            "The regular expression exceeds the maximum length of {} characters",
+            conf.archive.max_regex_length
+        ))
+    } else if has_nested_quantifier(&term) {
+        Some("The regular expression must not contain nested quantifiers".to_string())
     } else {
-        fuzzy::fuzzy_regex(term)
+        None
+    };
+    match rejection {
+        Some(msg) => {
+            info!("Reject regular expression '{}': {}", term, msg);
+            Err(ApiError {
+                err: "Bad Request".to_string(),
+                msg: Some(msg),
+                http_status_code: Status::BadRequest.code,
+            })
+        }
+        None => Ok(term),
+    }
+}
+
+/// Check whether a regular expression contains a quantified group which itself contains a quantifier such as `(a+)+`.
+/// These nested quantifiers may cause catastrophic backtracking.
+/// Escaped characters and character classes are not considered as quantifiers.
+///
+/// # Arguments
+///
+/// * `pattern`: the regular expression to check
+///
+/// returns: bool
+fn has_nested_quantifier(pattern: &str) -> bool {
+    let mut chars = pattern.chars();
+    let mut groups: Vec<bool> = vec![];
+    let mut in_class = false;
+    let mut closed_group = None;
+    while let Some(c) = chars.next() {
+        let previous_group = closed_group.take();
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            '[' if !in_class => in_class = true,
+            ']' if in_class => in_class = false,
+            _ if in_class => {}
+            '(' => groups.push(false),
+            ')' => {
+                let quantified = groups.pop().unwrap_or(false);
+                if let Some(parent) = groups.last_mut() {
+                    *parent |= quantified;
+                }
+                closed_group = Some(quantified);
+            }
+            '*' | '+' | '{' => {
+                if previous_group == Some(true) {
+                    return true;
+                }
+                if let Some(group) = groups.last_mut() {
+                    *group = true;
+                }
+            }
+            _ => {}
+        }
     }
+    false
 }
 
 fn no_op<'a, E>() -> Box<dyn FnOnce(E) -> E + Send + 'a> {
@@ -0,0 +1,317 @@
+// OpenKeg, the lightweight backend of the Musikverein Leopoldsdorf.
+// Copyright (C) 2022  Richard Stöckl
+//
+// This program is free software; you can redistribute it and/or
+// modify it under the terms of the GNU General Public License
+// as published by the Free Software Foundation; either version 2
+// of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+
+use super::*;
+use crate::archive::model::PageNumber;
+
+#[test]
+fn nested_quantifiers() {
+    assert!(has_nested_quantifier("(a+)+"));
+    assert!(has_nested_quantifier("(a*)*b"));
+    assert!(has_nested_quantifier("((ab)+c)*"));
+    assert!(has_nested_quantifier("(\\d+){2,}"));
+}
+
+#[test]
+fn harmless_quantifiers() {
+    assert!(!has_nested_quantifier("Radetzky.*Marsch"));
+    assert!(!has_nested_quantifier("(Polka|Walzer)+"));
+    assert!(!has_nested_quantifier("(a+)?"));
+    assert!(!has_nested_quantifier("(\\+)+"));
+    assert!(!has_nested_quantifier("([+*])+"));
+}
+
+#[test]
+fn regex_length() {
+    let conf = Config::default();
+    let term = "a".repeat(conf.archive.max_regex_length + 1);
+    assert!(term_from_regex(term.clone(), &Some(true), MatchMode::Substring, &conf).is_err());
+    assert!(term_from_regex(term, &Some(false), MatchMode::Substring, &conf).is_ok());
+    assert!(term_from_regex("^Rad".to_string(), &Some(true), MatchMode::Substring, &conf).is_ok());
+}
+
+fn sorted_parameters(
+    sort: Vec<ScoreSearchTermField>,
+    ascending: Vec<bool>,
+) -> ScoreSearchParameters {
+    ScoreSearchParameters {
+        search_term: None,
+        regex: None,
+        match_mode: None,
+        attributes: vec![],
+        all_fields: None,
+        book: vec![],
+        location: None,
+        grade_min: None,
+        grade_max: None,
+        sort,
+        ascending,
+        limit: 10,
+        bookmark: None,
+    }
+}
+
+#[test]
+fn sort_direction() {
+    let conf = Config::default();
+    let descending = sorted_parameters(vec![ScoreSearchTermField::Title], vec![false]);
+    let filter = construct_filter(&conf, descending).expect("filter");
+    assert_eq!(filter["sort"][0]["title"], "desc");
+    let ascending = sorted_parameters(vec![ScoreSearchTermField::Title], vec![true]);
+    let filter = construct_filter(&conf, ascending).expect("filter");
+    assert_eq!(filter["sort"][0]["title"], "asc");
+}
+
+#[test]
+fn multiple_sort_fields() {
+    let parameters = sorted_parameters(
+        vec![ScoreSearchTermField::Composers, ScoreSearchTermField::Title],
+        vec![true, false],
+    );
+    let filter = construct_filter(&Config::default(), parameters).expect("filter");
+    assert_eq!(
+        filter["sort"],
+        json!([{"composers": "asc"}, {"title": "desc"}])
+    );
+}
+
+#[test]
+fn single_sort_direction() {
+    let fields = [ScoreSearchTermField::Genres, ScoreSearchTermField::Title];
+    assert_eq!(
+        sort_criteria(&fields, &[false]),
+        json!([{"genres": "desc"}, {"title": "desc"}])
+    );
+    assert_eq!(
+        sort_criteria(&fields, &[]),
+        json!([{"genres": "asc"}, {"title": "asc"}])
+    );
+}
+
+#[test]
+fn multiple_books() {
+    let mut parameters = sorted_parameters(vec![], vec![]);
+    parameters.book = vec!["Marschbuch".to_string(), "Polkabuch".to_string()];
+    let filter = construct_filter(&Config::default(), parameters).expect("filter");
+    assert_eq!(
+        filter["selector"],
+        json!({"$and": [{"$or": [
+            {"pages": {"$elemMatch": {"book": "Marschbuch"}}},
+            {"pages": {"$elemMatch": {"book": "Polkabuch"}}},
+        ]}]})
+    );
+    let mut parameters = sorted_parameters(vec![], vec![]);
+    parameters.book = vec!["Marschbuch".to_string()];
+    let filter = construct_filter(&Config::default(), parameters).expect("filter");
+    assert_eq!(
+        filter["selector"],
+        json!({"pages": {"$elemMatch": {"book": "Marschbuch"}}})
+    );
+}
+
+#[test]
+fn sorted_browsing() {
+    let mut conf = Config::default();
+    let filter = sorted_filter(
+        &conf,
+        ScoreSearchTermField::Title,
+        false,
+        20,
+        Some("g1AAAABueJzLYWBgYMpgSmHg".to_string()),
+    );
+    assert_eq!(filter["selector"], json!({"title": {"$exists": true}}));
+    assert_eq!(filter["sort"], json!([{"title": "desc"}]));
+    assert_eq!(filter["bookmark"], "g1AAAABueJzLYWBgYMpgSmHg");
+    assert_eq!(filter["limit"], 20);
+    assert!(filter.get("use_index").is_none());
+    conf.database.sort_index = Some("_design/score-sort".to_string());
+    let filter = sorted_filter(&conf, ScoreSearchTermField::Composers, true, 20, None);
+    assert_eq!(filter["use_index"], "_design/score-sort");
+}
+
+#[test]
+fn page_limit_clamp() {
+    let mut conf = Config::default();
+    conf.database.max_page_limit = 50;
+    let mut parameters = sorted_parameters(vec![], vec![]);
+    parameters.limit = 10_000;
+    let filter = construct_filter(&conf, parameters).expect("filter");
+    assert_eq!(filter["limit"], 50);
+    let filter = sorted_filter(&conf, ScoreSearchTermField::Title, true, 10_000, None);
+    assert_eq!(filter["limit"], 50);
+    assert_eq!(page_limit(&conf, 20), 20);
+}
+
+#[test]
+fn all_fields() {
+    let mut parameters = sorted_parameters(vec![], vec![]);
+    parameters.search_term = Some("Marsch".to_string());
+    parameters.all_fields = Some(true);
+    let filter = construct_filter(&Config::default(), parameters).expect("filter");
+    let criteria = filter["selector"]["$or"].as_array().expect("or");
+    assert_eq!(criteria.len(), ScoreSearchTermField::ALL.len());
+    assert!(criteria[0]["title"]["$regex"].is_string());
+    assert!(criteria[1]["genres"]["$elemMatch"]["$regex"].is_string());
+    assert!(criteria[6]["publisher"]["$regex"].is_string());
+}
+
+#[test]
+fn all_fields_with_attributes() {
+    let mut parameters = sorted_parameters(vec![], vec![]);
+    parameters.search_term = Some("Marsch".to_string());
+    parameters.all_fields = Some(true);
+    parameters.attributes = vec![ScoreSearchTermField::Title];
+    let error = construct_filter(&Config::default(), parameters).unwrap_err();
+    assert_eq!(error.http_status_code, Status::UnprocessableEntity.code);
+}
+
+#[test]
+fn grade_range() {
+    let mut parameters = sorted_parameters(vec![], vec![]);
+    parameters.grade_min = Some("2".to_string());
+    parameters.grade_max = Some("4".to_string());
+    let filter = construct_filter(&Config::default(), parameters).expect("filter");
+    assert_eq!(
+        filter["selector"],
+        json!({"grade": {"$in": ["2", "3", "4"]}})
+    );
+    assert_eq!(
+        grade_criteria(Some("B".to_string()), None).expect("criteria"),
+        Some(json!({"$gte": "B"}))
+    );
+    assert_eq!(grade_criteria(None, None).expect("criteria"), None);
+}
+
+#[test]
+fn grade_bounds_are_compared_per_bound() {
+    let grades = |criteria: Option<Value>| criteria.expect("criteria")["$in"].clone();
+    let lower = grade_criteria(Some("99".to_string()), None).expect("criteria");
+    assert_eq!(grades(lower)[0], json!("99"));
+    let upper = grade_criteria(None, Some("2".to_string())).expect("criteria");
+    assert_eq!(grades(upper), json!(["0", "1", "2"]));
+    assert_eq!(
+        grade_criteria(Some("2".to_string()), Some("B".to_string())).expect("criteria"),
+        Some(json!({"$gte": "2", "$lte": "B"}))
+    );
+}
+
+#[test]
+fn inverted_grade_range() {
+    let numeric = grade_criteria(Some("4".to_string()), Some("2".to_string())).unwrap_err();
+    assert_eq!(numeric.http_status_code, 422);
+    let lexicographic = grade_criteria(Some("C".to_string()), Some("A".to_string())).unwrap_err();
+    assert_eq!(lexicographic.http_status_code, 422);
+}
+
+#[test]
+fn batch_rows() {
+    let response: BatchResponse = serde_json::from_value(json!({"rows": [
+        {"id": "scores:1", "key": "scores:1", "value": {"rev": "1-a"}, "doc": {"_id": "scores:1", "title": "Radetzky-Marsch"}},
+        {"key": "scores:2", "error": "not_found"},
+        {"id": "scores:3", "key": "scores:3", "value": {"rev": "2-b", "deleted": true}, "doc": null},
+    ]}))
+    .expect("response");
+    let batch = collect_batch(response);
+    assert_eq!(batch.scores["scores:1"].title, "Radetzky-Marsch");
+    assert_eq!(batch.missing, vec!["scores:2", "scores:3"]);
+}
+
+#[test]
+fn unique_titles_are_limited_after_deduplication() {
+    let titles = ["Marsch", "Marsch", "Marsch", "Polka", "Marsch", "Walzer"]
+        .iter()
+        .map(|t| t.to_string());
+    assert_eq!(unique_titles(titles, 2), vec!["Marsch", "Polka"]);
+}
+
+#[rocket::async_test]
+async fn batch_limit() {
+    let mut conf = Config::default();
+    conf.database.max_page_limit = 2;
+    let client = DatabaseClient::new(reqwest::Client::new());
+    let ids = vec!["scores:1", "scores:2", "scores:3"]
+        .into_iter()
+        .map(String::from)
+        .collect();
+    let error = get_scores_batch(&conf, &client, ids).await.unwrap_err();
+    assert_eq!(error.http_status_code, 422);
+}
+
+fn page(book: &str, begin: i64, end: Option<i64>) -> Page {
+    let number = |number| PageNumber {
+        prefix: Some("A".to_string()),
+        number: Some(number),
+        suffix: None,
+    };
+    Page {
+        book: book.to_string(),
+        begin: number(begin),
+        end: end.map(number),
+    }
+}
+
+#[test]
+fn page_ordering() {
+    let mut score = Score {
+        pages: vec![
+            page("Marschbuch", 3, Some(4)),
+            page("Polkabuch", 12, Some(11)),
+        ],
+        ..Default::default()
+    };
+    let error = validate_score(&mut score).expect_err("page ends before it begins");
+    assert_eq!(error.http_status_code, Status::UnprocessableEntity.code);
+    assert!(error.msg.expect("message").contains("Polkabuch"));
+    let mut score = Score {
+        pages: vec![page("Marschbuch", 3, Some(3)), page("Polkabuch", 12, None)],
+        ..Default::default()
+    };
+    assert!(validate_score(&mut score).is_ok());
+}
+
+#[test]
+fn page_book_trimming() {
+    let mut score = Score {
+        pages: vec![
+            page(" Marschbuch ", 3, None),
+            page("Marschbuch", 3, None),
+            page("Polkabuch\t", 5, Some(6)),
+        ],
+        ..Default::default()
+    };
+    validate_score(&mut score).expect("valid pages");
+    assert_eq!(
+        score.pages,
+        vec![page("Marschbuch", 3, None), page("Polkabuch", 5, Some(6))]
+    );
+}
+
+#[test]
+#[allow(deprecated)]
+fn legacy_ids_are_stored() {
+    let score = Score {
+        legacy_ids: vec![42],
+        ..Score::default()
+    };
+    let document = score_document(&score).expect("document");
+    assert_eq!(document["legacy_ids"], json!([42]));
+    let response = serde_json::to_value(&score).expect("response");
+    assert_eq!(
+        response.get("legacy_ids").is_some(),
+        !cfg!(feature = "omit-legacy-ids")
+    );
+}
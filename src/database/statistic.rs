@@ -16,16 +16,61 @@
 // Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
 
 use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use reqwest::{Client, Method};
+use reqwest::Method;
 use rocket::serde::json::Json;
+use rocket::tokio::sync::RwLock;
 
 use crate::archive::model::CountStatistic;
 use crate::archive::statistic::CountStatisticType;
-use crate::database::client::request;
+use crate::database::client::{query_view, request, DatabaseClient, ViewQuery};
 use crate::openapi::ApiResult;
 use crate::Config;
 
+/// The cache of the count statistics of each type.
+/// Each entry holds the instant the statistic was fetched in order to detect expired entries.
+pub type StatisticCache = Arc<RwLock<HashMap<CountStatisticType, (Instant, CountStatistic)>>>;
+
+/// Get a count statistic from the cache.
+/// The statistic is fetched from the database if the cache holds no entry, the entry is older than [crate::config::DatabaseConfig::statistic_cache_ttl] or `refresh` is set.
+///
+/// # Arguments
+///
+/// * `cache`: the cache to read from and to store freshly fetched statistics into
+/// * `conf`: the application configuration
+/// * `client`: the client to perform the database requests with
+/// * `subject`: the actual type of the statistic
+/// * `refresh`: whether to bypass the cache
+///
+/// returns: Result<Json<Statistic<String, u64>>, ApiError>
+pub async fn cached_count_statistic(
+    cache: &StatisticCache,
+    conf: &Config,
+    client: &DatabaseClient,
+    subject: CountStatisticType,
+    refresh: bool,
+) -> ApiResult<CountStatistic> {
+    let ttl = Duration::from_secs(conf.database.statistic_cache_ttl);
+    if !refresh {
+        if let Some((_, statistic)) = cache
+            .read()
+            .await
+            .get(&subject)
+            .filter(|(fetched, _)| fetched.elapsed() < ttl)
+        {
+            return Ok(Json(statistic.clone()));
+        }
+    }
+    let statistic = count_statistic(conf, client, subject).await?;
+    cache
+        .write()
+        .await
+        .insert(subject, (Instant::now(), statistic.0.clone()));
+    Ok(statistic)
+}
+
 /// Fetch different types of statistics from the database.
 /// 
 /// # Arguments 
@@ -37,18 +82,10 @@ use crate::Config;
 /// returns: Result<Json<Statistic<String, u64>>, ApiError> 
 pub async fn count_statistic(
     conf: &Config,
-    client: &Client,
+    client: &DatabaseClient,
     subject: CountStatisticType,
 ) -> ApiResult<CountStatistic> {
-    let db_mapping = &conf.database.database_mapping;
-    let api_url = match subject {
-        CountStatisticType::Genres => &db_mapping.genres_statistic,
-        CountStatisticType::Arrangers => &db_mapping.arrangers_statistic,
-        CountStatisticType::Composers => &db_mapping.composers_statistic,
-        CountStatisticType::Publishers => &db_mapping.publishers_statistic,
-        CountStatisticType::Locations => &db_mapping.locations_statistic,
-        CountStatisticType::Books => &db_mapping.books_statistic,
-    };
+    let api_url = statistic_url(conf, subject);
     let mut parameters = HashMap::new();
     parameters.insert("group".to_string(), "true".to_string());
     parameters.insert(
@@ -66,3 +103,51 @@ pub async fn count_statistic(
     .await
     .map(Json)
 }
+
+/// Fetch the entries of a count statistic whose key starts with the prefix, such as the composers for autocompletion.
+/// The prefix is case-sensitive and the number of entries is limited by [crate::config::ArchiveConfig::title_suggestions_limit].
+///
+/// # Arguments
+///
+/// * `conf`: the application configuration
+/// * `client`: the client to perform the database requests with
+/// * `subject`: the actual type of the statistic
+/// * `prefix`: the prefix of the keys
+///
+/// returns: Result<Json<Statistic<String, u64>>, ApiError>
+pub async fn prefix_statistic(
+    conf: &Config,
+    client: &DatabaseClient,
+    subject: CountStatisticType,
+    prefix: String,
+) -> ApiResult<CountStatistic> {
+    let query = ViewQuery {
+        group: Some(true),
+        limit: Some(conf.archive.title_suggestions_limit),
+        partition: Some(conf.database.score_partition.to_string()),
+        ..ViewQuery::prefix(&prefix)
+    };
+    query_view(conf, client, statistic_url(conf, subject), query)
+        .await
+        .map(Json)
+}
+
+/// Get the url of the view which provides the statistic.
+///
+/// # Arguments
+///
+/// * `conf`: the application configuration
+/// * `subject`: the actual type of the statistic
+///
+/// returns: &String
+fn statistic_url(conf: &Config, subject: CountStatisticType) -> &String {
+    let db_mapping = &conf.database.database_mapping;
+    match subject {
+        CountStatisticType::Genres => &db_mapping.genres_statistic,
+        CountStatisticType::Arrangers => &db_mapping.arrangers_statistic,
+        CountStatisticType::Composers => &db_mapping.composers_statistic,
+        CountStatisticType::Publishers => &db_mapping.publishers_statistic,
+        CountStatisticType::Locations => &db_mapping.locations_statistic,
+        CountStatisticType::Books => &db_mapping.books_statistic,
+    }
+}
@@ -23,11 +23,17 @@ use rocket_okapi::settings::OpenApiSettings;
 mod model;
 /// Provide the rest interface for accessing documents.
 mod service;
+/// Access the documents on a WebDav server.
+pub mod webdav;
 
 pub fn get_document_routes_and_docs(settings: &OpenApiSettings) -> (Vec<rocket::Route>, OpenApi) {
     openapi_get_routes_spec![
-        settings: service::list_documents,
+        settings: service::list_all_documents,
+        service::list_documents,
+        service::list_documents_recursive,
         service::get_document,
-        service::get_asset
+        service::get_asset,
+        service::put_document,
+        service::put_asset
     ]
 }
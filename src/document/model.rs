@@ -16,21 +16,28 @@
 // Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
 
 use std::default::Default;
+use std::pin::Pin;
 
 use okapi::map;
 use okapi::openapi3::RefOr;
 use okapi::openapi3::{Parameter, ParameterValue, Responses};
 use rocket::fs::NamedFile;
-use rocket::http::MediaType;
+use rocket::futures::Stream;
+use rocket::http::{ContentType, MediaType, Status};
 use rocket::request::FromParam;
-use rocket::response::Responder;
+use rocket::response::stream::ByteStream;
+use rocket::response::{self, Responder};
+use rocket::serde::{Deserialize, Serialize};
+use rocket::{Request, Response};
 use rocket_okapi::gen::OpenApiGenerator;
 use rocket_okapi::request::OpenApiFromParam;
 use rocket_okapi::response::OpenApiResponderInner;
 use schemars::schema::SchemaObject;
+use schemars::JsonSchema;
 use serde_json::Value::String as Vs;
 
-use crate::config::DocumentMapping;
+use crate::config::{DocumentMapping, DocumentSizeLimits};
+use crate::openapi::SchemaExample;
 
 /// The type of a document such as blackboard or chronicle.
 /// Used to differentiate between types of a document and their origin.
@@ -42,6 +49,13 @@ pub enum DocumentType {
 const BLACKBOARD_ID: &str = "blackboard";
 
 impl DocumentType {
+    /// Get all document types.
+    ///
+    /// returns: Vec<DocumentType>
+    pub fn all() -> Vec<DocumentType> {
+        vec![DocumentType::Blackboard]
+    }
+
     /// Get the location to the directory where all the documents of this type are stored at.
     /// The returned path is relative to the document server.
     ///
@@ -69,6 +83,19 @@ impl DocumentType {
             DocumentType::Blackboard => mapping.blackboard_assets.clone(),
         }
     }
+
+    /// Get the maximum size of a document of this type in *bytes*.
+    ///
+    /// # Arguments
+    ///
+    /// * `limits`: the size limits from the configuration
+    ///
+    /// returns: u64
+    pub fn max_size(&self, limits: &DocumentSizeLimits) -> u64 {
+        match self {
+            DocumentType::Blackboard => limits.blackboard,
+        }
+    }
 }
 
 impl ToString for DocumentType {
@@ -129,9 +156,97 @@ impl OpenApiFromParam<'_> for DocumentType {
     }
 }
 
-#[derive(Responder)]
-#[response(status = 200, content_type = "text/markdown")]
-pub struct MarkdownContent(pub NamedFile);
+/// The recursive listing of all documents of a document type.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(crate = "rocket::serde")]
+#[schemars(example = "Self::example")]
+pub struct DocumentListing {
+    /// The paths of all files relative to the directory of the document type, sorted by their path.
+    pub files: Vec<String>,
+    /// Whether the listing was stopped due to the configured depth or entry limits.
+    pub truncated: bool,
+}
+
+impl SchemaExample for DocumentListing {
+    fn example() -> Self {
+        Self {
+            files: vec![
+                "2023-01-01-neujahr.md".to_string(),
+                "archiv/2022-12-24-weihnachten.md".to_string(),
+            ],
+            truncated: false,
+        }
+    }
+}
+
+/// The result of storing a document or a document-asset.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(crate = "rocket::serde")]
+#[schemars(example = "Self::example")]
+pub struct DocumentUpload {
+    /// The filename of the stored file.
+    pub filename: String,
+}
+
+impl SchemaExample for DocumentUpload {
+    fn example() -> Self {
+        Self {
+            filename: "2023-01-01-neujahr.md".to_string(),
+        }
+    }
+}
+
+/// The chunks of a file which is streamed from the WebDav server.
+pub type RemoteStream = Pin<Box<dyn Stream<Item = Vec<u8>> + Send>>;
+
+/// The content of a document or a document-asset.
+pub enum DocumentContent {
+    /// A file read from the local filesystem.
+    File(NamedFile),
+    /// A file streamed from the WebDav server with the content type reported by the server.
+    Remote(ByteStream<RemoteStream>, ContentType),
+}
+
+impl<'r> Responder<'r, 'r> for DocumentContent {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'r> {
+        match self {
+            DocumentContent::File(file) => file.respond_to(request),
+            DocumentContent::Remote(stream, content_type) => {
+                Response::build_from(stream.respond_to(request)?)
+                    .header(content_type)
+                    .ok()
+            }
+        }
+    }
+}
+
+impl OpenApiResponderInner for DocumentContent {
+    fn responses(_gen: &mut OpenApiGenerator) -> rocket_okapi::Result<Responses> {
+        let content_response = okapi::openapi3::Response {
+            description: "The content of the file".to_string(),
+            content: map! {MediaType::Any.to_string() => okapi::openapi3::MediaType::default()},
+            ..okapi::openapi3::Response::default()
+        };
+        let responses = map! {"200".to_string() => RefOr::Object(content_response)};
+        Ok(Responses {
+            default: None,
+            responses,
+            extensions: map! {},
+        })
+    }
+}
+
+/// The content of a document in the markdown format.
+pub struct MarkdownContent(pub DocumentContent);
+
+impl<'r> Responder<'r, 'r> for MarkdownContent {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'r> {
+        Response::build_from(self.0.respond_to(request)?)
+            .status(Status::Ok)
+            .header(ContentType::Markdown)
+            .ok()
+    }
+}
 
 //todo: find a less verbose way to propagate text/markdown to openapi
 impl OpenApiResponderInner for MarkdownContent {
@@ -15,18 +15,33 @@
 // along with this program; if not, write to the Free Software
 // Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
 
-use std::path::Path;
+use std::collections::BTreeMap;
+use std::io;
+use std::path::{Path, PathBuf};
 
+use futures::future::join;
 use rocket::fs::NamedFile;
-use rocket::http::Status;
+use rocket::http::{ContentType, Status};
 use rocket::serde::json::Json;
 use rocket::State;
 use rocket_okapi::openapi;
 
-use crate::document::model::{DocumentType, MarkdownContent};
-use crate::openapi::{map_io_err, ApiError, ApiResult};
+use crate::config::{DocumentAccess, DocumentServer};
+use crate::document::model::{
+    DocumentContent, DocumentListing, DocumentType, DocumentUpload, MarkdownContent,
+};
+use crate::document::webdav::{
+    fetch_file, list_collection, put_file, walk_collection, DocumentClient,
+};
+use crate::openapi::{map_io_err, too_large_error, ApiError, ApiResult};
+use crate::upload::Upload;
+use crate::user::executives::{ExecutiveRole, Secretary};
 use crate::Config;
 
+#[cfg(test)]
+#[path = "service_tests.rs"]
+mod service_tests;
+
 /// List all documents of the provided [`DocumentType`] which are available on the server sorted by their filename.
 /// The list only includes files directly located at the configured directory of the document type.
 /// This means there is no support for recursive lookups nor directories.
@@ -34,6 +49,7 @@ use crate::Config;
 /// # Arguments
 ///
 /// * `doc_type`: the document type of all the listed documents
+/// * `client`: the client to access the WebDav server
 /// * `conf`: the application configuration
 ///
 /// returns: Result<Json<Vec<String, Global>>, ApiError>
@@ -41,30 +57,98 @@ use crate::Config;
 #[get("/<doc_type>")]
 pub async fn list_documents(
     doc_type: DocumentType,
+    client: &State<DocumentClient>,
     conf: &State<Config>,
 ) -> ApiResult<Vec<String>> {
+    list_files(&doc_type, client, conf, None).await.map(Json)
+}
+
+/// List all documents of all document types, mapped by the document type.
+/// The documents of each type are listed like [list_documents] and are intended for indexing all documents at once.
+/// At most [DocumentServer::max_listing_entries] documents are listed per document type.
+/// Entries which resolve to a location outside the directory of the document type such as symlinks are skipped.
+///
+/// # Arguments
+///
+/// * `client`: the client to access the WebDav server
+/// * `conf`: the application configuration
+///
+/// returns: Result<Json<BTreeMap<String, Vec<String>>>, ApiError>
+#[openapi(tag = "Documents")]
+#[get("/")]
+pub async fn list_all_documents(
+    client: &State<DocumentClient>,
+    conf: &State<Config>,
+) -> ApiResult<BTreeMap<String, Vec<String>>> {
+    let mut documents = BTreeMap::new();
+    for doc_type in DocumentType::all() {
+        let files = list_files(
+            &doc_type,
+            client,
+            conf,
+            Some(conf.document_server.max_listing_entries),
+        )
+        .await?;
+        documents.insert(doc_type.to_string(), files);
+    }
+    Ok(Json(documents))
+}
+
+/// List all documents of the provided [`DocumentType`] including the ones in subdirectories.
+/// The paths are relative to the configured directory of the document type and sorted.
+/// The traversal is limited by [DocumentServer::max_listing_depth] and [DocumentServer::max_listing_entries], the listing states whether one of them was hit.
+/// Entries which resolve to a location outside the directory of the document type such as symlinks are skipped.
+///
+/// # Arguments
+///
+/// * `doc_type`: the document type of all the listed documents
+/// * `client`: the client to access the WebDav server
+/// * `conf`: the application configuration
+///
+/// returns: Result<Json<DocumentListing>, ApiError>
+#[openapi(tag = "Documents")]
+#[get("/<doc_type>/tree")]
+pub async fn list_documents_recursive(
+    doc_type: DocumentType,
+    client: &State<DocumentClient>,
+    conf: &State<Config>,
+) -> ApiResult<DocumentListing> {
     let doc_type_path_str = doc_type.location(&conf.document_server.mapping);
+    if conf.document_server.access == DocumentAccess::WebDav {
+        let (mut files, truncated) =
+            walk_collection(client, &conf.document_server, &doc_type_path_str).await?;
+        files.sort();
+        return Ok(Json(DocumentListing { files, truncated }));
+    }
     let doc_type_path = map_io_err(
         Path::new(&doc_type_path_str).canonicalize(),
         Status::InternalServerError,
     )?;
-    let read_dir = map_io_err(doc_type_path.read_dir(), Status::InternalServerError)?;
-    let files = read_dir.flatten().filter(|f| f.path().is_file());
-    let mut files_names: Vec<String> = files
-        .flat_map(|f| f.file_name().to_str().map(ToString::to_string))
-        .collect();
-    files_names.sort();
-    Ok(Json(files_names))
+    let mut files = vec![];
+    let truncated = map_io_err(
+        walk_directory(
+            &doc_type_path,
+            &doc_type_path,
+            0,
+            &conf.document_server,
+            &mut files,
+        ),
+        Status::InternalServerError,
+    )?;
+    files.sort();
+    Ok(Json(DocumentListing { files, truncated }))
 }
 
-/// Read a document located on the servers file system.
+/// Read a document located on the servers file system or the WebDav server.
 /// Each document has a [DocumentType] with a corresponding base url.
 /// If the requested document name is not below the location of the [DocumentType], the server will return a 'Not Found'.
+/// If the document exceeds the configured size of its [DocumentType], the server will return a 'Payload Too Large'.
 ///
 /// # Arguments
 ///
 /// * `doc_type`: the document type to look for
 /// * `document`: the filename of the requested document
+/// * `client`: the client to access the WebDav server
 /// * `conf`: the application configuration
 ///
 /// returns: Result<MarkdownContent, ApiError>
@@ -73,14 +157,23 @@ pub async fn list_documents(
 pub async fn get_document(
     doc_type: DocumentType,
     document: String,
+    client: &State<DocumentClient>,
     conf: &State<Config>,
 ) -> Result<MarkdownContent, ApiError> {
     let doc_type_path_str = doc_type.location(&conf.document_server.mapping);
-    let doc = read_from_filesystem(document, doc_type_path_str).await?;
+    let max_size = doc_type.max_size(&conf.document_server.max_document_sizes);
+    let doc = read_file(
+        &document,
+        doc_type_path_str,
+        Some(max_size),
+        client,
+        &conf.document_server,
+    )
+    .await?;
     Ok(MarkdownContent(doc))
 }
 
-/// Read a document-asset located on the servers file system.
+/// Read a document-asset located on the servers file system or the WebDav server.
 /// Each document has a [DocumentType] with a corresponding base url.
 /// If the requested asset name is not below the assets location of the [DocumentType], the server will return a 'Not Found'.
 ///
@@ -88,19 +181,228 @@ pub async fn get_document(
 ///
 /// * `doc_type`: the document type to look for
 /// * `asset`: the filename of the requested asset
+/// * `client`: the client to access the WebDav server
 /// * `conf`: the application configuration
 ///
-/// returns: Result<NamedFile, ApiError>
+/// returns: Result<DocumentContent, ApiError>
 #[openapi(tag = "Documents")]
 #[get("/<doc_type>/assets/<asset>")]
 pub async fn get_asset(
     doc_type: DocumentType,
     asset: String,
+    client: &State<DocumentClient>,
     conf: &State<Config>,
-) -> Result<NamedFile, ApiError> {
+) -> Result<DocumentContent, ApiError> {
+    let assets_path_str = doc_type.assets_location(&conf.document_server.mapping);
+    read_file(&asset, assets_path_str, None, client, &conf.document_server).await
+}
+
+/// Store a document on the servers file system or the WebDav server.
+/// An existing document with the same name will be replaced.
+/// If the document name contains path separators, the server will return an 'Unprocessable Entity'.
+/// If the document exceeds the configured size of its [DocumentType], the server will return a 'Payload Too Large'.
+/// If the document is not sent as `text/markdown`, the server will return an 'Unsupported Media Type'.
+///
+/// # Arguments
+///
+/// * `doc_type`: the document type to store the document as
+/// * `document`: the filename of the document
+/// * `content_type`: the content type of the document
+/// * `content`: the document in the markdown format
+/// * `_secretary_role`: the secretary role guard
+/// * `client`: the client to access the WebDav server
+/// * `conf`: the application configuration
+///
+/// returns: Result<Json<DocumentUpload>, ApiError>
+#[openapi(tag = "Documents")]
+#[put("/<doc_type>/<document>", data = "<content>")]
+pub async fn put_document(
+    doc_type: DocumentType,
+    document: String,
+    content_type: Option<&ContentType>,
+    content: Upload<'_>,
+    _secretary_role: ExecutiveRole<Secretary>,
+    client: &State<DocumentClient>,
+    conf: &State<Config>,
+) -> ApiResult<DocumentUpload> {
+    if !matches!(content_type, Some(content_type) if *content_type == ContentType::Markdown) {
+        return Err(unsupported_media_type_error());
+    }
+    let doc_type_path_str = doc_type.location(&conf.document_server.mapping);
+    let max_size = doc_type.max_size(&conf.document_server.max_document_sizes);
+    write_file(
+        &document,
+        doc_type_path_str,
+        content,
+        Some(max_size),
+        client,
+        &conf.document_server,
+    )
+    .await
+    .map(Json)
+}
+
+/// Store a document-asset on the servers file system or the WebDav server.
+/// An existing asset with the same name will be replaced.
+/// If the asset name contains path separators, the server will return an 'Unprocessable Entity'.
+/// The maximum size of the asset is determined by the `bytes` limit of the server.
+///
+/// # Arguments
+///
+/// * `doc_type`: the document type to store the asset for
+/// * `asset`: the filename of the asset
+/// * `content`: the content of the asset
+/// * `_secretary_role`: the secretary role guard
+/// * `client`: the client to access the WebDav server
+/// * `conf`: the application configuration
+///
+/// returns: Result<Json<DocumentUpload>, ApiError>
+#[openapi(tag = "Documents")]
+#[put("/<doc_type>/assets/<asset>", data = "<content>")]
+pub async fn put_asset(
+    doc_type: DocumentType,
+    asset: String,
+    content: Upload<'_>,
+    _secretary_role: ExecutiveRole<Secretary>,
+    client: &State<DocumentClient>,
+    conf: &State<Config>,
+) -> ApiResult<DocumentUpload> {
     let assets_path_str = doc_type.assets_location(&conf.document_server.mapping);
-    let asset_file = read_from_filesystem(asset, assets_path_str).await?;
-    Ok(asset_file)
+    write_file(
+        &asset,
+        assets_path_str,
+        content,
+        None,
+        client,
+        &conf.document_server,
+    )
+    .await
+    .map(Json)
+}
+
+/// Write a file either to the filesystem or to the WebDav server, depending on the [DocumentServer::access].
+/// The filename is sanitized before the file is written in either way.
+/// The content is passed on while it is received instead of being buffered in memory.
+///
+/// # Arguments
+///
+/// * `filename`: the filename below the directory to write
+/// * `directory`: the directory which should contain the file
+/// * `content`: the content of the file
+/// * `max_size`: the maximum size of the file in *bytes*, limited by the server only if `None`
+/// * `client`: the client to access the WebDav server
+/// * `server`: the configuration of the document server
+///
+/// returns: Result<DocumentUpload, ApiError>
+async fn write_file(
+    filename: &str,
+    directory: String,
+    content: Upload<'_>,
+    max_size: Option<u64>,
+    client: &DocumentClient,
+    server: &DocumentServer,
+) -> Result<DocumentUpload, ApiError> {
+    let filename = sanitize_name(filename, Status::UnprocessableEntity)?;
+    match server.access {
+        DocumentAccess::Local => {
+            let file_path = guarded_path(filename, &directory, false)?;
+            content.write_to(&file_path, max_size).await?;
+            info!("Stored the file {:?}", file_path);
+        }
+        DocumentAccess::WebDav => {
+            let (body, upload) = content.forward(max_size);
+            let (stored, uploaded) =
+                join(put_file(client, server, &directory, filename, body), upload).await;
+            uploaded?;
+            stored?;
+        }
+    }
+    Ok(DocumentUpload {
+        filename: filename.to_string(),
+    })
+}
+
+/// Read a file either from the filesystem or from the WebDav server, depending on the [DocumentServer::access].
+/// The filename is sanitized before it is accessed in either way.
+///
+/// # Arguments
+///
+/// * `filename`: the filename below the directory to request
+/// * `directory`: the directory which should contain the file
+/// * `max_size`: the maximum size of the file in *bytes*, unlimited if `None`
+/// * `client`: the client to access the WebDav server
+/// * `server`: the configuration of the document server
+///
+/// returns: Result<DocumentContent, ApiError>
+async fn read_file(
+    filename: &str,
+    directory: String,
+    max_size: Option<u64>,
+    client: &DocumentClient,
+    server: &DocumentServer,
+) -> Result<DocumentContent, ApiError> {
+    let filename = sanitize_name(filename, Status::NotFound)?;
+    match server.access {
+        DocumentAccess::Local => read_from_filesystem(filename, directory, max_size)
+            .await
+            .map(DocumentContent::File),
+        DocumentAccess::WebDav => fetch_file(client, server, &directory, filename, max_size).await,
+    }
+}
+
+/// Ensure that a requested filename refers to a file directly located in a directory.
+/// Names which are empty, refer to the current or parent directory or contain path separators are rejected.
+/// Reading such a file results in 'Not Found' in order to not provide further information to potential attackers.
+///
+/// # Arguments
+///
+/// * `filename`: the filename from the request
+/// * `status`: the status of the [ApiError] for invalid names
+///
+/// returns: Result<&str, ApiError> the unchanged filename if it is valid
+fn sanitize_name(filename: &str, status: Status) -> Result<&str, ApiError> {
+    if filename.is_empty()
+        || filename == "."
+        || filename == ".."
+        || filename.contains(['/', '\\', '\0'])
+    {
+        info!("Reject the invalid filename {:?}", filename);
+        if status == Status::NotFound {
+            return Err(not_found_error());
+        }
+        return Err(ApiError {
+            err: status.reason_lossy().to_string(),
+            msg: Some("The filename must not contain path separators".to_string()),
+            http_status_code: status.code,
+        });
+    }
+    Ok(filename)
+}
+
+/// List all files directly located at the configured directory of the document type sorted by their filename.
+/// The files are listed either from the filesystem or from the WebDav server, depending on the [DocumentServer::access].
+///
+/// # Arguments
+///
+/// * `doc_type`: the document type of all the listed documents
+/// * `client`: the client to access the WebDav server
+/// * `conf`: the application configuration
+/// * `limit`: the maximum number of listed files, unlimited if `None`
+///
+/// returns: Result<Vec<String>, ApiError>
+async fn list_files(
+    doc_type: &DocumentType,
+    client: &DocumentClient,
+    conf: &Config,
+    limit: Option<usize>,
+) -> Result<Vec<String>, ApiError> {
+    match conf.document_server.access {
+        DocumentAccess::Local => list_directory(doc_type, conf, limit),
+        DocumentAccess::WebDav => {
+            let location = doc_type.location(&conf.document_server.mapping);
+            list_collection(client, &conf.document_server, &location, limit).await
+        }
+    }
 }
 
 /// Read a file from the filesystem in the context of a web request.
@@ -109,29 +411,168 @@ pub async fn get_asset(
 /// * canonicalization to check whether the file is below the directory
 /// * ensure to access no other directories than the specified one
 /// * obfuscate error codes from the filesystem to not provide further information to potential attackers
+/// * reject files which exceed the maximum size before opening them
 ///
 ///
 /// # Arguments
 ///
 /// * `filename`: the filename below the directory to request
 /// * `directory`: the directory which should contain the file
+/// * `max_size`: the maximum size of the file in *bytes*, unlimited if `None`
 ///
 /// returns: Result<NamedFile, ApiError>
-async fn read_from_filesystem(filename: String, directory: String) -> Result<NamedFile, ApiError> {
+async fn read_from_filesystem(
+    filename: &str,
+    directory: String,
+    max_size: Option<u64>,
+) -> Result<NamedFile, ApiError> {
+    let file_path = guarded_path(filename, &directory, true)?;
+    if let Some(max_size) = max_size {
+        let size = map_io_err(file_path.metadata(), Status::NotFound)?.len();
+        if size > max_size {
+            info!(
+                "Refuse to serve {:?} with {} bytes which exceeds the limit of {} bytes",
+                file_path, size, max_size
+            );
+            return Err(too_large_error(max_size));
+        }
+    }
+    map_io_err(NamedFile::open(file_path).await, Status::NotFound)
+}
+
+/// Resolve the path of a file below a directory on the filesystem.
+/// The path is canonicalized to ensure the file is located below the directory, also if it is a symlink.
+///
+/// # Arguments
+///
+/// * `filename`: the filename below the directory
+/// * `directory`: the directory which should contain the file
+/// * `existing`: whether the file must already exist, otherwise it may be created at the returned path
+///
+/// returns: Result<PathBuf, ApiError>
+fn guarded_path(filename: &str, directory: &str, existing: bool) -> Result<PathBuf, ApiError> {
     let directory_path = map_io_err(
-        Path::new(&directory).canonicalize(),
+        Path::new(directory).canonicalize(),
         Status::InternalServerError,
     )?;
-    let file_path = map_io_err(
-        directory_path.join(filename).canonicalize(),
-        Status::NotFound,
+    let joined_path = directory_path.join(filename);
+    let file_path = if existing || joined_path.symlink_metadata().is_ok() {
+        map_io_err(joined_path.canonicalize(), Status::NotFound)?
+    } else {
+        joined_path
+    };
+    if !file_path.as_path().starts_with(directory_path) || file_path.is_dir() {
+        return Err(not_found_error());
+    }
+    Ok(file_path)
+}
+
+/// Returns an [ApiError] indicating that a document is not sent in the markdown format.
+fn unsupported_media_type_error() -> ApiError {
+    ApiError {
+        err: "Unsupported Media Type".to_string(),
+        msg: Some("Documents have to be sent as text/markdown".to_string()),
+        http_status_code: Status::UnsupportedMediaType.code,
+    }
+}
+
+/// Returns an [ApiError] indicating that the requested file does not exist or must not be accessed.
+pub(super) fn not_found_error() -> ApiError {
+    ApiError {
+        err: "Not Found".to_string(),
+        msg: Some("File or directory not found".to_string()),
+        http_status_code: Status::NotFound.code,
+    }
+}
+
+/// List all files directly located at the configured directory of the document type sorted by their filename.
+/// Entries which do not resolve to a location below the directory are skipped.
+///
+/// # Arguments
+///
+/// * `doc_type`: the document type of all the listed documents
+/// * `conf`: the application configuration
+/// * `limit`: the maximum number of listed files, unlimited if `None`
+///
+/// returns: Result<Vec<String>, ApiError>
+fn list_directory(
+    doc_type: &DocumentType,
+    conf: &Config,
+    limit: Option<usize>,
+) -> Result<Vec<String>, ApiError> {
+    let doc_type_path_str = doc_type.location(&conf.document_server.mapping);
+    let doc_type_path = map_io_err(
+        Path::new(&doc_type_path_str).canonicalize(),
+        Status::InternalServerError,
     )?;
-    if !file_path.as_path().starts_with(directory_path) {
-        return Err(ApiError {
-            err: "Not Found".to_string(),
-            msg: Some("File or directory not found".to_string()),
-            http_status_code: Status::NotFound.code,
-        });
+    let read_dir = map_io_err(doc_type_path.read_dir(), Status::InternalServerError)?;
+    let files = read_dir.flatten().filter(|f| {
+        f.path()
+            .canonicalize()
+            .is_ok_and(|p| p.starts_with(&doc_type_path) && p.is_file())
+    });
+    let mut files_names: Vec<String> = files
+        .flat_map(|f| f.file_name().to_str().map(ToString::to_string))
+        .collect();
+    files_names.sort();
+    if let Some(limit) = limit {
+        files_names.truncate(limit);
     }
-    map_io_err(NamedFile::open(file_path).await, Status::NotFound)
+    Ok(files_names)
+}
+
+/// Recursively collect all files below a directory.
+/// The file paths are collected relative to the `root` directory.
+/// Entries which do not resolve to a location below the `root` are skipped.
+///
+/// # Arguments
+///
+/// * `root`: the canonicalized directory where the traversal started
+/// * `directory`: the directory to collect the files from
+/// * `depth`: the depth of the `directory` relative to the `root`
+/// * `document_server`: the configuration which contains the traversal limits
+/// * `files`: the collected file paths
+///
+/// returns: Result<bool, Error> whether the traversal was truncated due to the limits
+fn walk_directory(
+    root: &Path,
+    directory: &Path,
+    depth: usize,
+    document_server: &DocumentServer,
+    files: &mut Vec<String>,
+) -> io::Result<bool> {
+    let mut truncated = false;
+    for entry in directory.read_dir()?.flatten() {
+        let entry_path = entry.path();
+        let resolved_path = match entry_path.canonicalize() {
+            Ok(path) if path.starts_with(root) => path,
+            _ => {
+                debug!(
+                    "Skip {:?} which is not located below {:?}",
+                    entry_path, root
+                );
+                continue;
+            }
+        };
+        if resolved_path.is_file() {
+            if files.len() >= document_server.max_listing_entries {
+                return Ok(true);
+            }
+            if let Some(relative) = entry_path.strip_prefix(root).ok().and_then(|p| p.to_str()) {
+                files.push(relative.to_string());
+            }
+        } else if resolved_path.is_dir() {
+            if depth >= document_server.max_listing_depth {
+                truncated = true;
+                continue;
+            }
+            if walk_directory(root, &entry_path, depth + 1, document_server, files)? {
+                truncated = true;
+                if files.len() >= document_server.max_listing_entries {
+                    return Ok(true);
+                }
+            }
+        }
+    }
+    Ok(truncated)
 }
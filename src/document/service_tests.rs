@@ -0,0 +1,62 @@
+// OpenKeg, the lightweight backend of the Musikverein Leopoldsdorf.
+// Copyright (C) 2023  Richard Stöckl
+//
+// This program is free software; you can redistribute it and/or
+// modify it under the terms of the GNU General Public License
+// as published by the Free Software Foundation; either version 2
+// of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+
+use super::*;
+
+#[test]
+fn valid_names() {
+    assert_eq!(
+        sanitize_name("2023-01-01-neujahr.md", Status::NotFound).unwrap(),
+        "2023-01-01-neujahr.md"
+    );
+    assert_eq!(
+        sanitize_name("..hidden", Status::NotFound).unwrap(),
+        "..hidden"
+    );
+}
+
+#[test]
+fn traversal_names() {
+    for name in [
+        "",
+        ".",
+        "..",
+        "../keg.toml",
+        "Bilder/../../etc",
+        "..\\keg.toml",
+        "a\0b",
+    ] {
+        let error = sanitize_name(name, Status::NotFound).unwrap_err();
+        assert_eq!(error.http_status_code, Status::NotFound.code);
+        let error = sanitize_name(name, Status::UnprocessableEntity).unwrap_err();
+        assert_eq!(error.http_status_code, Status::UnprocessableEntity.code);
+    }
+}
+
+#[test]
+fn guarded_paths() {
+    let directory = std::env::temp_dir().join(format!("keg-documents-{}", std::process::id()));
+    std::fs::create_dir_all(directory.join("archiv")).unwrap();
+    let directory_str = directory.to_str().unwrap();
+    let path = guarded_path("neu.md", directory_str, false).unwrap();
+    assert!(path.starts_with(directory.canonicalize().unwrap()));
+    let error = guarded_path("neu.md", directory_str, true).unwrap_err();
+    assert_eq!(error.http_status_code, Status::NotFound.code);
+    let error = guarded_path("archiv", directory_str, false).unwrap_err();
+    assert_eq!(error.http_status_code, Status::NotFound.code);
+    std::fs::remove_dir_all(directory).unwrap();
+}
@@ -0,0 +1,428 @@
+// OpenKeg, the lightweight backend of the Musikverein Leopoldsdorf.
+// Copyright (C) 2023  Richard Stöckl
+//
+// This program is free software; you can redistribute it and/or
+// modify it under the terms of the GNU General Public License
+// as published by the Free Software Foundation; either version 2
+// of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+
+use std::collections::VecDeque;
+use std::future::ready;
+
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, CONTROLS};
+use reqwest::header::{HeaderValue, CONTENT_TYPE};
+use reqwest::{Body, Client, Method, RequestBuilder, Response, StatusCode, Url};
+use rocket::futures::{stream, StreamExt};
+use rocket::http::{ContentType, Status};
+use rocket::response::stream::ByteStream;
+
+use crate::config::DocumentServer;
+use crate::document::model::{DocumentContent, RemoteStream};
+use crate::document::service::not_found_error;
+use crate::keg_user_agent;
+use crate::openapi::{too_large_error, ApiError};
+
+#[cfg(test)]
+#[path = "webdav_tests.rs"]
+mod webdav_tests;
+
+/// The HTTP client to access the WebDav server.
+///
+/// A distinct type from the client of the database, since the rocket state only manages a single value per type.
+pub struct DocumentClient(Client);
+
+/// The characters which are encoded in a segment of a path.
+const PATH_SEGMENT: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'/')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
+
+/// The body of a `PROPFIND` request which only asks for the type of the resources.
+const PROPFIND_BODY: &str = r#"<?xml version="1.0" encoding="utf-8"?><d:propfind xmlns:d="DAV:"><d:prop><d:resourcetype/></d:prop></d:propfind>"#;
+
+/// The namespace of all WebDav elements.
+const DAV_NAMESPACE: &str = "DAV:";
+
+/// Create the client to access the WebDav server.
+///
+/// returns: DocumentClient
+pub fn create_client() -> DocumentClient {
+    DocumentClient(
+        Client::builder()
+            .user_agent(keg_user_agent())
+            .build()
+            .expect("document client"),
+    )
+}
+
+/// List the files directly located in a directory of the WebDav server sorted by their filename.
+///
+/// # Arguments
+///
+/// * `client`: the client to access the WebDav server
+/// * `server`: the configuration of the document server
+/// * `location`: the directory relative to the [DocumentServer::base_url]
+/// * `limit`: the maximum number of listed files, unlimited if `None`
+///
+/// returns: Result<Vec<String>, ApiError>
+pub async fn list_collection(
+    client: &DocumentClient,
+    server: &DocumentServer,
+    location: &str,
+    limit: Option<usize>,
+) -> Result<Vec<String>, ApiError> {
+    let mut files: Vec<String> = propfind(client, server, location, "")
+        .await?
+        .into_iter()
+        .filter(|(_, collection)| !collection)
+        .map(|(name, _)| name)
+        .collect();
+    files.sort();
+    if let Some(limit) = limit {
+        files.truncate(limit);
+    }
+    Ok(files)
+}
+
+/// List all files of a directory of the WebDav server including the ones in subdirectories.
+/// The paths are relative to the directory, the traversal is limited like for the local filesystem.
+///
+/// # Arguments
+///
+/// * `client`: the client to access the WebDav server
+/// * `server`: the configuration of the document server which contains the traversal limits
+/// * `location`: the directory relative to the [DocumentServer::base_url]
+///
+/// returns: Result<(Vec<String>, bool), ApiError> the file paths and whether the traversal was truncated
+pub async fn walk_collection(
+    client: &DocumentClient,
+    server: &DocumentServer,
+    location: &str,
+) -> Result<(Vec<String>, bool), ApiError> {
+    let mut files = vec![];
+    let mut truncated = false;
+    let mut pending = VecDeque::from([(String::new(), 0)]);
+    while let Some((directory, depth)) = pending.pop_front() {
+        for (name, collection) in propfind(client, server, location, &directory).await? {
+            let path = if directory.is_empty() {
+                name
+            } else {
+                format!("{}/{}", directory, name)
+            };
+            if !collection {
+                if files.len() >= server.max_listing_entries {
+                    return Ok((files, true));
+                }
+                files.push(path);
+            } else if depth >= server.max_listing_depth {
+                truncated = true;
+            } else {
+                pending.push_back((path, depth + 1));
+            }
+        }
+    }
+    Ok((files, truncated))
+}
+
+/// Request a file from the WebDav server and stream it back.
+/// Files with a maximum size are read completely in order to reject them if they exceed the size.
+///
+/// # Arguments
+///
+/// * `client`: the client to access the WebDav server
+/// * `server`: the configuration of the document server
+/// * `location`: the directory relative to the [DocumentServer::base_url]
+/// * `filename`: the sanitized name of the file below the directory
+/// * `max_size`: the maximum size of the file in *bytes*, unlimited if `None`
+///
+/// returns: Result<DocumentContent, ApiError>
+pub async fn fetch_file(
+    client: &DocumentClient,
+    server: &DocumentServer,
+    location: &str,
+    filename: &str,
+    max_size: Option<u64>,
+) -> Result<DocumentContent, ApiError> {
+    let url = resource_url(server, location, filename, false);
+    let mut response = send(authenticated(client.0.get(&url), server), &url).await?;
+    let content_type = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(ContentType::parse_flexible)
+        .or_else(|| {
+            filename
+                .rsplit_once('.')
+                .and_then(|(_, extension)| ContentType::from_extension(extension))
+        })
+        .unwrap_or(ContentType::Binary);
+    let content: RemoteStream = match max_size {
+        Some(max_size) => {
+            if response
+                .content_length()
+                .is_some_and(|size| size > max_size)
+            {
+                return Err(too_large_error(max_size));
+            }
+            let mut body = vec![];
+            while let Some(chunk) = response.chunk().await.map_err(|e| {
+                warn!(
+                    "Unable to read the file {} from the WebDav server: {}",
+                    url, e
+                );
+                upstream_error()
+            })? {
+                body.extend_from_slice(&chunk);
+                if body.len() as u64 > max_size {
+                    return Err(too_large_error(max_size));
+                }
+            }
+            Box::pin(stream::once(ready(body)))
+        }
+        None => Box::pin(response.bytes_stream().filter_map(move |chunk| {
+            ready(
+                chunk
+                    .map_err(|e| {
+                        warn!(
+                            "Unable to stream the file {} from the WebDav server: {}",
+                            url, e
+                        )
+                    })
+                    .ok()
+                    .map(|chunk| chunk.to_vec()),
+            )
+        })),
+    };
+    Ok(DocumentContent::Remote(ByteStream(content), content_type))
+}
+
+/// Store a file on the WebDav server, an existing file will be replaced.
+///
+/// # Arguments
+///
+/// * `client`: the client to access the WebDav server
+/// * `server`: the configuration of the document server
+/// * `location`: the directory relative to the [DocumentServer::base_url]
+/// * `filename`: the sanitized name of the file below the directory
+/// * `content`: the body which streams the content of the file
+///
+/// returns: Result<(), ApiError>
+pub async fn put_file(
+    client: &DocumentClient,
+    server: &DocumentServer,
+    location: &str,
+    filename: &str,
+    content: Body,
+) -> Result<(), ApiError> {
+    let url = resource_url(server, location, filename, false);
+    send(
+        authenticated(client.0.put(&url).body(content), server),
+        &url,
+    )
+    .await?;
+    info!("Stored the file {} on the WebDav server", url);
+    Ok(())
+}
+
+/// List the entries directly located in a directory of the WebDav server.
+///
+/// # Arguments
+///
+/// * `client`: the client to access the WebDav server
+/// * `server`: the configuration of the document server
+/// * `location`: the directory relative to the [DocumentServer::base_url]
+/// * `directory`: the subdirectory of the `location` to list
+///
+/// returns: Result<Vec<(String, bool)>, ApiError> the names of the entries and whether they are directories
+async fn propfind(
+    client: &DocumentClient,
+    server: &DocumentServer,
+    location: &str,
+    directory: &str,
+) -> Result<Vec<(String, bool)>, ApiError> {
+    let url = resource_url(server, location, directory, true);
+    let collection_path = Url::parse(&url)
+        .map(|url| url.path().to_string())
+        .map_err(|e| {
+            error!("The url {} of the WebDav server is invalid: {}", url, e);
+            ApiError {
+                err: "Internal Server Error".to_string(),
+                msg: Some("The document server is not configured properly".to_string()),
+                http_status_code: Status::InternalServerError.code,
+            }
+        })?;
+    let request = client
+        .0
+        .request(
+            Method::from_bytes(b"PROPFIND").expect("PROPFIND method"),
+            &url,
+        )
+        .header("Depth", HeaderValue::from_static("1"))
+        .header(CONTENT_TYPE, HeaderValue::from_static("application/xml"))
+        .body(PROPFIND_BODY);
+    let response = send(authenticated(request, server), &url).await?;
+    let body = response.text().await.map_err(|e| {
+        warn!(
+            "Unable to read the listing {} from the WebDav server: {}",
+            url, e
+        );
+        upstream_error()
+    })?;
+    parse_multistatus(&body, &collection_path).map_err(|e| {
+        warn!(
+            "Unable to parse the listing {} from the WebDav server: {}",
+            url, e
+        );
+        upstream_error()
+    })
+}
+
+/// Parse the multistatus response of a `PROPFIND` request with depth `1`.
+/// Entries which are not located directly in the requested directory, such as the directory itself, are skipped.
+///
+/// # Arguments
+///
+/// * `body`: the XML body of the response
+/// * `collection_path`: the percent-encoded path of the requested directory
+///
+/// returns: Result<Vec<(String, bool)>, Error> the decoded names of the entries and whether they are directories
+fn parse_multistatus(
+    body: &str,
+    collection_path: &str,
+) -> Result<Vec<(String, bool)>, roxmltree::Error> {
+    let document = roxmltree::Document::parse(body)?;
+    let collection_path = decode(collection_path);
+    let collection_path = collection_path.trim_end_matches('/');
+    let entries = document
+        .descendants()
+        .filter(|node| node.has_tag_name((DAV_NAMESPACE, "response")))
+        .filter_map(|response| {
+            let href = response
+                .children()
+                .find(|node| node.has_tag_name((DAV_NAMESPACE, "href")))?
+                .text()?
+                .trim();
+            let path = Url::parse(href)
+                .map(|url| url.path().to_string())
+                .unwrap_or_else(|_| href.to_string());
+            let path = decode(&path);
+            let name = path
+                .strip_prefix(collection_path)?
+                .strip_prefix('/')?
+                .trim_end_matches('/');
+            if name.is_empty() || name.contains('/') {
+                return None;
+            }
+            let collection = response
+                .descendants()
+                .any(|node| node.has_tag_name((DAV_NAMESPACE, "collection")));
+            Some((name.to_string(), collection))
+        })
+        .collect();
+    Ok(entries)
+}
+
+/// Build the url to a file or directory on the WebDav server.
+/// Each segment of the `location` and the `path` is percent-encoded.
+///
+/// # Arguments
+///
+/// * `server`: the configuration of the document server which contains the [DocumentServer::base_url]
+/// * `location`: the directory relative to the [DocumentServer::base_url]
+/// * `path`: the path relative to the `location`
+/// * `collection`: whether the url refers to a directory and should end with a slash
+///
+/// returns: String
+fn resource_url(server: &DocumentServer, location: &str, path: &str, collection: bool) -> String {
+    let segments: Vec<String> = location
+        .split('/')
+        .chain(path.split('/'))
+        .filter(|segment| !segment.is_empty())
+        .map(|segment| utf8_percent_encode(segment, PATH_SEGMENT).to_string())
+        .collect();
+    let mut url = format!(
+        "{}/{}",
+        server.base_url.trim_end_matches('/'),
+        segments.join("/")
+    );
+    if collection {
+        url.push('/');
+    }
+    url
+}
+
+/// Add the credentials of the [DocumentServer] to a request if there are any.
+///
+/// # Arguments
+///
+/// * `request`: the request to authenticate
+/// * `server`: the configuration which contains the credentials
+///
+/// returns: RequestBuilder
+fn authenticated(request: RequestBuilder, server: &DocumentServer) -> RequestBuilder {
+    match &server.username {
+        Some(username) => request.basic_auth(username, server.password.as_ref()),
+        None => request,
+    }
+}
+
+/// Send a request to the WebDav server.
+/// A missing file results in an [ApiError] with 'Not Found', all other failures in 'Bad Gateway'.
+///
+/// # Arguments
+///
+/// * `request`: the request to send
+/// * `url`: the url of the request for logging
+///
+/// returns: Result<Response, ApiError>
+async fn send(request: RequestBuilder, url: &str) -> Result<Response, ApiError> {
+    let response = request.send().await.map_err(|e| {
+        warn!("Unable to request {} from the WebDav server: {}", url, e);
+        upstream_error()
+    })?;
+    match response.status() {
+        status if status.is_success() => Ok(response),
+        StatusCode::NOT_FOUND => Err(not_found_error()),
+        status => {
+            warn!("The WebDav server responded to {} with {}", url, status);
+            Err(upstream_error())
+        }
+    }
+}
+
+/// Decode a percent-encoded path.
+///
+/// # Arguments
+///
+/// * `path`: the path to decode
+///
+/// returns: String
+fn decode(path: &str) -> String {
+    percent_decode_str(path).decode_utf8_lossy().to_string()
+}
+
+/// Returns an [ApiError] indicating that the WebDav server is not available or responded unexpectedly.
+fn upstream_error() -> ApiError {
+    ApiError {
+        err: "Bad Gateway".to_string(),
+        msg: Some("Unable to retrieve the documents from the document server".to_string()),
+        http_status_code: Status::BadGateway.code,
+    }
+}
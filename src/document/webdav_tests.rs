@@ -0,0 +1,74 @@
+// OpenKeg, the lightweight backend of the Musikverein Leopoldsdorf.
+// Copyright (C) 2023  Richard Stöckl
+//
+// This program is free software; you can redistribute it and/or
+// modify it under the terms of the GNU General Public License
+// as published by the Free Software Foundation; either version 2
+// of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+
+use super::*;
+
+const MULTISTATUS: &str = r#"<?xml version="1.0"?>
+<d:multistatus xmlns:d="DAV:" xmlns:oc="http://owncloud.org/ns">
+  <d:response>
+    <d:href>/remote.php/dav/files/keg/blackboard/</d:href>
+    <d:propstat><d:prop><d:resourcetype><d:collection/></d:resourcetype></d:prop></d:propstat>
+  </d:response>
+  <d:response>
+    <d:href>/remote.php/dav/files/keg/blackboard/2023-01-01%20neujahr.md</d:href>
+    <d:propstat><d:prop><d:resourcetype/></d:prop></d:propstat>
+  </d:response>
+  <d:response>
+    <d:href>https://cloud.example.org/remote.php/dav/files/keg/blackboard/Bilder/</d:href>
+    <d:propstat><d:prop><d:resourcetype><d:collection/></d:resourcetype></d:prop></d:propstat>
+  </d:response>
+  <d:response>
+    <d:href>/remote.php/dav/files/keg/private/secret.md</d:href>
+    <d:propstat><d:prop><d:resourcetype/></d:prop></d:propstat>
+  </d:response>
+</d:multistatus>"#;
+
+fn server() -> DocumentServer {
+    DocumentServer {
+        base_url: "https://cloud.example.org/remote.php/dav/files/keg/".to_string(),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn multistatus_entries() {
+    let entries = parse_multistatus(MULTISTATUS, "/remote.php/dav/files/keg/blackboard/").unwrap();
+    assert_eq!(
+        entries,
+        vec![
+            ("2023-01-01 neujahr.md".to_string(), false),
+            ("Bilder".to_string(), true)
+        ]
+    );
+}
+
+#[test]
+fn invalid_multistatus() {
+    assert!(parse_multistatus("<d:multistatus", "/").is_err());
+}
+
+#[test]
+fn encoded_resource_url() {
+    assert_eq!(
+        resource_url(&server(), "blackboard/Bilder", "Turm blasen#1.jpg", false),
+        "https://cloud.example.org/remote.php/dav/files/keg/blackboard/Bilder/Turm%20blasen%231.jpg"
+    );
+    assert_eq!(
+        resource_url(&server(), "/blackboard/", "", true),
+        "https://cloud.example.org/remote.php/dav/files/keg/blackboard/"
+    );
+}
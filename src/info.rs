@@ -15,16 +15,23 @@
 // along with this program; if not, write to the Free Software
 // Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
 
-use crate::keg_user_agent;
+use crate::database::client::DatabaseClient;
+use crate::ldap::pool::LdapConnectionManager;
 use crate::openapi::{ApiResult, SchemaExample};
+use crate::{keg_user_agent, Config, MemberStateMutex};
 use chrono::Local;
 use okapi::openapi3::OpenApi;
 use okapi::schemars::JsonSchema;
+use rocket::http::Status;
+use rocket::response::status::Custom;
 use rocket::serde::json::Json;
+use rocket::tokio::time::timeout;
 use rocket::State;
 use rocket_okapi::settings::OpenApiSettings;
 use rocket_okapi::{openapi, openapi_get_routes_spec};
 use serde::{Deserialize, Serialize};
+use std::env;
+use std::time::{Duration, Instant};
 
 /// A structure to provide basic information about the server.
 /// This is intended to determine if the server is up or not.
@@ -36,6 +43,10 @@ pub struct ServerInfo {
     version: String,
     /// Whether this server is run in debug mode or not.
     debug: bool,
+    /// The monotonic instant when this instance was started at, used to compute the uptime.
+    #[serde(skip, default = "Instant::now")]
+    #[schemars(skip)]
+    started: Instant,
 }
 
 impl ServerInfo {
@@ -46,8 +57,16 @@ impl ServerInfo {
             version: keg_user_agent(),
             start: Local::now().to_rfc3339(),
             debug: cfg!(feature = "debug"),
+            started: Instant::now(),
         }
     }
+
+    /// Create the build information of this instance with the current uptime.
+    ///
+    /// returns: BuildInfo
+    pub fn build_info(&self) -> BuildInfo {
+        BuildInfo::new(self.started.elapsed().as_secs())
+    }
 }
 
 impl SchemaExample for ServerInfo {
@@ -56,6 +75,101 @@ impl SchemaExample for ServerInfo {
             start: Local::now().to_rfc3339(),
             version: keg_user_agent(),
             debug: false,
+            started: Instant::now(),
+        }
+    }
+}
+
+/// The information about the build of the server software.
+/// The commit is compiled in from the `KEG_GIT_SHA` environment variable if it was set during the build.
+#[derive(Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+#[schemars(example = "Self::example")]
+pub struct BuildInfo {
+    /// The name of the server software.
+    name: String,
+    /// The version of the server software.
+    version: String,
+    /// The operating system the server was built for.
+    os: String,
+    /// The architecture the server was built for.
+    arch: String,
+    /// The number of seconds since the server was started.
+    uptime_seconds: u64,
+    /// The commit the server was built from, if known.
+    git_commit: Option<String>,
+}
+
+impl BuildInfo {
+    /// Create the build information of this server software.
+    ///
+    /// # Arguments
+    ///
+    /// * `uptime_seconds`: the number of seconds since the server was started
+    ///
+    /// returns: BuildInfo
+    fn new(uptime_seconds: u64) -> Self {
+        Self {
+            name: env!("CARGO_PKG_NAME").to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            os: env::consts::OS.to_string(),
+            arch: env::consts::ARCH.to_string(),
+            uptime_seconds,
+            git_commit: option_env!("KEG_GIT_SHA").map(|sha| sha.to_string()),
+        }
+    }
+}
+
+impl SchemaExample for BuildInfo {
+    fn example() -> Self {
+        Self::new(3600)
+    }
+}
+
+/// The health of the subsystems the server depends on.
+#[derive(Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+#[schemars(example = "Self::example")]
+pub struct Health {
+    /// The status of the connection to the database.
+    database: HealthStatus,
+    /// The status of the connection to the directory server.
+    ldap: HealthStatus,
+    /// The RFC3339 compliant date time of the last successful member synchronization.
+    last_member_sync: Option<String>,
+}
+
+impl Health {
+    /// Whether all subsystems are healthy.
+    fn healthy(&self) -> bool {
+        self.database == HealthStatus::Ok && self.ldap == HealthStatus::Ok
+    }
+}
+
+impl SchemaExample for Health {
+    fn example() -> Self {
+        Self {
+            database: HealthStatus::Ok,
+            ldap: HealthStatus::Ok,
+            last_member_sync: Some(Local::now().to_rfc3339()),
+        }
+    }
+}
+
+/// The status of a single subsystem.
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthStatus {
+    Ok,
+    Fail,
+}
+
+impl From<bool> for HealthStatus {
+    fn from(healthy: bool) -> Self {
+        if healthy {
+            HealthStatus::Ok
+        } else {
+            HealthStatus::Fail
         }
     }
 }
@@ -73,6 +187,69 @@ pub fn info(info_state: &State<ServerInfo>) -> ApiResult<ServerInfo> {
     Ok(Json((*info_state).clone()))
 }
 
+/// Return the information about the build of this server and its uptime.
+/// This is intended for an about page and does not require authentication.
+///
+/// # Arguments
+///
+/// * `info_state`: the state of the server
+///
+/// returns: Result<Json<BuildInfo>, Error>
+#[openapi(tag = "Misc")]
+#[get("/info")]
+pub fn build_info(info_state: &State<ServerInfo>) -> ApiResult<BuildInfo> {
+    Ok(Json(info_state.build_info()))
+}
+
+/// Check whether the database and the directory server are reachable.
+/// Each check is limited by [crate::config::HealthConfig::timeout], the server responds with 'Service Unavailable' if any of them fails.
+/// This is intended to be used as readiness probe.
+///
+/// # Arguments
+///
+/// * `conf`: the application configuration
+/// * `client`: the client to access the database
+/// * `ldap`: the connection manager of the directory server
+/// * `member_state`: the member state which contains the last synchronization
+///
+/// returns: Custom<Json<Health>>
+#[openapi(tag = "Misc")]
+#[get("/health")]
+pub async fn health(
+    conf: &State<Config>,
+    client: &State<DatabaseClient>,
+    ldap: &State<LdapConnectionManager>,
+    member_state: &State<MemberStateMutex>,
+) -> Custom<Json<Health>> {
+    let check_timeout = Duration::from_secs(conf.health.timeout);
+    let database_check = client
+        .get(format!("{}/_up", conf.database.url.trim_end_matches('/')))
+        .timeout(check_timeout)
+        .send();
+    let database = matches!(database_check.await, Ok(response) if response.status().is_success());
+    let ldap = matches!(timeout(check_timeout, ldap.check(conf)).await, Ok(true));
+    let last_member_sync = member_state
+        .read()
+        .await
+        .last_sync
+        .map(|time| time.to_rfc3339());
+    let health = Health {
+        database: database.into(),
+        ldap: ldap.into(),
+        last_member_sync,
+    };
+    let status = if health.healthy() {
+        Status::Ok
+    } else {
+        warn!(
+            "The health check failed, database: {:?}, ldap: {:?}",
+            health.database, health.ldap
+        );
+        Status::ServiceUnavailable
+    };
+    Custom(status, Json(health))
+}
+
 /// Generate the OpenApi documentation and routes for the info endpoint.
 ///
 /// # Arguments
@@ -81,5 +258,5 @@ pub fn info(info_state: &State<ServerInfo>) -> ApiResult<ServerInfo> {
 ///
 /// returns: (Vec<Route, Global>, OpenApi)
 pub fn get_info_routes_and_docs(settings: &OpenApiSettings) -> (Vec<rocket::Route>, OpenApi) {
-    openapi_get_routes_spec![settings: info,]
+    openapi_get_routes_spec![settings: info, build_info, health]
 }
@@ -19,9 +19,10 @@ use std::fmt::Debug;
 use std::fmt::Display;
 use std::fmt::Formatter;
 
-use ldap3::LdapConnAsync;
+use ldap3::Scope;
 
 use crate::config::Config;
+use crate::ldap::{connect, fill_filter_template};
 use crate::member::model::Member;
 use crate::member::state::Repository;
 use crate::MemberStateMutex;
@@ -94,12 +95,10 @@ pub async fn authenticate<'a>(
     let dn = &member.full_username;
     let ldap_config = &config.ldap;
     info!("Bind to auth server: {}", ldap_config.server);
-    let (conn, mut ldap) = LdapConnAsync::new(&*ldap_config.server)
-        .await
-        .map_err(|e| {
-            error!("Failed to open the auth session: {:#?}", e);
-            AuthenticationError::Session
-        })?;
+    let (conn, mut ldap) = connect(ldap_config).await.map_err(|e| {
+        error!("Failed to open the auth session: {:#?}", e);
+        AuthenticationError::Session
+    })?;
     ldap3::drive!(conn);
     let ldap_result = ldap.simple_bind(dn, password).await.map_err(|e| {
         warn!("Failed to bind to the ldap server: {:#?}", e);
@@ -108,5 +107,20 @@ pub async fn authenticate<'a>(
     ldap_result
         .success()
         .map_err(|_| AuthenticationError::Credentials(username))?;
+    if let Some(template) = &ldap_config.auth_filter {
+        let filter = fill_filter_template(template, username);
+        let (entries, _) = ldap
+            .search(dn, Scope::Base, &filter, vec!["1.1"])
+            .await
+            .and_then(|r| r.success())
+            .map_err(|e| {
+                warn!("Failed to apply the auth filter: {:#?}", e);
+                AuthenticationError::Credentials(username)
+            })?;
+        if entries.is_empty() {
+            info!("Member '{}' does not match the auth filter", username);
+            return Err(AuthenticationError::Credentials(username));
+        }
+    }
     Ok(member.clone())
 }
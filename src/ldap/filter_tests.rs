@@ -0,0 +1,75 @@
+// OpenKeg, the lightweight backend of the Musikverein Leopoldsdorf.
+// Copyright (C) 2022  Richard Stöckl
+//
+// This program is free software; you can redistribute it and/or
+// modify it under the terms of the GNU General Public License
+// as published by the Free Software Foundation; either version 2
+// of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+
+use super::*;
+
+#[test]
+fn normalize_dn_spacing_and_case() {
+    assert_eq!(
+        normalize_dn("UID=karl, OU=Members ,dc=mvl,  DC=at"),
+        "uid=karl,ou=Members,dc=mvl,dc=at"
+    );
+    assert_eq!(normalize_dn("cn=Karl+SN=Huber"), "cn=Karl+sn=Huber");
+}
+
+#[test]
+fn normalize_dn_escapes() {
+    assert_eq!(
+        normalize_dn("CN=Huber\\, Karl, ou=members"),
+        "cn=Huber\\, Karl,ou=members"
+    );
+    assert_eq!(
+        normalize_dn("cn=Karl\\ ,ou=members"),
+        "cn=Karl\\ ,ou=members"
+    );
+}
+
+#[test]
+fn escape_plain() {
+    assert_eq!(escape_ldap_filter_value("willi"), "willi");
+    assert_eq!(escape_ldap_filter_value("Stöckl"), "Stöckl");
+}
+
+#[test]
+fn escape_asterisk() {
+    assert_eq!(escape_ldap_filter_value("*"), "\\2a");
+    assert_eq!(escape_ldap_filter_value("wil*li"), "wil\\2ali");
+}
+
+#[test]
+fn escape_parentheses() {
+    assert_eq!(escape_ldap_filter_value("(willi)"), "\\28willi\\29");
+}
+
+#[test]
+fn escape_backslash() {
+    assert_eq!(escape_ldap_filter_value("wil\\li"), "wil\\5cli");
+    assert_eq!(escape_ldap_filter_value("\\2a"), "\\5c2a");
+}
+
+#[test]
+fn escape_nul() {
+    assert_eq!(escape_ldap_filter_value("wil\0li"), "wil\\00li");
+}
+
+#[test]
+fn template_injection() {
+    assert_eq!(
+        fill_filter_template("(&(objectClass=mvlMember)(uid={username}))", "*)(uid=*"),
+        "(&(objectClass=mvlMember)(uid=\\2a\\29\\28uid=\\2a))"
+    );
+}
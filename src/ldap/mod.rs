@@ -1,5 +1,14 @@
-use ldap3::{Ldap, LdapConnAsync, LdapError, Scope, SearchEntry};
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::sync::Arc;
 
+use ldap3::controls::{ControlType, PagedResults};
+use ldap3::{Ldap, LdapConnAsync, LdapConnSettings, LdapError, ResultEntry, Scope, SearchEntry};
+use rustls::{ClientConfig, RootCertStore};
+
+use crate::config::{AttributeCandidates, LdapConfig};
+
+use crate::ldap::pool::LdapConnectionManager;
 use crate::Config;
 
 // OpenKeg, the lightweight backend of the Musikverein Leopoldsdorf.
@@ -19,8 +28,14 @@ use crate::Config;
 // along with this program; if not, write to the Free Software
 // Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
 
+#[cfg(test)]
+#[path = "filter_tests.rs"]
+mod filter_tests;
+
 /// Module which provides authentication functionality to the directory server.
 pub mod auth;
+/// Module which keeps authenticated connections to the directory server for reuse.
+pub mod pool;
 /// Module which handles the member and group synchronization task.
 pub mod sync;
 
@@ -35,17 +50,99 @@ pub trait LdapDeserializable<T> {
     fn from_search_entry(entry: &SearchEntry, config: &Config) -> T;
 }
 
+/// The directory server which provides the entries of the members and groups.
+/// The synchronization only depends on this trait which allows to replace the directory server, e.g. in tests.
+#[rocket::async_trait]
+pub trait Directory: Sync {
+    /// Search for all entries below the base which match the filter.
+    ///
+    /// # Arguments
+    ///
+    /// * `base` : the base dn to search for
+    /// * `filter` : the auth filter used for the search
+    /// * `attributes` : the attributes to request, `*` for all user attributes
+    /// * `config` : the application configuration
+    ///
+    /// returns: Result<Vec<SearchEntry>, LdapError>
+    async fn search(
+        &self,
+        base: &str,
+        filter: &str,
+        attributes: &[String],
+        config: &Config,
+    ) -> Result<Vec<SearchEntry>, LdapError>;
+
+    /// Look up a single entry by its distinguished name.
+    ///
+    /// # Arguments
+    ///
+    /// * `dn` : the distinguished name of the entry
+    /// * `attributes` : the attributes to request
+    /// * `config` : the application configuration
+    ///
+    /// returns: Result<Option<SearchEntry>, LdapError> the entry, `None` if it does not exist
+    async fn lookup(
+        &self,
+        dn: &str,
+        attributes: &[String],
+        config: &Config,
+    ) -> Result<Option<SearchEntry>, LdapError>;
+}
+
+#[rocket::async_trait]
+impl Directory for LdapConnectionManager {
+    async fn search(
+        &self,
+        base: &str,
+        filter: &str,
+        attributes: &[String],
+        config: &Config,
+    ) -> Result<Vec<SearchEntry>, LdapError> {
+        let mut connection = self.get(config).await?;
+        let entries = search_paged(
+            &mut connection.ldap,
+            base,
+            filter,
+            attributes,
+            config.ldap.page_size,
+        )
+        .await?;
+        self.release(connection).await;
+        Ok(entries.into_iter().map(SearchEntry::construct).collect())
+    }
+
+    async fn lookup(
+        &self,
+        dn: &str,
+        attributes: &[String],
+        config: &Config,
+    ) -> Result<Option<SearchEntry>, LdapError> {
+        let mut connection = self.get(config).await?;
+        let (entries, _) = connection
+            .ldap
+            .search(dn, Scope::Base, "(objectClass=*)", attributes.to_vec())
+            .await?
+            .success()?;
+        self.release(connection).await;
+        Ok(entries.into_iter().next().map(SearchEntry::construct))
+    }
+}
+
 /// Search for entries in the auth directory and construct the entities.
 ///
 /// # Arguments
 ///
+/// * `directory` : the directory server to search in
 /// * `base` : the base dn to search for
 /// * `filter` : the auth filter used for the search
+/// * `attributes` : the attributes to request, `*` for all user attributes
 /// * `config` : the application configuration
 ///
 pub async fn search_entries<R, E>(
+    directory: &impl Directory,
     base: &str,
     filter: &str,
+    attributes: &[String],
     config: &Config,
 ) -> Result<Vec<R>, LdapError>
 where
@@ -55,24 +152,100 @@ where
         "Searching for in the auth server at '{}' with filter '{}'",
         base, filter
     );
-    let mut ldap = open_session(config).await?;
-    let (entries, _search_result) = ldap
-        .search(base, Scope::Subtree, filter, vec!["*"])
-        .await?
-        .success()?;
+    let entries = directory.search(base, filter, attributes, config).await?;
     debug!(
         "Received a result, looping through {} entries",
         entries.len()
     );
-    let mapped_entries = entries
+    Ok(entries
         .iter()
-        .map(|result_entry| {
-            let entry = SearchEntry::construct(result_entry.to_owned());
-            E::from_search_entry(&entry, config)
+        .map(|entry| E::from_search_entry(entry, config))
+        .collect())
+}
+
+/// Fetch the first non-empty binary value of an entry such as a photo.
+/// The candidate attributes are tried in their order.
+///
+/// # Arguments
+///
+/// * `directory` : the directory server to fetch the value from
+/// * `dn` : the distinguished name of the entry
+/// * `candidates` : the candidate attributes of the value
+/// * `config` : the application configuration
+///
+/// returns: Result<Vec<u8>, LdapError> the value, empty if the entry has none
+pub async fn fetch_binary_attribute(
+    directory: &impl Directory,
+    dn: &str,
+    candidates: &AttributeCandidates,
+    config: &Config,
+) -> Result<Vec<u8>, LdapError> {
+    let entry = directory
+        .lookup(dn, candidates.candidates(), config)
+        .await?;
+    let value = entry
+        .and_then(|entry| {
+            candidates.candidates().iter().find_map(|candidate| {
+                entry
+                    .bin_attrs
+                    .get(candidate)
+                    .and_then(|values| values.first())
+                    .filter(|value| !value.is_empty())
+                    .cloned()
+            })
         })
-        .collect();
-    ldap.unbind().await?;
-    Ok(mapped_entries)
+        .unwrap_or_default();
+    Ok(value)
+}
+
+/// Search for all entries using the paged results control.
+/// The pages are requested until the server does not return a cookie anymore.
+/// If the server ignores the control, only the first result is returned which may be truncated by the size limit.
+///
+/// # Arguments
+///
+/// * `ldap`: the bound ldap session to search with
+/// * `base` : the base dn to search for
+/// * `filter` : the auth filter used for the search
+/// * `attributes` : the attributes to request
+/// * `page_size`: the number of entries per page
+async fn search_paged(
+    ldap: &mut Ldap,
+    base: &str,
+    filter: &str,
+    attributes: &[String],
+    page_size: i32,
+) -> Result<Vec<ResultEntry>, LdapError> {
+    let mut entries = vec![];
+    let mut cookie = vec![];
+    loop {
+        let (page, result) = ldap
+            .with_controls(PagedResults {
+                size: page_size,
+                cookie,
+            })
+            .search(base, Scope::Subtree, filter, attributes.to_vec())
+            .await?
+            .success()?;
+        debug!("Received a page with {} entries", page.len());
+        entries.extend(page);
+        let paged_results = result
+            .ctrls
+            .iter()
+            .find(|c| matches!(c.0, Some(ControlType::PagedResults)))
+            .map(|c| c.1.parse::<PagedResults>());
+        match paged_results {
+            None => {
+                warn!(
+                    "The server ignored the paged results control, the results of '{}' may be truncated",
+                    base
+                );
+                return Ok(entries);
+            }
+            Some(p) if p.cookie.is_empty() => return Ok(entries),
+            Some(p) => cookie = p.cookie,
+        }
+    }
 }
 
 /// Open the ldap session
@@ -80,10 +253,10 @@ where
 /// # Arguments
 ///
 /// * `config` : the application configuration used for retrieving the ldap server credentials
-async fn open_session(config: &Config) -> Result<Ldap, LdapError> {
+pub(crate) async fn open_session(config: &Config) -> Result<Ldap, LdapError> {
     let ldap_config = &config.ldap;
     info!("Bind to ldap server: {}", ldap_config.server);
-    let (conn, mut ldap) = LdapConnAsync::new(&*ldap_config.server).await?;
+    let (conn, mut ldap) = connect(ldap_config).await?;
     ldap3::drive!(conn);
     if let Some(user) = ldap_config.dn.as_ref() {
         info!("Bind ldap user with dn '{}'", user);
@@ -99,3 +272,161 @@ async fn open_session(config: &Config) -> Result<Ldap, LdapError> {
     }
     Ok(ldap)
 }
+
+/// Connect to the directory server as configured, including the secure connection.
+/// If StartTLS is configured and the upgrade fails, the connection is aborted with an error.
+///
+/// # Arguments
+///
+/// * `ldap_config` : the configuration of the directory server
+pub(crate) async fn connect(ldap_config: &LdapConfig) -> Result<(LdapConnAsync, Ldap), LdapError> {
+    let settings = connection_settings(ldap_config).map_err(|e| {
+        error!(
+            "Unable to configure the secure connection to the ldap server: {}",
+            e
+        );
+        e
+    })?;
+    LdapConnAsync::with_settings(settings, &ldap_config.server)
+        .await
+        .map_err(|e| {
+            if ldap_config.tls.start_tls {
+                error!(
+                    "Unable to establish a StartTLS connection to the ldap server '{}', abort: {}",
+                    ldap_config.server, e
+                );
+            }
+            e
+        })
+}
+
+/// Create the connection settings out of the TLS configuration.
+///
+/// # Arguments
+///
+/// * `ldap_config` : the configuration of the directory server
+fn connection_settings(ldap_config: &LdapConfig) -> Result<LdapConnSettings, LdapError> {
+    let tls_config = &ldap_config.tls;
+    let settings = LdapConnSettings::new()
+        .set_starttls(tls_config.start_tls)
+        .set_no_tls_verify(tls_config.accept_invalid_certs);
+    if tls_config.accept_invalid_certs {
+        warn!("The certificate of the ldap server will not be verified, this is insecure");
+        return Ok(settings);
+    }
+    match &tls_config.ca_cert_path {
+        Some(ca_cert_path) => Ok(settings.set_config(Arc::new(client_config(ca_cert_path)?))),
+        None => Ok(settings),
+    }
+}
+
+/// Create a TLS client configuration which only trusts the CA certificates from the given file.
+///
+/// # Arguments
+///
+/// * `ca_cert_path` : the path to the CA certificates in the PEM format
+fn client_config(ca_cert_path: &str) -> io::Result<ClientConfig> {
+    let mut reader = BufReader::new(File::open(ca_cert_path)?);
+    let certificates = rustls_pemfile::certs(&mut reader)?;
+    let mut root_store = RootCertStore::empty();
+    let (valid, invalid) = root_store.add_parsable_certificates(&certificates);
+    debug!(
+        "Loaded {} CA certificates from '{}', skipped {} invalid ones",
+        valid, ca_cert_path, invalid
+    );
+    if valid == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("no valid CA certificate found in '{}'", ca_cert_path),
+        ));
+    }
+    Ok(ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(root_store)
+        .with_no_client_auth())
+}
+
+/// Escape a value according to RFC 4515 so it can be safely embedded in a search filter.
+/// The characters `*`, `(`, `)`, `\` and NUL are replaced by their hexadecimal escape sequences.
+///
+/// # Arguments
+///
+/// * `value`: the value to escape
+///
+/// returns: String
+pub fn escape_ldap_filter_value(value: &str) -> String {
+    value
+        .chars()
+        .fold(String::with_capacity(value.len()), |mut escaped, c| {
+            match c {
+                '*' => escaped.push_str("\\2a"),
+                '(' => escaped.push_str("\\28"),
+                ')' => escaped.push_str("\\29"),
+                '\\' => escaped.push_str("\\5c"),
+                '\0' => escaped.push_str("\\00"),
+                _ => escaped.push(c),
+            }
+            escaped
+        })
+}
+
+/// Fill a filter template from the configuration with a username.
+/// Every occurrence of `{username}` is replaced by the escaped username.
+///
+/// # Arguments
+///
+/// * `template`: the filter template
+/// * `username`: the username to substitute
+///
+/// returns: String
+pub fn fill_filter_template(template: &str, username: &str) -> String {
+    template.replace("{username}", &escape_ldap_filter_value(username))
+}
+
+/// Normalize a distinguished name so that insignificant differences do not matter on comparison.
+/// The attribute types are lowercased and the spaces around the separators of the RDNs are removed.
+/// The attribute values keep their case, escaped characters such as `\,` are preserved.
+///
+/// # Arguments
+///
+/// * `dn`: the distinguished name to normalize
+///
+/// returns: String such as `uid=karl,ou=members,dc=mvl,dc=at` for `UID=karl, OU=members ,dc=mvl,DC=at`
+pub fn normalize_dn(dn: &str) -> String {
+    let mut normalized = String::with_capacity(dn.len());
+    let mut component = String::new();
+    let mut escaped = false;
+    for c in dn.chars() {
+        if !escaped && (c == ',' || c == '+') {
+            normalized.push_str(&normalize_attribute_value_assertion(&component));
+            normalized.push(c);
+            component.clear();
+        } else {
+            component.push(c);
+        }
+        escaped = !escaped && c == '\\';
+    }
+    normalized.push_str(&normalize_attribute_value_assertion(&component));
+    normalized
+}
+
+/// Normalize a single `type=value` pair of a distinguished name.
+/// A trailing space which is escaped is kept as part of the value.
+///
+/// # Arguments
+///
+/// * `assertion`: the pair to normalize
+///
+/// returns: String
+fn normalize_attribute_value_assertion(assertion: &str) -> String {
+    match assertion.split_once('=') {
+        Some((attribute_type, value)) => {
+            let mut value = value.trim().to_string();
+            if value.ends_with('\\') && assertion.ends_with(' ') {
+                value.push(' ');
+            }
+            format!("{}={}", attribute_type.trim().to_lowercase(), value)
+        }
+        None => assertion.trim().to_string(),
+    }
+}
@@ -0,0 +1,204 @@
+// OpenKeg, the lightweight backend of the Musikverein Leopoldsdorf.
+// Copyright (C) 2022  Richard Stöckl
+//
+// This program is free software; you can redistribute it and/or
+// modify it under the terms of the GNU General Public License
+// as published by the Free Software Foundation; either version 2
+// of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use ldap3::{Ldap, LdapError, Scope};
+use rocket::tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::config::Config;
+use crate::ldap::open_session;
+
+/// A pool of authenticated connections to the directory server.
+/// Connections are bound with the service user of the configuration and are reused for searches.
+/// This avoids opening a new connection and binding for every single search.
+/// At most [crate::config::LdapConfig::pool_size] pooled connections are in use at the same time.
+/// If all of them are in use, further requests get a one-off connection which is closed after usage.
+/// The manager is cheap to clone, all clones share the same pool.
+#[derive(Clone)]
+pub struct LdapConnectionManager {
+    /// The idle connections ready to reuse.
+    idle: Arc<Mutex<Vec<PooledConnection>>>,
+    /// The permits for the connections in use, one per connection.
+    permits: Arc<Semaphore>,
+    /// The maximum number of open connections.
+    size: usize,
+    /// The duration after which an idle connection is closed instead of being reused.
+    idle_timeout: Duration,
+}
+
+/// An idle connection within the pool.
+struct PooledConnection {
+    /// The authenticated handle to the directory server.
+    ldap: Ldap,
+    /// The moment when this connection was returned to the pool.
+    idle_since: Instant,
+}
+
+/// A connection taken out of the pool or a one-off connection if the pool is exhausted.
+/// A pooled connection counts against the size of the pool until it is released or dropped.
+pub struct LdapConnection {
+    /// The authenticated handle to the directory server.
+    pub ldap: Ldap,
+    /// The permit which is returned to the pool together with the connection, `None` for a one-off connection.
+    permit: Option<OwnedSemaphorePermit>,
+}
+
+impl LdapConnectionManager {
+    /// Create a new empty connection manager.
+    /// Connections are only opened on demand.
+    ///
+    /// # Arguments
+    ///
+    /// * `config`: the application configuration which contains the pool size and the idle timeout
+    ///
+    /// returns: LdapConnectionManager
+    pub fn new(config: &Config) -> Self {
+        let size = config.ldap.pool_size.max(1);
+        Self {
+            idle: Arc::new(Mutex::new(Vec::with_capacity(size))),
+            permits: Arc::new(Semaphore::new(size)),
+            size,
+            idle_timeout: Duration::from_secs(config.ldap.pool_idle_timeout),
+        }
+    }
+
+    /// Get an authenticated connection to the directory server.
+    /// Idle connections are validated with a read of the root DSE before reuse, the ones which are expired or invalid are closed.
+    /// If there is no valid idle connection, a new one will be opened.
+    /// If all connections of the pool are in use, a one-off connection is opened which is closed on release instead of being pooled.
+    ///
+    /// # Arguments
+    ///
+    /// * `config`: the application configuration used to open new connections
+    ///
+    /// returns: Result<LdapConnection, LdapError>
+    pub async fn get(&self, config: &Config) -> Result<LdapConnection, LdapError> {
+        match self.permits.clone().try_acquire_owned() {
+            Ok(permit) => Ok(LdapConnection {
+                ldap: self.reuse_or_open(config).await?,
+                permit: Some(permit),
+            }),
+            Err(_) => {
+                debug!("The ldap connection pool is exhausted, open a one-off connection");
+                Ok(LdapConnection {
+                    ldap: open_session(config).await?,
+                    permit: None,
+                })
+            }
+        }
+    }
+
+    /// Take a valid idle connection or open a new one if there is none.
+    ///
+    /// # Arguments
+    ///
+    /// * `config`: the application configuration used to open new connections
+    ///
+    /// returns: Result<Ldap, LdapError>
+    async fn reuse_or_open(&self, config: &Config) -> Result<Ldap, LdapError> {
+        while let Some(connection) = self.pop_idle() {
+            let mut ldap = connection.ldap;
+            if connection.idle_since.elapsed() > self.idle_timeout {
+                debug!("Close expired idle ldap connection");
+                let _ = ldap.unbind().await;
+                continue;
+            }
+            if is_alive(&mut ldap).await {
+                debug!("Reuse pooled ldap connection");
+                return Ok(ldap);
+            }
+            debug!("Discard invalid pooled ldap connection");
+        }
+        debug!("No idle ldap connection available, open a new one");
+        open_session(config).await
+    }
+
+    /// Return a connection to the pool after usage.
+    /// One-off connections and connections which do not fit into the pool anymore will be closed.
+    /// Connections which encountered errors should not be returned but dropped.
+    ///
+    /// # Arguments
+    ///
+    /// * `connection`: the connection to return
+    pub async fn release(&self, connection: LdapConnection) {
+        let mut ldap = connection.ldap;
+        if connection.permit.is_none() {
+            debug!("Close the one-off ldap connection");
+            let _ = ldap.unbind().await;
+            return;
+        }
+        {
+            let mut idle = self.idle.lock().expect("Ldap connection pool lock");
+            if idle.len() < self.size {
+                idle.push(PooledConnection {
+                    ldap,
+                    idle_since: Instant::now(),
+                });
+                return;
+            }
+        }
+        debug!("The ldap connection pool is full, close the connection");
+        let _ = ldap.unbind().await;
+    }
+
+    /// Check whether the directory server is reachable with a read of the root DSE.
+    /// A healthy connection is returned to the pool afterwards.
+    ///
+    /// # Arguments
+    ///
+    /// * `config`: the application configuration used to open new connections
+    ///
+    /// returns: bool
+    pub async fn check(&self, config: &Config) -> bool {
+        let mut connection = match self.get(config).await {
+            Ok(connection) => connection,
+            Err(e) => {
+                warn!("Unable to connect to the directory server: {}", e);
+                return false;
+            }
+        };
+        let alive = is_alive(&mut connection.ldap).await;
+        if alive {
+            self.release(connection).await;
+        }
+        alive
+    }
+
+    /// Take the most recently used idle connection out of the pool.
+    fn pop_idle(&self) -> Option<PooledConnection> {
+        self.idle.lock().expect("Ldap connection pool lock").pop()
+    }
+}
+
+/// Check whether a connection is still usable with a cheap read of the root DSE.
+///
+/// # Arguments
+///
+/// * `ldap`: the connection to check
+///
+/// returns: bool
+async fn is_alive(ldap: &mut Ldap) -> bool {
+    match ldap
+        .search("", Scope::Base, "(objectClass=*)", vec!["1.1"])
+        .await
+    {
+        Ok(result) => result.success().is_ok(),
+        Err(_) => false,
+    }
+}
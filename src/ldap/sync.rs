@@ -15,33 +15,58 @@
 // along with this program; if not, write to the Free Software
 // Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
 
-use std::time::Duration;
+#[cfg(test)]
+#[path = "sync_tests.rs"]
+mod sync_tests;
 
+use std::collections::HashSet;
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+use futures::stream::{FuturesUnordered, StreamExt};
 use ldap3::LdapError;
 use rocket::tokio;
+use rocket::tokio::sync::{watch, Semaphore};
 
 use crate::config::{Config, LdapConfig};
-use crate::ldap::{search_entries, LdapDeserializable};
+use crate::ldap::{fetch_binary_attribute, search_entries, Directory, LdapDeserializable};
 use crate::member::model::{Group, Member};
-use crate::member::state::{MemberState, RegisterEntry};
+use crate::member::photo::content_hash;
+use crate::member::state::{MemberPhotos, MemberState, RegisterEntry};
+use crate::metrics::Metrics;
 use crate::MemberStateMutex;
 
 /// Synchronize all member and groups with the directory server.
 /// This includes transformations into the desired data structures which also includes sorting.
 /// Note that this modifies the provided structures but they only will be modified on success.
 /// If one of the fetching operations from the directory server fails, nothing will be modified in order to avoid inconsistency.
+/// The outcome of every attempt is recorded in the synchronization status of the member state, regardless of the success.
+/// If [crate::config::MembersConfig::deferred_photos] is set, the photos are fetched in a separate pass afterwards and the previous photos are kept until then.
 /// # Arguments
 ///
 /// * `conf` : the application configuration
+/// * `directory` : the directory server to fetch the entries from
 /// * `member_state` the mutex of the current member state which should be altered
-pub async fn synchronize_members_and_groups(conf: &Config, member_state: &mut MemberStateMutex) {
+/// * `metrics` the registry to record the outcome of the synchronization in
+pub async fn synchronize_members_and_groups(
+    conf: &Config,
+    directory: &impl Directory,
+    member_state: &mut MemberStateMutex,
+    metrics: &Metrics,
+) {
+    let start = Instant::now();
     let ldap_conf = &conf.ldap;
-    let result = fetch_results(conf, ldap_conf).await;
+    let result = fetch_results_with_retries(conf, directory, ldap_conf).await;
     if let Err(err) = result {
         warn!(
             "Unable to fetch partial data from the directory server, stop synchronizing: {:?}",
             err
         );
+        let mut member_state_lock = member_state.write().await;
+        member_state_lock.last_sync_duration = Some(start.elapsed());
+        member_state_lock.last_sync_error = Some(err.to_string());
+        metrics.record_sync(false, start.elapsed());
         return;
     }
     let (
@@ -54,6 +79,10 @@ pub async fn synchronize_members_and_groups(conf: &Config, member_state: &mut Me
 
     info!("Done fetching, begin with transformation");
     let mut member_state_lock = member_state.write().await;
+    let previous_photos = conf
+        .members
+        .deferred_photos
+        .then(|| collect_photos(&member_state_lock));
     fill_primitive_collections(
         conf,
         &mut member_state_lock,
@@ -65,7 +94,92 @@ pub async fn synchronize_members_and_groups(conf: &Config, member_state: &mut Me
     );
     debug!("Done with copying data, begin with sorting");
     construct_members_by_register(&mut member_state_lock, members_vector, registers_vector);
-    info!("Done with user synchronization")
+    member_state_lock.last_sync = Some(Utc::now());
+    member_state_lock.last_sync_duration = Some(start.elapsed());
+    member_state_lock.last_sync_error = None;
+    member_state_lock.initialized = true;
+    metrics.record_sync(true, start.elapsed());
+    info!("Done with user synchronization");
+    if let Some(previous_photos) = previous_photos {
+        member_state_lock.update_photos(&previous_photos);
+        drop(member_state_lock);
+        synchronize_photos(conf, directory, member_state).await;
+    }
+}
+
+/// Fetch the photos of all members concurrently and update them in the member state.
+/// The number of concurrent requests is limited by [crate::config::MembersConfig::photo_concurrency].
+/// Photos which cannot be fetched keep their previous value.
+///
+/// # Arguments
+///
+/// * `conf` : the application configuration
+/// * `directory` : the directory server to fetch the entries from
+/// * `member_state` the mutex of the current member state which should be altered
+async fn synchronize_photos(
+    conf: &Config,
+    directory: &impl Directory,
+    member_state: &MemberStateMutex,
+) {
+    let start = Instant::now();
+    let dns: HashSet<String> = {
+        let member_state_lock = member_state.read().await;
+        member_state_lock
+            .all_members
+            .iter()
+            .chain(member_state_lock.sutlers.iter())
+            .chain(member_state_lock.honorary_members.iter())
+            .map(|m| m.full_username.clone())
+            .collect()
+    };
+    let semaphore = Semaphore::new(conf.members.photo_concurrency.max(1));
+    let mut fetches: FuturesUnordered<_> = dns
+        .into_iter()
+        .map(|dn| {
+            let semaphore = &semaphore;
+            async move {
+                let _permit = semaphore.acquire().await.expect("Open semaphore");
+                let photo =
+                    fetch_binary_attribute(directory, &dn, &conf.ldap.member_mapping.photo, conf)
+                        .await;
+                (dn, photo)
+            }
+        })
+        .collect();
+    let mut photos = MemberPhotos::new();
+    while let Some((dn, photo)) = fetches.next().await {
+        match photo {
+            Ok(photo) => {
+                let photo_hash =
+                    (conf.members.hash_photos && !photo.is_empty()).then(|| content_hash(&photo));
+                photos.insert(dn, (photo, photo_hash));
+            }
+            Err(err) => warn!("Unable to fetch the photo of '{}': {}", dn, err),
+        }
+    }
+    member_state.write().await.update_photos(&photos);
+    info!(
+        "Done with synchronizing {} photos in {:?}",
+        photos.len(),
+        start.elapsed()
+    );
+}
+
+/// Collect the photos of all members of the member state.
+/// This allows to keep them while the photos are synchronized in a separate pass.
+fn collect_photos(member_state: &MemberState) -> MemberPhotos {
+    member_state
+        .all_members
+        .iter()
+        .chain(member_state.sutlers.iter())
+        .chain(member_state.honorary_members.iter())
+        .map(|m| {
+            (
+                m.full_username.clone(),
+                (m.photo.clone(), m.photo_hash.clone()),
+            )
+        })
+        .collect()
 }
 
 /// Constructs the sorted member by register collection and saves it to the application state.
@@ -126,9 +240,68 @@ fn fill_primitive_collections(
         .extend(executives_vector.iter().cloned());
 }
 
+/// Helper function to fetch all entries and retry with an exponential backoff on transient errors.
+/// Gives up after the configured number of attempts or on the first error which is not transient.
+async fn fetch_results_with_retries(
+    conf: &Config,
+    directory: &impl Directory,
+    ldap_conf: &LdapConfig,
+) -> Result<
+    (
+        Vec<Member>,
+        Vec<Member>,
+        Vec<Member>,
+        Vec<Group>,
+        Vec<Group>,
+    ),
+    LdapError,
+> {
+    let mut attempt = 1;
+    loop {
+        match fetch_results(conf, directory, ldap_conf).await {
+            Err(err) if attempt < ldap_conf.synchronization_attempts && is_transient(&err) => {
+                let delay = Duration::from_millis(
+                    ldap_conf
+                        .synchronization_retry_delay
+                        .saturating_mul(2u64.saturating_pow(attempt - 1)),
+                );
+                info!(
+                    "Synchronization attempt {} failed, retry in {:?}: {}",
+                    attempt, delay, err
+                );
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Check whether an error is caused by the connection and thus worth retrying.
+/// Errors within the results such as an invalid base dn are not transient except the server is busy or unavailable.
+fn is_transient(err: &LdapError) -> bool {
+    match err {
+        LdapError::Io { .. }
+        | LdapError::OpSend { .. }
+        | LdapError::ResultRecv { .. }
+        | LdapError::IdScrubSend { .. }
+        | LdapError::MiscSend { .. }
+        | LdapError::Timeout { .. }
+        | LdapError::EndOfStream => true,
+        LdapError::LdapResult { result } => result.rc == RC_BUSY || result.rc == RC_UNAVAILABLE,
+        _ => false,
+    }
+}
+
+/// The LDAP result code if the server is too busy to process the request.
+const RC_BUSY: u32 = 51;
+/// The LDAP result code if the server is unavailable.
+const RC_UNAVAILABLE: u32 = 52;
+
 /// Helper function to fetch entries and return them all or none is at least one was not successful.
 async fn fetch_results(
     conf: &Config,
+    directory: &impl Directory,
     ldap_conf: &LdapConfig,
 ) -> Result<
     (
@@ -140,38 +313,56 @@ async fn fetch_results(
     ),
     LdapError,
 > {
+    let all_attributes = vec!["*".to_string()];
+    let member_attributes = if conf.members.deferred_photos {
+        let mut attributes = ldap_conf.member_mapping.attributes_without_photo();
+        attributes.extend(ldap_conf.address_mapping.attributes());
+        attributes
+    } else {
+        all_attributes.clone()
+    };
     let members = fetch_entries::<Member, Member>(
+        directory,
         "member",
         &ldap_conf.member_base,
         &ldap_conf.member_filter,
+        &member_attributes,
         conf,
     )
     .await?;
     let sutlers = fetch_entries::<Member, Member>(
+        directory,
         "sutlers",
         &ldap_conf.sutler_base,
         &ldap_conf.sutler_filter,
+        &member_attributes,
         conf,
     )
     .await?;
     let honoraries = fetch_entries::<Member, Member>(
+        directory,
         "honorary member",
         &ldap_conf.honorary_base,
         &ldap_conf.honorary_filter,
+        &member_attributes,
         conf,
     )
     .await?;
     let registers = fetch_entries::<Group, Group>(
+        directory,
         "registers",
         &ldap_conf.register_base,
         &ldap_conf.register_filter,
+        &all_attributes,
         conf,
     )
     .await?;
     let executives = fetch_entries::<Group, Group>(
+        directory,
         "executive roles",
         &ldap_conf.executives_base,
         &ldap_conf.executives_filter,
+        &all_attributes,
         conf,
     )
     .await?;
@@ -182,20 +373,24 @@ async fn fetch_results(
 ///
 /// # Arguments
 ///
+/// * `directory` : the directory server to fetch the entries from
 /// * `typ` : the type of the entries which is used for messages
 /// * `base` : the base dn to search in
 /// * `filter` : the auth filter to use during search
+/// * `attributes` : the attributes to request
 /// * `conf` : the application configuration
 async fn fetch_entries<R, E>(
+    directory: &impl Directory,
     typ: &str,
     base: &str,
     filter: &str,
+    attributes: &[String],
     conf: &Config,
 ) -> Result<Vec<R>, LdapError>
 where
     E: LdapDeserializable<R>,
 {
-    let ldap_entries = search_entries::<R, E>(base, filter, conf).await?;
+    let ldap_entries = search_entries::<R, E>(directory, base, filter, attributes, conf).await?;
     info!(
         "Successfully received {} {} entries",
         ldap_entries.len(),
@@ -205,20 +400,58 @@ where
 }
 
 /// Runs the task to synchronize all member and groups and attaches it to the member state.
-/// This task runs periodically as configured and thus will run as long as the application lives.
+/// This task runs periodically as configured until the shutdown is requested.
+/// The first synchronization runs immediately at startup, the following ones after each full interval.
+/// A synchronization which is already running when the shutdown is requested is finished before the task stops.
 /// # Arguments
 ///
 /// * `conf`: the application configuration
+/// * `directory`: the directory server to fetch the entries from
 /// * `member_state`: the state which should be updated periodically
+/// * `metrics`: the registry to record the outcome of the synchronizations in
+/// * `shutdown`: the receiver which is notified once the server shuts down
+///
+/// returns: ()
+pub async fn member_synchronization_task(
+    conf: &Config,
+    directory: &impl Directory,
+    member_state: &mut MemberStateMutex,
+    metrics: &Metrics,
+    shutdown: watch::Receiver<bool>,
+) {
+    let period = Duration::from_secs(conf.ldap.synchronization_interval);
+    run_periodically(period, shutdown, || {
+        let mut member_state = member_state.clone();
+        async move {
+            info!("Running scheduled user synchronization");
+            synchronize_members_and_groups(conf, directory, &mut member_state, metrics).await;
+        }
+    })
+    .await;
+}
+
+/// Run the job immediately and then again after every period until the shutdown is requested.
+/// The period is measured from the start of the previous run, a job which is still running is not interrupted.
+///
+/// # Arguments
+///
+/// * `period`: the time between the start of two runs
+/// * `shutdown`: the receiver which is notified once the server shuts down
+/// * `job`: the function which creates the future to run
 ///
 /// returns: ()
-pub async fn member_synchronization_task(conf: &Config, member_state: &mut MemberStateMutex) {
-    let mut interval =
-        tokio::time::interval(Duration::from_secs(conf.ldap.synchronization_interval));
+async fn run_periodically<F, Fut>(period: Duration, mut shutdown: watch::Receiver<bool>, mut job: F)
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = ()>,
+{
+    let mut interval = tokio::time::interval_at(tokio::time::Instant::now() + period, period);
     loop {
-        interval.tick().await;
-        info!("Running scheduled user synchronization");
-        synchronize_members_and_groups(conf, member_state).await;
+        job().await;
+        tokio::select! {
+            _ = interval.tick() => {}
+            _ = shutdown.changed() => break,
+        }
     }
 }
 
@@ -0,0 +1,240 @@
+// OpenKeg, the lightweight backend of the Musikverein Leopoldsdorf.
+// Copyright (C) 2022  Richard Stöckl
+//
+// This program is free software; you can redistribute it and/or
+// modify it under the terms of the GNU General Public License
+// as published by the Free Software Foundation; either version 2
+// of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use ldap3::SearchEntry;
+
+use super::*;
+use crate::member::state::Repository;
+
+fn member(username: &str) -> Member {
+    Member {
+        username: username.to_string(),
+        full_username: format!("uid={}", username),
+        ..Member::test_member("Max", "Mustermann")
+    }
+}
+
+/// A directory which serves fixed entries and fails the first searches.
+#[derive(Default)]
+struct MockDirectory {
+    /// The entries by their base.
+    entries: HashMap<String, Vec<SearchEntry>>,
+    /// The number of searches which fail before the entries are served.
+    failures: usize,
+    /// The number of searches so far.
+    searches: AtomicUsize,
+}
+
+#[rocket::async_trait]
+impl Directory for MockDirectory {
+    async fn search(
+        &self,
+        base: &str,
+        _filter: &str,
+        _attributes: &[String],
+        _config: &Config,
+    ) -> Result<Vec<SearchEntry>, LdapError> {
+        if self.searches.fetch_add(1, Ordering::SeqCst) < self.failures {
+            return Err(LdapError::EndOfStream);
+        }
+        Ok(self.entries.get(base).cloned().unwrap_or_default())
+    }
+
+    async fn lookup(
+        &self,
+        dn: &str,
+        _attributes: &[String],
+        _config: &Config,
+    ) -> Result<Option<SearchEntry>, LdapError> {
+        Ok(self
+            .entries
+            .values()
+            .flatten()
+            .find(|entry| entry.dn == dn)
+            .cloned())
+    }
+}
+
+fn entry(dn: &str, attrs: &[(&str, &str)], bin_attrs: &[(&str, &[u8])]) -> SearchEntry {
+    SearchEntry {
+        dn: dn.to_string(),
+        attrs: attrs
+            .iter()
+            .map(|(k, v)| (k.to_string(), vec![v.to_string()]))
+            .collect(),
+        bin_attrs: bin_attrs
+            .iter()
+            .map(|(k, v)| (k.to_string(), vec![v.to_vec()]))
+            .collect(),
+    }
+}
+
+fn directory(failures: usize) -> MockDirectory {
+    let max = entry(
+        "uid=max,ou=members",
+        &[("uid", "max"), ("givenName", "Max"), ("sn", "Mustermann")],
+        &[("jpegPhoto", &[0xff, 0xd8])],
+    );
+    let erika = entry(
+        "uid=erika,ou=members",
+        &[
+            ("uid", "erika"),
+            ("givenName", "Erika"),
+            ("sn", "Musterfrau"),
+        ],
+        &[],
+    );
+    let register = entry(
+        "cn=Klarinette,ou=registers",
+        &[("cn", "Klarinetten"), ("member", "uid=max,ou=members")],
+        &[],
+    );
+    MockDirectory {
+        entries: HashMap::from([
+            ("ou=members".to_string(), vec![max, erika]),
+            ("ou=registers".to_string(), vec![register]),
+        ]),
+        failures,
+        ..Default::default()
+    }
+}
+
+fn config() -> Config {
+    let mut conf = Config::default();
+    conf.ldap.member_base = "ou=members".to_string();
+    conf.ldap.sutler_base = "ou=sutlers".to_string();
+    conf.ldap.honorary_base = "ou=honorary".to_string();
+    conf.ldap.register_base = "ou=registers".to_string();
+    conf.ldap.executives_base = "ou=executives".to_string();
+    conf.ldap.synchronization_attempts = 2;
+    conf.ldap.synchronization_retry_delay = 10;
+    conf
+}
+
+#[rocket::async_test]
+async fn task_synchronizes_directory() {
+    let conf = config();
+    let directory = directory(0);
+    let metrics = Metrics::default();
+    let mut member_state = MemberState::mutex();
+    let state = member_state.clone();
+    let (shutdown_sender, shutdown_receiver) = watch::channel(false);
+    let task = member_synchronization_task(
+        &conf,
+        &directory,
+        &mut member_state,
+        &metrics,
+        shutdown_receiver,
+    );
+    let check = async {
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while !state.read().await.initialized {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("initialized");
+        shutdown_sender.send(true).expect("shutdown");
+    };
+    tokio::time::timeout(Duration::from_secs(5), futures::future::join(task, check))
+        .await
+        .expect("stopped");
+    let state = state.read().await;
+    assert_eq!(state.all_members.len(), 2);
+    assert!(state.last_sync_error.is_none());
+    let register = state.members_by_register.front().expect("register");
+    assert_eq!(register.register.name_plural, "Klarinetten");
+    let musicians: Vec<&str> = register
+        .members
+        .iter()
+        .map(|m| m.username.as_str())
+        .collect();
+    assert_eq!(musicians, vec!["max"]);
+}
+
+#[rocket::async_test]
+async fn synchronization_retries_transient_errors() {
+    let conf = config();
+    let directory = directory(1);
+    let mut member_state = MemberState::mutex();
+    synchronize_members_and_groups(&conf, &directory, &mut member_state, &Metrics::default()).await;
+    assert!(member_state.read().await.initialized);
+    assert_eq!(member_state.read().await.all_members.len(), 2);
+}
+
+#[rocket::async_test]
+async fn failed_synchronization_keeps_state() {
+    let conf = config();
+    let directory = directory(usize::MAX);
+    let mut member_state = MemberState::mutex();
+    synchronize_members_and_groups(&conf, &directory, &mut member_state, &Metrics::default()).await;
+    let state = member_state.read().await;
+    assert!(!state.initialized);
+    assert!(state.all_members.is_empty());
+    assert!(state.last_sync_error.is_some());
+    assert_eq!(directory.searches.load(Ordering::SeqCst), 2);
+}
+
+#[rocket::async_test]
+async fn deferred_photos_are_fetched() {
+    let mut conf = config();
+    conf.members.deferred_photos = true;
+    let directory = directory(0);
+    let mut member_state = MemberState::mutex();
+    synchronize_members_and_groups(&conf, &directory, &mut member_state, &Metrics::default()).await;
+    let state = member_state.read().await;
+    let max = state.all_members.find(&"max".to_string()).expect("max");
+    assert_eq!(max.photo, vec![0xff, 0xd8]);
+    assert!(max.photo_hash.is_some());
+    let erika = state.all_members.find(&"erika".to_string()).expect("erika");
+    assert!(erika.photo.is_empty());
+}
+
+#[rocket::async_test]
+async fn first_run_is_immediate() {
+    let member_state = MemberState::mutex();
+    let (shutdown_sender, shutdown_receiver) = watch::channel(false);
+    let directory = vec![member("max"), member("erika")];
+    let state = member_state.clone();
+    let task = tokio::spawn(run_periodically(
+        Duration::from_secs(300),
+        shutdown_receiver,
+        move || {
+            let state = state.clone();
+            let directory = directory.clone();
+            async move {
+                state.write().await.all_members.extend(directory);
+            }
+        },
+    ));
+    let populated = tokio::time::timeout(Duration::from_secs(5), async {
+        while member_state.read().await.all_members.is_empty() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await;
+    assert!(populated.is_ok());
+    assert_eq!(member_state.read().await.all_members.len(), 2);
+    shutdown_sender.send(true).expect("shutdown");
+    tokio::time::timeout(Duration::from_secs(5), task)
+        .await
+        .expect("stopped")
+        .expect("joined");
+}
@@ -0,0 +1,89 @@
+// OpenKeg, the lightweight backend of the Musikverein Leopoldsdorf.
+// Copyright (C) 2022  Richard Stöckl
+//
+// This program is free software; you can redistribute it and/or
+// modify it under the terms of the GNU General Public License
+// as published by the Free Software Foundation; either version 2
+// of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+
+use std::env;
+use std::io::Write;
+
+use chrono::Utc;
+use log::Record;
+use serde_json::json;
+
+use crate::request_id::current_request_id;
+
+#[cfg(test)]
+#[path = "logging_tests.rs"]
+mod logging_tests;
+
+/// The environment variable which selects the format of the log lines.
+/// If set to `json`, every log line is a JSON object, otherwise the human-readable format of [env_logger] is used.
+pub const LOG_FORMAT_VARIABLE: &str = "KEG_LOG_FORMAT";
+
+/// Initialize the logger with the format selected by [LOG_FORMAT_VARIABLE].
+/// The level filtering is configured by the `RUST_LOG` environment variable in both formats.
+/// Log lines written while handling a request contain the id of the request, see [current_request_id].
+///
+/// returns: ()
+pub fn init_logger() {
+    let mut builder = env_logger::Builder::from_default_env();
+    if env::var(LOG_FORMAT_VARIABLE).is_ok_and(|format| format.eq_ignore_ascii_case("json")) {
+        builder.format(|buf, record| {
+            writeln!(
+                buf,
+                "{}",
+                json_line(record, current_request_id().as_deref())
+            )
+        });
+    } else {
+        builder.format(|buf, record| {
+            let request_id = current_request_id()
+                .map(|id| format!(" request_id={}", id))
+                .unwrap_or_default();
+            writeln!(
+                buf,
+                "[{} {:<5} {}{}] {}",
+                buf.timestamp(),
+                buf.default_styled_level(record.level()),
+                record.target(),
+                request_id,
+                record.args()
+            )
+        });
+    }
+    builder.init();
+}
+
+/// Format the log record as a single line JSON object.
+///
+/// # Arguments
+///
+/// * `record`: the record to format
+/// * `request_id`: the id of the request the record belongs to, omitted if `None`
+///
+/// returns: String
+fn json_line(record: &Record, request_id: Option<&str>) -> String {
+    let mut line = json!({
+        "timestamp": Utc::now().to_rfc3339(),
+        "level": record.level().as_str(),
+        "target": record.target(),
+        "module": record.module_path(),
+        "message": record.args().to_string(),
+    });
+    if let Some(request_id) = request_id {
+        line["request_id"] = json!(request_id);
+    }
+    line.to_string()
+}
@@ -0,0 +1,56 @@
+// OpenKeg, the lightweight backend of the Musikverein Leopoldsdorf.
+// Copyright (C) 2022  Richard Stöckl
+//
+// This program is free software; you can redistribute it and/or
+// modify it under the terms of the GNU General Public License
+// as published by the Free Software Foundation; either version 2
+// of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+
+use super::*;
+use log::Level;
+use serde_json::Value;
+
+#[test]
+fn json_record() {
+    let line = json_line(
+        &Record::builder()
+            .args(format_args!("Synchronized {} \"members\"", 42))
+            .level(Level::Warn)
+            .target("openkeg::ldap::sync")
+            .module_path(Some("openkeg::ldap::sync"))
+            .build(),
+        None,
+    );
+    assert!(!line.contains('\n'));
+    let value: Value = serde_json::from_str(&line).expect("json");
+    assert_eq!(value["level"], "WARN");
+    assert_eq!(value["target"], "openkeg::ldap::sync");
+    assert_eq!(value["module"], "openkeg::ldap::sync");
+    assert_eq!(value["message"], "Synchronized 42 \"members\"");
+    assert!(value.get("request_id").is_none());
+    assert!(value["timestamp"]
+        .as_str()
+        .is_some_and(|t| t.ends_with("+00:00")));
+}
+
+#[test]
+fn json_record_with_request_id() {
+    let line = json_line(
+        &Record::builder()
+            .args(format_args!("Unable to reach the database"))
+            .level(Level::Warn)
+            .build(),
+        Some("frontend-42"),
+    );
+    let value: Value = serde_json::from_str(&line).expect("json");
+    assert_eq!(value["request_id"], "frontend-42");
+}
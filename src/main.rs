@@ -20,25 +20,37 @@ extern crate rocket;
 
 use std::env;
 use std::sync::Arc;
+use std::time::Duration;
 
+use figment::providers::Serialized;
 use ldap3::tokio::task;
 use okapi::merge::merge_specs;
 use rocket::config::Ident;
 use rocket::fairing::AdHoc;
 use rocket::fs::{FileServer, Options};
-use rocket::tokio::sync::RwLock;
-use rocket::{Build, Rocket};
+use rocket::tokio::sync::{watch, Mutex, RwLock};
+use rocket::{tokio, Build, Rocket};
 use rocket_okapi::mount_endpoints_and_merged_docs;
 
+use crate::archive::webhook::WebhookQueue;
+use crate::calendar::cache::CalendarCache;
 use crate::config::Config;
 use crate::cors::{cors_preflight, Cors};
 use crate::database::client::initialize_client;
+use crate::database::statistic::StatisticCache;
+use crate::document::webdav::create_client;
 use crate::info::{get_info_routes_and_docs, ServerInfo};
 use crate::ldap::auth;
+use crate::ldap::pool::LdapConnectionManager;
 use crate::ldap::sync::member_synchronization_task;
+use crate::member::photo::ThumbnailCache;
 use crate::member::state::MemberState;
+use crate::metrics::{get_metrics, Metrics, MetricsRegistry, RequestMetrics};
 use crate::openapi::{custom_openapi_spec, openapi_settings};
-use crate::user::key::{read_private_key, read_public_key};
+use crate::request_id::{correlated, RequestIdFairing};
+use crate::user::key::{key_reload_task, read_private_key, read_public_key, KeyPair, Keys};
+use crate::user::throttle::LoginAttempts;
+use crate::user::tokens::SIGNING_ALGORITHM;
 
 /// Module which handles the archive rest interface.
 mod archive;
@@ -48,6 +60,8 @@ mod calendar;
 mod config;
 /// Module which adds HTTP CORS to the application server.
 mod cors;
+/// Module which provides the shared functionality to export data as CSV.
+mod csv;
 /// Module which provides the interface to the database.
 mod database;
 /// Module for accessing documents and their assets from a WebDav server.
@@ -56,10 +70,18 @@ mod document;
 mod info;
 /// Module which handles the communication to the directory server.
 mod ldap;
+/// Module which initializes the logger in the text or JSON format.
+mod logging;
 /// Module which provides the rest interface to fetch member and group information.
 mod member;
+/// Module which collects metrics and exposes them for Prometheus.
+mod metrics;
 /// Module which provides documentation via OpenApi.
 mod openapi;
+/// Module which correlates the log lines of a request by its id.
+mod request_id;
+/// Module which passes the bodies of uploads on while they are received.
+mod upload;
 /// Module which provides functionality for users in the context of the rest interface, not (only) member.
 mod user;
 
@@ -68,7 +90,7 @@ pub type MemberStateMutex = Arc<RwLock<MemberState>>;
 /// Entrypoint for the rocket application.
 #[rocket::main]
 async fn main() {
-    env_logger::init();
+    logging::init_logger();
     info!(
         "Welcome to OpenKeg {} - The backend of the Musikverein Leopoldsdorf!",
         env!("CARGO_PKG_VERSION")
@@ -96,13 +118,24 @@ pub fn keg_user_agent() -> String {
 }
 
 /// Create a new [Rocket<Build>] and merge the application configuration into it.
+/// Passwords configured by files are read and merged into the configuration.
+/// If a password file cannot be read or the configuration is invalid, this function will panic.
 ///
 /// returns: Rocket<Build> the fresh build state
 fn initialize_build_state() -> Rocket<Build> {
-    let figment = config::read_config().merge((
+    let mut figment = config::read_config().merge((
         "ident",
         Ident::try_new(keg_user_agent()).expect("Valid ident for server response"),
     ));
+    let password_files = config::read_password_files(&figment.extract().expect("config"))
+        .unwrap_or_else(|msg| panic!("Invalid configuration: {}", msg));
+    for (key, password) in password_files {
+        figment = figment.merge(Serialized::global(key, password));
+    }
+    let config: Config = figment.extract().expect("config");
+    if let Err(msg) = config.validate() {
+        panic!("Invalid configuration: {}", msg);
+    }
     rocket::custom(figment).attach(AdHoc::config::<Config>())
 }
 
@@ -115,12 +148,17 @@ fn initialize_build_state() -> Rocket<Build> {
 ///
 /// returns: Rocket<Build>
 async fn configure_rocket(rocket: Rocket<Build>) -> Rocket<Build> {
-    let configured_rocket = manage_database_client(manage_member_state(manage_keys(attach_cors(
-        manage_server_info(mount_static_directory(mount_controller_routes(rocket))),
-    ))))
+    let configured_rocket = manage_database_client(manage_ldap_connection_manager(
+        manage_statistic_cache(manage_webhook_queue(manage_document_client(
+            manage_calendar_cache(manage_thumbnail_cache(manage_member_state(
+                manage_login_attempts(manage_keys(manage_metrics(attach_cors(
+                    manage_server_info(mount_static_directory(mount_controller_routes(rocket))),
+                )))),
+            ))),
+        ))),
+    ))
     .await;
-    register_user_sync_task(&configured_rocket);
-    configured_rocket
+    register_user_sync_task(configured_rocket)
 }
 
 /// Fetch the routes and OpenApi documentation from the submodules and attach it to the rocket build.
@@ -140,16 +178,19 @@ fn mount_controller_routes(mut rocket: Rocket<Build>) -> Rocket<Build> {
     let custom_spec = (info_route, openapi_spec_header);
     mount_endpoints_and_merged_docs! {
         rocket, "/api/v1".to_owned(), openapi_settings,
-        "" => custom_spec,
-        "/scores" => archive::get_scores_routes_and_docs(&openapi_settings),
-        "/books" => archive::get_books_routes_and_docs(&openapi_settings),
-        "/statistics" => archive::get_statistics_routes_and_docs(&openapi_settings),
-        "/documents" => document::get_document_routes_and_docs(&openapi_settings),
-        "/calendar" => calendar::get_routes_and_docs(&openapi_settings),
-        "/members" => member::get_routes_and_docs(&openapi_settings),
-        "/users" => user::get_routes_and_docs(&openapi_settings),
+        "" => correlated(custom_spec),
+        "/scores" => correlated(archive::get_scores_routes_and_docs(&openapi_settings)),
+        "/books" => correlated(archive::get_books_routes_and_docs(&openapi_settings)),
+        "/statistics" => correlated(archive::get_statistics_routes_and_docs(&openapi_settings)),
+        "/documents" => correlated(document::get_document_routes_and_docs(&openapi_settings)),
+        "/calendar" => correlated(calendar::get_routes_and_docs(&openapi_settings)),
+        "/members" => correlated(member::get_routes_and_docs(&openapi_settings)),
+        "/users" => correlated(user::get_routes_and_docs(&openapi_settings)),
     }
-    rocket.mount("/", get_info_routes_and_docs(&openapi_settings).0.to_vec())
+    rocket
+        .attach(RequestIdFairing)
+        .mount("/", get_info_routes_and_docs(&openapi_settings).0.to_vec())
+        .register("/", catchers![member::controller::member_state_unavailable])
 }
 
 /// Serve a static directory from the file system.
@@ -194,7 +235,7 @@ fn manage_server_info(rocket: Rocket<Build>) -> Rocket<Build> {
     rocket.manage(ServerInfo::new())
 }
 
-/// Attach the cors fairing to the rocket build state,
+/// Attach the cors fairing with the configured origins to the rocket build state.
 ///
 /// # Arguments
 ///
@@ -203,11 +244,37 @@ fn manage_server_info(rocket: Rocket<Build>) -> Rocket<Build> {
 /// returns: Rocket<Build>
 fn attach_cors(rocket: Rocket<Build>) -> Rocket<Build> {
     info!("Create the CORS header and attach it");
-    rocket.attach(Cors).mount("/", routes![cors_preflight])
+    let config = rocket_configuration(&rocket);
+    rocket
+        .attach(Cors(config.cors))
+        .mount("/", routes![cors_preflight])
+}
+
+/// Create the metrics registry and let the rocket build state manage it.
+/// If [crate::config::MetricsConfig::enabled] is set, the request metrics fairing is attached and the metrics are exposed at `/metrics`.
+///
+/// # Arguments
+///
+/// * `rocket`: the build state to let manage the metrics registry
+///
+/// returns: Rocket<Build>
+fn manage_metrics(rocket: Rocket<Build>) -> Rocket<Build> {
+    info!("Create the metrics registry and let the server manage it");
+    let config = rocket_configuration(&rocket);
+    let registry: MetricsRegistry = Arc::new(Metrics::default());
+    let rocket = rocket.manage(registry.clone());
+    if !config.metrics.enabled {
+        return rocket;
+    }
+    info!("Expose the metrics at /metrics");
+    rocket
+        .attach(RequestMetrics(registry))
+        .mount("/", routes![get_metrics])
 }
 
 /// Let the server manage the private and the public key.
 /// Warnings will be printed to the log if this operation fails.
+/// The keys are reloaded on `SIGHUP`, see [key_reload_task].
 ///
 /// # Arguments
 ///
@@ -217,20 +284,15 @@ fn attach_cors(rocket: Rocket<Build>) -> Rocket<Build> {
 fn manage_keys(rocket: Rocket<Build>) -> Rocket<Build> {
     let config = rocket_configuration(&rocket);
     info!("Read the public and the private key");
-    let mut server_manage = rocket;
-    match read_private_key(&config) {
-        Ok(private_key) => {
-            server_manage = server_manage.manage(private_key);
-            info!("Private key successfully added to application state");
-        }
-        Err(err) => warn!(
-            "Unable to read the private key from {}: {}",
-            config.cert.private_key_path, err
-        ),
-    }
+    let mut key_pair = KeyPair::default();
+    let mut key_id = None;
     match read_public_key(&config) {
         Ok(public_key) => {
-            server_manage = server_manage.manage(public_key);
+            key_id = public_key.jwk(SIGNING_ALGORITHM).map(|jwk| jwk.kid);
+            if key_id.is_none() {
+                warn!("Unable to derive the key id from the public key, it will not be published");
+            }
+            key_pair.public_key = Some(public_key);
             info!("Public key successfully added to application state");
         }
         Err(err) => warn!(
@@ -238,7 +300,33 @@ fn manage_keys(rocket: Rocket<Build>) -> Rocket<Build> {
             config.cert.public_key_path, err
         ),
     }
-    server_manage
+    match read_private_key(&config) {
+        Ok(private_key) => {
+            key_pair.private_key = Some(private_key.with_key_id(key_id));
+            info!("Private key successfully added to application state");
+        }
+        Err(err) => warn!(
+            "Unable to read the private key from {}: {}",
+            config.cert.private_key_path, err
+        ),
+    }
+    let keys = Keys::new(key_pair);
+    task::spawn(key_reload_task(config, keys.clone()));
+    rocket.manage(keys)
+}
+
+/// Create an empty [LoginAttempts] and let the rocket build state manage it.
+/// This allows the login to reject further logins after too many failed ones.
+///
+/// # Arguments
+///
+/// * `rocket`: the build state to attach the [LoginAttempts] to
+///
+/// returns: Rocket<Build>
+fn manage_login_attempts(rocket: Rocket<Build>) -> Rocket<Build> {
+    info!("Create the failed login attempts and let the server manage it");
+    let login_attempts: LoginAttempts = Default::default();
+    rocket.manage(login_attempts)
 }
 
 /// Create an empty [MemberStateMutex] and let the rocket build state manage it.
@@ -255,6 +343,95 @@ fn manage_member_state(rocket: Rocket<Build>) -> Rocket<Build> {
     rocket.manage(member_state)
 }
 
+/// Create the cache for the member photo thumbnails and let the rocket build state manage it.
+///
+/// # Arguments
+///
+/// * `rocket`: the build state to let manage the thumbnail cache
+///
+/// returns: Rocket<Build>
+fn manage_thumbnail_cache(rocket: Rocket<Build>) -> Rocket<Build> {
+    info!("Create the thumbnail cache and let the server manage it");
+    let thumbnail_cache: ThumbnailCache = Default::default();
+    rocket.manage(thumbnail_cache)
+}
+
+/// Create the cache for the calendar events and let the rocket build state manage it.
+/// Warnings will be printed to the log for calendars whose url is not configured.
+///
+/// # Arguments
+///
+/// * `rocket`: the build state to let manage the calendar cache
+///
+/// returns: Rocket<Build>
+fn manage_calendar_cache(rocket: Rocket<Build>) -> Rocket<Build> {
+    let config = rocket_configuration(&rocket);
+    if config.calendar.ical_url.is_empty() {
+        warn!("The url of the public calendar is not configured, requests for it will fail");
+    }
+    if config.calendar.ical_internal_url.is_empty() {
+        warn!("The url of the internal calendar is not configured, requests for it will fail");
+    }
+    info!("Create the calendar cache and let the server manage it");
+    let calendar_cache = CalendarCache::new(&config.calendar);
+    rocket.manage(calendar_cache)
+}
+
+/// Create the cache for the count statistics and let the rocket build state manage it.
+///
+/// # Arguments
+///
+/// * `rocket`: the build state to let manage the statistic cache
+///
+/// returns: Rocket<Build>
+fn manage_statistic_cache(rocket: Rocket<Build>) -> Rocket<Build> {
+    info!("Create the statistic cache and let the server manage it");
+    let statistic_cache: StatisticCache = Default::default();
+    rocket.manage(statistic_cache)
+}
+
+/// Create the [WebhookQueue] which delivers the score changes in the background and let the rocket build state manage it.
+///
+/// # Arguments
+///
+/// * `rocket`: the build state to let manage the webhook queue
+///
+/// returns: Rocket<Build>
+fn manage_webhook_queue(rocket: Rocket<Build>) -> Rocket<Build> {
+    let config = rocket_configuration(&rocket);
+    info!(
+        "Create the webhook queue for {} webhooks and let the server manage it",
+        config.archive.webhook.urls.len()
+    );
+    let webhook_queue = WebhookQueue::new(&config.archive.webhook);
+    rocket.manage(webhook_queue)
+}
+
+/// Create the client to access the documents on the WebDav server and let the rocket build state manage it.
+///
+/// # Arguments
+///
+/// * `rocket`: the build state to let manage the document client
+///
+/// returns: Rocket<Build>
+fn manage_document_client(rocket: Rocket<Build>) -> Rocket<Build> {
+    info!("Create the document client and let the server manage it");
+    rocket.manage(create_client())
+}
+
+/// Create the [LdapConnectionManager] for all searches on the directory server and let the rocket build state manage it.
+///
+/// # Arguments
+///
+/// * `rocket`: the build state to let manage the connection manager
+///
+/// returns: Rocket<Build>
+fn manage_ldap_connection_manager(rocket: Rocket<Build>) -> Rocket<Build> {
+    info!("Create the ldap connection manager and let the server manage it");
+    let config = rocket_configuration(&rocket);
+    rocket.manage(LdapConnectionManager::new(&config))
+}
+
 /// Initialize the database client and let the rocket build state manage it.
 ///
 /// # Arguments
@@ -271,26 +448,62 @@ async fn manage_database_client(rocket: Rocket<Build>) -> Rocket<Build> {
 /// Create a new task which synchronizes the member state with the directory server in the interval given in the [Config].
 /// If there is no [MemberStateMutex] managed by the rocket build state, a warning will be printed and nothing will happen.
 /// This means that [manage_member_state] should be called with the build state first.
+/// A shutdown fairing stops the task and waits up to [crate::config::LdapConfig::synchronization_shutdown_timeout]
+/// for a running synchronization to finish.
 ///
 /// # Arguments
 ///
-/// * `rocket`: the rocket build state to fetch the [MemberStateMutex] from
+/// * `rocket`: the rocket build state to fetch the [MemberStateMutex] and the [LdapConnectionManager] from
 ///
-/// returns: ()
-fn register_user_sync_task(rocket: &Rocket<Build>) {
+/// returns: Rocket<Build>
+fn register_user_sync_task(rocket: Rocket<Build>) -> Rocket<Build> {
     info!("Create the member synchronization task and run it");
-    let config = rocket_configuration(rocket);
+    let config = rocket_configuration(&rocket);
     let member_state_option = rocket.state::<MemberStateMutex>();
     if member_state_option.is_none() {
         warn!("Unable to retrieve member state, scheduled user synchronization will not work. Was 'manage_member_state()' called before?");
-        return;
+        return rocket;
     }
     let mut member_state_clone = member_state_option
         .expect("Member state for synchronizing")
         .clone();
-    task::spawn(async move {
-        member_synchronization_task(&config, &mut member_state_clone).await;
+    let metrics = rocket
+        .state::<MetricsRegistry>()
+        .cloned()
+        .unwrap_or_default();
+    let connection_manager = rocket
+        .state::<LdapConnectionManager>()
+        .cloned()
+        .unwrap_or_else(|| LdapConnectionManager::new(&config));
+    let timeout = Duration::from_secs(config.ldap.synchronization_shutdown_timeout);
+    let (shutdown_sender, shutdown_receiver) = watch::channel(false);
+    let sync_task = task::spawn(async move {
+        member_synchronization_task(
+            &config,
+            &connection_manager,
+            &mut member_state_clone,
+            &metrics,
+            shutdown_receiver,
+        )
+        .await;
     });
+    let sync_task = Arc::new(Mutex::new(Some(sync_task)));
+    rocket.attach(AdHoc::on_shutdown("Member synchronization", move |_| {
+        let sync_task = sync_task.clone();
+        let _ = shutdown_sender.send(true);
+        Box::pin(async move {
+            info!("shutdown requested, finishing sync");
+            if let Some(handle) = sync_task.lock().await.take() {
+                match tokio::time::timeout(timeout, handle).await {
+                    Ok(_) => info!("sync finished, exiting"),
+                    Err(_) => warn!(
+                        "The member synchronization did not finish within {} seconds, exiting anyway",
+                        timeout.as_secs()
+                    ),
+                }
+            }
+        })
+    }))
 }
 
 /// Retrieve the configuration from the current rocket build state.
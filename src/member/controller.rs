@@ -15,6 +15,10 @@
 // along with this program; if not, write to the Free Software
 // Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
 
+use std::collections::BTreeMap;
+
+use base64::{engine, Engine};
+use chrono::Datelike;
 use ldap3::tokio::task;
 use rocket::http::Status;
 use rocket::serde::json::Json;
@@ -22,36 +26,202 @@ use rocket::State;
 use rocket_okapi::openapi;
 
 use crate::config::Config;
+use crate::csv::CsvAttachment;
+use crate::ldap::pool::LdapConnectionManager;
 use crate::ldap::sync::synchronize_members_and_groups;
-use crate::member::model::{Crew, Member, WebMember, WebRegister};
-use crate::member::photo::Photo;
-use crate::member::state::Repository;
+use crate::member::export::crew_csv;
+use crate::member::model::{
+    CachedCrew, ContactSheetEntry, Crew, ExecutiveAudit, GenderGroup, Member, MemberCard,
+    MemberTenure, RegisterCount, SynchronizationStatus, UpcomingBirthday, WebMember, WebRegister,
+};
+use crate::member::photo::{thumbnail, Photo, ThumbnailCache};
+use crate::member::state::{InitializedMemberState, MemberStateUnavailable, Repository};
+use crate::member::vcard::VCard;
+use crate::metrics::MetricsRegistry;
 use crate::openapi::{ApiError, ApiResult};
+use crate::user::executives::{audit, ExecutiveRole, Secretary};
 use crate::MemberStateMutex;
 
+#[cfg(test)]
+#[path = "controller_tests.rs"]
+mod controller_tests;
+
 /// Get all member without any sensitive data.
 /// Intended for the web representation of all member.
+/// Members which are not listed are only included for authenticated users.
+/// The `include_unlisted` flag is only honored for executives.
 ///
 /// # Arguments
 ///
-/// `member_state`: the current state of all members
+/// * `include_unlisted`: whether to include members which are not listed, only honored for executives
+/// * `authenticated`: the member guard, members which are not listed are omitted if absent
+/// * `_initialized`: the guard which rejects the request until the members have been synchronized
+/// * `member_state`: the current state of all members
 ///
-/// returns: ApiResult<Crew>
+/// returns: CachedCrew which is 'Not Modified' if the crew has not changed since the last synchronization
 #[openapi(tag = "Members")]
-#[get("/")]
-pub async fn all_members(member_state: &State<MemberStateMutex>) -> ApiResult<Crew> {
+#[get("/?<include_unlisted>")]
+pub async fn all_members(
+    include_unlisted: Option<bool>,
+    authenticated: Option<Member>,
+    _initialized: InitializedMemberState,
+    member_state: &State<MemberStateMutex>,
+) -> CachedCrew {
     let members = member_state.read().await;
+    let executive = authenticated.as_ref().is_some_and(|member| {
+        members
+            .executives
+            .iter()
+            .any(|e| e.members.contains(&member.full_username))
+    });
+    let include_unlisted =
+        authenticated.is_some() || (executive && include_unlisted.unwrap_or(false));
+    let member_filter: &dyn Fn(&Member) -> bool = &|m| include_unlisted || m.listed;
     let member_mapper: &dyn Fn(&Member) -> WebMember = &|m| WebMember::from_member(m, false);
-    Ok(Json(Crew::new(
+    let crew = Crew::new(
         &members.members_by_register,
         &members.sutlers,
         &members.honorary_members,
+        member_filter,
         member_mapper,
-        &|r| WebRegister::from_register(r, member_mapper),
-    )))
+        &|r| WebRegister::from_register(r, member_filter, member_mapper),
+    );
+    CachedCrew::new(crew, members.last_sync, include_unlisted)
+}
+
+/// Export the whole crew as CSV for spreadsheets, including the contact information of all members.
+/// The musicians are listed by their registers followed by the sutlers and the honorary members.
+///
+/// # Arguments
+///
+/// * `_secretary_role`: the secretary role guard
+/// * `member_state`: the current state of all members
+///
+/// returns: CsvAttachment
+#[openapi(tag = "Members")]
+#[get("/export.csv")]
+pub async fn export_crew(
+    _secretary_role: ExecutiveRole<Secretary>,
+    member_state: &State<MemberStateMutex>,
+) -> CsvAttachment {
+    crew_csv(&*member_state.read().await)
+}
+
+/// Get all registers with the number of their musicians in the order of the crew.
+/// This is much cheaper than requesting all members if only the registers are required.
+/// Members which are not listed are only counted for authenticated users.
+///
+/// # Arguments
+///
+/// * `authenticated`: the member guard, members which are not listed are not counted if absent
+/// * `member_state`: the current state of all members
+///
+/// returns: ApiResult<Vec<RegisterCount>>
+#[openapi(tag = "Members")]
+#[get("/registers")]
+pub async fn registers(
+    authenticated: Option<Member>,
+    member_state: &State<MemberStateMutex>,
+) -> ApiResult<Vec<RegisterCount>> {
+    let members = member_state.read().await;
+    let member_filter: &dyn Fn(&Member) -> bool = &|m| authenticated.is_some() || m.listed;
+    Ok(Json(
+        members
+            .members_by_register
+            .iter()
+            .map(|entry| RegisterCount::from_register(entry, member_filter))
+            .collect(),
+    ))
+}
+
+/// Get the primary contact information of all members flattened into a printable list.
+/// The list is sorted by the last name and the first name of the members.
+/// Members without any contact information are omitted if configured.
+///
+/// # Arguments
+///
+/// * `_member`: the member guard which must be logged in
+/// * `member_state`: the current state of all members
+/// * `config`: the application configuration
+///
+/// returns: ApiResult<Vec<ContactSheetEntry>>
+#[openapi(tag = "Members")]
+#[get("/contact-sheet")]
+pub async fn contact_sheet(
+    _member: Member,
+    member_state: &State<MemberStateMutex>,
+    config: &State<Config>,
+) -> ApiResult<Vec<ContactSheetEntry>> {
+    let members = member_state.read().await;
+    let mut sorted_members: Vec<&Member> = members.all_members.iter().collect();
+    sorted_members.sort_by(|a, b| {
+        a.last_name
+            .cmp(&b.last_name)
+            .then_with(|| a.first_name.cmp(&b.first_name))
+    });
+    let entries = sorted_members
+        .into_iter()
+        .map(|member| {
+            let register = members
+                .members_by_register
+                .iter()
+                .find(|r| r.members.contains(member))
+                .map(|r| &r.register);
+            ContactSheetEntry::from_member(member, register)
+        })
+        .filter(|entry| !config.members.contact_sheet_omit_without_contact || entry.has_contact())
+        .collect();
+    Ok(Json(entries))
+}
+
+/// Get all active members grouped by their gender, e.g. for the demographic reports of the association.
+/// Members without a known gender form the group 'u'.
+/// The groups are sorted by their gender and the members by their last and first name.
+///
+/// # Arguments
+///
+/// * `_member`: the member guard which must be logged in
+/// * `member_state`: the current state of all members
+///
+/// returns: ApiResult<Vec<GenderGroup>>
+#[openapi(tag = "Members")]
+#[get("/by-gender")]
+pub async fn members_by_gender(
+    _member: Member,
+    member_state: &State<MemberStateMutex>,
+) -> ApiResult<Vec<GenderGroup>> {
+    let members = member_state.read().await;
+    let mut groups: BTreeMap<char, Vec<&Member>> = BTreeMap::new();
+    members
+        .all_members
+        .iter()
+        .filter(|m| m.active)
+        .for_each(|m| groups.entry(m.gender).or_default().push(m));
+    Ok(Json(
+        groups
+            .into_iter()
+            .map(|(gender, mut group)| {
+                group.sort_by(|a, b| {
+                    a.last_name
+                        .cmp(&b.last_name)
+                        .then_with(|| a.first_name.cmp(&b.first_name))
+                });
+                GenderGroup {
+                    gender,
+                    count: group.len(),
+                    members: group
+                        .into_iter()
+                        .map(|m| WebMember::from_member(m, false))
+                        .collect(),
+                }
+            })
+            .collect(),
+    ))
 }
 
-/// Return the profile photo of a member in the JPEG format.
+/// Return the profile photo of a member in the JPEG or PNG format.
+/// If the member has no photo, the server will return a 'Not Found'.
+/// The response contains an entity tag, if it matches the `If-None-Match` header the server will return a 'Not Modified' without the photo.
 ///
 /// # Arguments
 ///
@@ -69,21 +239,252 @@ pub async fn photo(
     member_state_lock.all_members.find(&username).map_or_else(
         || {
             debug!("unable to find member with username {}", username);
-            Err(ApiError {
-                err: "Not Found".to_string(),
-                msg: Some("No member with such username".to_string()),
-                http_status_code: Status::NotFound.code,
-            })
+            Err(member_not_found())
+        },
+        |member| {
+            if member.photo.is_empty() {
+                debug!("member with username {} has no photo", username);
+                return Err(ApiError {
+                    err: "Not Found".to_string(),
+                    msg: Some("The member has no photo".to_string()),
+                    http_status_code: Status::NotFound.code,
+                });
+            }
+            Ok(Photo(member.photo.to_vec(), member.photo_hash.clone()))
         },
-        |member| Ok(Photo(member.photo.to_vec())),
     )
 }
 
+/// Return a thumbnail of the profile photo of a member in the JPEG or PNG format.
+/// The thumbnail fits into [crate::config::MembersConfig::thumbnail_size] while keeping the aspect ratio.
+/// If the photo cannot be downscaled, the original photo is returned.
+/// Everything else behaves like [photo].
+///
+/// # Arguments
+///
+/// * `username`: the username of the member whose photo is requested
+/// * `member_state`: the state of all member
+/// * `thumbnail_cache`: the cache of the generated thumbnails
+/// * `config`: the application configuration
+///
+/// returns: Result<Photo, Error>
+#[openapi(tag = "Members")]
+#[get("/<username>/photo/thumbnail")]
+pub async fn photo_thumbnail(
+    username: String,
+    member_state: &State<MemberStateMutex>,
+    thumbnail_cache: &State<ThumbnailCache>,
+    config: &State<Config>,
+) -> Result<Photo, ApiError> {
+    let original = photo(username.clone(), member_state).await?;
+    Ok(thumbnail(
+        thumbnail_cache,
+        &username,
+        original,
+        config.members.thumbnail_size,
+    )
+    .await)
+}
+
+/// Get a single member by their username.
+/// Sensitive data is only included for authenticated users.
+/// Members which are not listed can only be requested by authenticated users.
+///
+/// # Arguments
+///
+/// * `username`: the username of the requested member
+/// * `authenticated`: the member guard, sensitive data is omitted if absent
+/// * `member_state`: the state of all member
+///
+/// returns: ApiResult<WebMember>
+#[openapi(tag = "Members")]
+#[get("/<username>")]
+pub async fn member(
+    username: String,
+    authenticated: Option<Member>,
+    member_state: &State<MemberStateMutex>,
+) -> ApiResult<WebMember> {
+    let member_state_lock = member_state.read().await;
+    member_state_lock
+        .all_members
+        .find(&username)
+        .filter(|m| authenticated.is_some() || m.listed)
+        .map(|m| Json(WebMember::from_member(m, authenticated.is_some())))
+        .ok_or_else(|| {
+            debug!("unable to find member with username {}", username);
+            member_not_found()
+        })
+}
+
+/// Get the membership duration of a member in completed years together with the year the member joined.
+/// If the joining year of the member is unknown, both values are omitted.
+/// Members which are not listed can only be requested by authenticated users.
+///
+/// # Arguments
+///
+/// * `username`: the username of the requested member
+/// * `authenticated`: the member guard, members which are not listed are not found if absent
+/// * `member_state`: the state of all member
+///
+/// returns: ApiResult<MemberTenure>
+#[openapi(tag = "Members")]
+#[get("/<username>/tenure")]
+pub async fn tenure(
+    username: String,
+    authenticated: Option<Member>,
+    member_state: &State<MemberStateMutex>,
+) -> ApiResult<MemberTenure> {
+    let member_state_lock = member_state.read().await;
+    let current_year = chrono::Local::now().year() as u32;
+    member_state_lock
+        .all_members
+        .find(&username)
+        .filter(|m| authenticated.is_some() || m.listed)
+        .map(|m| Json(MemberTenure::from_member(m, current_year)))
+        .ok_or_else(|| {
+            debug!("unable to find member with username {}", username);
+            member_not_found()
+        })
+}
+
+/// Get the members whose birthday is within the next days, sorted by the next occurrence.
+/// Members whose birthday is unknown or does not match [crate::config::MembersConfig::birthday_format] are omitted.
+///
+/// # Arguments
+///
+/// * `withinDays`: the number of days to look ahead including today, defaults to `30`
+/// * `_member`: the member guard which must be logged in
+/// * `member_state`: the state of all member
+///
+/// returns: ApiResult<Vec<UpcomingBirthday>>
+#[openapi(tag = "Members")]
+#[get("/birthdays?<withinDays>")]
+#[allow(non_snake_case)]
+pub async fn birthdays(
+    withinDays: Option<u16>,
+    _member: Member,
+    member_state: &State<MemberStateMutex>,
+) -> ApiResult<Vec<UpcomingBirthday>> {
+    let within_days = withinDays.unwrap_or(30) as i64;
+    let today = chrono::Local::now().date_naive();
+    let member_state_lock = member_state.read().await;
+    let mut birthdays: Vec<UpcomingBirthday> = member_state_lock
+        .all_members
+        .iter()
+        .filter_map(|m| UpcomingBirthday::from_member(m, today))
+        .filter(|b| b.days < within_days)
+        .collect();
+    birthdays.sort_by(|a, b| {
+        (a.days, &a.member.last_name, &a.member.first_name).cmp(&(
+            b.days,
+            &b.member.last_name,
+            &b.member.first_name,
+        ))
+    });
+    Ok(Json(birthdays))
+}
+
+/// Get the profile card of a member which contains the member, their registers and the thumbnail of their photo.
+/// The thumbnail is the same as the one of [photo_thumbnail], the original photo is only available via [photo].
+/// Sensitive data is only included for authenticated users.
+/// Members which are not listed can only be requested by authenticated users.
+///
+/// # Arguments
+///
+/// * `username`: the username of the member whose card is requested
+/// * `authenticated`: the member guard, sensitive data is omitted if absent
+/// * `member_state`: the state of all member
+/// * `thumbnail_cache`: the cache of the generated thumbnails
+/// * `config`: the application configuration
+///
+/// returns: ApiResult<MemberCard>
+#[openapi(tag = "Members")]
+#[get("/<username>/card")]
+pub async fn card(
+    username: String,
+    authenticated: Option<Member>,
+    member_state: &State<MemberStateMutex>,
+    thumbnail_cache: &State<ThumbnailCache>,
+    config: &State<Config>,
+) -> ApiResult<MemberCard> {
+    let (member, registers, original) = {
+        let member_state_lock = member_state.read().await;
+        let member = member_state_lock
+            .all_members
+            .find(&username)
+            .filter(|m| authenticated.is_some() || m.listed)
+            .ok_or_else(|| {
+                debug!("unable to find member with username {}", username);
+                member_not_found()
+            })?;
+        let registers = member_state_lock
+            .members_by_register
+            .iter()
+            .filter(|r| r.members.contains(member))
+            .map(|r| r.register.clone())
+            .collect();
+        let original = (!member.photo.is_empty())
+            .then(|| Photo(member.photo.to_vec(), member.photo_hash.clone()));
+        (
+            WebMember::from_member(member, authenticated.is_some()),
+            registers,
+            original,
+        )
+    };
+    let photo = match original {
+        Some(original) => Some(
+            thumbnail(
+                thumbnail_cache,
+                &username,
+                original,
+                config.members.thumbnail_size,
+            )
+            .await,
+        ),
+        None => None,
+    };
+    Ok(Json(MemberCard {
+        member,
+        registers,
+        photo: photo.map(|p| engine::general_purpose::STANDARD.encode(p.0)),
+    }))
+}
+
+/// Export the contact details of a member as vCard to save them to a phone.
+/// This exposes sensitive data and therefore requires an authenticated member.
+///
+/// # Arguments
+///
+/// * `username`: the username of the member whose contact details are requested
+/// * `_member`: the member guard which must be logged in
+/// * `member_state`: the state of all member
+///
+/// returns: Result<VCard, ApiError>
+#[openapi(tag = "Members")]
+#[get("/<username>/vcard")]
+pub async fn vcard(
+    username: String,
+    _member: Member,
+    member_state: &State<MemberStateMutex>,
+) -> Result<VCard, ApiError> {
+    let member_state_lock = member_state.read().await;
+    member_state_lock
+        .all_members
+        .find(&username)
+        .map(VCard::from_member)
+        .ok_or_else(|| {
+            debug!("unable to find member with username {}", username);
+            member_not_found()
+        })
+}
+
 /// Synchronize all members as soon as possible.
 ///
 /// # Arguments
 ///
 /// * `member_state`: the current state of the members to update
+/// * `metrics`: the registry to record the outcome of the synchronization in
+/// * `ldap`: the connection manager of the directory server
 /// * `config`: the application configuration
 ///
 /// returns: ApiResult<()>
@@ -91,17 +492,75 @@ pub async fn photo(
 #[post("/synchronize")]
 pub fn synchronize(
     member_state: &State<MemberStateMutex>,
+    metrics: &State<MetricsRegistry>,
+    ldap: &State<LdapConnectionManager>,
     config: &State<Config>,
 ) -> ApiResult<()> {
     let conf_copy = config.inner().clone();
+    let ldap_clone = ldap.inner().clone();
     let mut member_state_clone = member_state.inner().clone();
+    let metrics_clone = metrics.inner().clone();
     let fetch_task = async move {
-        synchronize_members_and_groups(&conf_copy, &mut member_state_clone).await;
+        synchronize_members_and_groups(
+            &conf_copy,
+            &ldap_clone,
+            &mut member_state_clone,
+            &metrics_clone,
+        )
+        .await;
     };
     task::spawn(fetch_task);
     Ok(Json(()))
 }
 
+/// Get the status of the member synchronization with the directory server.
+/// This includes the time of the last successful synchronization, its duration and the number of entries per collection.
+/// The error of a failed synchronization is only included for authenticated users.
+///
+/// # Arguments
+///
+/// * `authenticated`: the member guard, the error is omitted if absent
+/// * `member_state`: the current state of the members
+///
+/// returns: ApiResult<SynchronizationStatus>
+#[openapi(tag = "Members")]
+#[get("/sync/status")]
+pub async fn synchronization_status(
+    authenticated: Option<Member>,
+    member_state: &State<MemberStateMutex>,
+) -> ApiResult<SynchronizationStatus> {
+    let member_state_lock = member_state.read().await;
+    Ok(Json(SynchronizationStatus::from_state(
+        &member_state_lock,
+        authenticated.is_some(),
+    )))
+}
+
+/// Audit the memberships of all executive groups for the governance records.
+/// Every member entry of a group is resolved against all members, entries without a known member are flagged as stale.
+///
+/// # Arguments
+///
+/// * `_secretary_role`: the secretary role guard
+/// * `member_state`: the current state of the members
+/// * `config`: the application configuration
+///
+/// returns: ApiResult<Vec<ExecutiveAudit>>
+#[openapi(tag = "Members")]
+#[get("/executives/audit")]
+pub async fn executives_audit(
+    _secretary_role: ExecutiveRole<Secretary>,
+    member_state: &State<MemberStateMutex>,
+    config: &State<Config>,
+) -> ApiResult<Vec<ExecutiveAudit>> {
+    let member_state_lock = member_state.read().await;
+    Ok(Json(audit(
+        &member_state_lock.executives,
+        &config.ldap.executive_mapping,
+        &member_state_lock.all_members,
+    )))
+}
+
 /// Print all member to the debug console.
 /// Only for debug purposes.
 #[cfg(debug_assertions)]
@@ -110,3 +569,21 @@ pub fn synchronize(
 pub async fn list_members(member_state: &State<MemberStateMutex>) {
     debug!("{:?}", member_state.read().await.all_members);
 }
+
+/// Respond to requests which have been rejected with 'Service Unavailable' by a request guard.
+/// This is the case for [InitializedMemberState] and [ExecutiveRole] while the members have not been synchronized yet.
+///
+/// returns: MemberStateUnavailable
+#[catch(503)]
+pub fn member_state_unavailable() -> MemberStateUnavailable {
+    MemberStateUnavailable
+}
+
+/// The error which is returned when a requested member does not exist.
+fn member_not_found() -> ApiError {
+    ApiError {
+        err: "Not Found".to_string(),
+        msg: Some("No member with such username".to_string()),
+        http_status_code: Status::NotFound.code,
+    }
+}
@@ -0,0 +1,99 @@
+// OpenKeg, the lightweight backend of the Musikverein Leopoldsdorf.
+// Copyright (C) 2022  Richard Stöckl
+//
+// This program is free software; you can redistribute it and/or
+// modify it under the terms of the GNU General Public License
+// as published by the Free Software Foundation; either version 2
+// of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+
+use chrono::{Days, NaiveDate};
+use rocket::http::Header;
+use rocket::local::blocking::Client;
+use serde_json::Value;
+
+use super::*;
+use crate::member::state::MemberState;
+use crate::user::key::{KeyPair, Keys, PrivateKey, PublicKey};
+use crate::user::tokens::generate_token;
+use crate::user::tokens::tokens_tests::{PRIVATE_KEY, PUBLIC_KEY};
+
+fn client(update: impl FnOnce(&mut MemberState)) -> Client {
+    let member_state = MemberState::mutex();
+    {
+        let mut state = member_state.try_write().expect("member state");
+        update(&mut state);
+        state.initialized = true;
+    }
+    let keys = Keys::new(KeyPair {
+        public_key: Some(PublicKey(PUBLIC_KEY.as_bytes().to_vec())),
+        private_key: Some(PrivateKey(PRIVATE_KEY.as_bytes().to_vec(), None)),
+    });
+    let rocket = rocket::build()
+        .manage(Config::default())
+        .manage(keys)
+        .manage(member_state)
+        .mount("/", routes![all_members, birthdays]);
+    Client::tracked(rocket).expect("rocket client")
+}
+
+fn bearer(member: &Member) -> Header<'static> {
+    let private_key = PrivateKey(PRIVATE_KEY.as_bytes().to_vec(), None);
+    let (_, token) =
+        generate_token(member, false, &Config::default(), &private_key).expect("token");
+    Header::new("Authorization", format!("Bearer {}", token))
+}
+
+fn get_json(client: &Client, uri: &str, member: &Member) -> Value {
+    let response = client
+        .get(uri.to_string())
+        .header(bearer(member))
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    response.into_json().expect("json")
+}
+
+#[test]
+fn birthdays_within_days() {
+    let in_forty_days = chrono::Local::now()
+        .date_naive()
+        .checked_add_days(Days::new(40))
+        .expect("date");
+    let member = Member {
+        birth_date: NaiveDate::from_ymd_opt(2000, in_forty_days.month(), in_forty_days.day()),
+        ..Member::test_member("Karl", "Huber")
+    };
+    let client = client(|state| {
+        state.all_members.insert(member.clone());
+    });
+    let default = get_json(&client, "/birthdays", &member);
+    assert_eq!(default.as_array().map(Vec::len), Some(0));
+    let within = get_json(&client, "/birthdays?withinDays=60", &member);
+    assert_eq!(within.as_array().map(Vec::len), Some(1));
+}
+
+#[test]
+fn all_members_includes_unlisted_for_authenticated_members() {
+    let member = Member::test_member("Karl", "Huber");
+    let unlisted = Member {
+        listed: false,
+        ..Member::test_member("Anna", "Gruber")
+    };
+    let client = client(|state| {
+        state.all_members.insert(member.clone());
+        state.sutlers.push_back(member.clone());
+        state.sutlers.push_back(unlisted.clone());
+    });
+    let anonymous: Value = client.get("/").dispatch().into_json().expect("json");
+    assert_eq!(anonymous["sutlers"].as_array().map(Vec::len), Some(1));
+    let authenticated = get_json(&client, "/", &member);
+    assert_eq!(authenticated["sutlers"].as_array().map(Vec::len), Some(2));
+}
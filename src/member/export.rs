@@ -0,0 +1,123 @@
+// OpenKeg, the lightweight backend of the Musikverein Leopoldsdorf.
+// Copyright (C) 2022  Richard Stöckl
+//
+// This program is free software; you can redistribute it and/or
+// modify it under the terms of the GNU General Public License
+// as published by the Free Software Foundation; either version 2
+// of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+
+use crate::csv::{csv_field, CsvAttachment};
+use crate::member::model::Member;
+use crate::member::state::MemberState;
+
+#[cfg(test)]
+#[path = "export_tests.rs"]
+mod export_tests;
+
+/// The header row of the crew export.
+const HEADER: [&str; 9] = [
+    "First Name",
+    "Last Name",
+    "Joining",
+    "Active",
+    "Official",
+    "Register",
+    "Mobile",
+    "Mail",
+    "Address",
+];
+
+/// The separator of multiple values within a single field such as the phone numbers.
+const VALUE_SEPARATOR: &str = "; ";
+
+/// The register column of the sutlers.
+const SUTLER_REGISTER: &str = "Sutler";
+
+/// The register column of the honorary members.
+const HONORARY_REGISTER: &str = "Honorary Member";
+
+/// Create the whole crew as CSV intended to be imported into a spreadsheet.
+/// The musicians are listed by their registers followed by the sutlers and the honorary members.
+/// A member which is part of multiple registers is listed once per register.
+///
+/// # Arguments
+///
+/// * `member_state`: the state which contains the crew
+///
+/// returns: CsvAttachment
+pub fn crew_csv(member_state: &MemberState) -> CsvAttachment {
+    let musicians = member_state.members_by_register.iter().flat_map(|entry| {
+        entry
+            .members
+            .iter()
+            .map(|member| (member, entry.register.name.as_str()))
+    });
+    let sutlers = member_state
+        .sutlers
+        .iter()
+        .map(|member| (member, SUTLER_REGISTER));
+    let honorary_members = member_state
+        .honorary_members
+        .iter()
+        .map(|member| (member, HONORARY_REGISTER));
+    let rows = musicians
+        .chain(sutlers)
+        .chain(honorary_members)
+        .map(|(member, register)| row(member, register));
+    let csv = std::iter::once(HEADER.map(|h| h.to_string()).to_vec())
+        .chain(rows)
+        .map(|fields| {
+            let line: Vec<String> = fields.iter().map(|f| csv_field(f)).collect();
+            format!("{}\r\n", line.join(","))
+        })
+        .collect();
+    CsvAttachment {
+        filename: "crew.csv".to_string(),
+        content: csv,
+    }
+}
+
+/// Create the fields of a single row in the order of the [HEADER].
+///
+/// # Arguments
+///
+/// * `member`: the member of the row
+/// * `register`: the register the member is listed in
+///
+/// returns: Vec<String>
+fn row(member: &Member, register: &str) -> Vec<String> {
+    let address = member
+        .address
+        .as_ref()
+        .map(|a| {
+            format!(
+                "{} {}, {} {}",
+                a.street, a.house_number, a.postal_code, a.city
+            )
+        })
+        .unwrap_or_default();
+    vec![
+        member.first_name.to_string(),
+        member.last_name.to_string(),
+        if member.joining > 0 {
+            member.joining.to_string()
+        } else {
+            "".to_string()
+        },
+        member.active.to_string(),
+        member.official.to_string(),
+        register.to_string(),
+        member.mobile.join(VALUE_SEPARATOR),
+        member.mail.join(VALUE_SEPARATOR),
+        address,
+    ]
+}
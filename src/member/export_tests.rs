@@ -0,0 +1,79 @@
+// OpenKeg, the lightweight backend of the Musikverein Leopoldsdorf.
+// Copyright (C) 2022  Richard Stöckl
+//
+// This program is free software; you can redistribute it and/or
+// modify it under the terms of the GNU General Public License
+// as published by the Free Software Foundation; either version 2
+// of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+
+use std::collections::LinkedList;
+
+use super::*;
+use crate::member::model::{Address, Group};
+use crate::member::state::RegisterEntry;
+
+fn member(first_name: &str, last_name: &str) -> Member {
+    Member {
+        joining: 2008,
+        ..Member::test_member(first_name, last_name)
+    }
+}
+
+fn group(name: &str) -> Group {
+    Group {
+        name: name.to_string(),
+        name_plural: format!("{}s", name),
+        description: "".to_string(),
+        members: vec![],
+    }
+}
+
+#[rocket::async_test]
+async fn crew_rows() {
+    let member_state = MemberState::mutex();
+    let mut state = member_state.write().await;
+    let musician = Member {
+        mobile: vec!["+43 664 1".to_string(), "+43 699 2".to_string()],
+        mail: vec!["max@mvl.at".to_string()],
+        address: Some(Address {
+            street: "Hauptstraße".to_string(),
+            house_number: "1".to_string(),
+            postal_code: "2333".to_string(),
+            city: "Leopoldsdorf".to_string(),
+            state: "".to_string(),
+            country_code: "AT".to_string(),
+        }),
+        ..member("Max", "Mustermann")
+    };
+    state.members_by_register.push_back(RegisterEntry {
+        register: group("Klarinette"),
+        members: LinkedList::from([musician]),
+    });
+    state.sutlers.push_back(Member {
+        joining: 0,
+        ..member("Erika", "Muster\"frau\"")
+    });
+    state
+        .honorary_members
+        .push_back(member("Karl", "Obmann, sen."));
+    let csv = crew_csv(&state).content;
+    let lines: Vec<&str> = csv.split_terminator("\r\n").collect();
+    assert_eq!(
+        lines,
+        vec![
+            "First Name,Last Name,Joining,Active,Official,Register,Mobile,Mail,Address",
+            "Max,Mustermann,2008,true,false,Klarinette,+43 664 1; +43 699 2,max@mvl.at,\"Hauptstraße 1, 2333 Leopoldsdorf\"",
+            "Erika,\"Muster\"\"frau\"\"\",,true,false,Sutler,,,",
+            "Karl,\"Obmann, sen.\",2008,true,false,Honorary Member,,,",
+        ]
+    );
+}
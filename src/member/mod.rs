@@ -21,19 +21,35 @@ use rocket_okapi::settings::OpenApiSettings;
 
 /// Module which handles all the rest endpoints regarding members.
 pub mod controller;
+/// Module which exports the whole crew as CSV.
+pub mod export;
 /// Module which holds the model regarding members and groups.
 pub mod model;
 /// Module which handles all the rest endpoints regarding the member photo.
 pub mod photo;
 /// Module which provides state structs to the application for members.
 pub mod state;
+/// Module which exports the contact details of members as vCard.
+pub mod vcard;
 
 #[cfg(debug_assertions)]
 pub fn get_routes_and_docs(settings: &OpenApiSettings) -> (Vec<rocket::Route>, OpenApi) {
     openapi_get_routes_spec![
         settings: controller::all_members,
+        controller::registers,
+        controller::contact_sheet,
+        controller::export_crew,
+        controller::members_by_gender,
+        controller::birthdays,
+        controller::member,
         controller::photo,
+        controller::photo_thumbnail,
+        controller::card,
+        controller::vcard,
+        controller::tenure,
         controller::synchronize,
+        controller::synchronization_status,
+        controller::executives_audit,
         controller::list_members,
     ]
 }
@@ -42,7 +58,19 @@ pub fn get_routes_and_docs(settings: &OpenApiSettings) -> (Vec<rocket::Route>, O
 pub fn get_routes_and_docs(settings: &OpenApiSettings) -> (Vec<rocket::Route>, OpenApi) {
     openapi_get_routes_spec![
         settings: controller::all_members,
+        controller::registers,
+        controller::contact_sheet,
+        controller::export_crew,
+        controller::members_by_gender,
+        controller::birthdays,
+        controller::member,
         controller::photo,
+        controller::photo_thumbnail,
+        controller::card,
+        controller::vcard,
+        controller::tenure,
         controller::synchronize,
+        controller::synchronization_status,
+        controller::executives_audit,
     ]
 }
@@ -15,17 +15,34 @@
 // along with this program; if not, write to the Free Software
 // Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
 
-use crate::config::Config;
-use crate::ldap::LdapDeserializable;
-use crate::member::state::{HonoraryMembers, MembersByRegister, RegisterEntry, Sutlers};
+use crate::config::{AttributeCandidates, Config};
+use crate::ldap::{normalize_dn, LdapDeserializable};
+use crate::member::photo::content_hash;
+use crate::member::photo::matches_etag;
+use crate::member::state::{
+    HonoraryMembers, MemberState, MembersByRegister, RegisterEntry, Sutlers,
+};
 use crate::openapi::SchemaExample;
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
 use ldap3::SearchEntry;
+use okapi::openapi3::{RefOr, Responses};
+use rocket::http::{Header, Status};
+use rocket::response::Responder;
+use rocket::serde::json::Json;
 use rocket::serde::{Deserialize, Serialize};
+use rocket::{Request, Response};
+use rocket_okapi::gen::OpenApiGenerator;
+use rocket_okapi::response::OpenApiResponderInner;
 use rocket_okapi::JsonSchema;
+use std::borrow::Cow;
 use std::cmp::Ordering;
 use std::collections::{HashMap, LinkedList};
 use std::hash::Hash;
 
+#[cfg(test)]
+#[path = "model_tests.rs"]
+mod model_tests;
+
 /// Representation of the whole crew intended to use for the REST API.
 #[derive(Clone, Default, Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(crate = "rocket::serde", rename_all = "camelCase")]
@@ -39,6 +56,79 @@ pub struct Crew {
     pub honorary_members: LinkedList<WebMember>,
 }
 
+/// The crew together with its entity tag which changes with every synchronization.
+/// If the entity tag matches the `If-None-Match` header, 'Not Modified' is returned without the crew.
+/// The response may be cached for [crate::config::LdapConfig::synchronization_interval].
+pub struct CachedCrew {
+    /// The crew to respond with
+    pub crew: Crew,
+    /// The quoted entity tag of the crew
+    pub etag: String,
+    /// Whether the crew contains data which is only visible for authenticated users
+    pub private: bool,
+}
+
+impl CachedCrew {
+    /// Create the response of the crew with an entity tag derived from the last synchronization.
+    /// Crews with members which are not listed get a different entity tag than the public one.
+    ///
+    /// # Arguments
+    ///
+    /// * `crew`: the crew to respond with
+    /// * `last_sync`: the time of the last successful synchronization
+    /// * `private`: whether the crew contains members which are not listed
+    ///
+    /// returns: CachedCrew
+    pub fn new(crew: Crew, last_sync: Option<DateTime<Utc>>, private: bool) -> Self {
+        let version = last_sync.map(|t| t.timestamp_millis()).unwrap_or_default();
+        let visibility = if private { "private" } else { "public" };
+        Self {
+            crew,
+            etag: format!("\"crew-{}-{}\"", version, visibility),
+            private,
+        }
+    }
+}
+
+impl<'r> Responder<'r, 'static> for CachedCrew {
+    fn respond_to(self, request: &'r Request<'_>) -> rocket::response::Result<'static> {
+        let max_age = request
+            .rocket()
+            .state::<Config>()
+            .map(|c| c.ldap.synchronization_interval)
+            .unwrap_or_default();
+        let visibility = if self.private { "private" } else { "public" };
+        let not_modified = matches_etag(request, &self.etag);
+        let mut response = if not_modified {
+            Response::build().status(Status::NotModified).finalize()
+        } else {
+            Json(self.crew).respond_to(request)?
+        };
+        response.set_header(Header::new("ETag", self.etag));
+        response.set_header(Header::new(
+            "Cache-Control",
+            format!("{}, max-age={}", visibility, max_age),
+        ));
+        response.set_header(Header::new("Vary", "Authorization"));
+        Ok(response)
+    }
+}
+
+impl OpenApiResponderInner for CachedCrew {
+    fn responses(gen: &mut OpenApiGenerator) -> rocket_okapi::Result<Responses> {
+        let mut responses = Json::<Crew>::responses(gen)?;
+        let not_modified_response = okapi::openapi3::Response {
+            description: "The crew matches the provided entity tag and was not modified"
+                .to_string(),
+            ..okapi::openapi3::Response::default()
+        };
+        responses
+            .responses
+            .insert("304".to_string(), RefOr::Object(not_modified_response));
+        Ok(responses)
+    }
+}
+
 /// Representation of a register intended to use for the REST API.
 #[derive(Clone, Default, Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(rename = "Register", crate = "rocket::serde", rename_all = "camelCase")]
@@ -122,6 +212,368 @@ pub struct WebAddress {
     pub country_code: String,
 }
 
+/// A single line of the contact sheet which contains only the primary contact information of a member.
+#[derive(Clone, Default, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(crate = "rocket::serde", rename_all = "camelCase")]
+#[schemars(example = "Self::example")]
+pub struct ContactSheetEntry {
+    /// The first name of the member
+    pub first_name: String,
+    /// The last name of the member
+    pub last_name: String,
+    /// The name of the register of the member if any
+    pub register: Option<String>,
+    /// The primary mobile number of the member
+    pub mobile: Option<String>,
+    /// The primary mail address of the member
+    pub mail: Option<String>,
+}
+
+impl SchemaExample for ContactSheetEntry {
+    fn example() -> Self {
+        Self {
+            first_name: "Karl".to_string(),
+            last_name: "Steinscheisser".to_string(),
+            register: Some("Kukuruz".to_string()),
+            mobile: Some("+43 664 91828374".to_string()),
+            mail: Some("kar@steinscheisser.at".to_string()),
+        }
+    }
+}
+
+impl ContactSheetEntry {
+    /// Create a contact sheet entry of a member.
+    ///
+    /// # Arguments
+    ///
+    /// * `member`: the member to create the entry for
+    /// * `register`: the register the member is part of
+    pub fn from_member(member: &Member, register: Option<&Group>) -> Self {
+        Self {
+            first_name: member.first_name.to_string(),
+            last_name: member.last_name.to_string(),
+            register: register.map(|r| r.name.to_string()),
+            mobile: member.mobile.first().cloned(),
+            mail: member.mail.first().cloned(),
+        }
+    }
+
+    /// Whether this entry contains at least one way to contact the member.
+    pub fn has_contact(&self) -> bool {
+        self.mobile.is_some() || self.mail.is_some()
+    }
+}
+
+/// All active members which share the same gender.
+#[derive(Clone, Default, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(crate = "rocket::serde", rename_all = "camelCase")]
+#[schemars(example = "Self::example")]
+pub struct GenderGroup {
+    /// The gender of the members, 'u' if unknown
+    pub gender: char,
+    /// The number of members in this group
+    pub count: usize,
+    /// The members of this group without sensitive data
+    pub members: Vec<WebMember>,
+}
+
+impl SchemaExample for GenderGroup {
+    fn example() -> Self {
+        Self {
+            gender: 'f',
+            count: 1,
+            members: vec![WebMember::example()],
+        }
+    }
+}
+
+/// A register with the number of its musicians, intended for navigation without fetching the whole crew.
+#[derive(Clone, Default, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(crate = "rocket::serde", rename_all = "camelCase")]
+#[schemars(example = "Self::example")]
+pub struct RegisterCount {
+    /// The name of the register
+    pub name: String,
+    /// The plural name of the register
+    pub name_plural: String,
+    /// The number of musicians in this register
+    pub member_count: usize,
+}
+
+impl RegisterCount {
+    /// Count the musicians of a register.
+    ///
+    /// # Arguments
+    ///
+    /// * `entry`: the register with its members
+    /// * `member_filter`: the filter which members are counted
+    ///
+    /// returns: RegisterCount
+    pub fn from_register(entry: &RegisterEntry, member_filter: &dyn Fn(&Member) -> bool) -> Self {
+        Self {
+            name: entry.register.name.to_string(),
+            name_plural: entry.register.name_plural.to_string(),
+            member_count: entry.members.iter().filter(|m| member_filter(m)).count(),
+        }
+    }
+}
+
+impl SchemaExample for RegisterCount {
+    fn example() -> Self {
+        Self {
+            name: "Klarinette".to_string(),
+            name_plural: "Klarinetten".to_string(),
+            member_count: 12,
+        }
+    }
+}
+
+/// The profile card of a single member which contains everything to render a profile with a single request.
+#[derive(Clone, Default, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(crate = "rocket::serde", rename_all = "camelCase")]
+#[schemars(example = "Self::example")]
+pub struct MemberCard {
+    /// The member itself, sensitive data is only included for authenticated users
+    pub member: WebMember,
+    /// The registers the member is part of
+    pub registers: Vec<Group>,
+    /// The base64 encoded thumbnail of the photo of the member if any, see [crate::config::MembersConfig::thumbnail_size]
+    pub photo: Option<String>,
+}
+
+impl SchemaExample for MemberCard {
+    fn example() -> Self {
+        Self {
+            member: WebMember::example(),
+            registers: vec![Group::example()],
+            photo: Some("/9j/4AAQSkZJRgABAQEASABIAAD".to_string()),
+        }
+    }
+}
+
+/// The duration of the membership of a member.
+#[derive(Clone, Default, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(crate = "rocket::serde", rename_all = "camelCase")]
+#[schemars(example = "Self::example")]
+pub struct MemberTenure {
+    /// The year the member joined, `None` if unknown
+    pub joining: Option<u32>,
+    /// The completed years of membership, `None` if the joining year is unknown
+    pub years: Option<u32>,
+}
+
+impl MemberTenure {
+    /// Compute the tenure of a member in the given year.
+    /// A joining year of `0` is considered unknown.
+    ///
+    /// # Arguments
+    ///
+    /// * `member`: the member to compute the tenure for
+    /// * `current_year`: the year to compute the tenure in
+    ///
+    /// returns: MemberTenure
+    pub fn from_member(member: &Member, current_year: u32) -> Self {
+        let joining = (member.joining != 0).then_some(member.joining);
+        Self {
+            joining,
+            years: joining.map(|j| current_year.saturating_sub(j)),
+        }
+    }
+}
+
+impl SchemaExample for MemberTenure {
+    fn example() -> Self {
+        Self {
+            joining: Some(2008),
+            years: Some(15),
+        }
+    }
+}
+
+/// The upcoming birthday of a member.
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(crate = "rocket::serde", rename_all = "camelCase")]
+#[schemars(example = "Self::example")]
+pub struct UpcomingBirthday {
+    /// The member whose birthday is upcoming
+    pub member: WebMember,
+    /// The date of the next birthday in the format `YYYY-MM-DD`
+    pub date: String,
+    /// The age of the member on the next birthday
+    pub age: u32,
+    /// The number of days until the next birthday, `0` if it is today
+    pub days: i64,
+}
+
+impl UpcomingBirthday {
+    /// Compute the next birthday of a member on or after the given day.
+    /// Members born on the 29th of February celebrate on the 28th of February in non-leap years.
+    ///
+    /// # Arguments
+    ///
+    /// * `member`: the member to compute the birthday for
+    /// * `today`: the day to start from
+    ///
+    /// returns: Option<UpcomingBirthday> which is `None` if the birthday of the member is unknown
+    pub fn from_member(member: &Member, today: NaiveDate) -> Option<Self> {
+        let birth_date = member.birth_date?;
+        let next = [today.year(), today.year() + 1]
+            .into_iter()
+            .filter_map(|year| anniversary(birth_date, year))
+            .find(|date| *date >= today)?;
+        Some(Self {
+            member: WebMember::from_member(member, false),
+            date: next.format("%Y-%m-%d").to_string(),
+            age: (next.year() - birth_date.year()).max(0) as u32,
+            days: (next - today).num_days(),
+        })
+    }
+}
+
+/// The anniversary of a date in the given year, the 29th of February is moved to the 28th in non-leap years.
+///
+/// # Arguments
+///
+/// * `date`: the original date
+/// * `year`: the year of the anniversary
+///
+/// returns: Option<NaiveDate>
+fn anniversary(date: NaiveDate, year: i32) -> Option<NaiveDate> {
+    date.with_year(year)
+        .or_else(|| NaiveDate::from_ymd_opt(year, date.month(), date.day() - 1))
+}
+
+impl SchemaExample for UpcomingBirthday {
+    fn example() -> Self {
+        Self {
+            member: WebMember {
+                sensitives: None,
+                ..WebMember::example()
+            },
+            date: "2024-05-06".to_string(),
+            age: 28,
+            days: 12,
+        }
+    }
+}
+
+/// The memberships of an executive group for auditing the governance records.
+#[derive(Clone, Default, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(crate = "rocket::serde", rename_all = "camelCase")]
+#[schemars(example = "Self::example")]
+pub struct ExecutiveAudit {
+    /// The plural name of the executive group
+    pub group: String,
+    /// The identifiers of the roles which are granted by this group such as `archive`
+    pub roles: Vec<String>,
+    /// The member entries of the group
+    pub assignments: Vec<ExecutiveAssignment>,
+}
+
+/// A member entry of an executive group.
+#[derive(Clone, Default, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(crate = "rocket::serde", rename_all = "camelCase")]
+pub struct ExecutiveAssignment {
+    /// The distinguished name of the member as stored in the group
+    pub dn: String,
+    /// The username of the member, `None` if the entry does not resolve to a known member
+    pub username: Option<String>,
+    /// Whether the entry does not resolve to a known member and should be removed from the group
+    pub stale: bool,
+}
+
+impl SchemaExample for ExecutiveAudit {
+    fn example() -> Self {
+        Self {
+            group: "Archivare".to_string(),
+            roles: vec!["archive".to_string()],
+            assignments: vec![
+                ExecutiveAssignment {
+                    dn: "uid=karl,ou=Musik,ou=Mitglieder,dc=mvl,dc=at".to_string(),
+                    username: Some("karl".to_string()),
+                    stale: false,
+                },
+                ExecutiveAssignment {
+                    dn: "uid=franz,ou=Musik,ou=Mitglieder,dc=mvl,dc=at".to_string(),
+                    username: None,
+                    stale: true,
+                },
+            ],
+        }
+    }
+}
+
+/// The status of the member synchronization with the directory server.
+/// Intended to detect a stale member state.
+#[derive(Clone, Default, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(crate = "rocket::serde", rename_all = "camelCase")]
+#[schemars(example = "Self::example")]
+pub struct SynchronizationStatus {
+    /// The RFC3339 compliant date time of the last successful synchronization, `None` if there was none yet
+    pub last_sync: Option<String>,
+    /// The duration of the last synchronization attempt in *milliseconds*
+    pub duration_millis: Option<u64>,
+    /// Whether the last synchronization attempt failed
+    pub failed: bool,
+    /// The error of the last failed synchronization attempt, only provided for authenticated users
+    pub error: Option<String>,
+    /// The number of members
+    pub members: usize,
+    /// The number of sutlers
+    pub sutlers: usize,
+    /// The number of honorary members
+    pub honoraries: usize,
+    /// The number of registers
+    pub registers: usize,
+    /// The number of executive roles
+    pub executives: usize,
+}
+
+impl SynchronizationStatus {
+    /// Create the synchronization status out of the current member state.
+    ///
+    /// # Arguments
+    ///
+    /// * `member_state`: the current member state
+    /// * `include_error`: whether to include the error message of the last attempt
+    ///
+    /// returns: SynchronizationStatus
+    pub fn from_state(member_state: &MemberState, include_error: bool) -> Self {
+        Self {
+            last_sync: member_state.last_sync.map(|t| t.to_rfc3339()),
+            duration_millis: member_state
+                .last_sync_duration
+                .map(|d| d.as_millis() as u64),
+            failed: member_state.last_sync_error.is_some(),
+            error: member_state
+                .last_sync_error
+                .clone()
+                .filter(|_| include_error),
+            members: member_state.all_members.len(),
+            sutlers: member_state.sutlers.len(),
+            honoraries: member_state.honorary_members.len(),
+            registers: member_state.registers.len(),
+            executives: member_state.executives.len(),
+        }
+    }
+}
+
+impl SchemaExample for SynchronizationStatus {
+    fn example() -> Self {
+        Self {
+            last_sync: Some("2023-05-01T12:00:00+00:00".to_string()),
+            duration_millis: Some(420),
+            failed: false,
+            error: None,
+            members: 62,
+            sutlers: 5,
+            honoraries: 12,
+            registers: 9,
+            executives: 11,
+        }
+    }
+}
+
 impl SchemaExample for Crew {
     fn example() -> Self {
         Self {
@@ -133,17 +585,28 @@ impl SchemaExample for Crew {
 }
 
 impl Crew {
+    /// Create the crew out of the member collections.
+    /// Members which do not pass the `member_filter` are dropped before they are mapped.
     pub fn new(
         musicians: &MembersByRegister,
         sutlers: &Sutlers,
         honorary_members: &HonoraryMembers,
+        member_filter: &dyn Fn(&Member) -> bool,
         member_mapper: &dyn Fn(&Member) -> WebMember,
         register_mapper: &dyn Fn(&RegisterEntry) -> WebRegister,
     ) -> Self {
         Self {
             musicians: musicians.iter().map(register_mapper).collect(),
-            sutlers: sutlers.iter().map(member_mapper).collect(),
-            honorary_members: honorary_members.iter().map(member_mapper).collect(),
+            sutlers: sutlers
+                .iter()
+                .filter(|m| member_filter(m))
+                .map(member_mapper)
+                .collect(),
+            honorary_members: honorary_members
+                .iter()
+                .filter(|m| member_filter(m))
+                .map(member_mapper)
+                .collect(),
         }
     }
 }
@@ -159,14 +622,22 @@ impl SchemaExample for WebRegister {
 }
 
 impl WebRegister {
+    /// Create the register out of a register entry.
+    /// Members which do not pass the `member_filter` are dropped before they are mapped.
     pub fn from_register(
         register: &RegisterEntry,
+        member_filter: &dyn Fn(&Member) -> bool,
         member_mapper: &dyn Fn(&Member) -> WebMember,
     ) -> Self {
         Self {
             name: register.register.name.to_string(),
             name_plural: register.register.name_plural.to_string(),
-            members: register.members.iter().map(member_mapper).collect(),
+            members: register
+                .members
+                .iter()
+                .filter(|m| member_filter(m))
+                .map(member_mapper)
+                .collect(),
         }
     }
 }
@@ -284,12 +755,53 @@ pub struct Member {
     pub active: bool,
     pub mobile: Vec<String>,
     pub birthday: String,
+    /// The birthday parsed with [crate::config::MembersConfig::birthday_format], `None` if absent or invalid.
+    pub birth_date: Option<NaiveDate>,
     pub mail: Vec<String>,
     pub photo: Vec<u8>,
+    /// The entity tag of the photo, computed during the synchronization if enabled.
+    pub photo_hash: Option<String>,
     pub titles: Vec<String>,
     pub address: Option<Address>,
 }
 
+#[cfg(test)]
+impl Member {
+    /// Create a listed and active member for tests whose username is the lowercase first name.
+    /// All other attributes are empty and may be overridden with the struct update syntax.
+    ///
+    /// # Arguments
+    ///
+    /// * `first_name`: the first name of the member
+    /// * `last_name`: the last name of the member
+    ///
+    /// returns: Member
+    pub(crate) fn test_member(first_name: &str, last_name: &str) -> Self {
+        let username = first_name.to_lowercase();
+        Self {
+            full_username: format!("uid={}", username),
+            username,
+            first_name: first_name.to_string(),
+            last_name: last_name.to_string(),
+            common_name: format!("{} {}", first_name, last_name),
+            whatsapp: false,
+            joining: 0,
+            listed: true,
+            official: false,
+            gender: 'm',
+            active: true,
+            mobile: vec![],
+            birthday: "".to_string(),
+            birth_date: None,
+            mail: vec![],
+            photo: vec![],
+            photo_hash: None,
+            titles: vec![],
+            address: None,
+        }
+    }
+}
+
 #[derive(Debug, Hash, Eq, PartialEq, Clone)]
 pub struct Address {
     pub street: String,
@@ -339,37 +851,44 @@ impl Ord for Member {
 
 impl LdapDeserializable<Member> for Member {
     fn from_search_entry(entry: &SearchEntry, config: &Config) -> Member {
-        let attrs = &entry.attrs;
+        let attrs = &*string_attributes(entry, config);
         let mapping = &config.ldap.member_mapping;
+        let attribute = |candidates| resolve_candidate(candidates, attrs);
+        let photo = entry
+            .bin_attrs
+            .get(resolve_candidate(&mapping.photo, &entry.bin_attrs))
+            .unwrap_or(&vec![])
+            .iter()
+            .next()
+            .unwrap_or(&vec![])
+            .to_owned();
+        let birthday = string_or_blank(attribute(&mapping.birthday), attrs)[0].to_string();
         Member {
-            username: string_or_blank(&mapping.username, attrs)[0].to_string(),
-            full_username: entry.dn.to_string(),
-            first_name: string_or_blank(&mapping.first_name, attrs)[0].to_string(),
-            last_name: string_or_blank(&mapping.last_name, attrs)[0].to_string(),
-            common_name: string_or_blank(&mapping.common_name, attrs)[0].to_string(),
-            whatsapp: bool_or_false(&mapping.whatsapp, attrs),
-            joining: string_or_blank(&mapping.joining, attrs)[0]
+            username: string_or_blank(attribute(&mapping.username), attrs)[0].to_string(),
+            full_username: normalize_dn(&entry.dn),
+            first_name: string_or_blank(attribute(&mapping.first_name), attrs)[0].to_string(),
+            last_name: string_or_blank(attribute(&mapping.last_name), attrs)[0].to_string(),
+            common_name: string_or_blank(attribute(&mapping.common_name), attrs)[0].to_string(),
+            whatsapp: bool_or_false(attribute(&mapping.whatsapp), attrs),
+            joining: string_or_blank(attribute(&mapping.joining), attrs)[0]
                 .parse::<u32>()
                 .unwrap_or(0),
-            listed: bool_or_false(&mapping.listed, attrs),
-            official: bool_or_false(&mapping.official, attrs),
-            gender: string_or_blank(&mapping.gender, attrs)[0]
+            listed: bool_or_false(attribute(&mapping.listed), attrs),
+            official: bool_or_false(attribute(&mapping.official), attrs),
+            gender: string_or_blank(attribute(&mapping.gender), attrs)[0]
                 .chars()
                 .next()
                 .unwrap_or('u'),
-            active: bool_or_false(&mapping.active, attrs),
-            mobile: string_or_empty(&mapping.mobile, attrs),
-            birthday: string_or_blank(&mapping.birthday, attrs)[0].to_string(),
-            mail: string_or_empty(&mapping.mail, attrs),
-            photo: entry
-                .bin_attrs
-                .get(&mapping.photo)
-                .unwrap_or(&vec![])
-                .iter()
-                .next()
-                .unwrap_or(&vec![])
-                .to_owned(),
-            titles: string_or_empty(&mapping.titles, attrs),
+            active: bool_or_false(attribute(&mapping.active), attrs),
+            mobile: string_or_empty(attribute(&mapping.mobile), attrs),
+            birth_date: NaiveDate::parse_from_str(birthday.trim(), &config.members.birthday_format)
+                .ok(),
+            birthday,
+            mail: string_or_empty(attribute(&mapping.mail), attrs),
+            photo_hash: (config.members.hash_photos && !photo.is_empty())
+                .then(|| content_hash(&photo)),
+            photo,
+            titles: string_or_empty(attribute(&mapping.titles), attrs),
             address: Address::from_search_entry(entry, config),
         }
     }
@@ -377,7 +896,7 @@ impl LdapDeserializable<Member> for Member {
 
 impl LdapDeserializable<Option<Address>> for Address {
     fn from_search_entry(entry: &SearchEntry, config: &Config) -> Option<Address> {
-        let attrs = &entry.attrs;
+        let attrs = &*string_attributes(entry, config);
         let mapping = &config.ldap.address_mapping;
         if !contains_all(
             attrs,
@@ -405,7 +924,7 @@ impl LdapDeserializable<Option<Address>> for Address {
 
 impl LdapDeserializable<Group> for Group {
     fn from_search_entry(entry: &SearchEntry, config: &Config) -> Group {
-        let attrs = &entry.attrs;
+        let attrs = &*string_attributes(entry, config);
         let mapping = &config.ldap.group_mapping;
         Group {
             name: string_or_blank(&mapping.name, attrs)[0].to_string(),
@@ -414,7 +933,9 @@ impl LdapDeserializable<Group> for Group {
             members: attrs
                 .get(mapping.members.as_str())
                 .unwrap_or(&vec![])
-                .clone(),
+                .iter()
+                .map(|dn| normalize_dn(dn))
+                .collect(),
         }
     }
 }
@@ -442,6 +963,71 @@ impl SchemaExample for Group {
     }
 }
 
+/// Get the string attributes of an entry.
+/// Values which are not valid UTF-8 are only contained in the binary attributes of the entry.
+/// If [crate::config::LdapConfig::lossy_decoding] is set, these values are decoded lossily and added, except the photo.
+///
+/// # Arguments
+///
+/// * `entry` : the entry to get the attributes from
+/// * `config` : the application configuration
+///
+/// returns: Cow<HashMap<String, Vec<String>>>
+fn string_attributes<'a>(
+    entry: &'a SearchEntry,
+    config: &Config,
+) -> Cow<'a, HashMap<String, Vec<String>>> {
+    let photo_attributes = config.ldap.member_mapping.photo.candidates();
+    let mut invalid_attributes = entry
+        .bin_attrs
+        .iter()
+        .filter(|(attribute, _)| !photo_attributes.contains(attribute))
+        .peekable();
+    if !config.ldap.lossy_decoding || invalid_attributes.peek().is_none() {
+        return Cow::Borrowed(&entry.attrs);
+    }
+    let mut attrs = entry.attrs.clone();
+    for (attribute, values) in invalid_attributes {
+        warn!(
+            "The attribute '{}' of '{}' is not valid UTF-8, decode it lossily",
+            attribute, entry.dn
+        );
+        attrs.entry(attribute.clone()).or_default().extend(
+            values
+                .iter()
+                .map(|value| String::from_utf8_lossy(value).into_owned()),
+        );
+    }
+    Cow::Owned(attrs)
+}
+
+/// Resolve the attribute to use out of the candidates.
+/// This is the first candidate whose first value is not empty or the first candidate if there is none.
+///
+/// # Arguments
+///
+/// * `candidates` : the candidate attributes in the order of their preference
+/// * `attrs` : the map of the attributes with the corresponding values
+fn resolve_candidate<'a, V>(
+    candidates: &'a AttributeCandidates,
+    attrs: &HashMap<String, Vec<V>>,
+) -> &'a str
+where
+    V: AsRef<[u8]>,
+{
+    let candidates = candidates.candidates();
+    candidates
+        .iter()
+        .find(|c| {
+            attrs
+                .get(c.as_str())
+                .and_then(|values| values.first())
+                .is_some_and(|value| !value.as_ref().is_empty())
+        })
+        .or_else(|| candidates.first())
+        .map_or("", |c| c.as_str())
+}
+
 /// Extract either the strings out of a vector map or fill the vector with one empty string if the attribute does not exist.
 ///
 /// # Arguments
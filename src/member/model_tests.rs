@@ -0,0 +1,213 @@
+// OpenKeg, the lightweight backend of the Musikverein Leopoldsdorf.
+// Copyright (C) 2022  Richard Stöckl
+//
+// This program is free software; you can redistribute it and/or
+// modify it under the terms of the GNU General Public License
+// as published by the Free Software Foundation; either version 2
+// of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+
+use super::*;
+use crate::config::MemberMapping;
+
+fn entry(attrs: &[(&str, &str)]) -> SearchEntry {
+    SearchEntry {
+        dn: "uid=willi,dc=mvl,dc=at".to_string(),
+        attrs: attrs
+            .iter()
+            .map(|(k, v)| (k.to_string(), vec![v.to_string()]))
+            .collect(),
+        bin_attrs: HashMap::new(),
+    }
+}
+
+fn config_with_first_name(candidates: &[&str]) -> Config {
+    let mut config = Config::default();
+    config.ldap.member_mapping.first_name =
+        AttributeCandidates::Multiple(candidates.iter().map(|c| c.to_string()).collect());
+    config
+}
+
+#[test]
+fn candidates_first_is_preferred() {
+    let config = config_with_first_name(&["givenName", "gn"]);
+    let member = Member::from_search_entry(
+        &entry(&[("givenName", "Wilhelm"), ("gn", "Willi")]),
+        &config,
+    );
+    assert_eq!(member.first_name, "Wilhelm");
+}
+
+#[test]
+fn candidates_fallback_when_missing() {
+    let config = config_with_first_name(&["givenName", "gn"]);
+    let member = Member::from_search_entry(&entry(&[("gn", "Willi")]), &config);
+    assert_eq!(member.first_name, "Willi");
+}
+
+#[test]
+fn candidates_fallback_when_empty() {
+    let config = config_with_first_name(&["givenName", "gn"]);
+    let member = Member::from_search_entry(&entry(&[("givenName", ""), ("gn", "Willi")]), &config);
+    assert_eq!(member.first_name, "Willi");
+}
+
+#[test]
+fn candidates_blank_when_none_present() {
+    let config = config_with_first_name(&["givenName", "gn"]);
+    let member = Member::from_search_entry(&entry(&[("sn", "Huber")]), &config);
+    assert_eq!(member.first_name, "");
+    assert_eq!(member.last_name, "Huber");
+}
+
+#[test]
+fn candidates_deserialize_single_and_multiple() {
+    let mut mapping = serde_json::to_value(MemberMapping::default()).expect("mapping");
+    mapping["first_name"] = serde_json::json!(["givenName", "gn"]);
+    let mapping: MemberMapping = serde_json::from_value(mapping).expect("mapping");
+    assert_eq!(mapping.username, AttributeCandidates::from("uid"));
+    assert_eq!(mapping.username.candidates(), ["uid".to_string()]);
+    assert_eq!(
+        mapping.first_name.candidates(),
+        ["givenName".to_string(), "gn".to_string()]
+    );
+}
+
+#[test]
+fn tenure_of_unknown_joining_is_omitted() {
+    let mut member = Member::test_member("Max", "Mustermann");
+    let tenure = MemberTenure::from_member(&member, 2023);
+    assert_eq!((tenure.joining, tenure.years), (None, None));
+    member.joining = 2008;
+    let tenure = MemberTenure::from_member(&member, 2023);
+    assert_eq!((tenure.joining, tenure.years), (Some(2008), Some(15)));
+}
+
+#[test]
+fn lossy_decoding() {
+    let mut invalid_entry = entry(&[("uid", "willi")]);
+    invalid_entry
+        .bin_attrs
+        .insert("sn".to_string(), vec![b"M\xfcller".to_vec()]);
+    invalid_entry
+        .bin_attrs
+        .insert("jpegPhoto".to_string(), vec![vec![0xff, 0xd8]]);
+    let mut config = Config::default();
+    let member = Member::from_search_entry(&invalid_entry, &config);
+    assert_eq!(member.last_name, "");
+    config.ldap.lossy_decoding = true;
+    let member = Member::from_search_entry(&invalid_entry, &config);
+    assert_eq!(member.last_name, "M\u{fffd}ller");
+    assert_eq!(member.photo, vec![0xff, 0xd8]);
+    assert_eq!(
+        string_attributes(&invalid_entry, &config).get("jpegPhoto"),
+        None
+    );
+}
+
+fn born(birthday: &str) -> Member {
+    let mut config = Config::default();
+    config.members.birthday_format = "%d.%m.%Y".to_string();
+    Member::from_search_entry(&entry(&[("birthday", birthday)]), &config)
+}
+
+fn day(date: &str) -> NaiveDate {
+    NaiveDate::parse_from_str(date, "%Y-%m-%d").expect("date")
+}
+
+#[test]
+fn birthday_parsing() {
+    assert_eq!(born("06.05.1996").birth_date, Some(day("1996-05-06")));
+    assert_eq!(born("1996-05-06").birth_date, None);
+    assert_eq!(born("").birth_date, None);
+}
+
+#[test]
+fn upcoming_birthday() {
+    let member = born("06.05.1996");
+    let birthday = UpcomingBirthday::from_member(&member, day("2024-05-01")).expect("birthday");
+    assert_eq!(
+        (birthday.date.as_str(), birthday.age, birthday.days),
+        ("2024-05-06", 28, 5)
+    );
+    let birthday = UpcomingBirthday::from_member(&member, day("2024-05-06")).expect("birthday");
+    assert_eq!((birthday.date.as_str(), birthday.days), ("2024-05-06", 0));
+    let birthday = UpcomingBirthday::from_member(&member, day("2024-05-07")).expect("birthday");
+    assert_eq!((birthday.date.as_str(), birthday.age), ("2025-05-06", 29));
+    assert!(UpcomingBirthday::from_member(&born("unknown"), day("2024-05-07")).is_none());
+}
+
+#[test]
+fn leap_day_birthday() {
+    let member = born("29.02.2000");
+    let birthday = UpcomingBirthday::from_member(&member, day("2023-02-01")).expect("birthday");
+    assert_eq!((birthday.date.as_str(), birthday.age), ("2023-02-28", 23));
+    let birthday = UpcomingBirthday::from_member(&member, day("2024-02-01")).expect("birthday");
+    assert_eq!((birthday.date.as_str(), birthday.age), ("2024-02-29", 24));
+    let birthday = UpcomingBirthday::from_member(&member, day("2023-03-01")).expect("birthday");
+    assert_eq!(birthday.date, "2024-02-29");
+}
+
+#[test]
+fn register_count() {
+    let listed = born("06.05.1996");
+    let unlisted = Member {
+        username: "erika".to_string(),
+        listed: false,
+        ..born("01.01.1990")
+    };
+    let entry = RegisterEntry {
+        register: Group {
+            name: "Klarinette".to_string(),
+            name_plural: "Klarinetten".to_string(),
+            description: "".to_string(),
+            members: vec![],
+        },
+        members: LinkedList::from([
+            Member {
+                listed: true,
+                ..listed
+            },
+            unlisted,
+        ]),
+    };
+    let public = RegisterCount::from_register(&entry, &|m| m.listed);
+    assert_eq!(
+        (
+            public.name.as_str(),
+            public.name_plural.as_str(),
+            public.member_count
+        ),
+        ("Klarinette", "Klarinetten", 1)
+    );
+    assert_eq!(
+        RegisterCount::from_register(&entry, &|_| true).member_count,
+        2
+    );
+}
+
+#[test]
+fn crew_etag() {
+    let crew = || Crew {
+        musicians: LinkedList::new(),
+        sutlers: LinkedList::new(),
+        honorary_members: LinkedList::new(),
+    };
+    let sync = DateTime::parse_from_rfc3339("2024-05-06T10:00:00Z")
+        .expect("time")
+        .with_timezone(&Utc);
+    let public = CachedCrew::new(crew(), Some(sync), false);
+    let private = CachedCrew::new(crew(), Some(sync), true);
+    let later = CachedCrew::new(crew(), Some(sync + chrono::Duration::seconds(300)), false);
+    assert_eq!(public.etag, "\"crew-1714989600000-public\"");
+    assert_ne!(public.etag, private.etag);
+    assert_ne!(public.etag, later.etag);
+}
@@ -15,22 +15,165 @@
 // along with this program; if not, write to the Free Software
 // Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
 
+use std::collections::HashMap;
 use std::io::Cursor;
+use std::sync::Arc;
 
 use okapi::map;
 use okapi::openapi3::{RefOr, Responses};
-use rocket::http::{ContentType, MediaType};
+use rocket::http::{ContentType, Header, MediaType, Status};
 use rocket::response::Responder;
+use rocket::tokio::sync::RwLock;
 use rocket::{Request, Response};
 use rocket_okapi::gen::OpenApiGenerator;
 use rocket_okapi::response::OpenApiResponderInner;
 
-pub struct Photo(pub(crate) Vec<u8>);
+use crate::Config;
+
+/// The cache of the generated thumbnails keyed by the username and the size.
+/// Each entry holds the entity tag of the original photo in order to detect changes after a synchronization.
+pub type ThumbnailCache = Arc<RwLock<HashMap<(String, u32), (String, Vec<u8>)>>>;
+
+/// The leading bytes of a PNG image.
+const PNG_MAGIC: &[u8] = b"\x89PNG";
+
+/// A member photo with its entity tag.
+/// The entity tag is computed out of the photo if absent.
+pub struct Photo(pub(crate) Vec<u8>, pub(crate) Option<String>);
+
+/// Compute the entity tag of a photo out of a SHA-256 hash of its bytes.
+///
+/// # Arguments
+///
+/// * `photo`: the bytes of the photo
+///
+/// returns: String the quoted entity tag
+pub fn content_hash(photo: &[u8]) -> String {
+    let digest = ring::digest::digest(&ring::digest::SHA256, photo);
+    let hex: String = digest
+        .as_ref()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    format!("\"{}\"", hex)
+}
+
+impl Photo {
+    /// Determine the content type of the photo by its leading magic bytes.
+    /// Directories may store PNG images in JPEG attributes, everything which is not a PNG is served as JPEG.
+    ///
+    /// returns: ContentType
+    pub fn content_type(&self) -> ContentType {
+        if self.0.starts_with(PNG_MAGIC) {
+            ContentType::PNG
+        } else {
+            ContentType::JPEG
+        }
+    }
+
+    /// Get the entity tag of the photo.
+    /// If the photo was not hashed during the synchronization, the hash is computed now.
+    ///
+    /// returns: String the quoted entity tag
+    pub fn etag(&self) -> String {
+        self.1.clone().unwrap_or_else(|| content_hash(&self.0))
+    }
+}
+
+/// Get the thumbnail of a member photo which fits into the size while keeping the aspect ratio.
+/// Thumbnails are cached until the original photo changes.
+/// If the photo cannot be decoded or the `thumbnails` feature is disabled, the original photo is returned.
+///
+/// # Arguments
+///
+/// * `cache`: the cache of the generated thumbnails
+/// * `username`: the username of the member the photo belongs to
+/// * `photo`: the original photo
+/// * `size`: the maximum width and height of the thumbnail in *pixels*
+///
+/// returns: Photo
+pub async fn thumbnail(cache: &ThumbnailCache, username: &str, photo: Photo, size: u32) -> Photo {
+    let key = (username.to_string(), size);
+    let etag = photo.etag();
+    let thumbnail_etag = format!("\"{}-{}\"", etag.trim_matches('"'), size);
+    if let Some((_, thumbnail)) = cache.read().await.get(&key).filter(|(e, _)| e == &etag) {
+        return Photo(thumbnail.clone(), Some(thumbnail_etag));
+    }
+    match downscale(&photo.0, size) {
+        Some(thumbnail) => {
+            cache.write().await.insert(key, (etag, thumbnail.clone()));
+            Photo(thumbnail, Some(thumbnail_etag))
+        }
+        None => photo,
+    }
+}
+
+/// Downscale an image to fit into the size while keeping its format.
+///
+/// # Arguments
+///
+/// * `photo`: the encoded image
+/// * `size`: the maximum width and height in *pixels*
+///
+/// returns: Option<Vec<u8>> the encoded thumbnail or `None` if the image cannot be decoded
+#[cfg(feature = "thumbnails")]
+fn downscale(photo: &[u8], size: u32) -> Option<Vec<u8>> {
+    let format = image::guess_format(photo).ok()?;
+    let original = image::load_from_memory_with_format(photo, format)
+        .map_err(|e| warn!("Unable to decode the photo, serve the original: {}", e))
+        .ok()?;
+    let mut thumbnail = Cursor::new(vec![]);
+    original
+        .thumbnail(size, size)
+        .write_to(&mut thumbnail, format)
+        .map_err(|e| warn!("Unable to encode the thumbnail, serve the original: {}", e))
+        .ok()?;
+    Some(thumbnail.into_inner())
+}
+
+/// Downscaling is not supported without the `thumbnails` feature.
+#[cfg(not(feature = "thumbnails"))]
+fn downscale(_photo: &[u8], _size: u32) -> Option<Vec<u8>> {
+    debug!("Built without the thumbnails feature, serve the original photo");
+    None
+}
+
+/// Check whether the `If-None-Match` header of the request matches the entity tag.
+///
+/// # Arguments
+///
+/// * `request`: the request which may contain the header
+/// * `etag`: the quoted entity tag of the current representation
+///
+/// returns: bool
+pub fn matches_etag(request: &Request<'_>, etag: &str) -> bool {
+    request
+        .headers()
+        .get("If-None-Match")
+        .flat_map(|v| v.split(','))
+        .any(|t| t.trim() == etag || t.trim() == "*")
+}
 
 impl<'r> Responder<'r, 'static> for Photo {
-    fn respond_to(self, _request: &'r Request<'_>) -> rocket::response::Result<'static> {
-        Response::build()
-            .header(ContentType::JPEG)
+    fn respond_to(self, request: &'r Request<'_>) -> rocket::response::Result<'static> {
+        let etag = self.etag();
+        let max_age = request
+            .rocket()
+            .state::<Config>()
+            .map(|c| c.ldap.synchronization_interval)
+            .unwrap_or_default();
+        let mut response = Response::build();
+        response
+            .header(Header::new("ETag", etag.clone()))
+            .header(Header::new(
+                "Cache-Control",
+                format!("private, max-age={}", max_age),
+            ));
+        if matches_etag(request, &etag) {
+            return response.status(Status::NotModified).ok();
+        }
+        response
+            .header(self.content_type())
             .streamed_body(Cursor::new(self.0))
             .ok()
     }
@@ -38,13 +181,23 @@ impl<'r> Responder<'r, 'static> for Photo {
 
 impl<'r> OpenApiResponderInner for Photo {
     fn responses(_gen: &mut OpenApiGenerator) -> rocket_okapi::Result<Responses> {
-        let jpeg = okapi::openapi3::MediaType::default();
         let image_response = okapi::openapi3::Response {
             description: "The photo image of the member".to_string(),
-            content: map! {MediaType::JPEG.to_string() => jpeg},
+            content: map! {
+                MediaType::JPEG.to_string() => okapi::openapi3::MediaType::default(),
+                MediaType::PNG.to_string() => okapi::openapi3::MediaType::default()
+            },
             ..okapi::openapi3::Response::default()
         };
-        let responses = map! {"200".to_string() => RefOr::Object(image_response)};
+        let not_modified_response = okapi::openapi3::Response {
+            description: "The photo matches the provided entity tag and was not modified"
+                .to_string(),
+            ..okapi::openapi3::Response::default()
+        };
+        let responses = map! {
+            "200".to_string() => RefOr::Object(image_response),
+            "304".to_string() => RefOr::Object(not_modified_response)
+        };
         Ok(Responses {
             default: None,
             responses,
@@ -15,14 +15,30 @@
 // along with this program; if not, write to the Free Software
 // Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
 
-use std::collections::{HashSet, LinkedList};
+use std::collections::{HashMap, HashSet, LinkedList};
 use std::sync::Arc;
+use std::time::Duration;
 
+use chrono::{DateTime, Utc};
+use okapi::map;
+use okapi::openapi3::{Object, ParameterValue, RefOr, Response, Responses};
+use rocket::http::{Header, Status};
+use rocket::outcome::Outcome::{Failure, Success};
+use rocket::request::{FromRequest, Outcome};
+use rocket::response::Responder;
 use rocket::tokio::sync::RwLock;
+use rocket::Request;
+use rocket_okapi::gen::OpenApiGenerator;
+use rocket_okapi::request::{OpenApiFromRequest, RequestHeaderInput};
+use rocket_okapi::response::OpenApiResponderInner;
 
 use crate::member::model::{Group, Member};
+use crate::openapi::ApiError;
 use crate::MemberStateMutex;
 
+/// The seconds after which a client should retry a request while the member state is not initialized yet.
+pub const UNINITIALIZED_RETRY_AFTER: u64 = 10;
+
 /// All member with no further order
 pub type AllMembers = HashSet<Member>;
 /// All registers with no further order
@@ -36,6 +52,8 @@ pub type MembersByRegister = LinkedList<RegisterEntry>;
 pub type Sutlers = LinkedList<Member>;
 /// All honorary member
 pub type HonoraryMembers = LinkedList<Member>;
+/// The photos of members with their entity tag by the distinguished name of the member
+pub type MemberPhotos = HashMap<String, (Vec<u8>, Option<String>)>;
 
 pub trait Repository<ID, E> {
     fn find(&self, id: &ID) -> Option<&E>;
@@ -58,6 +76,14 @@ pub struct MemberState {
     pub members_by_register: MembersByRegister,
     pub sutlers: Sutlers,
     pub honorary_members: HonoraryMembers,
+    /// The time of the last successful synchronization
+    pub last_sync: Option<DateTime<Utc>>,
+    /// The duration of the last synchronization attempt
+    pub last_sync_duration: Option<Duration>,
+    /// The error of the last synchronization attempt, `None` if it was successful
+    pub last_sync_error: Option<String>,
+    /// Whether the state has been filled by a successful synchronization at least once
+    pub initialized: bool,
 }
 
 impl MemberState {
@@ -69,10 +95,123 @@ impl MemberState {
             members_by_register: MembersByRegister::new(),
             sutlers: Sutlers::new(),
             honorary_members: HonoraryMembers::new(),
+            last_sync: None,
+            last_sync_duration: None,
+            last_sync_error: None,
+            initialized: false,
         }))
     }
 }
 
+/// A request guard which ensures that the member state has been initialized by a successful synchronization.
+/// Until then, the request fails with 'Service Unavailable' which is answered by [crate::member::controller::member_state_unavailable].
+pub struct InitializedMemberState;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for InitializedMemberState {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let member_state = request
+            .rocket()
+            .state::<MemberStateMutex>()
+            .expect("member state");
+        if member_state.read().await.initialized {
+            Success(InitializedMemberState)
+        } else {
+            debug!("Reject request as the members have not been synchronized yet");
+            Failure((Status::ServiceUnavailable, ()))
+        }
+    }
+}
+
+impl<'r> OpenApiFromRequest<'r> for InitializedMemberState {
+    fn from_request_input(
+        _gen: &mut OpenApiGenerator,
+        _name: String,
+        _required: bool,
+    ) -> rocket_okapi::Result<RequestHeaderInput> {
+        Ok(RequestHeaderInput::None)
+    }
+}
+
+/// The response while the member state has not been initialized yet.
+/// It contains the `Retry-After` header with [UNINITIALIZED_RETRY_AFTER].
+pub struct MemberStateUnavailable;
+
+impl<'r> Responder<'r, 'static> for MemberStateUnavailable {
+    fn respond_to(self, request: &'r Request<'_>) -> rocket::response::Result<'static> {
+        let mut response = ApiError {
+            err: "Service Unavailable".to_string(),
+            msg: Some("The members have not been synchronized yet, try again later".to_string()),
+            http_status_code: Status::ServiceUnavailable.code,
+        }
+        .respond_to(request)?;
+        response.set_header(Header::new(
+            "Retry-After",
+            UNINITIALIZED_RETRY_AFTER.to_string(),
+        ));
+        Ok(response)
+    }
+}
+
+impl OpenApiResponderInner for MemberStateUnavailable {
+    fn responses(_gen: &mut OpenApiGenerator) -> rocket_okapi::Result<Responses> {
+        use okapi::openapi3::Header;
+        let retry_header = map! {"Retry-After".to_string() => RefOr::Object(Header{
+            description: Some("The seconds after which the request should be retried".to_string()),
+            required: true,
+            deprecated: false,
+            allow_empty_value: false,
+            value: ParameterValue::Content {content: map!{}},
+            extensions: map! {}
+        })};
+        Ok(Responses {
+            responses: map! {"503".to_string() => RefOr::Object(Response {
+                description: "The members have not been synchronized since the start of the server".to_string(),
+                headers: retry_header,
+                content: map! {},
+                links: map! {},
+                extensions: Object::default(),
+            })},
+            ..Default::default()
+        })
+    }
+}
+
+impl MemberState {
+    /// Replace the photos of members in all collections.
+    /// Members are identified by their distinguished name, members without an entry in `photos` are left untouched.
+    ///
+    /// # Arguments
+    ///
+    /// * `photos`: the photos with their entity tag by the distinguished name of the member
+    ///
+    /// returns: ()
+    pub fn update_photos(&mut self, photos: &MemberPhotos) {
+        let update = |member: &mut Member| {
+            if let Some((photo, photo_hash)) = photos.get(&member.full_username) {
+                member.photo = photo.clone();
+                member.photo_hash = photo_hash.clone();
+            }
+        };
+        self.all_members = self
+            .all_members
+            .drain()
+            .map(|mut member| {
+                update(&mut member);
+                member
+            })
+            .collect();
+        self.sutlers.iter_mut().for_each(update);
+        self.honorary_members.iter_mut().for_each(update);
+        self.members_by_register
+            .iter_mut()
+            .flat_map(|entry| entry.members.iter_mut())
+            .for_each(update);
+    }
+}
+
 #[derive(Clone)]
 /// An entry which holds a register and all corresponding member
 pub struct RegisterEntry {
@@ -0,0 +1,183 @@
+// OpenKeg, the lightweight backend of the Musikverein Leopoldsdorf.
+// Copyright (C) 2022  Richard Stöckl
+//
+// This program is free software; you can redistribute it and/or
+// modify it under the terms of the GNU General Public License
+// as published by the Free Software Foundation; either version 2
+// of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+
+use std::io::Cursor;
+
+use base64::{engine, Engine};
+use okapi::map;
+use okapi::openapi3::{RefOr, Responses};
+use rocket::http::{ContentType, Header};
+use rocket::response::Responder;
+use rocket::{Request, Response};
+use rocket_okapi::gen::OpenApiGenerator;
+use rocket_okapi::response::OpenApiResponderInner;
+
+use crate::member::model::Member;
+use crate::member::photo::Photo;
+
+#[cfg(test)]
+#[path = "vcard_tests.rs"]
+mod vcard_tests;
+
+/// The maximum length of a line in *octets* before it is folded.
+const MAX_LINE_LENGTH: usize = 75;
+
+/// The contact details of a member in the vCard 3.0 format.
+/// The response is sent as attachment named after the username of the member.
+pub struct VCard {
+    /// The username of the member, used for the file name.
+    username: String,
+    /// The serialized vCard.
+    content: String,
+}
+
+impl VCard {
+    /// Create the vCard of a member out of its name, phone numbers, mail addresses, address, birthday and photo.
+    /// Properties without a value are omitted.
+    ///
+    /// # Arguments
+    ///
+    /// * `member`: the member to create the vCard for
+    ///
+    /// returns: VCard
+    pub fn from_member(member: &Member) -> Self {
+        let mut lines = vec![
+            "BEGIN:VCARD".to_string(),
+            "VERSION:3.0".to_string(),
+            format!(
+                "N:{};{};;;",
+                escape(&member.last_name),
+                escape(&member.first_name)
+            ),
+            format!(
+                "FN:{}",
+                escape(&format!("{} {}", member.first_name, member.last_name))
+            ),
+        ];
+        lines.extend(
+            member
+                .mobile
+                .iter()
+                .map(|mobile| format!("TEL;TYPE=CELL:{}", escape(mobile))),
+        );
+        lines.extend(
+            member
+                .mail
+                .iter()
+                .map(|mail| format!("EMAIL;TYPE=INTERNET:{}", escape(mail))),
+        );
+        if let Some(address) = &member.address {
+            lines.push(format!(
+                "ADR;TYPE=HOME:;;{};{};{};{};{}",
+                escape(format!("{} {}", address.street, address.house_number).trim()),
+                escape(&address.city),
+                escape(&address.state),
+                escape(&address.postal_code),
+                escape(&address.country_code)
+            ));
+        }
+        if !member.birthday.is_empty() {
+            lines.push(format!("BDAY:{}", escape(&member.birthday)));
+        }
+        if !member.photo.is_empty() {
+            let photo = Photo(member.photo.clone(), None);
+            let image_type = if photo.content_type() == ContentType::PNG {
+                "PNG"
+            } else {
+                "JPEG"
+            };
+            lines.push(format!(
+                "PHOTO;ENCODING=b;TYPE={}:{}",
+                image_type,
+                engine::general_purpose::STANDARD.encode(&photo.0)
+            ));
+        }
+        lines.push("END:VCARD".to_string());
+        Self {
+            username: member.username.to_string(),
+            content: lines.iter().map(|line| fold(line)).collect(),
+        }
+    }
+}
+
+/// Escape the characters which have a special meaning within a text value.
+///
+/// # Arguments
+///
+/// * `value`: the value to escape
+///
+/// returns: String
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('\n', "\\n")
+        .replace(',', "\\,")
+        .replace(';', "\\;")
+}
+
+/// Fold a content line into lines of at most [MAX_LINE_LENGTH] octets and terminate it.
+/// Continuation lines start with a single space, characters are never split.
+///
+/// # Arguments
+///
+/// * `line`: the content line to fold
+///
+/// returns: String the folded line including the trailing line break
+fn fold(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len() + line.len() / MAX_LINE_LENGTH * 3 + 2);
+    let mut length = 0;
+    for c in line.chars() {
+        if length + c.len_utf8() > MAX_LINE_LENGTH {
+            folded.push_str("\r\n ");
+            length = 1;
+        }
+        folded.push(c);
+        length += c.len_utf8();
+    }
+    folded.push_str("\r\n");
+    folded
+}
+
+impl<'r> Responder<'r, 'static> for VCard {
+    fn respond_to(self, _request: &'r Request<'_>) -> rocket::response::Result<'static> {
+        Response::build()
+            .header(ContentType::new("text", "vcard"))
+            .header(Header::new(
+                "Content-Disposition",
+                format!("attachment; filename=\"{}.vcf\"", self.username),
+            ))
+            .sized_body(self.content.len(), Cursor::new(self.content))
+            .ok()
+    }
+}
+
+impl OpenApiResponderInner for VCard {
+    fn responses(_gen: &mut OpenApiGenerator) -> rocket_okapi::Result<Responses> {
+        let vcard_response = okapi::openapi3::Response {
+            description: "The contact details of the member as vCard".to_string(),
+            content: map! {
+                "text/vcard".to_string() => okapi::openapi3::MediaType::default()
+            },
+            ..okapi::openapi3::Response::default()
+        };
+        Ok(Responses {
+            default: None,
+            responses: map! {"200".to_string() => RefOr::Object(vcard_response)},
+            extensions: map! {},
+        })
+    }
+}
@@ -0,0 +1,91 @@
+// OpenKeg, the lightweight backend of the Musikverein Leopoldsdorf.
+// Copyright (C) 2022  Richard Stöckl
+//
+// This program is free software; you can redistribute it and/or
+// modify it under the terms of the GNU General Public License
+// as published by the Free Software Foundation; either version 2
+// of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+
+use super::*;
+use crate::member::model::Address;
+
+fn member() -> Member {
+    Member {
+        joining: 2008,
+        mobile: vec!["+43 664 91828374".to_string()],
+        birthday: "1996-05-06".to_string(),
+        mail: vec!["max@example.com".to_string(), "max@mvl.at".to_string()],
+        address: Some(Address {
+            street: "Hauptstraße".to_string(),
+            house_number: "1".to_string(),
+            postal_code: "2333".to_string(),
+            city: "Leopoldsdorf".to_string(),
+            state: "Niederösterreich".to_string(),
+            country_code: "AT".to_string(),
+        }),
+        ..Member::test_member("Max", "Mustermann")
+    }
+}
+
+#[test]
+fn contact_details() {
+    let vcard = VCard::from_member(&member());
+    assert_eq!(vcard.username, "max");
+    assert_eq!(
+        vcard.content,
+        "BEGIN:VCARD\r\n\
+         VERSION:3.0\r\n\
+         N:Mustermann;Max;;;\r\n\
+         FN:Max Mustermann\r\n\
+         TEL;TYPE=CELL:+43 664 91828374\r\n\
+         EMAIL;TYPE=INTERNET:max@example.com\r\n\
+         EMAIL;TYPE=INTERNET:max@mvl.at\r\n\
+         ADR;TYPE=HOME:;;Hauptstraße 1;Leopoldsdorf;Niederösterreich;2333;AT\r\n\
+         BDAY:1996-05-06\r\n\
+         END:VCARD\r\n"
+    );
+}
+
+#[test]
+fn missing_details_are_omitted() {
+    let vcard = VCard::from_member(&Member {
+        address: None,
+        birthday: "".to_string(),
+        birth_date: None,
+        last_name: "Muster;mann".to_string(),
+        ..member()
+    });
+    assert!(!vcard.content.contains("ADR"));
+    assert!(!vcard.content.contains("BDAY"));
+    assert!(vcard.content.contains("N:Muster\\;mann;Max;;;\r\n"));
+}
+
+#[test]
+fn photo_is_folded() {
+    let vcard = VCard::from_member(&Member {
+        photo: vec![0xff; 300],
+        ..member()
+    });
+    assert!(vcard.content.contains("PHOTO;ENCODING=b;TYPE=JPEG://///"));
+    assert!(vcard.content.split("\r\n").all(|line| line.len() <= 75));
+    let unfolded = vcard.content.replace("\r\n ", "");
+    let photo = unfolded
+        .lines()
+        .find_map(|line| line.strip_prefix("PHOTO;ENCODING=b;TYPE=JPEG:"))
+        .expect("photo line");
+    assert_eq!(
+        engine::general_purpose::STANDARD
+            .decode(photo)
+            .expect("base64"),
+        vec![0xff; 300]
+    );
+}
@@ -0,0 +1,309 @@
+// OpenKeg, the lightweight backend of the Musikverein Leopoldsdorf.
+// Copyright (C) 2022  Richard Stöckl
+//
+// This program is free software; you can redistribute it and/or
+// modify it under the terms of the GNU General Public License
+// as published by the Free Software Foundation; either version 2
+// of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::ContentType;
+use rocket::{Data, Request, Response, State};
+
+#[cfg(test)]
+#[path = "metrics_tests.rs"]
+mod metrics_tests;
+
+/// The registry of the metrics which is shared between the fairing, the routes and the background tasks.
+pub type MetricsRegistry = Arc<Metrics>;
+
+/// The upper bounds of the buckets of the request duration histogram in *seconds*.
+const DURATION_BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// The metrics of the server which are exposed in the Prometheus text format.
+#[derive(Default)]
+pub struct Metrics {
+    /// The requests mapped by their route, method and status.
+    requests: Mutex<BTreeMap<RequestLabels, RequestMetric>>,
+    /// The number of successful member synchronizations.
+    sync_successes: AtomicU64,
+    /// The number of failed member synchronizations.
+    sync_failures: AtomicU64,
+    /// The duration of the last member synchronization in *microseconds*.
+    sync_last_duration: AtomicU64,
+    /// The number of successful logins with credentials.
+    login_successes: AtomicU64,
+    /// The number of failed logins with credentials.
+    login_failures: AtomicU64,
+}
+
+/// The labels which identify the metric of a request.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone)]
+struct RequestLabels {
+    route: String,
+    method: String,
+    status: u16,
+}
+
+/// The count and the duration histogram of requests with the same labels.
+#[derive(Default)]
+struct RequestMetric {
+    /// The number of requests per bucket of [DURATION_BUCKETS], not cumulated.
+    buckets: [u64; DURATION_BUCKETS.len()],
+    /// The number of all requests.
+    count: u64,
+    /// The sum of the durations of all requests in *seconds*.
+    sum: f64,
+}
+
+impl Metrics {
+    /// Record a handled request.
+    ///
+    /// # Arguments
+    ///
+    /// * `route`: the uri of the matched route, `unmatched` if there is none
+    /// * `method`: the HTTP method of the request
+    /// * `status`: the HTTP status code of the response
+    /// * `duration`: the time it took to handle the request
+    pub fn record_request(&self, route: &str, method: &str, status: u16, duration: Duration) {
+        let labels = RequestLabels {
+            route: route.to_string(),
+            method: method.to_string(),
+            status,
+        };
+        let seconds = duration.as_secs_f64();
+        let mut requests = self.requests.lock().expect("Request metrics lock");
+        let metric = requests.entry(labels).or_default();
+        if let Some(bucket) = DURATION_BUCKETS.iter().position(|bound| seconds <= *bound) {
+            metric.buckets[bucket] += 1;
+        }
+        metric.count += 1;
+        metric.sum += seconds;
+    }
+
+    /// Record a finished member synchronization.
+    ///
+    /// # Arguments
+    ///
+    /// * `success`: whether the synchronization succeeded
+    /// * `duration`: the time the synchronization took
+    pub fn record_sync(&self, success: bool, duration: Duration) {
+        let counter = if success {
+            &self.sync_successes
+        } else {
+            &self.sync_failures
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        self.sync_last_duration
+            .store(duration.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    /// Record a login with credentials.
+    ///
+    /// # Arguments
+    ///
+    /// * `success`: whether the user was authenticated
+    pub fn record_login(&self, success: bool) {
+        let counter = if success {
+            &self.login_successes
+        } else {
+            &self.login_failures
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Render all metrics in the Prometheus text format.
+    ///
+    /// returns: String
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let requests = self.requests.lock().expect("Request metrics lock");
+        header(
+            &mut out,
+            "keg_http_requests_total",
+            "counter",
+            "The number of handled HTTP requests.",
+        );
+        for (labels, metric) in requests.iter() {
+            let _ = writeln!(
+                out,
+                "keg_http_requests_total{{{}}} {}",
+                labels.render(),
+                metric.count
+            );
+        }
+        header(
+            &mut out,
+            "keg_http_request_duration_seconds",
+            "histogram",
+            "The duration of handling HTTP requests.",
+        );
+        for (labels, metric) in requests.iter() {
+            let labels = labels.render();
+            let mut cumulated = 0;
+            for (bound, count) in DURATION_BUCKETS.iter().zip(metric.buckets) {
+                cumulated += count;
+                let _ = writeln!(
+                    out,
+                    "keg_http_request_duration_seconds_bucket{{{},le=\"{}\"}} {}",
+                    labels, bound, cumulated
+                );
+            }
+            let _ = writeln!(
+                out,
+                "keg_http_request_duration_seconds_bucket{{{},le=\"+Inf\"}} {}",
+                labels, metric.count
+            );
+            let _ = writeln!(
+                out,
+                "keg_http_request_duration_seconds_sum{{{}}} {}",
+                labels, metric.sum
+            );
+            let _ = writeln!(
+                out,
+                "keg_http_request_duration_seconds_count{{{}}} {}",
+                labels, metric.count
+            );
+        }
+        drop(requests);
+        header(
+            &mut out,
+            "keg_member_sync_total",
+            "counter",
+            "The number of member synchronizations with the directory server.",
+        );
+        result_counters(
+            &mut out,
+            "keg_member_sync_total",
+            &self.sync_successes,
+            &self.sync_failures,
+        );
+        header(
+            &mut out,
+            "keg_member_sync_last_duration_seconds",
+            "gauge",
+            "The duration of the last member synchronization.",
+        );
+        let _ = writeln!(
+            out,
+            "keg_member_sync_last_duration_seconds {}",
+            self.sync_last_duration.load(Ordering::Relaxed) as f64 / 1_000_000.0
+        );
+        header(
+            &mut out,
+            "keg_logins_total",
+            "counter",
+            "The number of logins with credentials.",
+        );
+        result_counters(
+            &mut out,
+            "keg_logins_total",
+            &self.login_successes,
+            &self.login_failures,
+        );
+        out
+    }
+}
+
+impl RequestLabels {
+    /// Render the labels in the Prometheus text format without the surrounding braces.
+    fn render(&self) -> String {
+        format!(
+            "route=\"{}\",method=\"{}\",status=\"{}\"",
+            escape(&self.route),
+            escape(&self.method),
+            self.status
+        )
+    }
+}
+
+/// Write the help and type lines of a metric.
+fn header(out: &mut String, name: &str, metric_type: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, metric_type);
+}
+
+/// Write a counter which is split into successes and failures by the `result` label.
+fn result_counters(out: &mut String, name: &str, successes: &AtomicU64, failures: &AtomicU64) {
+    let _ = writeln!(
+        out,
+        "{}{{result=\"success\"}} {}",
+        name,
+        successes.load(Ordering::Relaxed)
+    );
+    let _ = writeln!(
+        out,
+        "{}{{result=\"failure\"}} {}",
+        name,
+        failures.load(Ordering::Relaxed)
+    );
+}
+
+/// Escape a label value for the Prometheus text format.
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// The fairing which records the count and duration of all requests.
+pub struct RequestMetrics(pub MetricsRegistry);
+
+#[rocket::async_trait]
+impl Fairing for RequestMetrics {
+    fn info(&self) -> Info {
+        Info {
+            name: "Record request metrics",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, _: &mut Data<'_>) {
+        request.local_cache(Instant::now);
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let start = request.local_cache(Instant::now);
+        let route = request
+            .route()
+            .map_or("unmatched".to_string(), |route| route.uri.to_string());
+        self.0.record_request(
+            &route,
+            request.method().as_str(),
+            response.status().code,
+            start.elapsed(),
+        );
+    }
+}
+
+/// Expose all metrics in the Prometheus text format.
+/// This route is only mounted if [crate::config::MetricsConfig::enabled] is set.
+///
+/// # Arguments
+///
+/// * `metrics`: the registry of the metrics
+///
+/// returns: (ContentType, String)
+#[get("/metrics")]
+pub fn get_metrics(metrics: &State<MetricsRegistry>) -> (ContentType, String) {
+    let content_type = ContentType::new("text", "plain").with_params(("version", "0.0.4"));
+    (content_type, metrics.render())
+}
@@ -0,0 +1,64 @@
+// OpenKeg, the lightweight backend of the Musikverein Leopoldsdorf.
+// Copyright (C) 2022  Richard Stöckl
+//
+// This program is free software; you can redistribute it and/or
+// modify it under the terms of the GNU General Public License
+// as published by the Free Software Foundation; either version 2
+// of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+
+use super::*;
+
+#[test]
+fn render_requests() {
+    let metrics = Metrics::default();
+    metrics.record_request("/api/v1/scores", "GET", 200, Duration::from_millis(30));
+    metrics.record_request("/api/v1/scores", "GET", 200, Duration::from_secs(20));
+    let rendered = metrics.render();
+    let labels = r#"route="/api/v1/scores",method="GET",status="200""#;
+    assert!(rendered.contains(&format!("keg_http_requests_total{{{}}} 2", labels)));
+    assert!(rendered.contains(&format!(
+        "keg_http_request_duration_seconds_bucket{{{},le=\"0.025\"}} 0",
+        labels
+    )));
+    assert!(rendered.contains(&format!(
+        "keg_http_request_duration_seconds_bucket{{{},le=\"0.05\"}} 1",
+        labels
+    )));
+    assert!(rendered.contains(&format!(
+        "keg_http_request_duration_seconds_bucket{{{},le=\"10\"}} 1",
+        labels
+    )));
+    assert!(rendered.contains(&format!(
+        "keg_http_request_duration_seconds_bucket{{{},le=\"+Inf\"}} 2",
+        labels
+    )));
+}
+
+#[test]
+fn render_counters() {
+    let metrics = Metrics::default();
+    metrics.record_sync(false, Duration::from_secs(1));
+    metrics.record_sync(true, Duration::from_millis(1500));
+    metrics.record_login(true);
+    metrics.record_login(false);
+    metrics.record_login(false);
+    let rendered = metrics.render();
+    assert!(rendered.contains("keg_member_sync_total{result=\"success\"} 1\n"));
+    assert!(rendered.contains("keg_member_sync_total{result=\"failure\"} 1\n"));
+    assert!(rendered.contains("keg_member_sync_last_duration_seconds 1.5\n"));
+    assert!(rendered.contains("keg_logins_total{result=\"failure\"} 2\n"));
+}
+
+#[test]
+fn escape_labels() {
+    assert_eq!(escape("a\"b\\c\nd"), "a\\\"b\\\\c\\nd");
+}
@@ -159,6 +159,24 @@ pub fn map_io_err<T>(result: io::Result<T>, status: Status) -> Result<T, ApiErro
     })
 }
 
+/// Returns an [ApiError] indicating that a file or an upload exceeds its maximum size.
+///
+/// # Arguments
+///
+/// * `max_size`: the maximum size of the file in *bytes*
+///
+/// returns: ApiError
+pub fn too_large_error(max_size: u64) -> ApiError {
+    ApiError {
+        err: "Payload Too Large".to_string(),
+        msg: Some(format!(
+            "The document exceeds the maximum size of {} bytes",
+            max_size
+        )),
+        http_status_code: Status::PayloadTooLarge.code,
+    }
+}
+
 impl From<rocket::serde::json::Error<'_>> for ApiError {
     fn from(err: rocket::serde::json::Error) -> Self {
         use rocket::serde::json::Error::*;
@@ -218,23 +236,52 @@ pub fn custom_openapi_spec(rocket: &Rocket<Build>) -> OpenApi {
             version: env!("CARGO_PKG_VERSION").to_owned(),
             ..Default::default()
         },
-        servers: vec![
-            Server {
-                url: config.openapi_url,
-                description: Some("Self Hosted Instance".to_owned()),
-                ..Default::default()
-            },
-            Server {
+        servers: [
+            self_hosted_server(&config),
+            Some(Server {
                 url: format!("http://localhost:{}/api/v1/", rocket_config.port),
                 description: Some("Localhost".to_owned()),
                 ..Default::default()
-            },
-            Server {
+            }),
+            Some(Server {
                 url: "https://keg.mvl.at/api/v1/".to_owned(),
                 description: Some("Sample Production Server".to_owned()),
                 ..Default::default()
-            },
-        ],
+            }),
+        ]
+        .into_iter()
+        .flatten()
+        .collect(),
         ..Default::default()
     }
 }
+
+/// Create the OpenApi server entry for the [Config::openapi_url].
+/// A warning will be printed to the log if the URL does not use HTTPS.
+/// If [Config::enforce_https_openapi_url] is set, such an URL is omitted.
+///
+/// # Arguments
+///
+/// * `config`: the application configuration
+///
+/// returns: Option<Server>
+fn self_hosted_server(config: &Config) -> Option<okapi::openapi3::Server> {
+    if !config.openapi_url.starts_with("https://") {
+        if config.enforce_https_openapi_url {
+            warn!(
+                "The OpenApi url '{}' does not use HTTPS and is omitted from the schema",
+                config.openapi_url
+            );
+            return None;
+        }
+        warn!(
+            "The OpenApi url '{}' does not use HTTPS, generated clients may use an insecure connection",
+            config.openapi_url
+        );
+    }
+    Some(okapi::openapi3::Server {
+        url: config.openapi_url.clone(),
+        description: Some("Self Hosted Instance".to_owned()),
+        ..Default::default()
+    })
+}
@@ -0,0 +1,168 @@
+// OpenKeg, the lightweight backend of the Musikverein Leopoldsdorf.
+// Copyright (C) 2022  Richard Stöckl
+//
+// This program is free software; you can redistribute it and/or
+// modify it under the terms of the GNU General Public License
+// as published by the Free Software Foundation; either version 2
+// of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::Header;
+use rocket::request::{FromRequest, Outcome};
+use rocket::route::{self, Handler};
+use rocket::{Data, Request, Response, Route};
+use rocket_okapi::gen::OpenApiGenerator;
+use rocket_okapi::okapi::openapi3::OpenApi;
+use rocket_okapi::request::{OpenApiFromRequest, RequestHeaderInput};
+use uuid::Uuid;
+
+#[cfg(test)]
+#[path = "request_id_tests.rs"]
+mod request_id_tests;
+
+/// The header which carries the id of a request, both in the request and the response.
+pub const REQUEST_ID_HEADER: &str = "X-Request-Id";
+/// The maximum length of a request id provided by a client, longer ids are replaced.
+const MAX_REQUEST_ID_LENGTH: usize = 128;
+
+rocket::tokio::task_local! {
+    /// The id of the request which is currently handled by the task.
+    static CURRENT_REQUEST_ID: String;
+}
+
+/// The id which correlates the log lines of a single request.
+/// It is taken from the [REQUEST_ID_HEADER] of the request or generated as UUID.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestId(pub String);
+
+impl RequestId {
+    /// Take the id provided by the client or generate a new one.
+    /// Ids which are empty, too long or contain other than visible ASCII characters are replaced.
+    ///
+    /// # Arguments
+    ///
+    /// * `header`: the value of the [REQUEST_ID_HEADER], if any
+    ///
+    /// returns: RequestId
+    fn from_header(header: Option<&str>) -> Self {
+        match header {
+            Some(id)
+                if !id.is_empty()
+                    && id.len() <= MAX_REQUEST_ID_LENGTH
+                    && id.chars().all(|c| c.is_ascii_graphic()) =>
+            {
+                Self(id.to_string())
+            }
+            _ => Self(Uuid::new_v4().to_string()),
+        }
+    }
+
+    /// Get the id of the request, it is determined once per request.
+    ///
+    /// # Arguments
+    ///
+    /// * `request`: the request to get the id of
+    ///
+    /// returns: &RequestId
+    pub fn of<'r>(request: &'r Request<'_>) -> &'r RequestId {
+        request.local_cache(|| Self::from_header(request.headers().get_one(REQUEST_ID_HEADER)))
+    }
+}
+
+/// Get the id of the request which is currently handled, if any.
+/// Only available within the handlers of routes wrapped by [correlated].
+///
+/// returns: Option<String>
+pub fn current_request_id() -> Option<String> {
+    CURRENT_REQUEST_ID.try_with(Clone::clone).ok()
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for RequestId {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(RequestId::of(request).clone())
+    }
+}
+
+impl<'r> OpenApiFromRequest<'r> for RequestId {
+    fn from_request_input(
+        _gen: &mut OpenApiGenerator,
+        _name: String,
+        _required: bool,
+    ) -> rocket_okapi::Result<RequestHeaderInput> {
+        Ok(RequestHeaderInput::None)
+    }
+}
+
+/// The fairing which determines the id of every request and echoes it in the [REQUEST_ID_HEADER] of the response.
+pub struct RequestIdFairing;
+
+#[rocket::async_trait]
+impl Fairing for RequestIdFairing {
+    fn info(&self) -> Info {
+        Info {
+            name: "Correlate requests by their id",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, _data: &mut Data<'_>) {
+        let id = RequestId::of(request);
+        debug!(
+            "Request {} {} has the id {}",
+            request.method(),
+            request.uri(),
+            id.0
+        );
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        response.set_header(Header::new(
+            REQUEST_ID_HEADER,
+            RequestId::of(request).0.clone(),
+        ));
+    }
+}
+
+/// A handler which makes the id of the request available to the log lines of the wrapped handler.
+#[derive(Clone)]
+struct CorrelatedHandler(Box<dyn Handler>);
+
+#[rocket::async_trait]
+impl Handler for CorrelatedHandler {
+    async fn handle<'r>(&self, request: &'r Request<'_>, data: Data<'r>) -> route::Outcome<'r> {
+        let id = RequestId::of(request).0.clone();
+        CURRENT_REQUEST_ID
+            .scope(id, self.0.handle(request, data))
+            .await
+    }
+}
+
+/// Wrap the handlers of the routes so that their log lines contain the id of the request, see [current_request_id].
+///
+/// # Arguments
+///
+/// * `routes_and_docs`: the routes and their OpenApi documentation
+///
+/// returns: (Vec<Route>, OpenApi)
+pub fn correlated((routes, docs): (Vec<Route>, OpenApi)) -> (Vec<Route>, OpenApi) {
+    let routes = routes
+        .into_iter()
+        .map(|mut route| {
+            route.handler = Box::new(CorrelatedHandler(route.handler.clone()));
+            route
+        })
+        .collect();
+    (routes, docs)
+}
@@ -0,0 +1,45 @@
+// OpenKeg, the lightweight backend of the Musikverein Leopoldsdorf.
+// Copyright (C) 2022  Richard Stöckl
+//
+// This program is free software; you can redistribute it and/or
+// modify it under the terms of the GNU General Public License
+// as published by the Free Software Foundation; either version 2
+// of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+
+use super::*;
+
+#[test]
+fn provided_id() {
+    assert_eq!(
+        RequestId::from_header(Some("frontend-42")),
+        RequestId("frontend-42".to_string())
+    );
+}
+
+#[test]
+fn generated_id() {
+    for header in [None, Some(""), Some("with space"), Some("ü")] {
+        let id = RequestId::from_header(header);
+        assert!(Uuid::parse_str(&id.0).is_ok(), "{:?}", header);
+    }
+    let long = "a".repeat(MAX_REQUEST_ID_LENGTH + 1);
+    assert_ne!(RequestId::from_header(Some(&long)).0, long);
+}
+
+#[rocket::async_test]
+async fn current_id_within_scope() {
+    assert_eq!(current_request_id(), None);
+    let id = CURRENT_REQUEST_ID
+        .scope("frontend-42".to_string(), async { current_request_id() })
+        .await;
+    assert_eq!(id.as_deref(), Some("frontend-42"));
+}
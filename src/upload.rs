@@ -0,0 +1,168 @@
+// OpenKeg, the lightweight backend of the Musikverein Leopoldsdorf.
+// Copyright (C) 2022  Richard Stöckl
+//
+// This program is free software; you can redistribute it and/or
+// modify it under the terms of the GNU General Public License
+// as published by the Free Software Foundation; either version 2
+// of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+
+use std::future::Future;
+use std::io;
+use std::path::Path;
+
+use futures::channel::mpsc;
+use futures::SinkExt;
+use okapi::openapi3::RequestBody;
+use reqwest::Body;
+use rocket::data::{ByteUnit, FromData, Limits, Outcome};
+use rocket::http::Status;
+use rocket::tokio::io::AsyncReadExt;
+use rocket::{Data, Request};
+use rocket_okapi::gen::OpenApiGenerator;
+use rocket_okapi::request::OpenApiFromData;
+
+#[cfg(test)]
+#[path = "upload_tests.rs"]
+mod upload_tests;
+
+use crate::openapi::{map_io_err, too_large_error, ApiError};
+
+/// The size of the chunks in which an upload is forwarded.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// The body of a request which is passed on while it is received instead of being buffered in memory.
+/// Its size is limited by the `bytes` limit of the server.
+pub struct Upload<'r> {
+    /// The data of the request.
+    data: Data<'r>,
+    /// The `bytes` limit of the server.
+    limit: u64,
+}
+
+#[rocket::async_trait]
+impl<'r> FromData<'r> for Upload<'r> {
+    type Error = ();
+
+    async fn from_data(request: &'r Request<'_>, data: Data<'r>) -> Outcome<'r, Self> {
+        let limit = request.limits().get("bytes").unwrap_or(Limits::BYTES);
+        Outcome::Success(Upload {
+            data,
+            limit: limit.as_u64(),
+        })
+    }
+}
+
+impl<'r> OpenApiFromData<'r> for Upload<'r> {
+    fn request_body(gen: &mut OpenApiGenerator) -> rocket_okapi::Result<RequestBody> {
+        Vec::<u8>::request_body(gen)
+    }
+}
+
+impl<'r> Upload<'r> {
+    /// Forward the upload as body of a request to another server.
+    /// The returned future receives the upload and has to be awaited concurrently with the request, e.g. with [futures::future::join].
+    /// If the upload exceeds its maximum size, the body is aborted with an error and the future fails with 'Payload Too Large'.
+    /// Since the body cannot be cloned, the request cannot be repeated.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_size`: the maximum size of the upload in *bytes*, limited by the server only if `None`
+    ///
+    /// returns: (Body, impl Future<Output=Result<(), ApiError>>) the body and the future which fills it
+    pub fn forward(
+        self,
+        max_size: Option<u64>,
+    ) -> (Body, impl Future<Output = Result<(), ApiError>> + 'r) {
+        let max_size = self.max_size(max_size);
+        let (mut sender, receiver) = mpsc::channel::<io::Result<Vec<u8>>>(1);
+        let receive = async move {
+            let mut stream = self.data.open(ByteUnit::from(max_size.saturating_add(1)));
+            let mut received = 0u64;
+            loop {
+                let mut chunk = vec![0; CHUNK_SIZE];
+                let read = match stream.read(&mut chunk).await {
+                    Ok(0) => return Ok(()),
+                    Ok(read) => read,
+                    Err(e) => {
+                        warn!("Unable to receive the upload: {}", e);
+                        let _ = sender.send(Err(e)).await;
+                        return Err(incomplete_error());
+                    }
+                };
+                received += read as u64;
+                if received > max_size {
+                    let _ = sender
+                        .send(Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            "The upload exceeds the maximum size",
+                        )))
+                        .await;
+                    return Err(too_large_error(max_size));
+                }
+                chunk.truncate(read);
+                if sender.send(Ok(chunk)).await.is_err() {
+                    debug!("The request which forwards the upload was aborted");
+                    return Ok(());
+                }
+            }
+        };
+        (Body::wrap_stream(receiver), receive)
+    }
+
+    /// Write the upload to a file.
+    /// The upload is received in a temporary file next to it first, an existing file is only replaced once the upload is complete.
+    ///
+    /// # Arguments
+    ///
+    /// * `path`: the path of the file
+    /// * `max_size`: the maximum size of the upload in *bytes*, limited by the server only if `None`
+    ///
+    /// returns: Result<(), ApiError>
+    pub async fn write_to(self, path: &Path, max_size: Option<u64>) -> Result<(), ApiError> {
+        let max_size = self.max_size(max_size);
+        let mut temporary_name = path.file_name().unwrap_or_default().to_os_string();
+        temporary_name.push(".upload");
+        let temporary_path = path.with_file_name(temporary_name);
+        let written = self
+            .data
+            .open(ByteUnit::from(max_size.saturating_add(1)))
+            .into_file(&temporary_path)
+            .await
+            .map(|file| file.n.written);
+        let result = match written {
+            Ok(written) if written > max_size => Err(too_large_error(max_size)),
+            Ok(_) => map_io_err(
+                rocket::tokio::fs::rename(&temporary_path, path).await,
+                Status::InternalServerError,
+            ),
+            Err(e) => map_io_err(Err(e), Status::InternalServerError),
+        };
+        if result.is_err() {
+            let _ = rocket::tokio::fs::remove_file(&temporary_path).await;
+        }
+        result
+    }
+
+    /// The effective maximum size of the upload which is never larger than the `bytes` limit of the server.
+    fn max_size(&self, max_size: Option<u64>) -> u64 {
+        max_size.map_or(self.limit, |max_size| max_size.min(self.limit))
+    }
+}
+
+/// Returns an [ApiError] indicating that the upload was not received completely.
+fn incomplete_error() -> ApiError {
+    ApiError {
+        err: "Bad Request".to_string(),
+        msg: Some("The upload was not received completely".to_string()),
+        http_status_code: Status::BadRequest.code,
+    }
+}
@@ -0,0 +1,154 @@
+// OpenKeg, the lightweight backend of the Musikverein Leopoldsdorf.
+// Copyright (C) 2022  Richard Stöckl
+//
+// This program is free software; you can redistribute it and/or
+// modify it under the terms of the GNU General Public License
+// as published by the Free Software Foundation; either version 2
+// of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::{env, fs, thread};
+
+use futures::future::join;
+use rocket::local::asynchronous::Client;
+use rocket::State;
+use uuid::Uuid;
+
+use super::*;
+
+/// The URL of the server which receives the forwarded uploads.
+struct Target(String);
+
+/// Accept a single request with a chunked body and answer with the received body.
+fn echo_server() -> Target {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("listener");
+    let url = format!("http://{}", listener.local_addr().expect("address"));
+    thread::spawn(move || {
+        let (stream, _) = listener.accept().expect("connection");
+        let mut reader = BufReader::new(stream.try_clone().expect("stream"));
+        let mut line = String::new();
+        while reader.read_line(&mut line).expect("header") > 2 {
+            line.clear();
+        }
+        let mut body = vec![];
+        loop {
+            line.clear();
+            if reader.read_line(&mut line).unwrap_or(0) == 0 {
+                return;
+            }
+            let size = usize::from_str_radix(line.trim(), 16).expect("chunk size");
+            let mut chunk = vec![0; size + 2];
+            if reader.read_exact(&mut chunk).is_err() {
+                return;
+            }
+            if size == 0 {
+                break;
+            }
+            body.extend_from_slice(&chunk[..size]);
+        }
+        let mut stream = stream;
+        write!(
+            stream,
+            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            body.len()
+        )
+        .expect("response head");
+        stream.write_all(&body).expect("response body");
+    });
+    Target(url)
+}
+
+#[put("/forward?<max_size>", data = "<upload>")]
+async fn forward(
+    upload: Upload<'_>,
+    max_size: Option<u64>,
+    target: &State<Target>,
+) -> Result<String, ApiError> {
+    let (body, receive) = upload.forward(max_size);
+    let request = async {
+        reqwest::Client::new()
+            .put(&target.0)
+            .body(body)
+            .send()
+            .await?
+            .text()
+            .await
+    };
+    let (response, received) = join(request, receive).await;
+    received?;
+    Ok(response.expect("forwarded"))
+}
+
+#[put("/file?<max_size>", data = "<upload>")]
+async fn file(
+    upload: Upload<'_>,
+    max_size: Option<u64>,
+    path: &State<PathBuf>,
+) -> Result<(), ApiError> {
+    upload.write_to(path, max_size).await
+}
+
+async fn client() -> Client {
+    let figment = rocket::Config::figment().merge(("limits.bytes", "1 MiB"));
+    let path = env::temp_dir().join(format!("openkeg-upload-{}.md", Uuid::new_v4()));
+    let rocket = rocket::custom(figment)
+        .manage(echo_server())
+        .manage(path)
+        .mount("/", routes![forward, file]);
+    Client::tracked(rocket).await.expect("rocket client")
+}
+
+#[rocket::async_test]
+async fn forwards_upload() {
+    let content = "Ibimsdapdfkoal".repeat(10_000);
+    let client = client().await;
+    let response = client.put("/forward").body(&content).dispatch().await;
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.into_string().await, Some(content));
+}
+
+#[rocket::async_test]
+async fn rejects_too_large_upload() {
+    let client = client().await;
+    let response = client
+        .put("/forward?max_size=4")
+        .body("Ibimsdapdfkoal")
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::PayloadTooLarge);
+}
+
+#[rocket::async_test]
+async fn writes_complete_upload_only() {
+    let client = client().await;
+    let path = client.rocket().state::<PathBuf>().expect("path").clone();
+    let response = client
+        .put("/file")
+        .body("# Ibimsdablackboard")
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    let response = client
+        .put("/file?max_size=4")
+        .body("# Ibimsdaneueblackboard")
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::PayloadTooLarge);
+    let content = fs::read_to_string(&path).expect("written file");
+    fs::remove_file(&path).expect("removed file");
+    assert_eq!(content, "# Ibimsdablackboard");
+    let mut temporary_path = path.into_os_string();
+    temporary_path.push(".upload");
+    assert!(!PathBuf::from(temporary_path).exists());
+}
@@ -23,10 +23,11 @@ use okapi::openapi3::{
     Object, ParameterValue, RefOr, Response, Responses, SecurityRequirement, SecurityScheme,
     SecuritySchemeData,
 };
-use rocket::http::{ContentType, Header, Status};
+use rocket::http::{ContentType, Cookie, Header, SameSite, Status};
 use rocket::outcome::Outcome::{Failure, Forward, Success};
 use rocket::request::{FromRequest, Outcome};
 use rocket::response::Responder;
+use rocket::time::Duration;
 use rocket::Request;
 use rocket_okapi::gen::OpenApiGenerator;
 use rocket_okapi::request::{OpenApiFromRequest, RequestHeaderInput};
@@ -35,9 +36,14 @@ use rocket_okapi::response::OpenApiResponderInner;
 use crate::member::model::Member;
 use crate::openapi::ApiError;
 use crate::user::tokens::{
-    member_from_claims, Claims, AUTHORIZATION_HEADER, AUTHORIZATION_RENEWAL_HEADER,
+    member_from_claims, renewal_name, Claims, AUTHORIZATION_HEADER, DEFAULT_RENEWAL_NAME,
+    LEGACY_RENEWAL_HEADER,
 };
-use crate::MemberStateMutex;
+use crate::{Config, MemberStateMutex};
+
+#[cfg(test)]
+#[path = "auth_tests.rs"]
+mod auth_tests;
 
 /// The basic auth structure as used in the HTTP protocol.
 #[non_exhaustive]
@@ -185,7 +191,7 @@ pub(crate) fn authorization_error() -> ApiError {
 }
 
 impl<'r> Responder<'r, 'static> for AuthenticationResponder {
-    fn respond_to(self, _request: &'r Request<'_>) -> rocket::response::Result<'static> {
+    fn respond_to(self, request: &'r Request<'_>) -> rocket::response::Result<'static> {
         if (self.request_token.is_none() && self.request_token_required)
             || (self.renewal_token.is_none() && self.renewal_token_required)
         {
@@ -205,8 +211,23 @@ impl<'r> Responder<'r, 'static> for AuthenticationResponder {
             ));
         }
         if let Some(token) = self.renewal_token {
+            let name = renewal_name(request);
+            let expiration = request
+                .rocket()
+                .state::<Config>()
+                .map(|c| c.jwt.renewal_expiration)
+                .unwrap_or_default();
+            request.cookies().add(
+                Cookie::build(name.clone(), token.clone())
+                    .max_age(Duration::hours(expiration))
+                    .http_only(true)
+                    .same_site(SameSite::Strict)
+                    .secure(request.rocket().config().tls_enabled())
+                    .finish(),
+            );
+            response_builder.header(Header::new(name, format!("Bearer {}", token)));
             response_builder.header(Header::new(
-                AUTHORIZATION_RENEWAL_HEADER,
+                LEGACY_RENEWAL_HEADER,
                 format!("Bearer {}", token),
             ));
         }
@@ -224,13 +245,20 @@ impl OpenApiResponderInner for AuthenticationResponder {
             allow_empty_value: false,
             value: ParameterValue::Content {content: map!{}},
             extensions: map! {}
-        }),AUTHORIZATION_RENEWAL_HEADER.to_string() => RefOr::Object(Header{
-            description: Some("The renewal token, prefixed with 'Bearer '".to_string()),
+        }),DEFAULT_RENEWAL_NAME.to_string() => RefOr::Object(Header{
+            description: Some("The renewal token, prefixed with 'Bearer '. The token is also set as cookie with the same name. The name is configurable and may differ".to_string()),
             required: false,
             deprecated: false,
             allow_empty_value: false,
             value: ParameterValue::Content {content: map!{}},
             extensions: map! {}
+        }),LEGACY_RENEWAL_HEADER.to_string() => RefOr::Object(Header{
+            description: Some("The renewal token, prefixed with 'Bearer '. Only kept for clients which do not read the configurable header".to_string()),
+            required: false,
+            deprecated: true,
+            allow_empty_value: false,
+            value: ParameterValue::Content {content: map!{}},
+            extensions: map! {}
         })};
         let err_response = Response {
             description: "The authentication failed".to_string(),
@@ -0,0 +1,137 @@
+// OpenKeg, the lightweight backend of the Musikverein Leopoldsdorf.
+// Copyright (C) 2022  Richard Stöckl
+//
+// This program is free software; you can redistribute it and/or
+// modify it under the terms of the GNU General Public License
+// as published by the Free Software Foundation; either version 2
+// of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+
+use rocket::local::blocking::Client;
+
+use super::*;
+use crate::user::tokens::RenewalToken;
+
+#[get("/login")]
+fn issue_tokens() -> AuthenticationResponder {
+    AuthenticationResponder {
+        request_token: Some("request".to_string()),
+        request_token_required: true,
+        renewal_token: Some("renewal".to_string()),
+        renewal_token_required: true,
+    }
+}
+
+#[get("/renewal")]
+fn read_renewal(token: RenewalToken) -> String {
+    token.0
+}
+
+fn client(renewal_name: &str) -> Client {
+    let mut config = Config::default();
+    config.jwt.renewal_name = renewal_name.to_string();
+    let rocket = rocket::build().manage(config).mount(
+        "/",
+        routes![
+            issue_tokens,
+            read_renewal,
+            crate::user::self_service::logout
+        ],
+    );
+    Client::tracked(rocket).expect("rocket client")
+}
+
+#[test]
+fn login_sets_renewal_name_read_by_renewal() {
+    let client = client("Renewal");
+    let login = client.get("/login").dispatch();
+    assert_eq!(login.status(), Status::Ok);
+    assert_eq!(login.headers().get_one("Renewal"), Some("Bearer renewal"));
+    assert_eq!(
+        login.cookies().get("Renewal").map(|c| c.value()),
+        Some("renewal")
+    );
+    let renewal = client.get("/renewal").dispatch();
+    assert_eq!(renewal.into_string(), Some("renewal".to_string()));
+}
+
+#[test]
+fn renewal_cookie_is_restricted() {
+    let client = client("Renewal");
+    let login = client.get("/login").dispatch();
+    let cookie = login
+        .cookies()
+        .get("Renewal")
+        .cloned()
+        .expect("renewal cookie");
+    assert_eq!(cookie.http_only(), Some(true));
+    assert_eq!(cookie.same_site(), Some(SameSite::Strict));
+    assert_ne!(cookie.secure(), Some(true));
+}
+
+#[test]
+fn renewal_name_is_configurable() {
+    let client = client("X-Keg-Renewal");
+    let login = client.get("/login").dispatch();
+    assert!(login.headers().get_one("Renewal").is_none());
+    assert!(login.cookies().get("X-Keg-Renewal").is_some());
+    let renewal = client
+        .get("/renewal")
+        .header(Header::new("X-Keg-Renewal", "Bearer from-header"))
+        .dispatch();
+    assert_eq!(renewal.into_string(), Some("renewal".to_string()));
+}
+
+#[test]
+fn renewal_reads_configured_header() {
+    let client = client("X-Keg-Renewal");
+    let renewal = client
+        .get("/renewal")
+        .header(Header::new("X-Keg-Renewal", "Bearer from-header"))
+        .dispatch();
+    assert_eq!(renewal.into_string(), Some("from-header".to_string()));
+}
+
+#[test]
+fn logout_removes_renewal_cookie() {
+    let client = client("Renewal");
+    client.get("/login").dispatch();
+    assert!(client.cookies().get("Renewal").is_some());
+    let logout = client.get("/logout").dispatch();
+    assert_eq!(logout.status(), Status::Ok);
+    assert!(client.cookies().get("Renewal").is_none());
+}
+
+#[test]
+fn renewal_reads_authorization_renewal_header() {
+    let client = client("Renewal");
+    let renewal = client
+        .get("/renewal")
+        .header(Header::new("Authorization-Renewal", "Bearer legacy"))
+        .dispatch();
+    assert_eq!(renewal.into_string(), Some("legacy".to_string()));
+}
+
+#[test]
+fn renewal_keeps_legacy_header() {
+    let login_client = client("Renewal");
+    let login = login_client.get("/login").dispatch();
+    assert_eq!(
+        login.headers().get_one("x-authorization-renewal"),
+        Some("Bearer renewal")
+    );
+    let renewal_client = client("Renewal");
+    let renewal = renewal_client
+        .get("/renewal")
+        .header(Header::new("x-authorization-renewal", "Bearer legacy"))
+        .dispatch();
+    assert_eq!(renewal.into_string(), Some("legacy".to_string()));
+}
@@ -23,12 +23,21 @@ use rocket_okapi::gen::OpenApiGenerator;
 use rocket_okapi::request::{OpenApiFromRequest, RequestHeaderInput};
 
 use crate::config::ExecutiveMapping;
-use crate::member::model::Member;
+use crate::ldap::normalize_dn;
+use crate::member::model::{ExecutiveAssignment, ExecutiveAudit, Member};
+use crate::member::state::{AllMembers, Executives};
 use crate::user::auth::bearer_documentation;
 use crate::{Config, MemberStateMutex};
 
+#[cfg(test)]
+#[path = "executives_tests.rs"]
+mod executives_tests;
+
 /// Provide the ability of read the group name out of the [`ExecutiveMapping`].
 pub trait GroupName {
+    /// The identifier of the role which matches the field in the [`ExecutiveMapping`].
+    const ROLE_ID: &'static str;
+
     fn group_name(executive_mapping: &ExecutiveMapping) -> &String;
 }
 
@@ -44,11 +53,37 @@ where
 pub struct Archive();
 
 impl GroupName for Archive {
+    const ROLE_ID: &'static str = "archive";
+
     fn group_name(executive_mapping: &ExecutiveMapping) -> &String {
         &executive_mapping.archive
     }
 }
 
+/// A role which is able to manage the calendar.
+#[derive(Default, Debug)]
+pub struct Calendar();
+
+impl GroupName for Calendar {
+    const ROLE_ID: &'static str = "calendar";
+
+    fn group_name(executive_mapping: &ExecutiveMapping) -> &String {
+        &executive_mapping.calendar
+    }
+}
+
+/// A role which is able to keep the governance records such as auditing the executive groups.
+#[derive(Default, Debug)]
+pub struct Secretary();
+
+impl GroupName for Secretary {
+    const ROLE_ID: &'static str = "secretary";
+
+    fn group_name(executive_mapping: &ExecutiveMapping) -> &String {
+        &executive_mapping.secretary
+    }
+}
+
 #[rocket::async_trait]
 impl<'r, G> FromRequest<'r> for ExecutiveRole<G>
 where
@@ -65,21 +100,17 @@ where
             .rocket()
             .state::<MemberStateMutex>()
             .expect("member state");
-        let executives = &member_state.read().await.executives;
+        let member_state = member_state.read().await;
+        if !member_state.initialized {
+            debug!("Reject executive request as the members have not been synchronized yet");
+            return Failure((Status::ServiceUnavailable, ()));
+        }
+        let executives = &member_state.executives;
         let member_outcome: Outcome<Member, ()> = Member::from_request(request).await;
         if let Success(member) = member_outcome {
             debug!("Request contains the member '{}'", member.full_username);
             let group_name = G::group_name(&conf.ldap.executive_mapping);
-            let group = executives
-                .iter()
-                .find(|g| g.name_plural.eq_ignore_ascii_case(group_name));
-            if group.is_some()
-                && group
-                    .expect("Executive group")
-                    .members
-                    .iter()
-                    .any(|m| m.eq_ignore_ascii_case(member.full_username.as_str()))
-            {
+            if is_group_member(executives, group_name, &member) {
                 Success(ExecutiveRole(G::default()))
             } else {
                 warn!("Member '{}' is not member of the '{}' executive role or the group does not exist on the directory server", member.full_username, group_name);
@@ -104,3 +135,122 @@ where
         bearer_documentation()
     }
 }
+
+/// Check whether a member is part of an executive group.
+/// The group is looked up by its plural name, both the group name and the member name are compared case-insensitive.
+/// The names of the members are compared as normalized distinguished names, see [normalize_dn].
+///
+/// # Arguments
+///
+/// * `executives`: the executive groups
+/// * `group_name`: the plural name of the group
+/// * `member`: the member to check
+///
+/// returns: bool
+fn is_group_member(executives: &Executives, group_name: &str, member: &Member) -> bool {
+    executives
+        .iter()
+        .find(|g| g.name_plural.eq_ignore_ascii_case(group_name))
+        .is_some_and(|group| {
+            group
+                .members
+                .iter()
+                .any(|m| is_same_dn(m, &member.full_username))
+        })
+}
+
+/// Get the identifiers of all executive roles of a member.
+///
+/// # Arguments
+///
+/// * `executives`: the executive groups
+/// * `executive_mapping`: the mapping of the roles to the group names
+/// * `member`: the member to get the roles from
+///
+/// returns: Vec<String>
+pub fn role_ids(
+    executives: &Executives,
+    executive_mapping: &ExecutiveMapping,
+    member: &Member,
+) -> Vec<String> {
+    roles(executive_mapping)
+        .iter()
+        .filter(|(_, group_name)| is_group_member(executives, group_name, member))
+        .map(|(id, _)| id.to_string())
+        .collect()
+}
+
+/// Audit the memberships of all executive groups.
+/// Every member entry of a group is resolved against all known members, entries which cannot be resolved are flagged as stale.
+/// The groups are sorted by their plural name.
+///
+/// # Arguments
+///
+/// * `executives`: the executive groups
+/// * `executive_mapping`: the mapping of the roles to the group names
+/// * `all_members`: all known members
+///
+/// returns: Vec<ExecutiveAudit>
+pub fn audit(
+    executives: &Executives,
+    executive_mapping: &ExecutiveMapping,
+    all_members: &AllMembers,
+) -> Vec<ExecutiveAudit> {
+    let roles = roles(executive_mapping);
+    let mut audits: Vec<ExecutiveAudit> = executives
+        .iter()
+        .map(|group| ExecutiveAudit {
+            group: group.name_plural.clone(),
+            roles: roles
+                .iter()
+                .filter(|(_, group_name)| group.name_plural.eq_ignore_ascii_case(group_name))
+                .map(|(id, _)| id.to_string())
+                .collect(),
+            assignments: group
+                .members
+                .iter()
+                .map(|dn| {
+                    let username = all_members
+                        .iter()
+                        .find(|m| is_same_dn(&m.full_username, dn))
+                        .map(|m| m.username.clone());
+                    ExecutiveAssignment {
+                        dn: dn.clone(),
+                        stale: username.is_none(),
+                        username,
+                    }
+                })
+                .collect(),
+        })
+        .collect();
+    audits.sort_by(|a, b| a.group.cmp(&b.group));
+    audits
+}
+
+/// Check whether two distinguished names refer to the same entry.
+/// Both are normalized and compared case-insensitive.
+///
+/// # Arguments
+///
+/// * `a`: the first distinguished name
+/// * `b`: the second distinguished name
+///
+/// returns: bool
+fn is_same_dn(a: &str, b: &str) -> bool {
+    normalize_dn(a).eq_ignore_ascii_case(&normalize_dn(b))
+}
+
+/// Get the identifiers of all executive roles with the names of their groups.
+///
+/// # Arguments
+///
+/// * `executive_mapping`: the mapping of the roles to the group names
+///
+/// returns: [(&str, &String); 3]
+fn roles(executive_mapping: &ExecutiveMapping) -> [(&'static str, &String); 3] {
+    [
+        (Archive::ROLE_ID, Archive::group_name(executive_mapping)),
+        (Calendar::ROLE_ID, Calendar::group_name(executive_mapping)),
+        (Secretary::ROLE_ID, Secretary::group_name(executive_mapping)),
+    ]
+}
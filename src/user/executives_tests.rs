@@ -0,0 +1,56 @@
+// OpenKeg, the lightweight backend of the Musikverein Leopoldsdorf.
+// Copyright (C) 2022  Richard Stöckl
+//
+// This program is free software; you can redistribute it and/or
+// modify it under the terms of the GNU General Public License
+// as published by the Free Software Foundation; either version 2
+// of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+
+use super::*;
+use crate::member::model::Group;
+
+fn member(full_username: &str) -> Member {
+    Member {
+        full_username: full_username.to_string(),
+        ..Member::test_member("Karl", "Huber")
+    }
+}
+
+fn executives(members: &[&str]) -> Executives {
+    Executives::from([Group {
+        name: "Archivar".to_string(),
+        name_plural: "Archivare".to_string(),
+        description: "".to_string(),
+        members: members.iter().map(|m| m.to_string()).collect(),
+    }])
+}
+
+#[test]
+fn group_member_comma_spacing() {
+    let executives = executives(&["uid=karl, ou=members ,dc=mvl,dc=at"]);
+    let member = member("uid=karl,ou=members,dc=mvl,dc=at");
+    assert!(is_group_member(&executives, "archivare", &member));
+}
+
+#[test]
+fn group_member_attribute_type_case() {
+    let executives = executives(&["UID=karl,OU=members,DC=mvl,DC=at"]);
+    let member = member("uid=karl,ou=members,dc=mvl,dc=at");
+    assert!(is_group_member(&executives, "Archivare", &member));
+}
+
+#[test]
+fn group_member_other_dn() {
+    let executives = executives(&["uid=karla,ou=members,dc=mvl,dc=at"]);
+    let member = member("uid=karl,ou=members,dc=mvl,dc=at");
+    assert!(!is_group_member(&executives, "Archivare", &member));
+}
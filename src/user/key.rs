@@ -17,15 +17,261 @@
 
 use std::fs;
 use std::io::Error;
+use std::sync::{Arc, PoisonError, RwLock};
 
+use base64::{engine, Engine};
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey};
+use rocket::serde::{Deserialize, Serialize};
+use rocket::tokio::signal::unix::{signal, SignalKind};
+use rocket_okapi::JsonSchema;
+
+use crate::openapi::SchemaExample;
+use crate::user::tokens::SIGNING_ALGORITHM;
 use crate::Config;
 
+#[cfg(test)]
+#[path = "key_tests.rs"]
+mod key_tests;
+
+/// The tag of an ASN.1 sequence.
+const DER_SEQUENCE: u8 = 0x30;
+/// The tag of an ASN.1 integer.
+const DER_INTEGER: u8 = 0x02;
+/// The tag of an ASN.1 bit string.
+const DER_BIT_STRING: u8 = 0x03;
+
 /// The private key used in this application e.g. for jwt signing.
-pub struct PrivateKey(pub(crate) Vec<u8>);
+/// The second field holds the id of the key pair which is propagated in the header of signed tokens.
+pub struct PrivateKey(pub(crate) Vec<u8>, pub(crate) Option<String>);
 
 /// The public key used in this application e.g. for signature checks.
 pub struct PublicKey(pub(crate) Vec<u8>);
 
+/// The public and the private key of this application, each is absent if it cannot be read.
+#[derive(Default)]
+pub struct KeyPair {
+    /// The public key to verify the tokens with.
+    pub public_key: Option<PublicKey>,
+    /// The private key to sign the tokens with.
+    pub private_key: Option<PrivateKey>,
+}
+
+/// The keys of this application managed by the server.
+/// The keys can be replaced while the server is running which allows to rotate them without a restart, see [reload_keys].
+#[derive(Clone, Default)]
+pub struct Keys(Arc<RwLock<KeyPair>>);
+
+impl Keys {
+    /// Create the managed keys from a key pair.
+    ///
+    /// # Arguments
+    ///
+    /// * `key_pair`: the initial keys
+    ///
+    /// returns: Keys
+    pub fn new(key_pair: KeyPair) -> Self {
+        Self(Arc::new(RwLock::new(key_pair)))
+    }
+
+    /// Call a function with the current public key.
+    /// The keys cannot be replaced while the function is running.
+    ///
+    /// # Arguments
+    ///
+    /// * `f`: the function to call with the public key
+    ///
+    /// returns: Option<T> the result of the function or `None` if no public key is loaded
+    pub fn with_public_key<T>(&self, f: impl FnOnce(&PublicKey) -> T) -> Option<T> {
+        let key_pair = self.0.read().unwrap_or_else(PoisonError::into_inner);
+        key_pair.public_key.as_ref().map(f)
+    }
+
+    /// Call a function with the current private key.
+    /// The keys cannot be replaced while the function is running.
+    ///
+    /// # Arguments
+    ///
+    /// * `f`: the function to call with the private key
+    ///
+    /// returns: Option<T> the result of the function or `None` if no private key is loaded
+    pub fn with_private_key<T>(&self, f: impl FnOnce(&PrivateKey) -> T) -> Option<T> {
+        let key_pair = self.0.read().unwrap_or_else(PoisonError::into_inner);
+        key_pair.private_key.as_ref().map(f)
+    }
+
+    /// Replace both keys at once.
+    ///
+    /// # Arguments
+    ///
+    /// * `key_pair`: the new keys
+    fn replace(&self, key_pair: KeyPair) {
+        *self.0.write().unwrap_or_else(PoisonError::into_inner) = key_pair;
+    }
+}
+
+/// A set of JSON web keys as defined in [RFC 7517](https://www.rfc-editor.org/rfc/rfc7517).
+/// Allows other services to verify the tokens signed by this application.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(crate = "rocket::serde")]
+#[schemars(example = "Self::example")]
+pub struct Jwks {
+    /// The public keys of this application, empty if no public key is loaded.
+    pub keys: Vec<Jwk>,
+}
+
+impl SchemaExample for Jwks {
+    fn example() -> Self {
+        Self {
+            keys: vec![Jwk::example()],
+        }
+    }
+}
+
+/// A single RSA public key in the JSON web key format.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(crate = "rocket::serde")]
+#[schemars(example = "Self::example")]
+pub struct Jwk {
+    /// The key type, always `RSA`.
+    pub kty: String,
+    /// The intended use of the key, always `sig`.
+    #[serde(rename = "use")]
+    pub key_use: String,
+    /// The algorithm the tokens are signed with.
+    pub alg: String,
+    /// The id of the key, the RFC 7638 thumbprint of the key.
+    pub kid: String,
+    /// The base64url encoded modulus.
+    pub n: String,
+    /// The base64url encoded public exponent.
+    pub e: String,
+}
+
+impl SchemaExample for Jwk {
+    fn example() -> Self {
+        Self {
+            kty: "RSA".to_string(),
+            key_use: "sig".to_string(),
+            alg: "RS512".to_string(),
+            kid: "NzbLsXh8uDCcd-6MNwXF4W_7noWXFZAfHkxZsRGC9Xs".to_string(),
+            n: "0vx7agoebGcQSuuPiLJXZptN9nndrQmbXEps2aiAFbWhM78LhWx4cbbfAAtVT86zwu1RK7aPFFxuhDR1L6tSoc_BJECPebWKRXjBZCiFV4n3oknjhMstn64tZ_2W-5JsGY4Hc5n9yBXArwl93lqt7_RN5w6Cf0h4QyQ5v-65YGjQR0_FDW2QvzqY368QQMicAtaSqzs8KJZgnYb9c7d0zgdAZHzu6qMQvRL5hajrn1n91CbOpbISD08qNLyrdkt-bFTWhAI4vMQFh6WeZu0fM4lFd2NcRwr3XPksINHaQ-G_xBniIqbw0Ls1jF44-csFCur-kEgU8awapJzKnqDKgw".to_string(),
+            e: "AQAB".to_string(),
+        }
+    }
+}
+
+impl PrivateKey {
+    /// Attach the id of the key pair to the private key.
+    ///
+    /// # Arguments
+    ///
+    /// * `key_id`: the id of the key pair, see [PublicKey::jwk]
+    ///
+    /// returns: PrivateKey
+    pub fn with_key_id(self, key_id: Option<String>) -> Self {
+        Self(self.0, key_id)
+    }
+}
+
+impl PublicKey {
+    /// Convert the public key into a JSON web key.
+    /// Both PKCS#1 (`RSA PUBLIC KEY`) and X.509 (`PUBLIC KEY`) PEM encoded keys are supported.
+    ///
+    /// # Arguments
+    ///
+    /// * `algorithm`: the algorithm the tokens are signed with
+    ///
+    /// returns: Option<Jwk> the key or `None` if the key cannot be parsed
+    pub fn jwk(&self, algorithm: Algorithm) -> Option<Jwk> {
+        let body: String = String::from_utf8_lossy(&self.0)
+            .lines()
+            .filter(|l| !l.starts_with("-----"))
+            .map(str::trim)
+            .collect();
+        let der = engine::general_purpose::STANDARD.decode(body).ok()?;
+        let (n, e) = rsa_components(&der)?;
+        let encoder = &engine::general_purpose::URL_SAFE_NO_PAD;
+        let (n, e) = (encoder.encode(n), encoder.encode(e));
+        let thumbprint_input = format!(r#"{{"e":"{}","kty":"RSA","n":"{}"}}"#, e, n);
+        let thumbprint = ring::digest::digest(&ring::digest::SHA256, thumbprint_input.as_bytes());
+        Some(Jwk {
+            kty: "RSA".to_string(),
+            key_use: "sig".to_string(),
+            alg: format!("{:?}", algorithm),
+            kid: encoder.encode(thumbprint.as_ref()),
+            n,
+            e,
+        })
+    }
+}
+
+/// Extract the modulus and the public exponent out of a DER encoded RSA public key.
+/// The leading zero bytes of both integers are removed.
+///
+/// # Arguments
+///
+/// * `der`: the key either as PKCS#1 `RSAPublicKey` or as X.509 `SubjectPublicKeyInfo`
+///
+/// returns: Option<(&[u8], &[u8])> the modulus and the exponent
+fn rsa_components(der: &[u8]) -> Option<(&[u8], &[u8])> {
+    let (tag, sequence, _) = der_element(der)?;
+    if tag != DER_SEQUENCE {
+        return None;
+    }
+    let (tag, first, rest) = der_element(sequence)?;
+    match tag {
+        DER_INTEGER => {
+            let (tag, exponent, _) = der_element(rest)?;
+            (tag == DER_INTEGER)
+                .then(|| (strip_leading_zeros(first), strip_leading_zeros(exponent)))
+        }
+        DER_SEQUENCE => {
+            let (tag, bit_string, _) = der_element(rest)?;
+            if tag != DER_BIT_STRING {
+                return None;
+            }
+            rsa_components(bit_string.get(1..)?)
+        }
+        _ => None,
+    }
+}
+
+/// Split the first DER element off the data.
+///
+/// # Arguments
+///
+/// * `data`: the DER encoded data
+///
+/// returns: Option<(u8, &[u8], &[u8])> the tag, the content and the remaining data
+fn der_element(data: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let tag = *data.first()?;
+    let first_length = *data.get(1)?;
+    let (length, header) = if first_length & 0x80 == 0 {
+        (first_length as usize, 2)
+    } else {
+        let length_bytes = (first_length & 0x7f) as usize;
+        if length_bytes > std::mem::size_of::<usize>() {
+            return None;
+        }
+        let length = data
+            .get(2..2 + length_bytes)?
+            .iter()
+            .fold(0usize, |l, b| (l << 8) | *b as usize);
+        (length, 2 + length_bytes)
+    };
+    let content = data.get(header..header.checked_add(length)?)?;
+    Some((tag, content, &data[header + length..]))
+}
+
+/// Remove the leading zero bytes of a DER integer.
+fn strip_leading_zeros(integer: &[u8]) -> &[u8] {
+    let start = integer
+        .iter()
+        .position(|b| *b != 0)
+        .unwrap_or(integer.len());
+    &integer[start..]
+}
+
 /// Reads the private key from the file whose path is provided in the application configuration.
 ///
 /// # Arguments
@@ -34,7 +280,7 @@ pub struct PublicKey(pub(crate) Vec<u8>);
 ///
 /// returns: Result<PrivateKey, Error>
 pub fn read_private_key(config: &Config) -> Result<PrivateKey, Error> {
-    fs::read(&config.cert.private_key_path).map(PrivateKey)
+    fs::read(&config.cert.private_key_path).map(|key| PrivateKey(key, None))
 }
 
 /// Reads the public key from the file whose path is provided in the application configuration.
@@ -47,3 +293,62 @@ pub fn read_private_key(config: &Config) -> Result<PrivateKey, Error> {
 pub fn read_public_key(config: &Config) -> Result<PublicKey, Error> {
     fs::read(&config.cert.public_key_path).map(PublicKey)
 }
+
+/// Read the public and the private key again and replace the managed keys with them.
+/// Both keys must be readable and valid PEM encoded RSA keys, otherwise the previous keys are kept.
+///
+/// # Arguments
+///
+/// * `config`: the application configuration with the paths of the keys
+/// * `keys`: the managed keys to replace
+///
+/// returns: Result<(), String> the reason if the keys are not replaced
+pub fn reload_keys(config: &Config, keys: &Keys) -> Result<(), String> {
+    let public_key = read_public_key(config).map_err(|e| {
+        format!(
+            "Unable to read the public key from {}: {}",
+            config.cert.public_key_path, e
+        )
+    })?;
+    let private_key = read_private_key(config).map_err(|e| {
+        format!(
+            "Unable to read the private key from {}: {}",
+            config.cert.private_key_path, e
+        )
+    })?;
+    DecodingKey::from_rsa_pem(&public_key.0).map_err(|e| format!("Invalid public key: {}", e))?;
+    EncodingKey::from_rsa_pem(&private_key.0).map_err(|e| format!("Invalid private key: {}", e))?;
+    let key_id = public_key.jwk(SIGNING_ALGORITHM).map(|jwk| jwk.kid);
+    keys.replace(KeyPair {
+        public_key: Some(public_key),
+        private_key: Some(private_key.with_key_id(key_id)),
+    });
+    Ok(())
+}
+
+/// Reload the keys whenever the process receives `SIGHUP`, see [reload_keys].
+/// If the keys cannot be reloaded, an error is logged and the previous keys are kept.
+///
+/// # Arguments
+///
+/// * `config`: the application configuration with the paths of the keys
+/// * `keys`: the managed keys to replace
+pub async fn key_reload_task(config: Config, keys: Keys) {
+    let mut hangup = match signal(SignalKind::hangup()) {
+        Ok(hangup) => hangup,
+        Err(e) => {
+            warn!(
+                "Unable to listen for SIGHUP, the keys cannot be reloaded: {}",
+                e
+            );
+            return;
+        }
+    };
+    while hangup.recv().await.is_some() {
+        info!("Received SIGHUP, reload the public and the private key");
+        match reload_keys(&config, &keys) {
+            Ok(()) => info!("Public and private key successfully reloaded"),
+            Err(msg) => error!("Unable to reload the keys, keep the previous ones: {}", msg),
+        }
+    }
+}
@@ -0,0 +1,66 @@
+// OpenKeg, the lightweight backend of the Musikverein Leopoldsdorf.
+// Copyright (C) 2022  Richard Stöckl
+//
+// This program is free software; you can redistribute it and/or
+// modify it under the terms of the GNU General Public License
+// as published by the Free Software Foundation; either version 2
+// of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+
+use super::*;
+
+const X509_KEY: &str = "-----BEGIN PUBLIC KEY-----
+MIIBIjANBgkqhkiG9w0BAQEFAAOCAQ8AMIIBCgKCAQEAqBtAt9e0fDKhtEZylJV2
+xkoN5o2qQxFcwVIQHgqV6uRf7sNZPmpNPXxXTAgphgygbOnx/O5cg14/7/KDiL0m
+qN3XT9pRKCL6ZN57etagblkR9nXhfLA0Y96CPFipOy9D2QjGaOd9SmwhIrfolN3D
+psS2+wOf6qiBCy9+gYjbasHSpHSqTIWt03GI8JjyfS96soUkWRuTrBTHVFlWFYL2
+fRIIHPnulgtxCMdyJr5u/GYavRbSDh8u0rfujJg7XMqU+MtiXFZiwDhAe6TZj+jz
+KTSFFaJBEG5zgcCbBwqE/NCEgjx3iSfty7xBXPKzdBynYVw85XJBGmqqS8BPfy0N
+MwIDAQAB
+-----END PUBLIC KEY-----
+";
+
+const PKCS1_KEY: &str = "-----BEGIN RSA PUBLIC KEY-----
+MIIBCgKCAQEAqBtAt9e0fDKhtEZylJV2xkoN5o2qQxFcwVIQHgqV6uRf7sNZPmpN
+PXxXTAgphgygbOnx/O5cg14/7/KDiL0mqN3XT9pRKCL6ZN57etagblkR9nXhfLA0
+Y96CPFipOy9D2QjGaOd9SmwhIrfolN3DpsS2+wOf6qiBCy9+gYjbasHSpHSqTIWt
+03GI8JjyfS96soUkWRuTrBTHVFlWFYL2fRIIHPnulgtxCMdyJr5u/GYavRbSDh8u
+0rfujJg7XMqU+MtiXFZiwDhAe6TZj+jzKTSFFaJBEG5zgcCbBwqE/NCEgjx3iSft
+y7xBXPKzdBynYVw85XJBGmqqS8BPfy0NMwIDAQAB
+-----END RSA PUBLIC KEY-----
+";
+
+const MODULUS: &str = "qBtAt9e0fDKhtEZylJV2xkoN5o2qQxFcwVIQHgqV6uRf7sNZPmpNPXxXTAgphgygbOnx_O5cg14_7_KDiL0mqN3XT9pRKCL6ZN57etagblkR9nXhfLA0Y96CPFipOy9D2QjGaOd9SmwhIrfolN3DpsS2-wOf6qiBCy9-gYjbasHSpHSqTIWt03GI8JjyfS96soUkWRuTrBTHVFlWFYL2fRIIHPnulgtxCMdyJr5u_GYavRbSDh8u0rfujJg7XMqU-MtiXFZiwDhAe6TZj-jzKTSFFaJBEG5zgcCbBwqE_NCEgjx3iSfty7xBXPKzdBynYVw85XJBGmqqS8BPfy0NMw";
+
+#[test]
+fn jwk_from_x509_key() {
+    let jwk = PublicKey(X509_KEY.as_bytes().to_vec())
+        .jwk(Algorithm::RS512)
+        .expect("jwk");
+    assert_eq!(jwk.n, MODULUS);
+    assert_eq!(jwk.e, "AQAB");
+    assert_eq!(jwk.alg, "RS512");
+    assert_eq!(jwk.kid, "6blxE7ghwsEKd0D7UuEIErD34SqMaCAkWhLWB_KadIA");
+}
+
+#[test]
+fn jwk_from_pkcs1_key_has_same_kid() {
+    let x509 = PublicKey(X509_KEY.as_bytes().to_vec()).jwk(Algorithm::RS512);
+    let pkcs1 = PublicKey(PKCS1_KEY.as_bytes().to_vec()).jwk(Algorithm::RS512);
+    assert_eq!(x509.map(|k| k.kid), pkcs1.map(|k| k.kid));
+}
+
+#[test]
+fn jwk_from_invalid_key() {
+    assert!(PublicKey(b"not a key".to_vec())
+        .jwk(Algorithm::RS512)
+        .is_none());
+}
@@ -28,6 +28,8 @@ pub mod executives;
 pub mod key;
 /// A controller module for endpoints which provides self-service functionality to the user.
 mod self_service;
+/// Module to throttle logins after too many failed attempts.
+pub mod throttle;
 /// Module to manage JWTs.
 /// Contains the possibility to generate and verify them.
 pub mod tokens;
@@ -36,7 +38,12 @@ pub fn get_routes_and_docs(settings: &OpenApiSettings) -> (Vec<rocket::Route>, O
     openapi_get_routes_spec![
         settings: self_service::login,
         self_service::login_with_renewal,
+        self_service::renew,
+        self_service::logout,
+        self_service::jwks,
         self_service::info,
-        self_service::executive_roles
+        self_service::token_expiry,
+        self_service::executive_roles,
+        self_service::roles
     ]
 }
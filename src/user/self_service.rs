@@ -15,19 +15,34 @@
 // along with this program; if not, write to the Free Software
 // Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
 
+use std::net::IpAddr;
+
 use rocket::form::validate::Contains;
+use rocket::http::{Cookie, CookieJar};
 use rocket::serde::json::Json;
 use rocket::State;
 use rocket_okapi::openapi;
 
 use crate::auth::authenticate;
 use crate::member::model::{Group, Member, WebMember};
+use crate::metrics::MetricsRegistry;
 use crate::openapi::{ApiError, ApiResult};
 use crate::user::auth::{authorization_error, AuthenticationResponder, BasicAuth};
-use crate::user::key::PrivateKey;
-use crate::user::tokens::{generate_token, member_from_claims, Claims};
+use crate::user::executives::role_ids;
+use crate::user::key::{Jwks, Keys};
+use crate::user::throttle::{
+    check_login, record_failed_login, reset_failed_logins, LoginAttempts, LoginThrottled,
+};
+use crate::user::tokens::{
+    decode_claims, generate_token, member_from_claims, Claims, RenewalToken, TokenLifetime,
+    SIGNING_ALGORITHM,
+};
 use crate::{Config, MemberStateMutex};
 
+#[cfg(test)]
+#[path = "self_service_tests.rs"]
+mod self_service_tests;
+
 /// Login the user.
 /// On success, this generates two keys:
 ///
@@ -35,74 +50,103 @@ use crate::{Config, MemberStateMutex};
 /// * refresh token: a jwt which can only be used to generate a new request tokens
 ///
 /// The request token expires much earlier than the refresh token which means that applications should only store the refresh token permanently and then gather a new request token when required.
-/// Instead of returning them via the body, the response will attach the request token into the `Authorization` header and the refresh token into the `Renewal` cookie.
+/// Instead of returning them via the body, the response will attach the request token into the `Authorization` header and the refresh token into the `Renewal` header and cookie.
+/// The name of the renewal header and cookie is configurable.
 /// Note that both values will be prefixed with `Bearer `.
 /// Despite being required for future requests, this prefix needs to be removed before deserialization.  
+/// After [crate::config::SecurityConfig::login_attempts] failed logins for a username from the same client within [crate::config::SecurityConfig::login_window], further logins are rejected with `429 Too Many Requests`.
+/// A successful login resets the failed logins.
 ///
 /// # Arguments
 ///
 /// * `auth`: the structure which holds the credentials to use for authentication
-/// * `cookies`: the current cookie store used to store the generated renewal token
-/// * `private_key`: the private key to sign the jwt with
+/// * `client_ip`: the address of the client used to count failed logins
+/// * `keys`: the keys whose private key signs the jwt
 /// * `member_state`: the current member state
+/// * `login_attempts`: the failed logins used to reject further logins
+/// * `metrics`: the registry to record the outcome of the login in
 /// * `config`: the application configuration
 ///
-/// returns: Result<Json<()>, Error>
+/// returns: Result<AuthenticationResponder, LoginThrottled>
 #[openapi(tag = "Self Service")]
 #[post("/auth")]
 pub async fn login(
     auth: BasicAuth,
-    private_key: &State<PrivateKey>,
+    client_ip: Option<IpAddr>,
+    keys: &State<Keys>,
     member_state: &State<MemberStateMutex>,
+    login_attempts: &State<LoginAttempts>,
+    metrics: &State<MetricsRegistry>,
     config: &State<Config>,
-) -> AuthenticationResponder {
+) -> Result<AuthenticationResponder, LoginThrottled> {
+    if let Err(throttled) =
+        check_login(login_attempts, &config.security, &auth.username, client_ip).await
+    {
+        info!(
+            "Reject login of {} due to too many failed logins",
+            auth.username
+        );
+        return Err(throttled);
+    }
     let mut member_state_clone = member_state.inner().clone();
-    authenticate(
+    let result = authenticate(
         config,
         &mut member_state_clone,
         &auth.username,
         &auth.password,
     )
-    .await
-    .map_or_else(
-        |err| {
+    .await;
+    match result {
+        Err(err) => {
             info!("Failed to authenticate: {}", err);
-            AuthenticationResponder {
+            metrics.record_login(false);
+            record_failed_login(login_attempts, &config.security, &auth.username, client_ip).await;
+            Ok(AuthenticationResponder {
                 request_token: None,
                 request_token_required: true,
                 renewal_token: None,
                 renewal_token_required: true,
-            }
-        },
-        |member| {
+            })
+        }
+        Ok(member) => {
             debug!("Authenticated user: {}", member.username);
-            let (request_token, renewal_token) = (
-                generate_token(&member, false, config, private_key),
-                generate_token(&member, true, config, private_key),
-            );
+            metrics.record_login(true);
+            reset_failed_logins(login_attempts, &auth.username, client_ip).await;
+            let (request_token, renewal_token) = keys
+                .with_private_key(|private_key| {
+                    (
+                        generate_token(&member, false, config, private_key),
+                        generate_token(&member, true, config, private_key),
+                    )
+                })
+                .unwrap_or_else(|| {
+                    warn!("Unable to retrieve private key, no tokens will be generated");
+                    (Err(()), Err(()))
+                });
             debug!(
                 "Generated tokens {:?} and {:?}",
                 request_token, renewal_token
             );
-            AuthenticationResponder {
+            Ok(AuthenticationResponder {
                 request_token: request_token.ok().map(|(_claims, token)| token),
                 request_token_required: true,
                 renewal_token: renewal_token.ok().map(|(_claims, token)| token),
                 renewal_token_required: true,
-            }
-        },
-    )
+            })
+        }
+    }
 }
 
 /// Login a user with a refresh token.
 /// The refresh key is the cookie generated by the credentials login.
+/// It may also be sent via the `Renewal` header or the `Authorization` header, both prefixed with `Bearer `.
 /// The refresh token must be valid, otherwise an [`ApiError`] will be returned.
 /// Most things are identical to [`login()`] with two exceptions: the refresh token is required instead of the request token and no refresh token will be generated.
 ///
 /// # Arguments
 ///
-/// * `claims`: the validated claims deserialized from the token
-/// * `private_key`: the private key to sign the new token with
+/// * `renewal_token`: the raw renewal token extracted from the request
+/// * `keys`: the keys to verify the renewal token with and to sign the new token with
 /// * `member_state`: the state with all members
 /// * `config`: the application configuration
 ///
@@ -110,20 +154,12 @@ pub async fn login(
 #[openapi(tag = "Self Service")]
 #[post("/renewal")]
 pub async fn login_with_renewal(
-    claims: Claims,
-    private_key: &State<PrivateKey>,
+    renewal_token: RenewalToken,
+    keys: &State<Keys>,
     member_state: &State<MemberStateMutex>,
     config: &State<Config>,
 ) -> Result<AuthenticationResponder, ApiError> {
-    let members_lock = member_state.read().await;
-    let member = member_from_claims(claims, true, &members_lock.all_members).map_err(|err| {
-        info!("Cannot validate renewal token: {}", err);
-        authorization_error()
-    })?;
-    let (_claims, token) = generate_token(&member, false, config, private_key).map_err(|_err| {
-        info!("Cannot generate new token for {}", member.username);
-        authorization_error()
-    })?;
+    let token = request_token_from_renewal(&renewal_token, keys, member_state, config).await?;
     Ok(AuthenticationResponder {
         request_token: Some(token),
         request_token_required: true,
@@ -132,6 +168,104 @@ pub async fn login_with_renewal(
     })
 }
 
+/// Renew the request token with a refresh token.
+/// The refresh token is read from the `Renewal` cookie, the `Renewal` header, the `Authorization-Renewal` header or the `Authorization` header, the headers prefixed with `Bearer `.
+/// Request tokens are rejected, only refresh tokens are accepted.
+/// Other than [`login_with_renewal()`], the renewal cookie is set again which refreshes its expiry.
+/// The expiry of the refresh token itself is not extended.
+///
+/// # Arguments
+///
+/// * `renewal_token`: the raw renewal token extracted from the request
+/// * `keys`: the keys to verify the renewal token with and to sign the new token with
+/// * `member_state`: the state with all members
+/// * `config`: the application configuration
+///
+/// returns: Result<AuthenticationResponder, ApiError>
+#[openapi(tag = "Self Service")]
+#[get("/renew")]
+pub async fn renew(
+    renewal_token: RenewalToken,
+    keys: &State<Keys>,
+    member_state: &State<MemberStateMutex>,
+    config: &State<Config>,
+) -> Result<AuthenticationResponder, ApiError> {
+    let token = request_token_from_renewal(&renewal_token, keys, member_state, config).await?;
+    Ok(AuthenticationResponder {
+        request_token: Some(token),
+        request_token_required: true,
+        renewal_token: Some(renewal_token.0),
+        renewal_token_required: true,
+    })
+}
+
+/// Get the identifiers of all executive roles of the currently logged-in user such as `archive`.
+/// The identifiers match the fields of the executive mapping and allow frontends to show or hide functionality.
+/// The list is empty if the user has no executive role.
+///
+/// # Arguments
+///
+/// * `member`: the logged-in member to get the roles from, 'Unauthorized' if absent
+/// * `member_state`: the member state
+/// * `config`: the application configuration
+///
+/// returns: Result<Json<Vec<String>>, ApiError>
+#[openapi(tag = "Self Service")]
+#[get("/roles")]
+pub async fn roles(
+    member: Option<Member>,
+    member_state: &State<MemberStateMutex>,
+    config: &State<Config>,
+) -> ApiResult<Vec<String>> {
+    let member = member.ok_or_else(authorization_error)?;
+    let members = member_state.read().await;
+    Ok(Json(role_ids(
+        &members.executives,
+        &config.ldap.executive_mapping,
+        &member,
+    )))
+}
+
+/// Get the public keys of this application as JSON web key set.
+/// This allows other services to verify the tokens issued by this application.
+/// The `kid` of a key matches the `kid` in the header of the tokens.
+/// If no public key is loaded, the set is empty.
+///
+/// # Arguments
+///
+/// * `keys`: the keys of the application
+///
+/// returns: Result<Json<Jwks>, ApiError>
+#[openapi(tag = "Self Service")]
+#[get("/.well-known/jwks.json")]
+pub async fn jwks(keys: &State<Keys>) -> ApiResult<Jwks> {
+    Ok(Json(Jwks {
+        keys: keys
+            .with_public_key(|k| k.jwk(SIGNING_ALGORITHM))
+            .flatten()
+            .into_iter()
+            .collect(),
+    }))
+}
+
+/// Logout the user by removing the renewal cookie.
+/// The name of the renewal cookie is configurable.
+/// Since tokens are stateless, they cannot be invalidated server-side.
+/// This means that existing request tokens remain valid until they expire.
+///
+/// # Arguments
+///
+/// * `cookies`: the current cookie store to remove the renewal token from
+/// * `config`: the application configuration
+///
+/// returns: Result<Json<()>, ApiError>
+#[openapi(tag = "Self Service")]
+#[get("/logout")]
+pub async fn logout(cookies: &CookieJar<'_>, config: &State<Config>) -> ApiResult<()> {
+    cookies.remove(Cookie::named(config.jwt.renewal_name.clone()));
+    Ok(Json(()))
+}
+
 /// Get the information of the currently logged in member.
 /// This also includes sensitive data such as the address.
 ///
@@ -146,6 +280,21 @@ pub async fn info(member: Member) -> ApiResult<WebMember> {
     Ok(Json(WebMember::from_member(&member, true)))
 }
 
+/// Get the remaining lifetime of the provided bearer token.
+/// This works for both request and renewal tokens and allows clients to refresh their tokens before they expire.
+/// The token must be valid, this request has no side effects.
+///
+/// # Arguments
+///
+/// * `claims`: the validated claims deserialized from the token
+///
+/// returns: Result<Json<TokenLifetime>, ApiError>
+#[openapi(tag = "Self Service")]
+#[get("/me/token-expiry")]
+pub async fn token_expiry(claims: Claims) -> ApiResult<TokenLifetime> {
+    Ok(Json(TokenLifetime::from_claims(&claims)))
+}
+
 /// Get all executive roles of the currently logged-in user.
 /// Executive roles are roles which are allowed to perform actions which are forbidden for normal users.
 ///
@@ -169,3 +318,46 @@ pub async fn executive_roles(
         .cloned();
     Ok(Json(groups.collect()))
 }
+
+/// Validate a refresh token and generate a new request token for its member.
+///
+/// # Arguments
+///
+/// * `renewal_token`: the raw renewal token extracted from the request
+/// * `keys`: the keys to verify the renewal token with and to sign the new token with
+/// * `member_state`: the state with all members
+/// * `config`: the application configuration
+///
+/// returns: Result<String, ApiError> the new request token
+async fn request_token_from_renewal(
+    renewal_token: &RenewalToken,
+    keys: &Keys,
+    member_state: &MemberStateMutex,
+    config: &Config,
+) -> Result<String, ApiError> {
+    let claims = keys
+        .with_public_key(|public_key| {
+            decode_claims(&renewal_token.0, public_key, config.jwt.audience.as_deref())
+        })
+        .ok_or_else(|| {
+            warn!("Unable to retrieve public key, renewal tokens cannot be validated");
+            authorization_error()
+        })?
+        .map_err(|err| {
+            info!("Cannot decode renewal token: {}", err);
+            authorization_error()
+        })?;
+    let members_lock = member_state.read().await;
+    let member = member_from_claims(claims, true, &members_lock.all_members).map_err(|err| {
+        info!("Cannot validate renewal token: {}", err);
+        authorization_error()
+    })?;
+    let (_claims, token) = keys
+        .with_private_key(|private_key| generate_token(&member, false, config, private_key))
+        .unwrap_or(Err(()))
+        .map_err(|_err| {
+            info!("Cannot generate new token for {}", member.username);
+            authorization_error()
+        })?;
+    Ok(token)
+}
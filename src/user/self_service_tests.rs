@@ -0,0 +1,106 @@
+// OpenKeg, the lightweight backend of the Musikverein Leopoldsdorf.
+// Copyright (C) 2022  Richard Stöckl
+//
+// This program is free software; you can redistribute it and/or
+// modify it under the terms of the GNU General Public License
+// as published by the Free Software Foundation; either version 2
+// of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+
+use rocket::http::{Cookie, Header, Status};
+use rocket::local::blocking::Client;
+use rocket::time::Duration;
+
+use super::*;
+use crate::member::state::MemberState;
+use crate::user::key::{KeyPair, PrivateKey, PublicKey};
+use crate::user::tokens::tokens_tests::{PRIVATE_KEY, PUBLIC_KEY};
+
+fn keys() -> Keys {
+    Keys::new(KeyPair {
+        public_key: Some(PublicKey(PUBLIC_KEY.as_bytes().to_vec())),
+        private_key: Some(PrivateKey(PRIVATE_KEY.as_bytes().to_vec(), None)),
+    })
+}
+
+fn token(renewal: bool) -> String {
+    let private_key = PrivateKey(PRIVATE_KEY.as_bytes().to_vec(), None);
+    generate_token(
+        &Member::test_member("Karl", "Huber"),
+        renewal,
+        &Config::default(),
+        &private_key,
+    )
+    .expect("token")
+    .1
+}
+
+fn client() -> Client {
+    let member_state = MemberState::mutex();
+    member_state
+        .try_write()
+        .expect("member state")
+        .all_members
+        .insert(Member::test_member("Karl", "Huber"));
+    let rocket = rocket::build()
+        .manage(Config::default())
+        .manage(keys())
+        .manage(member_state)
+        .mount("/", routes![renew]);
+    Client::tracked(rocket).expect("rocket client")
+}
+
+#[test]
+fn renew_rejects_request_token() {
+    let client = client();
+    let response = client
+        .get("/renew")
+        .header(Header::new("Renewal", format!("Bearer {}", token(false))))
+        .dispatch();
+    assert_eq!(response.status(), Status::Unauthorized);
+    assert!(response.headers().get_one("Authorization").is_none());
+}
+
+#[test]
+fn renew_returns_request_token() {
+    let client = client();
+    let response = client
+        .get("/renew")
+        .cookie(Cookie::new("Renewal", token(true)))
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let request_token = response
+        .headers()
+        .get_one("Authorization")
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .expect("request token");
+    let public_key = PublicKey(PUBLIC_KEY.as_bytes().to_vec());
+    let claims = decode_claims(request_token, &public_key, None).expect("claims");
+    assert_eq!(claims.sub, "karl");
+    assert!(!claims.ren);
+}
+
+#[test]
+fn renew_refreshes_cookie_expiry() {
+    let client = client();
+    let renewal_token = token(true);
+    let response = client
+        .get("/renew")
+        .header(Header::new("Renewal", format!("Bearer {}", renewal_token)))
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let cookie = response.cookies().get("Renewal").expect("renewal cookie");
+    assert_eq!(cookie.value(), renewal_token);
+    assert_eq!(
+        cookie.max_age(),
+        Some(Duration::hours(Config::default().jwt.renewal_expiration))
+    );
+}
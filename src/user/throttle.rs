@@ -0,0 +1,156 @@
+// OpenKeg, the lightweight backend of the Musikverein Leopoldsdorf.
+// Copyright (C) 2022  Richard Stöckl
+//
+// This program is free software; you can redistribute it and/or
+// modify it under the terms of the GNU General Public License
+// as published by the Free Software Foundation; either version 2
+// of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use okapi::map;
+use okapi::openapi3::{Object, ParameterValue, RefOr, Response, Responses};
+use rocket::http::{Header, Status};
+use rocket::response::Responder;
+use rocket::tokio::sync::RwLock;
+use rocket::Request;
+use rocket_okapi::gen::OpenApiGenerator;
+use rocket_okapi::response::OpenApiResponderInner;
+
+use crate::config::SecurityConfig;
+use crate::openapi::ApiError;
+
+#[cfg(test)]
+#[path = "throttle_tests.rs"]
+mod throttle_tests;
+
+/// The failed logins keyed by the username and the client address.
+/// Each entry holds the number of failed logins and the instant of the first one.
+pub type LoginAttempts = Arc<RwLock<HashMap<(String, Option<IpAddr>), (u32, Instant)>>>;
+
+/// The response if too many logins failed.
+/// Contains the seconds after which a login is accepted again.
+pub struct LoginThrottled(pub u64);
+
+/// Check whether a login for the username and client address is allowed.
+///
+/// # Arguments
+///
+/// * `attempts`: the failed logins
+/// * `conf`: the security configuration
+/// * `username`: the username of the login
+/// * `client_ip`: the address of the client, if known
+///
+/// returns: Result<(), LoginThrottled>
+pub async fn check_login(
+    attempts: &LoginAttempts,
+    conf: &SecurityConfig,
+    username: &str,
+    client_ip: Option<IpAddr>,
+) -> Result<(), LoginThrottled> {
+    let window = Duration::from_secs(conf.login_window);
+    match attempts
+        .read()
+        .await
+        .get(&(username.to_lowercase(), client_ip))
+    {
+        Some((count, since)) if *count >= conf.login_attempts && since.elapsed() < window => Err(
+            LoginThrottled(window.saturating_sub(since.elapsed()).as_secs().max(1)),
+        ),
+        _ => Ok(()),
+    }
+}
+
+/// Record a failed login for the username and client address.
+/// Expired entries are pruned to bound the number of entries.
+///
+/// # Arguments
+///
+/// * `attempts`: the failed logins
+/// * `conf`: the security configuration
+/// * `username`: the username of the login
+/// * `client_ip`: the address of the client, if known
+pub async fn record_failed_login(
+    attempts: &LoginAttempts,
+    conf: &SecurityConfig,
+    username: &str,
+    client_ip: Option<IpAddr>,
+) {
+    let window = Duration::from_secs(conf.login_window);
+    let mut attempts = attempts.write().await;
+    attempts.retain(|_, (_, since)| since.elapsed() < window);
+    let entry = attempts
+        .entry((username.to_lowercase(), client_ip))
+        .or_insert((0, Instant::now()));
+    entry.0 += 1;
+}
+
+/// Reset the failed logins for the username and client address after a successful login.
+///
+/// # Arguments
+///
+/// * `attempts`: the failed logins
+/// * `username`: the username of the login
+/// * `client_ip`: the address of the client, if known
+pub async fn reset_failed_logins(
+    attempts: &LoginAttempts,
+    username: &str,
+    client_ip: Option<IpAddr>,
+) {
+    attempts
+        .write()
+        .await
+        .remove(&(username.to_lowercase(), client_ip));
+}
+
+impl<'r> Responder<'r, 'static> for LoginThrottled {
+    fn respond_to(self, request: &'r Request<'_>) -> rocket::response::Result<'static> {
+        let mut response = ApiError {
+            err: "Too Many Requests".to_string(),
+            msg: Some(format!(
+                "Too many failed logins, try again in {} seconds",
+                self.0
+            )),
+            http_status_code: Status::TooManyRequests.code,
+        }
+        .respond_to(request)?;
+        response.set_header(Header::new("Retry-After", self.0.to_string()));
+        Ok(response)
+    }
+}
+
+impl OpenApiResponderInner for LoginThrottled {
+    fn responses(_gen: &mut OpenApiGenerator) -> rocket_okapi::Result<Responses> {
+        use okapi::openapi3::Header;
+        let retry_header = map! {"Retry-After".to_string() => RefOr::Object(Header{
+            description: Some("The seconds after which a login is accepted again".to_string()),
+            required: true,
+            deprecated: false,
+            allow_empty_value: false,
+            value: ParameterValue::Content {content: map!{}},
+            extensions: map! {}
+        })};
+        Ok(Responses {
+            responses: map! {"429".to_string() => RefOr::Object(Response {
+                description: "Too many failed logins for the username from this client".to_string(),
+                headers: retry_header,
+                content: map! {},
+                links: map! {},
+                extensions: Object::default(),
+            })},
+            ..Default::default()
+        })
+    }
+}
@@ -0,0 +1,63 @@
+// OpenKeg, the lightweight backend of the Musikverein Leopoldsdorf.
+// Copyright (C) 2022  Richard Stöckl
+//
+// This program is free software; you can redistribute it and/or
+// modify it under the terms of the GNU General Public License
+// as published by the Free Software Foundation; either version 2
+// of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+
+use super::*;
+
+fn security(login_attempts: u32) -> SecurityConfig {
+    SecurityConfig {
+        login_attempts,
+        login_window: 300,
+    }
+}
+
+#[rocket::async_test]
+async fn throttle_after_failed_logins() {
+    let attempts = LoginAttempts::default();
+    let conf = security(2);
+    let ip = Some(IpAddr::from([127, 0, 0, 1]));
+    for _ in 0..2 {
+        assert!(check_login(&attempts, &conf, "max", ip).await.is_ok());
+        record_failed_login(&attempts, &conf, "max", ip).await;
+    }
+    let throttled = check_login(&attempts, &conf, "Max", ip).await;
+    assert!(throttled.is_err_and(|t| t.0 > 0 && t.0 <= 300));
+    assert!(check_login(&attempts, &conf, "max", None).await.is_ok());
+    assert!(check_login(&attempts, &conf, "erika", ip).await.is_ok());
+}
+
+#[rocket::async_test]
+async fn successful_login_resets_failed_logins() {
+    let attempts = LoginAttempts::default();
+    let conf = security(1);
+    record_failed_login(&attempts, &conf, "max", None).await;
+    assert!(check_login(&attempts, &conf, "max", None).await.is_err());
+    reset_failed_logins(&attempts, "max", None).await;
+    assert!(check_login(&attempts, &conf, "max", None).await.is_ok());
+}
+
+#[rocket::async_test]
+async fn expired_failed_logins_are_pruned() {
+    let attempts = LoginAttempts::default();
+    let conf = SecurityConfig {
+        login_attempts: 1,
+        login_window: 0,
+    };
+    record_failed_login(&attempts, &conf, "max", None).await;
+    record_failed_login(&attempts, &conf, "erika", None).await;
+    assert!(check_login(&attempts, &conf, "max", None).await.is_ok());
+    assert_eq!(attempts.read().await.len(), 1);
+}
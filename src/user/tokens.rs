@@ -25,15 +25,34 @@ use rocket::serde::{Deserialize, Serialize};
 use rocket::Request;
 use rocket_okapi::gen::OpenApiGenerator;
 use rocket_okapi::request::{OpenApiFromRequest, RequestHeaderInput};
+use rocket_okapi::JsonSchema;
 
 use crate::member::model::Member;
 use crate::member::state::{AllMembers, Repository};
+use crate::openapi::SchemaExample;
 use crate::user::auth::bearer_documentation;
-use crate::user::key::{PrivateKey, PublicKey};
+use crate::user::key::{Keys, PrivateKey, PublicKey};
 use crate::Config;
 
+#[cfg(test)]
+#[path = "tokens_tests.rs"]
+pub(crate) mod tokens_tests;
+
 pub const AUTHORIZATION_HEADER: &str = "authorization";
-pub const AUTHORIZATION_RENEWAL_HEADER: &str = "x-authorization-renewal";
+/// The algorithm used to sign and verify tokens.
+pub const SIGNING_ALGORITHM: Algorithm = Algorithm::RS512;
+/// The default name of the header and the cookie which carry the renewal token.
+pub const DEFAULT_RENEWAL_NAME: &str = "Renewal";
+/// An additional header which carries the renewal token, independent of the configured renewal name.
+pub const AUTHORIZATION_RENEWAL_HEADER: &str = "Authorization-Renewal";
+/// The header which carried the renewal token before its name became configurable.
+/// It is still read from requests and set in login responses for existing clients.
+pub const LEGACY_RENEWAL_HEADER: &str = "x-authorization-renewal";
+
+/// A raw renewal token extracted from a request.
+/// The token is read from the renewal cookie, the renewal header, the [`AUTHORIZATION_RENEWAL_HEADER`], the [`LEGACY_RENEWAL_HEADER`] or the `Authorization` header, in this order.
+/// The names of the renewal cookie and header are configured via [`crate::config::JwtConfig::renewal_name`].
+pub struct RenewalToken(pub String);
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
@@ -41,9 +60,50 @@ pub struct Claims {
     pub(crate) iss: String,
     pub(crate) exp: u64,
     pub(crate) ren: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) aud: Option<String>,
     _private: (),
 }
 
+/// The remaining lifetime of a token.
+/// Intended to let clients refresh their tokens before they expire.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(crate = "rocket::serde", rename_all = "camelCase")]
+#[schemars(example = "Self::example")]
+pub struct TokenLifetime {
+    /// The seconds until the token expires.
+    pub remaining_seconds: u64,
+    /// Whether the token is a renewal token or a request token.
+    pub renewal: bool,
+}
+
+impl TokenLifetime {
+    /// Compute the remaining lifetime of a token from its claims.
+    /// The remaining seconds will be 0 if the token is already expired.
+    ///
+    /// # Arguments
+    ///
+    /// * `claims`: the claims of the token
+    ///
+    /// returns: TokenLifetime
+    pub fn from_claims(claims: &Claims) -> Self {
+        let now = chrono::Local::now().timestamp() as u64;
+        Self {
+            remaining_seconds: claims.exp.saturating_sub(now),
+            renewal: claims.ren,
+        }
+    }
+}
+
+impl SchemaExample for TokenLifetime {
+    fn example() -> Self {
+        Self {
+            remaining_seconds: 3600,
+            renewal: false,
+        }
+    }
+}
+
 #[rocket::async_trait]
 impl<'r> FromRequest<'r> for Claims {
     type Error = ();
@@ -61,9 +121,14 @@ impl<'r> FromRequest<'r> for Claims {
             return Forward(());
         }
         let token = token_optional.expect("Stripped token");
-        let public_key = request.rocket().state::<PublicKey>();
-        if let Some(pk) = public_key {
-            let claims_result = decode_claims(token, pk);
+        let audience = request
+            .rocket()
+            .state::<Config>()
+            .and_then(|c| c.jwt.audience.clone());
+        let claims_result = request.rocket().state::<Keys>().and_then(|keys| {
+            keys.with_public_key(|pk| decode_claims(token, pk, audience.as_deref()))
+        });
+        if let Some(claims_result) = claims_result {
             match claims_result {
                 Ok(claims) => Success(claims),
                 Err(err) => {
@@ -81,6 +146,63 @@ impl<'r> FromRequest<'r> for Claims {
     }
 }
 
+/// Get the configured name of the renewal header and cookie.
+///
+/// # Arguments
+///
+/// * `request`: the request which provides the configuration
+///
+/// returns: String
+pub(crate) fn renewal_name(request: &Request<'_>) -> String {
+    request
+        .rocket()
+        .state::<Config>()
+        .map(|c| c.jwt.renewal_name.clone())
+        .unwrap_or_else(|| DEFAULT_RENEWAL_NAME.to_string())
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for RenewalToken {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let name = renewal_name(request);
+        let token = request
+            .cookies()
+            .get(&name)
+            .map(|c| c.value().to_string())
+            .or_else(|| {
+                [
+                    name.as_str(),
+                    AUTHORIZATION_RENEWAL_HEADER,
+                    LEGACY_RENEWAL_HEADER,
+                    AUTHORIZATION_HEADER,
+                ]
+                .iter()
+                .filter_map(|h| request.headers().get_one(h))
+                .find_map(|v| v.strip_prefix("Bearer "))
+                .map(|t| t.to_string())
+            });
+        match token {
+            Some(token) => Success(RenewalToken(token)),
+            None => {
+                debug!("Request does not contain a renewal token");
+                Forward(())
+            }
+        }
+    }
+}
+
+impl<'r> OpenApiFromRequest<'r> for RenewalToken {
+    fn from_request_input(
+        _gen: &mut OpenApiGenerator,
+        _name: String,
+        _required: bool,
+    ) -> rocket_okapi::Result<RequestHeaderInput> {
+        bearer_documentation()
+    }
+}
+
 impl<'r> OpenApiFromRequest<'r> for Claims {
     fn from_request_input(
         _gen: &mut OpenApiGenerator,
@@ -93,6 +215,7 @@ impl<'r> OpenApiFromRequest<'r> for Claims {
 
 /// Function to generate a jwt token.
 /// This returns the [`Claims`] struct and the encoded value.
+/// The header of the token contains the id of the key pair if it is known.
 ///
 /// # Arguments
 ///
@@ -119,6 +242,7 @@ pub(crate) fn generate_token(
         iss: config.jwt.issuer.to_string(),
         exp: expiration.timestamp() as u64,
         ren: renewal,
+        aud: config.jwt.audience.clone(),
         _private: (),
     };
     debug!("Private key length: {}", &private_key.0.len());
@@ -128,7 +252,11 @@ pub(crate) fn generate_token(
             e
         )
     })?;
-    jsonwebtoken::encode(&Header::new(Algorithm::RS512), &claims, encoding_key)
+    let header = Header {
+        kid: private_key.1.clone(),
+        ..Header::new(SIGNING_ALGORITHM)
+    };
+    jsonwebtoken::encode(&header, &claims, encoding_key)
         .map(|encoded| (claims, encoded))
         .map_err(|e| warn!("Encoding error: {}", e))
 }
@@ -174,17 +302,29 @@ pub(crate) fn member_from_claims(
 ///  * the token is not expired
 ///  * the token is signed by the key used by this application
 ///  * the token is expected to be a request/refresh token and is actual one
+///  * the token is intended for the audience, if one is configured
 ///
 /// # Arguments
 ///
 /// * `token`: the token string to decode
 /// * `public_key`: the public key used for the signature verification
+/// * `audience`: the required audience of the token, see [crate::config::JwtConfig::audience]
 ///
 /// returns: Result<Claims, Error>
-pub(crate) fn decode_claims(token: &str, public_key: &PublicKey) -> Result<Claims, Error> {
+pub(crate) fn decode_claims(
+    token: &str,
+    public_key: &PublicKey,
+    audience: Option<&str>,
+) -> Result<Claims, Error> {
     let mut validation = Validation::default();
-    validation.set_required_spec_claims(&["iss", "sub", "ren", "exp"]);
-    validation.algorithms = vec![Algorithm::RS512];
+    match audience {
+        Some(audience) => {
+            validation.set_required_spec_claims(&["iss", "sub", "ren", "exp", "aud"]);
+            validation.set_audience(&[audience]);
+        }
+        None => validation.set_required_spec_claims(&["iss", "sub", "ren", "exp"]),
+    }
+    validation.algorithms = vec![SIGNING_ALGORITHM];
     debug!("Public key length: {}", &public_key.0.len());
     let decoding_key = &DecodingKey::from_rsa_pem(public_key.0.as_slice()).map_err(|e| {
         warn!(
@@ -195,7 +335,16 @@ pub(crate) fn decode_claims(token: &str, public_key: &PublicKey) -> Result<Claim
     })?;
     let claims = jsonwebtoken::decode::<Claims>(token, decoding_key, &validation)
         .map_err(|e| {
-            info!("Cannot validate token: {}", e);
+            match e.kind() {
+                ErrorKind::InvalidAudience => info!(
+                    "Reject token which is not intended for the audience {:?}: {}",
+                    audience, e
+                ),
+                ErrorKind::MissingRequiredClaim(claim) if claim == "aud" => {
+                    info!("Reject token without audience, expected {:?}", audience)
+                }
+                _ => info!("Cannot validate token: {}", e),
+            }
             e
         })?
         .claims;